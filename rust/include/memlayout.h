@@ -0,0 +1,66 @@
+/*
+ * C interface to memlayout, built with `cargo build --release --features capi`
+ * as target/release/libmemlayout.so.
+ *
+ * Every pointer argument may be null, in which case the function fails or
+ * does nothing. Non-null pointers must be valid for the access described;
+ * the library never keeps a pointer it was given.
+ */
+#ifndef MEMLAYOUT_H
+#define MEMLAYOUT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MmvRegions MmvRegions;
+
+typedef struct {
+    uint64_t start;
+    uint64_t end;
+    uint64_t offset;
+    uint64_t inode;
+    uint8_t readable;
+    uint8_t writable;
+    uint8_t executable;
+    uint8_t shared;
+} MmvRegion;
+
+/* Parses `len` bytes of /proc/PID/maps or smaps text, which need not be
+ * NUL-terminated. Lines that don't parse are skipped; returns null only if
+ * the text is not UTF-8. */
+MmvRegions *mmv_parse_maps(const char *text, size_t len);
+
+/* Parses the calling process's own /proc/self/maps; returns null if it
+ * cannot be read. */
+MmvRegions *mmv_parse_self(void);
+
+size_t mmv_regions_count(const MmvRegions *regions);
+
+/* Copies region `index` into `out`. Returns 0, or -1 if out of range. */
+int mmv_region_get(const MmvRegions *regions, size_t index, MmvRegion *out);
+
+/* The path or pseudo-path such as "[heap]", or null for anonymous memory.
+ * Valid until the regions are freed. */
+const char *mmv_region_path(const MmvRegions *regions, size_t index);
+
+void mmv_regions_free(MmvRegions *regions);
+
+/* Renders a PNG with the command line's default options at width x height.
+ * On success stores the buffer and its length and returns 0; the buffer
+ * must be released with mmv_buffer_free, not free(). Returns -1 on failure. */
+int mmv_render_png_to_buffer(const MmvRegions *regions, uint32_t width, uint32_t height, uint8_t **out_data, size_t *out_len);
+
+void mmv_buffer_free(uint8_t *data, size_t len);
+
+/* A static string; do not free it. */
+const char *mmv_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,40 @@
+# Smoke test of the Python module: pip install . from the crate directory,
+# then python -m unittest discover python.
+import unittest
+
+import memlayout
+
+MAPS = """\
+00400000-00452000 r-xp 00000000 08:02 173521                     /usr/bin/dbus-daemon
+00651000-00652000 rw-p 00051000 08:02 173521                     /usr/bin/dbus-daemon
+0148b000-014ac000 rw-p 00000000 00:00 0                          [heap]
+7f3a1c000000-7f3a1c021000 rw-p 00000000 00:00 0
+not a maps line
+"""
+
+
+class ParseMapsTest(unittest.TestCase):
+    def test_regions(self):
+        regions = memlayout.parse_maps(MAPS)
+        self.assertEqual(len(regions), 4)
+        first = regions[0]
+        self.assertEqual((first.start, first.end, first.size), (0x400000, 0x452000, 0x52000))
+        self.assertEqual(first.perms, "r-xp")
+        self.assertEqual(first.path, "/usr/bin/dbus-daemon")
+        self.assertIsNone(regions[3].path)
+        self.assertEqual(regions[2].as_dict()["path"], "[heap]")
+
+
+class RenderTest(unittest.TestCase):
+    def test_png(self):
+        for regions in (MAPS, memlayout.parse_maps(MAPS)):
+            png = memlayout.render(regions, width=200, height=400)
+            self.assertEqual(png[:8], b"\x89PNG\r\n\x1a\n")
+
+    def test_bad_size(self):
+        with self.assertRaises(ValueError):
+            memlayout.render(MAPS, width=0)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,61 @@
+use std::process::Command;
+
+#[derive(Clone)]
+pub struct AdbTarget {
+    pub serial: String,
+    pub package: Option<String>,
+}
+
+impl AdbTarget {
+    pub fn shell(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new("adb")
+            .arg("-s")
+            .arg(&self.serial)
+            .arg("shell")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Unable to run adb: {}", e))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() || stdout.trim().is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("adb shell {} failed: {}{}", args.join(" "), stderr.trim(), stdout.trim()));
+        }
+        Ok(stdout)
+    }
+
+    pub fn resolve_pid(&self) -> Result<u32, String> {
+        let package = self.package.as_deref().ok_or("No package given")?;
+        let output = self.shell(&["pidof", package])?;
+        output
+            .split_whitespace()
+            .next()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .ok_or_else(|| format!("Package {} is not running", package))
+    }
+
+    // Plain access works on rooted/userdebug devices (after `adb root`), run-as
+    // works for debuggable packages, and su covers rooted user builds.
+    pub fn read_proc_file(&self, pid: u32, name: &str) -> Result<String, String> {
+        let path = format!("/proc/{}/{}", pid, name);
+        let mut errors = Vec::new();
+
+        match self.shell(&["cat", &path]) {
+            Ok(contents) => return Ok(contents),
+            Err(e) => errors.push(e),
+        }
+        if let Some(package) = &self.package {
+            match self.shell(&["run-as", package, "cat", &path]) {
+                Ok(contents) => return Ok(contents),
+                Err(e) => errors.push(e),
+            }
+        }
+        match self.shell(&["su", "0", "cat", &path]) {
+            Ok(contents) => Ok(contents),
+            Err(e) => {
+                errors.push(e);
+                Err(errors.join("\n"))
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{jit, region_category, MemoryRegion};
+use plotters::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub struct Sample {
+    pub timestamp: u64,
+    pub category_bytes: BTreeMap<String, usize>,
+    // The program break, and every anonymous mapping taken together.
+    pub heap_end: Option<usize>,
+    pub anonymous: usize,
+    // Bytes of JIT code mapped or unmapped per second since the sample
+    // before, 0 for the first.
+    pub jit_churn: usize,
+}
+
+// Stored next to the categories under names no category has; samples
+// stored before these existed read back without them.
+const HEAP_END: &str = "@heap_end";
+const ANONYMOUS: &str = "@anonymous";
+const JIT_CHURN: &str = "@jit_churn";
+
+// Samples are keyed by (pid, timestamp) in big endian so a pid's history is
+// one contiguous, time ordered key range.
+pub struct HistoryStore {
+    db: sled::Db,
+}
+
+fn sample_key(pid: u32, timestamp: u64) -> [u8; 12] {
+    let mut key = [0u8; 12];
+    key[..4].copy_from_slice(&pid.to_be_bytes());
+    key[4..].copy_from_slice(&timestamp.to_be_bytes());
+    key
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl HistoryStore {
+    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(HistoryStore { db: sled::open(path)? })
+    }
+
+    pub fn insert(&self, pid: u32, sample: &Sample) -> Result<(), Box<dyn std::error::Error>> {
+        let mut value: Vec<String> = sample.category_bytes.iter().map(|(category, bytes)| format!("{}={}", category, bytes)).collect();
+        value.extend(sample.heap_end.map(|end| format!("{}={}", HEAP_END, end)));
+        value.push(format!("{}={}", ANONYMOUS, sample.anonymous));
+        value.push(format!("{}={}", JIT_CHURN, sample.jit_churn));
+        self.db.insert(sample_key(pid, sample.timestamp), value.join(",").as_bytes())?;
+        Ok(())
+    }
+
+    pub fn prune(&self, pid: u32, retention: Duration) -> Result<usize, Box<dyn std::error::Error>> {
+        let cutoff = now().saturating_sub(retention.as_secs());
+        let mut removed = 0;
+        for entry in self.db.range(sample_key(pid, 0)..sample_key(pid, cutoff)) {
+            let (key, _) = entry?;
+            self.db.remove(key)?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    pub fn query(&self, pid: u32, window: Duration) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+        let since = now().saturating_sub(window.as_secs());
+        let mut samples = Vec::new();
+        for entry in self.db.range(sample_key(pid, since)..=sample_key(pid, u64::MAX)) {
+            let (key, value) = entry?;
+            let timestamp = u64::from_be_bytes(key[4..12].try_into()?);
+            let mut sample = Sample { timestamp, category_bytes: BTreeMap::new(), heap_end: None, anonymous: 0, jit_churn: 0 };
+            for pair in String::from_utf8_lossy(&value).split(',') {
+                match pair.split_once('=') {
+                    Some((HEAP_END, end)) => sample.heap_end = Some(end.parse()?),
+                    Some((ANONYMOUS, bytes)) => sample.anonymous = bytes.parse()?,
+                    Some((JIT_CHURN, bytes)) => sample.jit_churn = bytes.parse()?,
+                    Some((category, bytes)) => {
+                        sample.category_bytes.insert(category.to_string(), bytes.parse()?);
+                    }
+                    None => {}
+                }
+            }
+            samples.push(sample);
+        }
+        Ok(samples)
+    }
+}
+
+pub fn sample(memory_regions: &[MemoryRegion]) -> Sample {
+    let mut category_bytes = BTreeMap::new();
+    for region in memory_regions.iter().filter(|r| r.attributes.allocated) {
+        *category_bytes.entry(region_category(region).to_string()).or_insert(0) += region.size;
+    }
+    let heap_end = memory_regions.iter().find(|region| region.file_name.as_deref() == Some("[heap]")).map(|region| region.end);
+    let anonymous = memory_regions.iter().filter(|region| region_category(region).ends_with("anon") || jit::is_jit(region)).map(|region| region.size).sum();
+    Sample { timestamp: now(), category_bytes, heap_end, anonymous, jit_churn: 0 }
+}
+
+const CSV_HEADER: &str = "timestamp,mapped,rss,regions,heap,stack,jit,jit_churn";
+
+// One row per sample, for graphing a long run without the history store.
+// RSS is left empty without smaps.
+fn csv_row(sample: &Sample, memory_regions: &[MemoryRegion]) -> String {
+    let mapped = memory_regions.iter().filter(|region| region.attributes.allocated);
+    let named = |name: &str| memory_regions.iter().filter(|region| region.file_name.as_deref() == Some(name)).map(|region| region.size).sum::<usize>();
+    let rss = match memory_regions.iter().any(|region| region.smaps.is_some()) {
+        true => memory_regions.iter().filter_map(|region| region.smaps.as_ref()).map(|smaps| smaps.rss).sum::<usize>().to_string(),
+        false => String::new(),
+    };
+    let jit = sample.category_bytes.get("jit").copied().unwrap_or(0);
+    format!(
+        "{},{},{},{},{},{},{},{}\n",
+        sample.timestamp,
+        mapped.clone().map(|region| region.size).sum::<usize>(),
+        rss,
+        mapped.count(),
+        named("[heap]"),
+        named("[stack]"),
+        jit,
+        sample.jit_churn
+    )
+}
+
+// A sample that can't be read is logged and skipped; the next interval
+// tries again.
+pub fn run_agent<F: Fn() -> Result<Vec<MemoryRegion>, String>>(store: &HistoryStore, pid: u32, interval: Duration, retention: Duration, csv: Option<&str>, capture: F) -> Result<(), Box<dyn std::error::Error>> {
+    let mut csv = match csv {
+        Some(path) => {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            if file.metadata()?.len() == 0 {
+                writeln!(file, "{}", CSV_HEADER)?;
+            }
+            Some(file)
+        }
+        None => None,
+    };
+    let mut previous: Option<(Instant, Vec<(usize, usize)>)> = None;
+    loop {
+        let memory_regions = match capture() {
+            Ok(memory_regions) => memory_regions,
+            Err(e) => {
+                tracing::warn!(pid, "sample skipped: {}", e);
+                thread::sleep(interval);
+                continue;
+            }
+        };
+        let mut sample = sample(&memory_regions);
+        let jit_ranges = jit::ranges(&memory_regions);
+        if let Some((taken, ranges)) = &previous {
+            sample.jit_churn = jit::churn(ranges, &jit_ranges, taken.elapsed());
+        }
+        previous = Some((Instant::now(), jit_ranges));
+        if let Some(file) = &mut csv {
+            file.write_all(csv_row(&sample, &memory_regions).as_bytes())?;
+            file.flush()?;
+        }
+        store.insert(pid, &sample)?;
+        store.prune(pid, retention)?;
+        store.db.flush()?;
+        tracing::debug!(pid, regions = memory_regions.len(), anonymous = sample.anonymous, "sample stored");
+        thread::sleep(interval);
+    }
+}
+
+// The category chart, and when the samples carry them a growth panel half
+// as wide again to its right.
+pub fn draw_timeline_chart(samples: &[Sample], path: &str, image_width: u32, image_height: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let growth = samples.iter().any(|s| s.heap_end.is_some() || s.anonymous > 0);
+    let total_width = if growth { image_width + image_width / 2 } else { image_width };
+    let image = BitMapBackend::new(path, (total_width, image_height)).into_drawing_area();
+    image.fill(&WHITE)?;
+    let (root, panel) = image.split_horizontally(image_width);
+
+    let first = samples.first().map_or(0, |s| s.timestamp);
+    let last = samples.last().map_or(1, |s| s.timestamp).max(first + 1);
+    let max_bytes = samples.iter().flat_map(|s| s.category_bytes.values()).copied().max().unwrap_or(1).max(1);
+    let to_hours = |timestamp: u64| (timestamp as f64 - last as f64) / 3600.0;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Category bytes over time", ("sans-serif", 16))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(70)
+        .build_cartesian_2d(to_hours(first)..0.0, 0.0..(max_bytes as f64 / (1024.0 * 1024.0)) * 1.05)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("hours")
+        .y_desc("MiB")
+        .draw()?;
+
+    let mut categories: Vec<&String> = samples.iter().flat_map(|s| s.category_bytes.keys()).collect();
+    categories.sort();
+    categories.dedup();
+
+    for (index, category) in categories.into_iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        let points = samples.iter().map(|s| {
+            let bytes = s.category_bytes.get(category).copied().unwrap_or(0);
+            (to_hours(s.timestamp), bytes as f64 / (1024.0 * 1024.0))
+        });
+        chart
+            .draw_series(LineSeries::new(points, color.stroke_width(2)))?
+            .label(category.as_str())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+    }
+
+    chart.configure_series_labels().background_style(WHITE.mix(0.8)).border_style(BLACK).draw()?;
+    if growth {
+        draw_growth_panel(&panel, samples, to_hours(first))?;
+    }
+    image.present()?;
+    Ok(())
+}
+
+// How far the program break moved since the first sample that had a
+// [heap], next to the anonymous total: the two a leak shows up in first.
+fn draw_growth_panel<DB: DrawingBackend>(area: &DrawingArea<DB, plotters::coord::Shift>, samples: &[Sample], since: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let last = samples.last().map_or(1, |s| s.timestamp);
+    let to_hours = |timestamp: u64| (timestamp as f64 - last as f64) / 3600.0;
+    let to_mib = |bytes: f64| bytes / (1024.0 * 1024.0);
+    let base = samples.iter().find_map(|s| s.heap_end);
+    let heap: Vec<(f64, f64)> = samples.iter().filter_map(|s| Some((to_hours(s.timestamp), to_mib(s.heap_end? as f64 - base? as f64)))).collect();
+    let anonymous: Vec<(f64, f64)> = samples.iter().map(|s| (to_hours(s.timestamp), to_mib(s.anonymous as f64))).collect();
+    let values = || heap.iter().chain(&anonymous).map(|(_, mib)| *mib);
+    let low = values().fold(0.0, f64::min);
+    let high = values().fold(0.0, f64::max).max(low + 1.0);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption("Heap growth", ("sans-serif", 16))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(60)
+        .build_cartesian_2d(since..0.0, low * 1.05..high * 1.05)?;
+    chart.configure_mesh().x_labels(5).x_desc("hours").y_desc("MiB").draw()?;
+
+    if let (Some(base), Some(end)) = (base, samples.iter().rev().find_map(|s| s.heap_end)) {
+        let color = RED;
+        chart
+            .draw_series(LineSeries::new(heap, color.stroke_width(2)))?
+            .label(format!("[heap] end {:#x} -> {:#x}", base, end))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+    }
+    let color = BLUE;
+    chart
+        .draw_series(LineSeries::new(anonymous, color.stroke_width(2)))?
+        .label("anonymous mappings")
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color));
+    chart.configure_series_labels().position(SeriesLabelPosition::UpperLeft).background_style(WHITE.mix(0.8)).border_style(BLACK).draw()?;
+    Ok(())
+}
@@ -0,0 +1,335 @@
+use crate::{format_count, format_size, MemoryRegion};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+// Frames a stack is shown without: the allocator itself and the profiler's
+// replacements of it.
+const ALLOCATOR: [&str; 14] = [
+    "malloc",
+    "calloc",
+    "realloc",
+    "reallocarray",
+    "aligned_alloc",
+    "posix_memalign",
+    "memalign",
+    "valloc",
+    "operator new",
+    "std::allocator",
+    "std::__new_allocator",
+    "__gnu_cxx::new_allocator",
+    "__libc_",
+    "alloc::alloc::",
+];
+// Frames kept per stack in the labels.
+const FRAMES: usize = 3;
+// glibc serves requests from this size up with their own mmap (the default
+// M_MMAP_THRESHOLD), not from an arena.
+const MMAP_THRESHOLD: u64 = 128 << 10;
+
+// The allocations made from one call stack, innermost frame first.
+pub struct Stack {
+    pub frames: Vec<String>,
+    pub allocated: u64,
+    pub allocations: u64,
+    // Live at the moment the whole heap peaked, and at the end.
+    pub peak: u64,
+    pub leaked: u64,
+}
+
+impl Stack {
+    pub fn label(&self) -> String {
+        let frames: Vec<&str> = self.frames.iter().take(FRAMES).map(String::as_str).collect();
+        if frames.is_empty() {
+            return "unknown".to_string();
+        }
+        frames.join(" < ")
+    }
+
+    fn average(&self) -> u64 {
+        self.allocated / self.allocations.max(1)
+    }
+}
+
+pub struct Profile {
+    pub tool: &'static str,
+    pub command: String,
+    pub peak: u64,
+    pub stacks: Vec<Stack>,
+}
+
+fn is_allocator(function: &str) -> bool {
+    ALLOCATOR.iter().any(|allocator| function.starts_with(allocator)) || function.contains("vg_replace_malloc")
+}
+
+// The frames worth showing: the first after the allocator and every one
+// below it.
+fn caller_frames(frames: Vec<String>) -> Vec<String> {
+    let first = frames.iter().position(|frame| !is_allocator(frame)).unwrap_or(frames.len());
+    frames.into_iter().skip(first).collect()
+}
+
+// Stacks with the same shown frames are one, as heaptrack's own views
+// merge them.
+fn merge(stacks: Vec<Stack>) -> Vec<Stack> {
+    let mut merged: Vec<Stack> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for stack in stacks {
+        let key = stack.label();
+        match index.get(&key) {
+            Some(&at) => {
+                let into = &mut merged[at];
+                into.allocated += stack.allocated;
+                into.allocations += stack.allocations;
+                into.peak += stack.peak;
+                into.leaked += stack.leaked;
+            }
+            None => {
+                index.insert(key, merged.len());
+                merged.push(stack);
+            }
+        }
+    }
+    merged.sort_by(|a, b| b.peak.cmp(&a.peak).then(b.allocated.cmp(&a.allocated)));
+    merged
+}
+
+fn hex(field: Option<&str>) -> Option<usize> {
+    usize::from_str_radix(field?, 16).ok()
+}
+
+// heaptrack's data as heaptrack_interpret writes it, every number in hex:
+// "s" strings (prefixed by their length from file format 3), "i" for an
+// instruction pointer with its module and function strings, "t" for a
+// trace node (ip, parent), "a" for allocation info (size, trace) and the
+// "+" and "-" events on it. Strings, ips and traces count from 1, allocation
+// info from 0.
+pub fn parse_heaptrack(text: &str) -> Result<Profile, String> {
+    let (mut strings, mut functions, mut traces) = (vec![String::new()], vec![0usize], vec![(0usize, 0usize)]);
+    let mut infos: Vec<(u64, usize)> = Vec::new();
+    let mut events: Vec<(bool, usize)> = Vec::new();
+    let (mut command, mut format) = (String::new(), 0);
+    for line in text.lines() {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut fields = rest.split_whitespace();
+        match kind {
+            "v" => format = hex(rest.split_whitespace().nth(1)).unwrap_or(0),
+            "X" => command = rest.to_string(),
+            "s" if format >= 3 => strings.push(rest.split_once(' ').map_or("", |(_, string)| string).to_string()),
+            "s" => strings.push(rest.to_string()),
+            "i" => functions.push(hex(fields.nth(2)).unwrap_or(0)),
+            "t" => traces.push((hex(fields.next()).unwrap_or(0), hex(fields.next()).unwrap_or(0))),
+            "a" => infos.push((hex(fields.next()).unwrap_or(0) as u64, hex(fields.next()).unwrap_or(0))),
+            "+" | "-" if rest.split_whitespace().count() > 1 => return Err("This is raw heaptrack data; run it through heaptrack_interpret first".to_string()),
+            "+" | "-" => events.push((kind == "+", hex(fields.next()).ok_or_else(|| format!("Invalid heaptrack line: {}", line))?)),
+            _ => {}
+        }
+    }
+    if infos.is_empty() {
+        return Err("No allocations in the heaptrack data".to_string());
+    }
+    // The heap's peak, then what each allocation info held at it and at
+    // the end.
+    let (mut live, mut peak, mut peak_at) = (0i64, 0i64, 0);
+    for (at, (allocation, info)) in events.iter().enumerate() {
+        let size = infos.get(*info).map_or(0, |(size, _)| *size as i64);
+        live += if *allocation { size } else { -size };
+        if live > peak {
+            (peak, peak_at) = (live, at + 1);
+        }
+    }
+    let (mut at_peak, mut at_end, mut counts) = (vec![0i64; infos.len()], vec![0i64; infos.len()], vec![0u64; infos.len()]);
+    for (at, (allocation, info)) in events.iter().enumerate() {
+        let Some((size, _)) = infos.get(*info) else { continue };
+        let change = if *allocation { *size as i64 } else { -(*size as i64) };
+        at_end[*info] += change;
+        if at < peak_at {
+            at_peak[*info] += change;
+        }
+        if *allocation {
+            counts[*info] += 1;
+        }
+    }
+    let frames = |mut trace: usize| {
+        let mut frames = Vec::new();
+        while let Some(&(ip, parent)) = traces.get(trace).filter(|_| trace != 0) {
+            let function = functions.get(ip).and_then(|function| strings.get(*function)).filter(|name| !name.is_empty());
+            frames.push(function.cloned().unwrap_or_else(|| "??".to_string()));
+            trace = parent;
+        }
+        caller_frames(frames)
+    };
+    let stacks = infos
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| counts[*index] > 0)
+        .map(|(index, (size, trace))| Stack {
+            frames: frames(*trace),
+            allocated: size * counts[index],
+            allocations: counts[index],
+            peak: at_peak[index].max(0) as u64,
+            leaked: at_end[index].max(0) as u64,
+        })
+        .collect();
+    Ok(Profile { tool: "heaptrack", command, peak: peak as u64, stacks: merge(stacks) })
+}
+
+// "0x4005F4: f (a.c:5)" as "f (a.c:5)".
+fn dhat_frame(frame: &str) -> String {
+    match frame.split_once(": ") {
+        Some((address, rest)) if address.starts_with("0x") => rest.to_string(),
+        _ => frame.to_string(),
+    }
+}
+
+// DHAT's JSON (--tool=dhat, read by dh_view.html): a program point per
+// stack in "pps", with total bytes and blocks ("tb", "tbk"), bytes at the
+// global peak ("gb") and at the end ("eb"), and its frames as indices into
+// "ftbl".
+pub fn parse_dhat(json: &Value) -> Result<Profile, String> {
+    if json["mode"].as_str().is_some_and(|mode| mode != "heap") {
+        return Err("The DHAT profile isn't of the heap (--mode=heap)".to_string());
+    }
+    let table: Vec<&str> = json["ftbl"].as_array().ok_or("The DHAT profile has no frame table")?.iter().map(|frame| frame.as_str().unwrap_or("??")).collect();
+    let points = json["pps"].as_array().ok_or("The DHAT profile has no program points")?;
+    let number = |point: &Value, key: &str| point[key].as_u64().unwrap_or(0);
+    let stacks = points
+        .iter()
+        .map(|point| {
+            let frames = point["fs"].as_array().into_iter().flatten().filter_map(|index| table.get(index.as_u64()? as usize)).map(|frame| dhat_frame(frame)).collect();
+            Stack { frames: caller_frames(frames), allocated: number(point, "tb"), allocations: number(point, "tbk"), peak: number(point, "gb"), leaked: number(point, "eb") }
+        })
+        .collect::<Vec<Stack>>();
+    let peak = stacks.iter().map(|stack| stack.peak).sum();
+    Ok(Profile { tool: "DHAT", command: json["cmd"].as_str().unwrap_or("").to_string(), peak, stacks: merge(stacks) })
+}
+
+// heaptrack.NAME.PID.zst (or .gz, or already decompressed) or DHAT JSON.
+pub fn read(path: &str) -> Result<Profile, String> {
+    let mut file = File::open(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let text = if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        let mut text = String::new();
+        ruzstd::decoding::StreamingDecoder::new(bytes.as_slice())
+            .map_err(|e| format!("Unable to decompress {}: {}", path, e))?
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Unable to decompress {}: {}", path, e))?;
+        text
+    } else if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(bytes.as_slice()).read_to_string(&mut text).map_err(|e| format!("Unable to decompress {}: {}", path, e))?;
+        text
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    if text.trim_start().starts_with('{') {
+        let json: Value = serde_json::from_str(&text).map_err(|e| format!("{} is not JSON: {}", path, e))?;
+        return parse_dhat(&json);
+    }
+    parse_heaptrack(&text)
+}
+
+pub fn print(profile: &Profile, limit: usize) {
+    println!("{} profile of {}: peak heap {}", profile.tool, profile.command, format_size(profile.peak as usize));
+    println!("{:>10} {:>10} {:>10} {:>12}  stack", "at peak", "leaked", "allocated", "allocations");
+    for stack in profile.stacks.iter().take(limit) {
+        println!("{:>10} {:>10} {:>10} {:>12}  {}", format_size(stack.peak as usize), format_size(stack.leaked as usize), format_size(stack.allocated as usize), format_count(stack.allocations as usize), stack.label());
+    }
+}
+
+// Where the top stacks' memory lives: small allocations in the heap (the
+// main arena, [heap]), those past glibc's mmap threshold in anonymous
+// mappings of their own, the largest of which takes their labels. Neither
+// profiler records addresses, so this is by size alone.
+pub fn annotations(profile: &Profile, memory_regions: &[MemoryRegion], limit: usize) -> Vec<(usize, String)> {
+    let anonymous = memory_regions
+        .iter()
+        .filter(|region| region.attributes.allocated && region.file_name.is_none() && region.attributes.writable && !region.attributes.shared)
+        .max_by_key(|region| region.size)
+        .map(|region| region.start);
+    let heap = memory_regions.iter().find(|region| region.file_name.as_deref() == Some("[heap]")).map(|region| region.start).or(anonymous);
+    profile
+        .stacks
+        .iter()
+        .take(limit)
+        .filter(|stack| stack.peak > 0)
+        .filter_map(|stack| {
+            let at = if stack.average() >= MMAP_THRESHOLD { anonymous } else { heap }?;
+            Some((at, format!("{} at peak: {}", format_size(stack.peak as usize), stack.label())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // heaptrack_interpret output, file format 3: main calls load_config,
+    // which mallocs 1 KiB three times, and mallocs 128 KiB once itself.
+    const HEAPTRACK: &str = "\
+v 10700 3
+X ./server --port 8080
+s 6 server
+s 4 main
+s 6 malloc
+s b load_config
+i 401000 1 2
+i 402000 1 4
+i 403000 1 3
+t 1 0
+t 2 1
+t 3 2
+t 3 1
+a 400 3
+a 20000 4
++ 0
++ 0
++ 1
+- 0
+- 1
++ 0
+";
+
+    const DHAT: &str = r#"{"dhatFileVersion": 2, "mode": "heap", "verb": "Allocated", "cmd": "./server", "pid": 1234, "te": 1000, "tg": 800,
+ "pps": [
+  {"tb": 3072, "tbk": 3, "tl": 100, "mb": 2048, "mbk": 2, "gb": 2048, "gbk": 2, "eb": 1024, "ebk": 1, "fs": [1, 2, 3]},
+  {"tb": 4096, "tbk": 1, "tl": 10, "mb": 4096, "mbk": 1, "gb": 0, "gbk": 0, "eb": 0, "ebk": 0, "fs": [1, 4]}
+ ],
+ "ftbl": ["[root]", "0x483DD99: malloc (in /usr/libexec/valgrind/vgpreload_dhat-amd64-linux.so)", "0x109C8E: load_config (config.c:17)", "0x109D12: main (server.c:40)", "0x109E00: main (server.c:52)"]}"#;
+
+    fn summary(profile: &Profile) -> Vec<(String, u64, u64, u64, u64)> {
+        profile.stacks.iter().map(|stack| (stack.label(), stack.peak, stack.leaked, stack.allocated, stack.allocations)).collect()
+    }
+
+    #[test]
+    fn parses_interpreted_heaptrack_data() {
+        let profile = parse_heaptrack(HEAPTRACK).unwrap();
+        assert_eq!((profile.command.as_str(), profile.peak), ("./server --port 8080", 2048 + 0x20000));
+        // Without the malloc frames, largest at the peak first.
+        assert_eq!(summary(&profile), [("main".to_string(), 0x20000, 0, 0x20000, 1), ("load_config < main".to_string(), 2048, 2048, 3072, 3)]);
+    }
+
+    #[test]
+    fn asks_for_interpreted_heaptrack_data() {
+        assert!(parse_heaptrack("v 10700 3\n+ 400 7f0000001000 3\n").is_err_and(|e| e.contains("heaptrack_interpret")));
+        assert!(parse_heaptrack("v 10700 3\n").is_err());
+    }
+
+    #[test]
+    fn parses_dhat_json() {
+        let profile = parse_dhat(&serde_json::from_str(DHAT).unwrap()).unwrap();
+        assert_eq!((profile.tool, profile.command.as_str(), profile.peak), ("DHAT", "./server", 2048));
+        assert_eq!(summary(&profile), [("load_config (config.c:17) < main (server.c:40)".to_string(), 2048, 1024, 3072, 3), ("main (server.c:52)".to_string(), 0, 0, 4096, 1)]);
+        assert!(parse_dhat(&serde_json::json!({"mode": "copy", "pps": [], "ftbl": []})).is_err());
+    }
+
+    #[test]
+    fn places_large_allocations_in_anonymous_mappings() {
+        let region = |line: &str| line.parse::<MemoryRegion>().unwrap();
+        let memory_regions = [region("55d4c4a1b000-55d4c4a3c000 rw-p 00000000 00:00 0 [heap]"), region("7f2a1c400000-7f2a1c800000 rw-p 00000000 00:00 0")];
+        let annotations = annotations(&parse_heaptrack(HEAPTRACK).unwrap(), &memory_regions, 10);
+        assert_eq!(annotations, [(0x7f2a1c400000, "128.0 KiB at peak: main".to_string()), (0x55d4c4a1b000, "2.0 KiB at peak: load_config < main".to_string())]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::snapshot::Snapshot;
+use crate::{AnimationFormat, MemoryRegion};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbImage};
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+pub fn load_series(dir: &str) -> Result<Vec<Snapshot>, Box<dyn std::error::Error>> {
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "mmsnap") {
+            snapshots.push(Snapshot::load(&path.to_string_lossy())?);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+    Ok(snapshots)
+}
+
+// Splits every frame at the boundaries of all frames, so each frame is the
+// same list of intervals, mapped or not. With bar heights derived from the
+// interval sizes alone, a given address sits at the same height throughout
+// the animation.
+pub fn align_frames(frames: &[Vec<MemoryRegion>]) -> Vec<Vec<MemoryRegion>> {
+    let mapped = |frame: &[MemoryRegion]| -> Vec<MemoryRegion> {
+        let mut regions: Vec<MemoryRegion> = frame.iter().filter(|region| region.attributes.allocated).cloned().collect();
+        regions.sort_by_key(|region| region.start);
+        regions
+    };
+    let frames: Vec<Vec<MemoryRegion>> = frames.iter().map(|frame| mapped(frame)).collect();
+
+    // One sweep over every start and end: between two neighbouring
+    // boundaries, some frame maps the interval while any region is open.
+    let mut edges: Vec<(usize, isize)> = frames.iter().flatten().flat_map(|region| [(region.start, 1), (region.end, -1)]).collect();
+    edges.sort_unstable();
+    let mut intervals = Vec::new();
+    let mut open = 0;
+    for (i, &(at, delta)) in edges.iter().enumerate() {
+        open += delta;
+        match edges.get(i + 1) {
+            Some(&(next, _)) if next > at && open > 0 => intervals.push((at, next)),
+            _ => {}
+        }
+    }
+    let covers = |region: &MemoryRegion, start: usize, end: usize| region.start <= start && end <= region.end;
+
+    frames
+        .iter()
+        .map(|frame| {
+            let mut next = 0;
+            intervals
+                .iter()
+                .map(|&(start, end)| {
+                    while next < frame.len() && frame[next].end <= start {
+                        next += 1;
+                    }
+                    match frame.get(next).filter(|region| covers(region, start, end)) {
+                        Some(region) => {
+                            let mut piece = region.clone();
+                            if piece.file_name.is_some() {
+                                piece.offset += start - region.start;
+                            }
+                            piece.start = start;
+                            piece.end = end;
+                            piece.size = end - start;
+                            piece
+                        }
+                        None => MemoryRegion::gap(start, end),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Frames are encoded as they come, so a long series never has to fit in
+// memory at once.
+pub fn write_gif<I: IntoIterator<Item = RgbImage>>(frames: I, delay: Duration, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = GifEncoder::new(File::create(path)?);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(delay.as_millis() as u32, 1);
+    encoder.encode_frames(frames.into_iter().map(|frame| {
+        let rgba = image::DynamicImage::ImageRgb8(frame).into_rgba8();
+        Frame::from_parts(rgba, 0, 0, delay)
+    }))?;
+    Ok(())
+}
+
+// Raw RGB frames piped through ffmpeg, at one frame per delay.
+pub fn write_video<I: IntoIterator<Item = RgbImage>>(frames: I, delay: Duration, path: &str, codec: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut frames = frames.into_iter().peekable();
+    let Some(first) = frames.peek() else { return Err("No frames to encode".into()) };
+    let size = format!("{}x{}", first.width(), first.height());
+    let rate = format!("{}", 1000.0 / delay.as_millis().max(1) as f64);
+    let mut child = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24", "-s", &size, "-framerate", &rate, "-i", "-"])
+        .args(codec)
+        // Most players want 4:2:0, which needs even dimensions.
+        .args(["-pix_fmt", "yuv420p", "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", path])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to run ffmpeg: {}", e))?;
+    let mut stdin = child.stdin.take().unwrap();
+    for frame in frames {
+        stdin.write_all(frame.as_raw())?;
+    }
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed: {}", status).into());
+    }
+    Ok(())
+}
+
+pub fn write<I: IntoIterator<Item = RgbImage>>(frames: I, delay: Duration, path: &str, format: AnimationFormat) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        AnimationFormat::Gif => write_gif(frames, delay, path),
+        AnimationFormat::Mp4 => write_video(frames, delay, path, &["-c:v", "libx264", "-movflags", "+faststart"]),
+        AnimationFormat::Webm => write_video(frames, delay, path, &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapped(start: usize, end: usize, path: &str) -> MemoryRegion {
+        format!("{:x}-{:x} r--p 00000000 00:00 0 {}", start, end, path).parse().unwrap()
+    }
+
+    #[test]
+    fn frames_share_their_intervals() {
+        let before = vec![mapped(0x1000, 0x3000, "/lib/a.so"), mapped(0x8000, 0x9000, "")];
+        let after = vec![mapped(0x2000, 0x5000, "/lib/a.so")];
+        let aligned = align_frames(&[before, after]);
+        let intervals = |frame: &[MemoryRegion]| frame.iter().map(|region| (region.start, region.end, region.attributes.allocated)).collect::<Vec<_>>();
+        assert_eq!(
+            intervals(&aligned[0]),
+            [(0x1000, 0x2000, true), (0x2000, 0x3000, true), (0x3000, 0x5000, false), (0x8000, 0x9000, true)]
+        );
+        assert_eq!(
+            intervals(&aligned[1]),
+            [(0x1000, 0x2000, false), (0x2000, 0x3000, true), (0x3000, 0x5000, true), (0x8000, 0x9000, false)]
+        );
+        // A piece of a file mapping keeps its place in the file.
+        assert_eq!(aligned[1][2].offset, 0x1000);
+    }
+
+    #[test]
+    fn unmapped_everywhere_is_left_out() {
+        let aligned = align_frames(&[vec![mapped(0x1000, 0x2000, "")], vec![mapped(0x4000, 0x5000, "")]]);
+        assert!(aligned.iter().all(|frame| frame.iter().map(|region| (region.start, region.end)).eq([(0x1000, 0x2000), (0x4000, 0x5000)])));
+    }
+}
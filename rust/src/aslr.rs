@@ -0,0 +1,113 @@
+use crate::{parse_memory_regions, MemoryRegion};
+use plotters::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+pub struct ObjectEntropy {
+    pub name: String,
+    pub samples: usize,
+    pub distinct: usize,
+    pub varying_bits: u32,
+}
+
+// One base address per object of interest: the lowest mapping of the
+// executable and of each shared library, plus the pseudo regions.
+pub fn base_addresses(memory_regions: &[MemoryRegion], exe: Option<&str>) -> BTreeMap<String, usize> {
+    let mut bases = BTreeMap::new();
+    for region in memory_regions {
+        let name = match region.file_name.as_deref() {
+            Some(path) if Some(path) == exe => "executable".to_string(),
+            Some(name @ ("[heap]" | "[vdso]" | "[vvar]")) => name.to_string(),
+            Some("[stack]") => "[stack]".to_string(),
+            Some(path) if path.contains(".so") => path.rsplit('/').next().unwrap_or(path).to_string(),
+            _ => continue,
+        };
+        // The stack grows down from its top, so that is the randomized base.
+        let base = if name == "[stack]" { region.end } else { region.start };
+        bases.entry(name).or_insert(base);
+    }
+    bases
+}
+
+pub fn sample_launches(command: &[String], runs: usize, delay: Duration) -> Result<Vec<BTreeMap<String, usize>>, String> {
+    let (program, args) = command.split_first().ok_or("No command given")?;
+    let mut samples = Vec::new();
+
+    for _ in 0..runs {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Unable to launch {}: {}", program, e))?;
+        thread::sleep(delay);
+
+        let pid = child.id();
+        let maps = fs::read_to_string(format!("/proc/{}/maps", pid));
+        let exe = fs::read_link(format!("/proc/{}/exe", pid)).ok();
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let maps = maps.map_err(|e| format!("Unable to read maps of {} (did it exit early?): {}", program, e))?;
+        let exe = exe.map(|path| path.to_string_lossy().into_owned());
+        samples.push(base_addresses(&parse_memory_regions(maps.as_bytes()).0, exe.as_deref()));
+    }
+    Ok(samples)
+}
+
+// With a handful of runs the sample can't show the full distribution, so
+// report the number of address bits that changed at least once, an upper
+// bound on the randomization actually applied.
+pub fn entropy(samples: &[BTreeMap<String, usize>]) -> Vec<ObjectEntropy> {
+    let names: BTreeSet<&String> = samples.iter().flat_map(|sample| sample.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let bases: Vec<usize> = samples.iter().filter_map(|sample| sample.get(name).copied()).collect();
+            let first = bases.first().copied().unwrap_or(0);
+            let varying = bases.iter().fold(0usize, |acc, base| acc | (base ^ first));
+            ObjectEntropy {
+                name: name.clone(),
+                samples: bases.len(),
+                distinct: bases.iter().collect::<BTreeSet<_>>().len(),
+                varying_bits: varying.count_ones(),
+            }
+        })
+        .collect()
+}
+
+pub fn draw_entropy_chart(entropies: &[ObjectEntropy], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let height = 60 + 22 * entropies.len().max(1) as u32;
+    let root = BitMapBackend::new(path, (700, height)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let max_bits = entropies.iter().map(|e| e.varying_bits).max().unwrap_or(1).max(1);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("ASLR: randomized address bits per object", ("sans-serif", 16))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(180)
+        .build_cartesian_2d(0u32..max_bits + 2, (0..entropies.len()).into_segmented())?;
+
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_desc("varying bits")
+        .y_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => entropies.get(*i).map(|e| e.name.clone()).unwrap_or_default(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    chart.draw_series(entropies.iter().enumerate().map(|(i, e)| {
+        let color = if e.varying_bits == 0 { RED } else { BLUE };
+        Rectangle::new([(0, SegmentValue::Exact(i)), (e.varying_bits, SegmentValue::Exact(i + 1))], color.mix(0.7).filled())
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use crate::{audit, format_count, format_size, fragmentation, MemoryRegion};
+use std::str::FromStr;
+
+// Status for a --fail-if that held, apart from 1 for --audit findings and
+// errors and 2 for usage errors, so a gate can tell them apart.
+pub const FAILED_STATUS: i32 = 3;
+pub const UNCHECKED_STATUS: i32 = 2;
+
+// What --fail-if can test: name, whether it counts bytes, whether it needs
+// smaps.
+const METRICS: [(&str, bool, bool); 9] = [
+    ("mapped", true, false),
+    ("rss", true, true),
+    ("pss", true, true),
+    ("swap", true, true),
+    ("locked", true, true),
+    ("largest", true, false),
+    ("regions", false, false),
+    ("wx-regions", false, false),
+    ("deleted-mappings", false, false),
+];
+
+// Two-character operators first, so ">=" isn't read as ">".
+const OPERATORS: [&str; 7] = [">=", "<=", "==", "!=", ">", "<", "="];
+
+#[derive(Clone)]
+pub struct Assertion {
+    text: String,
+    metric: &'static str,
+    operator: &'static str,
+    limit: u64,
+}
+
+impl FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A count on its own asks whether there are any.
+        if METRICS.iter().any(|(metric, bytes, _)| *metric == s.trim() && !bytes) {
+            return format!("{}>0", s.trim()).parse().map(|assertion: Assertion| Assertion { text: s.to_string(), ..assertion });
+        }
+        let position = s.find(['<', '>', '=', '!']).ok_or_else(|| format!("No comparison in {}, expected e.g. rss>2G", s))?;
+        let operator = OPERATORS.into_iter().find(|operator| s[position..].starts_with(operator)).ok_or_else(|| format!("Unknown comparison in {}", s))?;
+        let name = s[..position].trim();
+        let (metric, bytes, _) = METRICS.into_iter().find(|(metric, _, _)| *metric == name).ok_or_else(|| {
+            let known: Vec<&str> = METRICS.iter().map(|(metric, _, _)| *metric).collect();
+            format!("Unknown metric {} in {}, expected one of {}", name, s, known.join(", "))
+        })?;
+        let value = s[position + operator.len()..].trim();
+        let limit = if bytes { fragmentation::parse_size(value)? } else { value.parse().map_err(|_| format!("Invalid count {} in {}", value, s))? };
+        Ok(Assertion { text: s.to_string(), metric, operator, limit })
+    }
+}
+
+impl Assertion {
+    pub fn needs_smaps(&self) -> bool {
+        METRICS.iter().any(|(metric, _, smaps)| *metric == self.metric && *smaps)
+    }
+
+    fn measure(&self, memory_regions: &[MemoryRegion]) -> u64 {
+        let mapped = memory_regions.iter().filter(|region| region.attributes.allocated);
+        let smaps = memory_regions.iter().filter_map(|region| region.smaps.as_ref());
+        (match self.metric {
+            "mapped" => mapped.map(|region| region.size).sum(),
+            "rss" => smaps.map(|smaps| smaps.rss).sum(),
+            "pss" => smaps.map(|smaps| smaps.pss).sum(),
+            "swap" => smaps.map(|smaps| smaps.swap).sum(),
+            "locked" => smaps.map(|smaps| smaps.locked).sum(),
+            "largest" => mapped.map(|region| region.size).max().unwrap_or(0),
+            "regions" => mapped.count(),
+            "wx-regions" => mapped.filter(|region| audit::is_writable_executable(region)).count(),
+            _ => mapped.filter(|region| audit::is_deleted_mapping(region)).count(),
+        }) as u64
+    }
+
+    fn holds(&self, value: u64) -> bool {
+        match self.operator {
+            ">=" => value >= self.limit,
+            "<=" => value <= self.limit,
+            "!=" => value != self.limit,
+            ">" => value > self.limit,
+            "<" => value < self.limit,
+            _ => value == self.limit,
+        }
+    }
+
+    fn format(&self, value: u64) -> String {
+        match METRICS.iter().any(|(metric, bytes, _)| *metric == self.metric && *bytes) {
+            true => format_size(value as usize),
+            false => format_count(value as usize),
+        }
+    }
+}
+
+// Prints a line per assertion and gives the exit status the run should end
+// with, if any held or couldn't be checked.
+pub fn check(assertions: &[Assertion], memory_regions: &[MemoryRegion]) -> Option<i32> {
+    let has_smaps = memory_regions.iter().any(|region| region.smaps.is_some());
+    let mut status = None;
+    for assertion in assertions {
+        if assertion.needs_smaps() && !has_smaps {
+            eprintln!("fail-if {}: not checked, there is no smaps detail", assertion.text);
+            status = status.or(Some(UNCHECKED_STATUS));
+            continue;
+        }
+        let value = assertion.measure(memory_regions);
+        if assertion.holds(value) {
+            eprintln!("fail-if {}: FAILED, {} is {}", assertion.text, assertion.metric, assertion.format(value));
+            status = Some(FAILED_STATUS);
+        } else {
+            eprintln!("fail-if {}: ok, {} is {}", assertion.text, assertion.metric, assertion.format(value));
+        }
+    }
+    status
+}
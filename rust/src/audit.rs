@@ -0,0 +1,67 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::guards;
+use crate::MemoryRegion;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::report::hex;
+#[cfg(not(target_arch = "wasm32"))]
+use serde::Serialize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize)]
+pub struct Finding {
+    pub kind: &'static str,
+    #[serde(serialize_with = "hex")]
+    pub start: usize,
+    #[serde(serialize_with = "hex")]
+    pub end: usize,
+    pub perms: String,
+    pub path: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{:#x}-{:#x}\t{}\t{}", self.kind, self.start, self.end, self.perms, self.path.as_deref().unwrap_or("-"))
+    }
+}
+
+pub fn is_writable_executable(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && region.attributes.writable && region.attributes.executable
+}
+
+// A file replaced or removed while mapped, most often a library an upgrade
+// swapped out under a process that never restarted. memfd, shmem and
+// ashmem paths always read deleted and are left out.
+pub fn is_deleted_mapping(region: &MemoryRegion) -> bool {
+    region.attributes.allocated
+        && region.shared_memory_kind().is_none()
+        && region.anon_name().is_none()
+        && region.file_name.as_deref().is_some_and(|path| path.starts_with('/') && path.ends_with(" (deleted)"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Finding {
+    fn new(kind: &'static str, region: &MemoryRegion) -> Self {
+        Finding {
+            kind,
+            start: region.start,
+            end: region.end,
+            perms: region.attributes.perms(),
+            path: region.file_name.clone(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn audit(memory_regions: &[MemoryRegion]) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = memory_regions
+        .iter()
+        .filter(|region| is_writable_executable(region))
+        .map(|region| Finding::new("wx-region", region))
+        .collect();
+    findings.extend(guards::unguarded_stacks(memory_regions).into_iter().map(|region| Finding::new("stack-without-guard", region)));
+    findings.extend(memory_regions.iter().filter(|region| is_deleted_mapping(region)).map(|region| Finding::new("deleted-mapping", region)));
+    findings
+}
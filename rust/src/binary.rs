@@ -0,0 +1,181 @@
+use crate::{MemoryAttributes, MemoryRegion};
+use object::elf::{FileHeader32, FileHeader64, PF_R, PF_W, PF_X, PT_LOAD, SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE};
+use object::read::elf::{ElfFile, FileHeader, ProgramHeader};
+use object::pe::{IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE};
+use object::{Endianness, FileKind, Object, ObjectSection, ObjectSegment, SectionFlags, SectionKind, SegmentFlags};
+use std::fs;
+
+pub struct Segment {
+    pub kind: String,
+    pub address: u64,
+    pub size: u64,
+    pub flags: u32,
+}
+
+impl Segment {
+    pub fn perms(&self) -> String {
+        let flag = |bit: u32, c: char| if self.flags & bit != 0 { c } else { '-' };
+        [flag(PF_R, 'r'), flag(PF_W, 'w'), flag(PF_X, 'x')].iter().collect()
+    }
+}
+
+fn segment_kind(p_type: u32) -> String {
+    match p_type {
+        object::elf::PT_NULL => "NULL".to_string(),
+        PT_LOAD => "LOAD".to_string(),
+        object::elf::PT_DYNAMIC => "DYNAMIC".to_string(),
+        object::elf::PT_INTERP => "INTERP".to_string(),
+        object::elf::PT_NOTE => "NOTE".to_string(),
+        object::elf::PT_PHDR => "PHDR".to_string(),
+        object::elf::PT_TLS => "TLS".to_string(),
+        object::elf::PT_GNU_EH_FRAME => "GNU_EH_FRAME".to_string(),
+        object::elf::PT_GNU_STACK => "GNU_STACK".to_string(),
+        object::elf::PT_GNU_RELRO => "GNU_RELRO".to_string(),
+        object::elf::PT_GNU_PROPERTY => "GNU_PROPERTY".to_string(),
+        other => format!("{:#x}", other),
+    }
+}
+
+fn region(start: u64, size: u64, offset: u64, readable: bool, writable: bool, executable: bool, name: &str) -> MemoryRegion {
+    MemoryRegion {
+        start: start as usize,
+        end: (start + size) as usize,
+        size: size as usize,
+        attributes: MemoryAttributes { readable, writable, executable, shared: false, allocated: true },
+        offset: offset as usize,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name.to_string()),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// Whatever part of a loadable segment no section covers (headers,
+// alignment padding) is shown as the segment itself.
+fn fill_segments<F: Fn(&Segment) -> bool>(segments: &[Segment], sections: &[MemoryRegion], loadable: F) -> Vec<MemoryRegion> {
+    let mut regions = Vec::new();
+    for segment in segments.iter().filter(|segment| loadable(segment) && segment.size > 0) {
+        let (start, end) = (segment.address as usize, (segment.address + segment.size) as usize);
+        let perms = (segment.flags & PF_R != 0, segment.flags & PF_W != 0, segment.flags & PF_X != 0);
+        let mut cursor = start;
+        for section in sections.iter().filter(|section| section.start >= start && section.end <= end) {
+            if section.start > cursor {
+                regions.push(region(cursor as u64, (section.start - cursor) as u64, 0, perms.0, perms.1, perms.2, &segment.kind));
+            }
+            if section.start >= cursor {
+                regions.push(section.clone());
+                cursor = section.end;
+            }
+        }
+        if cursor < end {
+            regions.push(region(cursor as u64, (end - cursor) as u64, 0, perms.0, perms.1, perms.2, &segment.kind));
+        }
+    }
+    regions.sort_by_key(|region| region.start);
+    regions
+}
+
+fn parse_elf<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<(Vec<Segment>, Vec<MemoryRegion>), Box<dyn std::error::Error>> {
+    let file = ElfFile::<Elf>::parse(data)?;
+    let endian = file.endian();
+
+    let segments: Vec<Segment> = file
+        .elf_program_headers()
+        .iter()
+        .map(|header| Segment {
+            kind: segment_kind(header.p_type(endian)),
+            address: header.p_vaddr(endian).into(),
+            size: header.p_memsz(endian).into(),
+            flags: header.p_flags(endian),
+        })
+        .collect();
+
+    // .tbss occupies no address space of its own; it is only the template
+    // size for each thread's TLS block and would overlap the next section.
+    let mut sections: Vec<MemoryRegion> = file
+        .sections()
+        .filter_map(|section| {
+            let SectionFlags::Elf { sh_flags } = section.flags() else { return None };
+            if sh_flags & SHF_ALLOC as u64 == 0 || section.size() == 0 || section.kind() == SectionKind::UninitializedTls {
+                return None;
+            }
+            let offset = section.file_range().map_or(0, |(offset, _)| offset);
+            let name = section.name().unwrap_or("?");
+            Some(region(section.address(), section.size(), offset, true, sh_flags & SHF_WRITE as u64 != 0, sh_flags & SHF_EXECINSTR as u64 != 0, name))
+        })
+        .collect();
+    sections.sort_by_key(|section| section.start);
+
+    let regions = fill_segments(&segments, &sections, |segment| segment.kind == "LOAD");
+    Ok((segments, regions))
+}
+
+fn segment_flags(flags: SegmentFlags) -> u32 {
+    match flags {
+        SegmentFlags::MachO { initprot, .. } => {
+            let prot = |bit: u32, flag: u32| if initprot & bit != 0 { flag } else { 0 };
+            prot(object::macho::VM_PROT_READ, PF_R) | prot(object::macho::VM_PROT_WRITE, PF_W) | prot(object::macho::VM_PROT_EXECUTE, PF_X)
+        }
+        SegmentFlags::Coff { characteristics } => section_flags(SectionFlags::Coff { characteristics }),
+        SegmentFlags::Elf { p_flags } => p_flags,
+        _ => 0,
+    }
+}
+
+fn section_flags(flags: SectionFlags) -> u32 {
+    match flags {
+        SectionFlags::Coff { characteristics } => {
+            let characteristic = |bit: u32, flag: u32| if characteristics & bit != 0 { flag } else { 0 };
+            characteristic(IMAGE_SCN_MEM_READ, PF_R) | characteristic(IMAGE_SCN_MEM_WRITE, PF_W) | characteristic(IMAGE_SCN_MEM_EXECUTE, PF_X)
+        }
+        _ => 0,
+    }
+}
+
+// PE and Mach-O through the format independent reader. PE has no program
+// headers, so each section doubles as its own segment; Mach-O sections
+// carry no protection and take their segment's.
+fn parse_object(data: &[u8]) -> Result<(Vec<Segment>, Vec<MemoryRegion>), Box<dyn std::error::Error>> {
+    let file = object::File::parse(data)?;
+    let segments: Vec<Segment> = file
+        .segments()
+        .map(|segment| Segment {
+            kind: segment.name().ok().flatten().unwrap_or("?").to_string(),
+            address: segment.address(),
+            size: segment.size(),
+            flags: segment_flags(segment.flags()),
+        })
+        .collect();
+
+    let mut sections: Vec<MemoryRegion> = file
+        .sections()
+        .filter(|section| section.address() != 0 && section.size() > 0)
+        .map(|section| {
+            let containing = segments.iter().find(|segment| section.address() >= segment.address && section.address() < segment.address + segment.size);
+            let flags = match section.flags() {
+                SectionFlags::MachO { .. } => containing.map_or(0, |segment| segment.flags),
+                flags => section_flags(flags),
+            };
+            let offset = section.file_range().map_or(0, |(offset, _)| offset);
+            let name = section.name().unwrap_or("?");
+            region(section.address(), section.size(), offset, flags & PF_R != 0, flags & PF_W != 0, flags & PF_X != 0, name)
+        })
+        .collect();
+    sections.sort_by_key(|section| section.start);
+
+    let regions = fill_segments(&segments, &sections, |_| true);
+    Ok((segments, regions))
+}
+
+pub fn read_binary_layout(path: &str) -> Result<(Vec<Segment>, Vec<MemoryRegion>), Box<dyn std::error::Error>> {
+    let data = fs::read(path)?;
+    match FileKind::parse(&*data)? {
+        FileKind::Elf32 => parse_elf::<FileHeader32<Endianness>>(&data),
+        FileKind::Elf64 => parse_elf::<FileHeader64<Endianness>>(&data),
+        FileKind::Pe32 | FileKind::Pe64 | FileKind::MachO32 | FileKind::MachO64 => parse_object(&data),
+        _ => Err(format!("{} is not an ELF, PE or Mach-O file", path).into()),
+    }
+}
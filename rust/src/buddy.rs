@@ -0,0 +1,188 @@
+use crate::{format_count, format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::fs;
+
+const CELL_WIDTH: i32 = 64;
+const ROW_HEIGHT: i32 = 26;
+const LABEL_WIDTH: i32 = 150;
+const TREND_HEIGHT: i32 = 220;
+
+pub struct Zone {
+    // "Node 0 Normal".
+    pub name: String,
+    // Free blocks of each order, 2^order pages apiece.
+    pub free: Vec<usize>,
+}
+
+impl Zone {
+    pub fn free_pages(&self) -> usize {
+        self.free.iter().enumerate().map(|(order, count)| count << order).sum()
+    }
+
+    // The kernel's unusable free space index for an order
+    // (/sys/kernel/debug/extfrag/unusable_index): the share of free pages
+    // in blocks too small to satisfy it. 0 when nothing is free.
+    pub fn unusable_index(&self, order: usize) -> f64 {
+        let total = self.free_pages();
+        if total == 0 {
+            return 0.0;
+        }
+        let usable: usize = self.free.iter().enumerate().skip(order).map(|(order, count)| count << order).sum();
+        (total - usable) as f64 / total as f64
+    }
+}
+
+// "Node 0, zone   Normal   8688  10493   3928 ..." per line of
+// /proc/buddyinfo, one count per order up to MAX_ORDER.
+pub fn parse(text: &str) -> Result<Vec<Zone>, String> {
+    let mut zones = Vec::new();
+    for line in text.lines() {
+        let Some((node, rest)) = line.split_once(", zone") else { continue };
+        let mut fields = rest.split_whitespace();
+        let zone = fields.next().ok_or_else(|| format!("Invalid buddyinfo line: {}", line))?;
+        let free = fields.map(|field| field.parse::<usize>().map_err(|_| format!("Invalid buddyinfo count: {}", field))).collect::<Result<Vec<usize>, String>>()?;
+        zones.push(Zone { name: format!("{} {}", node.trim(), zone), free });
+    }
+    if zones.is_empty() {
+        return Err("No zones in buddyinfo".to_string());
+    }
+    Ok(zones)
+}
+
+pub fn read(path: &str) -> Result<Vec<Zone>, String> {
+    parse(&fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?)
+}
+
+// The order a transparent hugepage takes, 9 for 2 MiB pages of 4 KiB.
+pub fn hugepage_order(page_size: usize) -> usize {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/hpage_pmd_size")
+        .ok()
+        .and_then(|size| size.trim().parse::<usize>().ok())
+        .filter(|size| *size >= page_size)
+        .map_or(9, |size| (size / page_size).trailing_zeros() as usize)
+}
+
+pub fn print(zones: &[Zone], page_size: usize, order: usize) {
+    let orders = zones.iter().map(|zone| zone.free.len()).max().unwrap_or(0);
+    print!("{:<16}", "zone");
+    for order in 0..orders {
+        print!(" {:>9}", format_size(page_size << order));
+    }
+    println!(" {:>10}  unusable at {}", "free", format_size(page_size << order));
+    for zone in zones {
+        print!("{:<16}", zone.name);
+        for count in &zone.free {
+            print!(" {:>9}", format_count(*count));
+        }
+        println!(" {:>10}  {:.3}", format_size(zone.free_pages() * page_size), zone.unusable_index(order));
+    }
+}
+
+// Dark for no free blocks, brightening with the log of the free memory
+// they hold, so the few large blocks show next to the many small ones.
+fn heat(pages: usize, most: usize) -> RGBColor {
+    if pages == 0 {
+        return RGBColor(30, 30, 40);
+    }
+    let t = ((pages as f64).ln_1p() / (most as f64).ln_1p()).clamp(0.0, 1.0);
+    RGBColor((40.0 + t * 215.0) as u8, (40.0 + t * 140.0) as u8, (90.0 - t * 60.0) as u8)
+}
+
+// A heatmap of free memory per zone and order, and below it, once there
+// is more than one sample, each zone's unusable index at `order` over the
+// seconds sampled.
+pub fn draw(zones: &[Zone], page_size: usize, order: usize, trend: &[(f64, Vec<f64>)], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let orders = zones.iter().map(|zone| zone.free.len()).max().unwrap_or(0);
+    let grid_top = 56;
+    let trend_top = grid_top + zones.len() as i32 * ROW_HEIGHT + 20;
+    let width = px(LABEL_WIDTH + orders as i32 * CELL_WIDTH + 90);
+    let height = px(trend_top + if trend.len() > 1 { TREND_HEIGHT } else { 0 } + 10);
+    let root = BitMapBackend::new(path, (width as u32, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let title = format!("buddy allocator: free blocks per order, unusable index at {}", format_size(page_size << order));
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let most = zones.iter().flat_map(|zone| zone.free.iter().enumerate().map(|(order, count)| count << order)).max().unwrap_or(1);
+    for order in 0..orders {
+        let x = px(LABEL_WIDTH + order as i32 * CELL_WIDTH);
+        root.draw(&Text::new(format_size(page_size << order), (x + px(4), px(grid_top - 16)), font.color(&ink)))?;
+    }
+    root.draw(&Text::new("unusable", (px(LABEL_WIDTH + orders as i32 * CELL_WIDTH + 8), px(grid_top - 16)), font.color(&ink)))?;
+    for (row, zone) in zones.iter().enumerate() {
+        let y = px(grid_top + row as i32 * ROW_HEIGHT);
+        root.draw(&Text::new(zone.name.clone(), (px(10), y + px(7)), font.color(&ink)))?;
+        for (column, count) in zone.free.iter().enumerate() {
+            let x = px(LABEL_WIDTH + column as i32 * CELL_WIDTH);
+            let color = heat(count << column, most);
+            root.draw(&Rectangle::new([(x, y), (x + px(CELL_WIDTH - 2), y + px(ROW_HEIGHT - 2))], color.filled()))?;
+            // A hugepage needs a block of at least its order; the line
+            // marks where those start.
+            if column == order {
+                root.draw(&Rectangle::new([(x - px(2), y), (x - px(1), y + px(ROW_HEIGHT - 2))], RED.filled()))?;
+            }
+            let text_color = if color.0 > 150 { BLACK } else { WHITE };
+            root.draw(&Text::new(format_count(*count), (x + px(4), y + px(7)), font.color(&text_color)))?;
+        }
+        let index = zone.unusable_index(order);
+        root.draw(&Text::new(format!("{:.3}", index), (px(LABEL_WIDTH + orders as i32 * CELL_WIDTH + 8), y + px(7)), font.color(if index > 0.9 { &RED } else { &ink })))?;
+    }
+
+    if trend.len() > 1 {
+        let area = root.margin(px(trend_top), px(10), px(10), px(20));
+        let last = trend.last().map_or(1.0, |(seconds, _)| seconds.max(1.0));
+        let mut chart = ChartBuilder::on(&area)
+            .caption("unusable index over time", ("sans-serif", 12.0 * options.scale_factor).into_font().color(&ink))
+            .x_label_area_size(px(22))
+            .y_label_area_size(px(40))
+            .build_cartesian_2d(0.0..last, 0.0..1.0)?;
+        chart.configure_mesh().x_desc("seconds").label_style(font.color(&ink)).axis_style(ink).light_line_style(ink.mix(0.1)).draw()?;
+        for (index, zone) in zones.iter().enumerate() {
+            let color = Palette99::pick(index).to_rgba();
+            chart
+                .draw_series(LineSeries::new(trend.iter().filter_map(|(seconds, indices)| Some((*seconds, *indices.get(index)?))), color.stroke_width(2)))?
+                .label(zone.name.clone())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 14, y)], color.stroke_width(2)));
+        }
+        chart.configure_series_labels().label_font(font.color(&ink)).background_style(options.background().mix(0.8)).border_style(ink).draw()?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDDYINFO: &str = "\
+Node 0, zone      DMA      0      0      0      0      0      0      0      0      1      1      3
+Node 0, zone    DMA32      6      5      4      6      4      5      5      5      6      3    731
+Node 0, zone   Normal   8688  10493   3928    824    236     79     23     11      4      0      0
+";
+
+    #[test]
+    fn parses_buddyinfo() {
+        let zones = parse(BUDDYINFO).unwrap();
+        assert_eq!(zones.iter().map(|zone| zone.name.as_str()).collect::<Vec<_>>(), ["Node 0 DMA", "Node 0 DMA32", "Node 0 Normal"]);
+        assert_eq!(zones[2].free, [8688, 10493, 3928, 824, 236, 79, 23, 11, 4, 0, 0]);
+        assert_eq!(zones[0].free_pages(), (1 << 8) + (1 << 9) + 3 * (1 << 10));
+    }
+
+    #[test]
+    fn unusable_index_counts_blocks_below_the_order() {
+        let zones = parse(BUDDYINFO).unwrap();
+        // Normal has no free block of order 9 or above.
+        assert_eq!(zones[2].unusable_index(9), 1.0);
+        assert_eq!(zones[0].unusable_index(0), 0.0);
+        let empty = Zone { name: "Node 0 Movable".to_string(), free: vec![0; 11] };
+        assert_eq!(empty.unusable_index(9), 0.0);
+    }
+
+    #[test]
+    fn rejects_bad_counts() {
+        assert!(parse("Node 0, zone   Normal   12 x 3\n").is_err());
+        assert!(parse("").is_err());
+    }
+}
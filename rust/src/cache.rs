@@ -0,0 +1,94 @@
+use crate::validate::{ParseWarning, Warnings};
+use crate::{capture, name_hash, parse_memory_regions, smaps, MemoryRegion};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Region lists of live processes as parsed, so a render with other options
+// or a server poll of an unchanged process skips smaps. An entry is keyed
+// by pid and the process's start time, which a reused pid doesn't share,
+// and holds the hash of the maps it was parsed with: any mapping change
+// misses. smaps figures move without the maps changing, so those entries
+// also expire after the given age.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    maps_hash: u64,
+    // Milliseconds since the epoch.
+    taken: u64,
+    regions: Vec<MemoryRegion>,
+    // The lines skipped parsing them, reported again on every hit.
+    skipped_lines: usize,
+    parse_warnings: Vec<ParseWarning>,
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("memory-map-visualizer"))
+}
+
+// Field 22 of stat, in clock ticks since boot.
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    stat.rsplit_once(')')?.1.split_whitespace().nth(19)?.parse().ok()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+fn entry_name(pid: u32, start: u64, smaps: bool) -> String {
+    format!("{}-{}-{}.bin", pid, start, if smaps { "smaps" } else { "maps" })
+}
+
+// Entries of processes that are gone, which nothing would ever hit again.
+fn prune(dir: &std::path::Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let mut fields = name.splitn(3, '-');
+        let live = match (fields.next().and_then(|pid| pid.parse::<u32>().ok()), fields.next().and_then(|start| start.parse::<u64>().ok())) {
+            (Some(pid), Some(start)) => start_time(pid) == Some(start),
+            _ => true,
+        };
+        if !live {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+pub fn regions(pid: u32, smaps: bool, max_age: Duration) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+    let maps = capture::read_proc_file(pid, "maps")?;
+    let maps_hash = name_hash(&maps);
+    let path = cache_dir().zip(start_time(pid)).map(|(dir, start)| dir.join(entry_name(pid, start, smaps)));
+    let cached = path.as_ref().and_then(|path| bincode::deserialize::<Entry>(&fs::read(path).ok()?).ok());
+    if let Some(entry) = cached.filter(|entry| entry.maps_hash == maps_hash && (!smaps || now().saturating_sub(entry.taken) <= max_age.as_millis() as u64)) {
+        tracing::debug!(pid, regions = entry.regions.len(), "cache hit");
+        let warnings = Warnings { skipped_lines: entry.skipped_lines, parse_warnings: entry.parse_warnings, ..Warnings::default() };
+        return Ok((entry.regions, warnings));
+    }
+
+    let (regions, warnings) = if smaps {
+        let text = capture::read_proc_file(pid, "smaps")?;
+        smaps::parse_smaps(text.as_bytes())
+    } else {
+        parse_memory_regions(maps.as_bytes())
+    };
+    if let Some(path) = path {
+        let entry = Entry { maps_hash, taken: now(), regions, skipped_lines: warnings.skipped_lines, parse_warnings: warnings.parse_warnings.clone() };
+        let stored = fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string()).and_then(|_| {
+            let data = bincode::serialize(&entry).map_err(|e| e.to_string())?;
+            fs::write(&path, data).map_err(|e| e.to_string())
+        });
+        if let Err(e) = stored {
+            tracing::debug!("Unable to cache {}: {}", path.display(), e);
+        }
+        prune(path.parent().unwrap());
+        return Ok((entry.regions, warnings));
+    }
+    Ok((regions, warnings))
+}
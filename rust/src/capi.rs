@@ -0,0 +1,125 @@
+// The C interface declared in include/memlayout.h, which also spells out
+// the safety contract of every function here.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{compose_image, embedded_input, parse_memory_regions, validate, MemoryRegion};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+pub struct MmvRegions {
+    memory_regions: Vec<MemoryRegion>,
+    // Kept alongside so mmv_region_path can hand out stable pointers.
+    paths: Vec<Option<CString>>,
+}
+
+#[repr(C)]
+pub struct MmvRegion {
+    pub start: u64,
+    pub end: u64,
+    pub offset: u64,
+    pub inode: u64,
+    pub readable: u8,
+    pub writable: u8,
+    pub executable: u8,
+    pub shared: u8,
+}
+
+// Parses the text of a maps or smaps file of `len` bytes, which needn't be
+// NUL-terminated. Lines that don't parse are skipped, as on the command
+// line; only text that isn't UTF-8 returns null.
+#[no_mangle]
+pub unsafe extern "C" fn mmv_parse_maps(text: *const c_char, len: usize) -> *mut MmvRegions {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let bytes = std::slice::from_raw_parts(text as *const u8, len);
+    if std::str::from_utf8(bytes).is_err() {
+        return ptr::null_mut();
+    }
+    let (memory_regions, _) = validate::validate(parse_memory_regions(bytes).0);
+    let paths = memory_regions.iter().map(|region| region.file_name.as_ref().and_then(|name| CString::new(name.as_str()).ok())).collect();
+    Box::into_raw(Box::new(MmvRegions { memory_regions, paths }))
+}
+
+// The calling process's own /proc/self/maps; null if it can't be read.
+#[no_mangle]
+pub extern "C" fn mmv_parse_self() -> *mut MmvRegions {
+    let Ok(maps) = std::fs::read_to_string("/proc/self/maps") else { return ptr::null_mut() };
+    unsafe { mmv_parse_maps(maps.as_ptr() as *const c_char, maps.len()) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mmv_regions_count(regions: *const MmvRegions) -> usize {
+    regions.as_ref().map_or(0, |regions| regions.memory_regions.len())
+}
+
+// Fills `out` with region `index`; returns 0, or -1 if there is no such region.
+#[no_mangle]
+pub unsafe extern "C" fn mmv_region_get(regions: *const MmvRegions, index: usize, out: *mut MmvRegion) -> c_int {
+    let (Some(regions), Some(out)) = (regions.as_ref(), out.as_mut()) else { return -1 };
+    let Some(region) = regions.memory_regions.get(index) else { return -1 };
+    let attributes = &region.attributes;
+    *out = MmvRegion {
+        start: region.start as u64,
+        end: region.end as u64,
+        offset: region.offset as u64,
+        inode: region.inode,
+        readable: attributes.readable as u8,
+        writable: attributes.writable as u8,
+        executable: attributes.executable as u8,
+        shared: attributes.shared as u8,
+    };
+    0
+}
+
+// The path or pseudo-path ("[heap]") of region `index`, or null for an
+// anonymous region. It lives as long as `regions`.
+#[no_mangle]
+pub unsafe extern "C" fn mmv_region_path(regions: *const MmvRegions, index: usize) -> *const c_char {
+    regions.as_ref().and_then(|regions| regions.paths.get(index)).and_then(Option::as_ref).map_or(ptr::null(), |path| path.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mmv_regions_free(regions: *mut MmvRegions) {
+    if !regions.is_null() {
+        drop(Box::from_raw(regions));
+    }
+}
+
+// Renders the regions with the command line's default options at the given
+// size. The PNG is stored in a buffer owned by the library, which must be
+// released with mmv_buffer_free rather than free(). Returns 0, or -1 on
+// failure.
+#[no_mangle]
+pub unsafe extern "C" fn mmv_render_png_to_buffer(regions: *const MmvRegions, width: u32, height: u32, out_data: *mut *mut u8, out_len: *mut usize) -> c_int {
+    let (Some(regions), Some(out_data), Some(out_len)) = (regions.as_ref(), out_data.as_mut(), out_len.as_mut()) else { return -1 };
+    if width == 0 || height == 0 {
+        return -1;
+    }
+    // Drawing failures panic, which must not unwind into C.
+    let png = catch_unwind(AssertUnwindSafe(|| -> Option<Vec<u8>> {
+        let (memory_regions, options) = embedded_input(regions.memory_regions.clone(), width, height).ok()?;
+        let img = compose_image(&memory_regions, &options);
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(img).write_to(&mut png, image::ImageOutputFormat::Png).ok()?;
+        Some(png)
+    }));
+    let Ok(Some(png)) = png else { return -1 };
+    *out_len = png.len();
+    *out_data = Box::into_raw(png.into_boxed_slice()) as *mut u8;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mmv_buffer_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+// The library version, as a static string that is never freed.
+#[no_mangle]
+pub extern "C" fn mmv_version() -> *const c_char {
+    CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()).unwrap().as_ptr()
+}
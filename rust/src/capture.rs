@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+pub const CAPTURE_STAGE_ARG: &str = "--capture-stage";
+
+// Every /proc/PID file the renderer reads, and all the capture stage will
+// open. Entries such as root/ and cwd/ lead anywhere on the filesystem, so
+// nothing outside this list is read with elevated privileges.
+const PROC_FILES: [&str; 8] = ["maps", "smaps", "smaps_rollup", "status", "comm", "limits", "numa_maps", "cgroup"];
+
+// What the capture stage writes to its pipe: the file it was asked for,
+// read whole, and which process and file that was, so the renderer can
+// refuse a reply that isn't the one it asked for.
+#[derive(Serialize, Deserialize)]
+struct CapturedFile {
+    pid: u32,
+    name: String,
+    contents: String,
+}
+
+// The capture stage runs before any argument parsing or rendering code, so
+// the only thing executed with elevated privileges is reading the /proc file
+// and serializing it to stdout.
+pub fn run_capture_stage(args: &[String]) -> ! {
+    let code = match args {
+        [pid, name] => match (pid.parse::<u32>(), PROC_FILES.contains(&name.as_str())) {
+            (Ok(pid), true) => match fs::read_to_string(format!("/proc/{}/{}", pid, name)) {
+                Ok(contents) => {
+                    let captured = CapturedFile { pid, name: name.clone(), contents };
+                    match bincode::serialize(&captured).map(|data| io::stdout().write_all(&data)) {
+                        Ok(Ok(())) => 0,
+                        _ => 1,
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            },
+            _ => 2,
+        },
+        _ => 2,
+    };
+    std::process::exit(code)
+}
+
+fn elevated_read(pid: u32, name: &str) -> Result<String, String> {
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let helper = env::var("MEMLAYOUT_ELEVATE").unwrap_or_else(|_| "sudo -n".to_string());
+    let mut helper = helper.split_whitespace();
+    let program = helper.next().ok_or("MEMLAYOUT_ELEVATE is empty")?;
+
+    let output = Command::new(program)
+        .args(helper)
+        .arg(exe)
+        .arg(CAPTURE_STAGE_ARG)
+        .arg(pid.to_string())
+        .arg(name)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Unable to run {}: {}", program, e))?;
+
+    if !output.status.success() {
+        return Err(format!("Elevated capture failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let captured: CapturedFile = bincode::deserialize(&output.stdout).map_err(|e| format!("Invalid reply from the capture stage: {}", e))?;
+    if captured.pid != pid || captured.name != name {
+        return Err(format!("The capture stage returned /proc/{}/{} instead of /proc/{}/{}", captured.pid, captured.name, pid, name));
+    }
+    Ok(captured.contents)
+}
+
+pub fn read_proc_file(pid: u32, name: &str) -> Result<String, String> {
+    let path = format!("/proc/{}/{}", pid, name);
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied && env::var("MEMLAYOUT_ELEVATE").as_deref() != Ok("none") => {
+            elevated_read(pid, name)
+        }
+        Err(e) => Err(format!("Unable to read {}: {}", path, e)),
+    }
+}
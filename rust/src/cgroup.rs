@@ -0,0 +1,198 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adb::AdbTarget;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{capture, fit_text};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::format_size;
+#[cfg(not(target_arch = "wasm32"))]
+use plotters::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+#[cfg(not(target_arch = "wasm32"))]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+// v1 reports "no limit" as the largest page-aligned i64 rather than a word.
+// On a 32-bit target no limit parses at all.
+#[cfg(not(target_arch = "wasm32"))]
+const UNLIMITED: usize = if usize::BITS > 60 { (1u64 << 60) as usize } else { usize::MAX };
+
+// Memory charged to the cgroup a process belongs to, and the tightest limit
+// set on it or any of its ancestors.
+#[derive(Debug, Clone)]
+pub struct CgroupMemory {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub path: String,
+    pub current: usize,
+    pub limit: Option<usize>,
+    // What memory.stat says the charge is made of, in PANEL_PARTS order,
+    // and swap, which is charged on top of it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub breakdown: Vec<(&'static str, usize)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub swap: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub const PANEL_PARTS: [(&str, [u8; 3]); 5] = [
+    ("anon", [240, 150, 40]),
+    ("file", [90, 150, 220]),
+    ("slab", [150, 90, 200]),
+    ("kernel", [120, 120, 120]),
+    ("sock", [60, 180, 150]),
+];
+#[cfg(not(target_arch = "wasm32"))]
+pub const SWAP_COLOR: [u8; 3] = [220, 60, 60];
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_stat(stat: &str) -> Vec<(&str, usize)> {
+    stat.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key, value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// v2 counts slab inside kernel, so kernel here is the rest of it; kernels
+// before 5.18 have only its parts. v1 has anon and file as rss and cache
+// and no kernel breakdown in memory.stat at all.
+#[cfg(not(target_arch = "wasm32"))]
+fn breakdown(stat: &str, v2: bool) -> (Vec<(&'static str, usize)>, Option<usize>) {
+    let stat = parse_stat(stat);
+    let get = |key: &str| stat.iter().find(|(name, _)| *name == key).map(|(_, value)| *value);
+    if !v2 {
+        return (vec![("anon", get("rss").unwrap_or(0)), ("file", get("cache").unwrap_or(0))], get("swap"));
+    }
+    let slab = get("slab").unwrap_or(0);
+    let kernel = get("kernel").unwrap_or_else(|| ["kernel_stack", "pagetables", "percpu", "vmalloc"].iter().filter_map(|key| get(key)).sum::<usize>() + slab);
+    let parts = vec![
+        ("anon", get("anon").unwrap_or(0)),
+        ("file", get("file").unwrap_or(0)),
+        ("slab", slab),
+        ("kernel", kernel.saturating_sub(slab)),
+        ("sock", get("sock").unwrap_or(0)),
+    ];
+    (parts, None)
+}
+
+impl CgroupMemory {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read(pid: u32, adb: Option<&AdbTarget>) -> Result<Self, String> {
+        let read = |path: String| match adb {
+            Some(adb) => adb.shell(&["cat", &path]),
+            None => fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path, e)),
+        };
+        let number = |path: String| -> Option<usize> { read(path).ok()?.trim().parse().ok() };
+        let membership = match adb {
+            Some(adb) => adb.read_proc_file(pid, "cgroup")?,
+            None => capture::read_proc_file(pid, "cgroup")?,
+        };
+
+        // Hybrid hosts list both the v2 "0::" line and v1 controllers, so
+        // whichever hierarchy actually has the accounting files wins.
+        for line in membership.lines() {
+            let mut fields = line.splitn(3, ':');
+            let (controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(_), Some(controllers), Some(path)) => (controllers, path.trim_end_matches('/')),
+                _ => continue,
+            };
+            let (base, usage, max) = if controllers.is_empty() {
+                (CGROUP_ROOT.to_string(), "memory.current", "memory.max")
+            } else if controllers.split(',').any(|c| c == "memory") {
+                (format!("{}/memory", CGROUP_ROOT), "memory.usage_in_bytes", "memory.limit_in_bytes")
+            } else {
+                continue;
+            };
+            let current = match number(format!("{}{}/{}", base, path, usage)) {
+                Some(current) => current,
+                None => continue,
+            };
+            // "max" doesn't parse, which is the same as no limit at that level.
+            let mut limit: Option<usize> = None;
+            let mut dir = path;
+            loop {
+                if let Some(max) = number(format!("{}{}/{}", base, dir, max)).filter(|max| *max < UNLIMITED) {
+                    limit = Some(limit.map_or(max, |limit| limit.min(max)));
+                }
+                match dir.rfind('/') {
+                    Some(parent) => dir = &dir[..parent],
+                    None => break,
+                }
+            }
+            let v2 = controllers.is_empty();
+            let (breakdown, swap) = breakdown(&read(format!("{}{}/memory.stat", base, path)).unwrap_or_default(), v2);
+            let swap = if v2 { number(format!("{}{}/memory.swap.current", base, path)) } else { swap };
+            let path = if path.is_empty() { "/".to_string() } else { path.to_string() };
+            return Ok(CgroupMemory { path, current, limit, breakdown, swap });
+        }
+        Err(format!("No memory accounting found for the cgroup of pid {}", pid))
+    }
+
+    pub fn fraction(&self) -> Option<f64> {
+        self.limit.map(|limit| self.current as f64 / limit.max(1) as f64)
+    }
+
+    // True when usage is within margin (a fraction of the limit) of it.
+    pub fn near_limit(&self, margin: f64) -> bool {
+        self.fraction().is_some_and(|fraction| fraction >= 1.0 - margin)
+    }
+}
+
+// One column stacked from the bottom up to memory.current, the parts in
+// PANEL_PARTS order with whatever memory.stat doesn't itemize left as an
+// outline, swap above it and the limit as a line when one is set.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn draw_panel(memory: &CgroupMemory, width: u32, height: u32) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    {
+        let root = BitMapBackend::with_buffer(&mut imgbuf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let font = ("sans-serif", 11).into_font();
+        root.draw(&Text::new("cgroup memory.stat", (10, 8), ("sans-serif", 13).into_font()))?;
+        let fitted = fit_text(&root, &memory.path, &font, width as i32 - 20)?.unwrap_or_default();
+        root.draw(&Text::new(fitted, (10, 24), font.clone()))?;
+
+        let swap = memory.swap.unwrap_or(0);
+        let scale_top = (memory.current + swap).max(memory.limit.unwrap_or(0)).max(1) as f64;
+        let (bar_top, bar_bottom, bar_left, bar_right) = (45, 345, 10, 60);
+        let to_y = |bytes: usize| bar_bottom - (bytes as f64 / scale_top * (bar_bottom - bar_top) as f64) as i32;
+        let mut filled = 0;
+        for (name, bytes) in &memory.breakdown {
+            let Some((_, [r, g, b])) = PANEL_PARTS.iter().find(|(part, _)| part == name) else { continue };
+            root.draw(&Rectangle::new([(bar_left, to_y(filled + bytes)), (bar_right, to_y(filled))], RGBColor(*r, *g, *b).filled()))?;
+            filled += bytes;
+        }
+        root.draw(&Rectangle::new([(bar_left, to_y(memory.current)), (bar_right, bar_bottom)], BLACK.stroke_width(1)))?;
+        let [r, g, b] = SWAP_COLOR;
+        if swap > 0 {
+            root.draw(&Rectangle::new([(bar_left, to_y(memory.current + swap)), (bar_right, to_y(memory.current))], RGBColor(r, g, b).filled()))?;
+        }
+        if let Some(limit) = memory.limit {
+            root.draw(&PathElement::new(vec![(bar_left - 5, to_y(limit)), (bar_right + 5, to_y(limit))], RED.stroke_width(2)))?;
+        }
+
+        let mut y = bar_top;
+        let mut key = |label: String, color: RGBColor| -> Result<(), Box<dyn std::error::Error>> {
+            root.draw(&Rectangle::new([(bar_right + 12, y), (bar_right + 22, y + 10)], color.filled()))?;
+            root.draw(&Rectangle::new([(bar_right + 12, y), (bar_right + 22, y + 10)], BLACK.stroke_width(1)))?;
+            root.draw(&Text::new(label, (bar_right + 26, y), font.clone()))?;
+            y += 16;
+            Ok(())
+        };
+        for (name, bytes) in &memory.breakdown {
+            if let Some((_, [r, g, b])) = PANEL_PARTS.iter().find(|(part, _)| part == name) {
+                key(format!("{} {}", name, format_size(*bytes)), RGBColor(*r, *g, *b))?;
+            }
+        }
+        if let Some(swap) = memory.swap {
+            key(format!("swap {}", format_size(swap)), RGBColor(r, g, b))?;
+        }
+        key(format!("other {}", format_size(memory.current.saturating_sub(filled))), WHITE)?;
+        key(format!("current {}", format_size(memory.current)), BLACK)?;
+        if let Some(limit) = memory.limit {
+            key(format!("limit {}", format_size(limit)), RED)?;
+        }
+    }
+    Ok(imgbuf)
+}
@@ -0,0 +1,684 @@
+#[cfg(not(target_arch = "wasm32"))]
+use clap::error::ErrorKind;
+use clap::builder::RangedU64ValueParser;
+#[cfg(not(target_arch = "wasm32"))]
+use clap::parser::ValueSource;
+#[cfg(not(target_arch = "wasm32"))]
+use clap::CommandFactory;
+use clap::{value_parser, Args, FromArgMatches, ValueHint};
+#[cfg(not(target_arch = "wasm32"))]
+use clap::{ArgAction, ArgGroup, Parser, Subcommand};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::assertions::Assertion;
+use crate::emphasis::Selector;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::filter::{FlagFilter, PermFilter};
+use crate::fragmentation::parse_size;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::symbols::parse_address;
+use crate::theme::Theme;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::parse_range;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pattern::Regex;
+use std::num::NonZeroUsize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+// What a PID given as the positional argument rules out, in the commands
+// that take all of RenderArgs.
+#[cfg(not(target_arch = "wasm32"))]
+const PROCESS_CONFLICTS: [&str; 12] = ["pid", "own", "package", "container", "source", "all", "binary", "kernel_modules", "vmallocinfo", "physical", "shm", "library_sharing"];
+// And in the ones that only take TargetArgs.
+#[cfg(not(target_arch = "wasm32"))]
+const TARGET_CONFLICTS: [&str; 4] = ["pid", "own", "package", "container"];
+
+// One of these names what to draw; without any, there's nothing to read.
+#[cfg(not(target_arch = "wasm32"))]
+const DRAW_TARGETS: [&str; 19] = [
+    "process", "pid", "own", "container", "adb", "source", "all", "binary", "kernel_modules", "vmallocinfo", "physical", "shm", "library_sharing", "uboot", "page_owner", "slabinfo", "buddyinfo", "zoneinfo", "aslr",
+];
+#[cfg(not(target_arch = "wasm32"))]
+const PROCESS_TARGETS: [&str; 5] = ["process", "pid", "own", "container", "adb"];
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Parser)]
+#[command(name = "Memory Map Visualizer", bin_name = "memlayout", version = "1.0", author = "Your Name <your@email.com>", about = "Visualizes the memory layout of a process")]
+#[command(arg_required_else_help = true, subcommand_negates_reqs = true, subcommand_value_name = "SUBCOMMAND")]
+#[command(override_usage = "memlayout [OPTIONS] [PID] [-- <COMMAND>...]\n       memlayout <SUBCOMMAND>")]
+pub struct Cli {
+    #[arg(long, global = true, value_name = "FILE", help = "Read default options from FILE instead of ~/.config/memory-map-visualizer/config.toml")]
+    pub config: Option<String>,
+    #[arg(long, global = true, help = "Fail on any line of maps or smaps input that doesn't parse instead of skipping it with a warning")]
+    pub strict: bool,
+    #[command(flatten)]
+    pub log: LogArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    // Without a subcommand the arguments are render's, so `memlayout PID`
+    // keeps working.
+    #[command(flatten)]
+    pub default: RenderCommand,
+}
+
+// Parsed once, so the size of the variants doesn't matter.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    #[command(about = "Draw the memory map of a process, a binary or the kernel; what memlayout does without a subcommand")]
+    Render(RenderCommand),
+    #[command(about = "Print every region, or the --top N, along with whatever else was asked for, without drawing")]
+    Report(RenderCommand),
+    #[command(about = "Redraw memory_map.png every --interval and print what changed, until the process exits")]
+    Watch(RenderCommand),
+    #[command(about = "Serve a web page that shows the live memory map of a process")]
+    Serve(ServeArgs),
+    #[command(about = "Keep a rotating ring of snapshots on disk and dump the last minutes on SIGUSR1 or when the process exits")]
+    Record(RecordArgs),
+    #[command(about = "Replay a directory of snapshots as an animated GIF")]
+    Animate(AnimateArgs),
+    #[command(about = "Rebuild the map from an `strace -e trace=memory` log or perf mmap records, with what created every region")]
+    Replay(ReplayArgs),
+    #[command(about = "Compare two captures in one image: before, the changes, and after, on one address scale")]
+    Diff(DiffArgs),
+    #[command(about = "Save a capture to a .mmsnap file or render one", subcommand_required = true)]
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+    #[command(about = "Print a completion script for the shell")]
+    Completions {
+        #[arg(value_parser = ["bash", "zsh", "fish"])]
+        shell: String,
+    },
+    #[cfg(feature = "self-update")]
+    #[command(about = "Replace this binary with the latest release")]
+    SelfUpdate,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Clone)]
+pub enum SnapshotCommand {
+    #[command(about = "Capture maps and smaps of a process into a snapshot")]
+    Save(SaveArgs),
+    #[command(about = "Render a snapshot as if it had just been captured")]
+    Render(SnapshotRenderArgs),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+#[command(group(ArgGroup::new("target").args(DRAW_TARGETS).multiple(true).required(true)))]
+pub struct RenderCommand {
+    #[arg(value_name = "PID", conflicts_with_all = PROCESS_CONFLICTS, help = "Process ID to visualize; 0 or self for memlayout itself")]
+    pub process: Option<String>,
+    #[arg(value_name = "COMMAND", last = true, help = "Program and arguments launched by --aslr")]
+    pub command: Vec<String>,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+#[command(group(ArgGroup::new("target").args(DRAW_TARGETS).multiple(true).required(true)))]
+pub struct ServeArgs {
+    #[arg(value_name = "PID", conflicts_with_all = PROCESS_CONFLICTS, help = "Process ID to show (or use --pid/--package)")]
+    pub process: Option<String>,
+    #[arg(long, default_value = "127.0.0.1:8080", help = "Address to listen on")]
+    pub listen: String,
+    #[arg(long, default_value = "2s", value_parser = parse_duration, help = "How often the page redraws the map")]
+    pub refresh: Duration,
+    #[arg(long, help = "Require this bearer token on every request, or a cookie set by opening /?token= once; defaults to $MEMLAYOUT_API_TOKEN, and needed when listening off loopback")]
+    pub token: Option<String>,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+#[command(group(ArgGroup::new("target").args(PROCESS_TARGETS).multiple(true).required(true)))]
+pub struct RecordArgs {
+    #[arg(value_name = "PID", conflicts_with_all = TARGET_CONFLICTS, help = "Process ID to record (or use --pid/--package)")]
+    pub process: Option<String>,
+    #[arg(long, default_value = "memlayout-recording", help = "Directory of the ring; dumps go into dump-<time> directories inside it")]
+    pub dir: String,
+    #[arg(long, default_value = "1s", value_parser = parse_duration, help = "How often a snapshot is taken")]
+    pub interval: Duration,
+    #[arg(long, default_value = "30m", value_parser = parse_duration, help = "Delete snapshots older than this")]
+    pub keep_for: Duration,
+    #[arg(long, default_value = "1G", value_parser = parse_size, help = "Delete the oldest snapshots while the ring is larger than this")]
+    pub max_size: u64,
+    #[arg(long, default_value = "5m", value_parser = parse_duration, help = "How far back a dump reaches")]
+    pub dump_window: Duration,
+    #[arg(long, conflicts_with = "adb", help = "Keep the first 64 bytes of each readable region in the snapshots; needs ptrace access")]
+    pub hex_preview: bool,
+    #[command(flatten)]
+    pub target: TargetArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct AnimateArgs {
+    #[arg(value_name = "DIR", help = "Directory of .mmsnap files")]
+    pub dir: String,
+    #[arg(short, long, default_value = "memory_map.gif", help = "File to write; the default takes the --format extension")]
+    pub output: String,
+    #[arg(long, value_parser = ["gif", "mp4", "webm"], default_value = "gif", help = "Write a GIF, or an MP4 or WebM video through ffmpeg for long series")]
+    pub format: String,
+    #[arg(long, default_value = "500ms", value_parser = parse_duration, help = "How long each frame is shown")]
+    pub frame_delay: Duration,
+    #[arg(long, value_name = "N", num_args = 0..=1, require_equals = true, default_missing_value = "6", help = "Instead of an animation, draw N snapshots spread over the series (6 if not given) as adjacent columns of one PNG, memory_timeline.png unless --output says otherwise, with ribbons joining the regions that persist")]
+    pub timeline: Option<usize>,
+    #[command(flatten)]
+    pub draw: DrawArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct ReplayArgs {
+    #[arg(value_name = "LOG", help = "strace output (run strace with -y to get file names), perf script --show-mmap-events output, or perf.data")]
+    pub log: String,
+    #[arg(long, help = "Only replay this pid: from strace -f logs, or instead of the busiest process in perf records")]
+    pub pid: Option<u32>,
+    #[arg(long, help = "Write an animated GIF of the map after each change instead of the final map")]
+    pub animate: bool,
+    #[arg(long, value_name = "N", default_value = "1", help = "With --animate, draw a frame every N changes")]
+    pub every: NonZeroUsize,
+    #[arg(short, long, default_value = "memory_replay.gif", help = "File to write with --animate; the default takes the --format extension")]
+    pub output: String,
+    #[arg(long, value_parser = ["gif", "mp4", "webm"], default_value = "gif", help = "With --animate, write a GIF, or an MP4 or WebM video through ffmpeg")]
+    pub format: String,
+    #[arg(long, default_value = "200ms", value_parser = parse_duration, help = "How long each frame is shown")]
+    pub frame_delay: Duration,
+    #[command(flatten)]
+    pub draw: DrawArgs,
+    #[command(flatten)]
+    pub export: ExportArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct DiffArgs {
+    #[arg(value_name = "BEFORE", help = "Snapshot (.mmsnap), maps or smaps file, or --source spec such as procfs:PID")]
+    pub before: String,
+    #[arg(value_name = "AFTER", help = "The same for the later state")]
+    pub after: String,
+    #[arg(short, long, default_value = "memory_diff.png", help = "PNG file to write")]
+    pub output: String,
+    #[arg(long, value_name = "PATH", help = "Also write the added, removed, resized and permission-changed regions as JSON (- for stdout)")]
+    pub json: Option<String>,
+    #[arg(long, help = "Compare two processes rather than one process twice: each side on its own address scale, with the regions of the same file or named mapping joined across and their RSS compared; what procfs:A against procfs:B does by default")]
+    pub by_object: bool,
+    #[arg(long, value_name = "N", default_value = "20", help = "Objects listed when comparing by object")]
+    pub top_objects: usize,
+    #[command(flatten)]
+    pub draw: DrawArgs,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+#[command(group(ArgGroup::new("target").args(PROCESS_TARGETS).multiple(true).required(true)))]
+pub struct SaveArgs {
+    #[arg(value_name = "PID", conflicts_with_all = TARGET_CONFLICTS, help = "Process ID to capture (or use --pid/--package)")]
+    pub process: Option<String>,
+    #[arg(short, long, default_value = "memory_map.mmsnap", help = "Snapshot file to write")]
+    pub output: String,
+    #[arg(long, conflicts_with = "adb", help = "Keep the first 64 bytes of each readable region in the snapshot; needs ptrace access")]
+    pub hex_preview: bool,
+    #[command(flatten)]
+    pub target: TargetArgs,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct SnapshotRenderArgs {
+    #[arg(value_name = "FILE", help = "Snapshot file to read")]
+    pub file: String,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+// Logging of the long-running modes, accepted by every command.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct LogArgs {
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet", help = "Log more: -v adds capture timings and served requests, -vv everything")]
+    pub verbose: u8,
+    #[arg(short, long, global = true, help = "Only log errors")]
+    pub quiet: bool,
+    #[arg(long, global = true, value_parser = ["text", "json"], default_value = "text", help = "Write logs to stderr as plain lines or as one JSON object per line")]
+    pub log_format: String,
+}
+
+// Which process, when it isn't given as the positional PID.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct TargetArgs {
+    #[arg(long, help = "Process ID to visualize")]
+    pub pid: Option<String>,
+    #[arg(long = "self", conflicts_with_all = ["pid", "package", "container", "adb"], help = "Visualize memlayout's own address space, as PID 0 or self does")]
+    pub own: bool,
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["pid", "adb"], help = "Visualize the init process of a Docker or Podman container, by name or ID")]
+    pub container: Option<String>,
+    #[arg(long, value_name = "SERIAL", help = "Read the memory map from an Android device over adb")]
+    pub adb: Option<String>,
+    #[arg(long, requires = "adb", conflicts_with = "pid", help = "Android package whose process to visualize")]
+    pub package: Option<String>,
+}
+
+// Everything that shapes the picture, shared by every command that draws.
+#[derive(Args, Clone)]
+pub struct DrawArgs {
+    #[arg(long, value_parser = ["permissions", "swap", "file", "rss"], default_value = "permissions", help = "Attribute that drives the region colors (swap and rss read smaps, file gives each backing path its own hue)")]
+    pub color_by: String,
+    #[arg(long, value_name = "NAME|FILE", value_hint = ValueHint::FilePath, help = "Permission colors: classic, viridis, high-contrast, colorblind or a JSON theme file")]
+    pub theme: Option<Theme>,
+    #[arg(long, value_parser = ["default", "colorblind"], default_value = "default", help = "colorblind uses a protanopia and deuteranopia safe theme and stripes writable (horizontal) and executable (vertical) regions")]
+    pub palette: String,
+    #[arg(long, value_parser = ["shared", "private", "file-backed", "anonymous", "none"], default_value = "shared", help = "Regions to overlay with --pattern, independent of their color")]
+    pub pattern_by: String,
+    #[arg(long, value_parser = ["stripes", "dots"], default_value = "stripes")]
+    pub pattern: String,
+    #[arg(long, value_parser = ["stack", "skip", "overlap"], default_value = "stack", help = "How address and marker labels that would overlap are placed")]
+    pub labels: String,
+    #[arg(long, help = "Draw on a dark background with light labels")]
+    pub dark: bool,
+    #[arg(long, value_name = "PERCENT", default_value = "10", value_parser = percentage, help = "Warn when cgroup usage is within this many percent of the limit")]
+    pub cgroup_margin: f64,
+    #[arg(long, help = "Leave out the banner with the process, capture time and totals")]
+    pub no_header: bool,
+    #[arg(long, value_parser = ["hex", "offset", "regions"], default_value = "hex", help = "Label the address ruler with addresses or with offsets into each cluster of mappings; regions prints every region's start and size instead")]
+    pub axis_labels: String,
+    #[arg(long, value_name = "PX", default_value = "60", value_parser = value_parser!(u32).range(1..), help = "Distance between labeled ticks on the address ruler")]
+    pub tick_spacing: u32,
+    #[arg(long, value_name = "N", default_value = "4", help = "Unlabeled ticks between two labeled ones")]
+    pub minor_ticks: u32,
+    #[arg(long, value_parser = ["bottom-left", "top-left", "right", "none"], default_value = "bottom-left", help = "Where to draw the legend; right adds a column beside the map")]
+    pub legend: String,
+    #[arg(long, visible_alias = "size-by", value_parser = ["virtual", "rss", "pss", "swap", "dirty"], default_value = "virtual", help = "Metric that drives bar heights (all but virtual read smaps)")]
+    pub size_metric: String,
+    #[arg(long, value_parser = ["linear", "log", "sqrt", "equal"], default_value = "log", help = "How sizes map to bar heights: proportional, log2 cubed, square root or one row per region")]
+    pub scale: String,
+    #[arg(long, value_name = "PIXELS", default_value = "0", help = "Draw every region at least this tall")]
+    pub min_region_px: u32,
+    #[arg(long, value_name = "FRACTION", default_value = "1", value_parser = fraction, help = "Cap any single region at this fraction of the image height")]
+    pub max_region_fraction: f64,
+    #[arg(long, value_name = "SIZE", value_parser = size, help = "Draw unmapped ranges larger than SIZE (e.g. 1G) as a fixed-height break")]
+    pub collapse_gaps: Option<usize>,
+    #[arg(long, default_value = "300", help = "Image width in pixels before --scale-factor")]
+    pub width: u32,
+    #[arg(long, default_value = "2000", help = "Image height in pixels before --scale-factor")]
+    pub height: u32,
+    #[arg(long, default_value = "1", value_parser = factor, help = "Scale the image, its fonts and its lines by this factor, e.g. 2 for high DPI screens")]
+    pub scale_factor: f64,
+    #[arg(long, help = "Add a thin bar locating the drawn mappings in the whole user address space, with the kernel half marked")]
+    pub context_bar: bool,
+    #[arg(long, help = "Mark mmap_min_addr, the end of user space and the start of the kernel half as found on this machine, and flag mappings below, between or right against them")]
+    pub boundaries: bool,
+    #[arg(long, help = "Add each region's uncommon VmFlags (dd, gd, ht, ...) to its label (reads smaps)")]
+    pub vm_flags: bool,
+    #[arg(long, help = "Hatch the share of each region backed by huge pages (reads smaps)")]
+    pub hugepages: bool,
+    #[arg(long, help = "Outline mlocked regions with a lock and report locked bytes against RLIMIT_MEMLOCK (reads smaps)")]
+    pub locked: bool,
+    #[arg(long, help = "Flag writable and executable regions, stacks without a guard and mappings of deleted files; exit with status 1 if any exist")]
+    pub audit: bool,
+    #[arg(long, value_name = "SELECTOR", help = "Fade regions matching kind=<category> or perm=<rwx>")]
+    pub dim: Vec<Selector>,
+    #[arg(long, value_name = "SELECTOR", help = "Outline and saturate matching regions, fading the rest")]
+    pub emphasize: Vec<Selector>,
+    #[arg(long, default_value = "0.3", help = "Opacity of faded regions, from 0 to 1")]
+    pub dim_opacity: f64,
+    #[arg(long, default_value = "2000", value_parser = RangedU64ValueParser::<usize>::new().range(1..), help = "Coalesce smaller regions when the map has more regions than this")]
+    pub max_regions: usize,
+    #[arg(long, value_name = "N", help = "Split PNG output into memory_map_001.png, memory_map_002.png, ... of N regions each")]
+    pub tile_regions: Option<NonZeroUsize>,
+    #[arg(long, value_name = "N", default_value = "3", help = "Regions of the neighbouring tiles repeated at each seam")]
+    pub tile_overlap: usize,
+}
+
+// Which regions are drawn, and how they are merged.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct FilterArgs {
+    #[arg(long, value_name = "REGEX", help = "Only draw regions whose path matches REGEX")]
+    pub include: Vec<Regex>,
+    #[arg(long, value_name = "REGEX", help = "Leave out regions whose path matches REGEX")]
+    pub exclude: Vec<Regex>,
+    #[arg(long, value_name = "PERMS", help = "Only draw regions with these of r, w, x, p and s; a - before a letter rules it out, e.g. w-x")]
+    pub perm: Vec<PermFilter>,
+    #[arg(long, value_name = "FLAGS", help = "Only draw regions with these smaps VmFlags codes, comma separated; a - before a code rules it out, e.g. dd or gd,-ht")]
+    pub vm_flag: Vec<FlagFilter>,
+    #[arg(long, value_name = "SIZE", value_parser = size, help = "Only draw regions of at least SIZE, e.g. 4K")]
+    pub min_size: Option<usize>,
+    #[arg(long, value_name = "SIZE", value_parser = size, help = "Only draw regions of at most SIZE, e.g. 1G")]
+    pub max_size: Option<usize>,
+    #[arg(long, help = "Leave out the ASan and TSan shadow mappings of sanitized binaries, which reserve terabytes")]
+    pub exclude_shadow: bool,
+    #[arg(long, help = "Draw the gaps left by region filters as collapsed breaks")]
+    pub collapse_filtered: bool,
+    #[arg(long, value_parser = ["shared", "private"], help = "Only show shared or only private mappings")]
+    pub sharing: Option<String>,
+    #[arg(long, value_parser = ["file", "jit"], help = "Merge all mappings of an object (file), or each run of adjacent JIT code mappings (jit), into one block with its summed size and RSS")]
+    pub group_by: Vec<String>,
+    #[arg(long, value_name = "PATTERN", requires = "group_by", help = "Keep the mappings of files whose path contains PATTERN separate")]
+    pub expand: Vec<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct ExportArgs {
+    #[arg(long, value_parser = ["json", "csv", "dot"], help = "Write the parsed region list to --export-path; dot writes a Graphviz graph of the process and the files and shared memory it maps instead, with --all or --library-sharing of every process and what they share, edges weighted by mapped size")]
+    pub export: Option<String>,
+    #[arg(long, value_name = "PATH", requires = "export", help = "Where --export writes (default memory_regions.<format>, - for stdout)")]
+    pub export_path: Option<String>,
+    #[arg(long, value_parser = ["address", "size", "rss", "path"], default_value = "address", help = "Order of the rows in --export and of the bars in --ranked-chart")]
+    pub sort: String,
+    #[arg(long, help = "Skip rendering memory_map.png")]
+    pub no_image: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Args, Clone)]
+pub struct RenderArgs {
+    #[command(flatten)]
+    pub target: TargetArgs,
+    #[command(flatten)]
+    pub draw: DrawArgs,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    #[command(flatten)]
+    pub export: ExportArgs,
+    #[arg(long, value_parser = ["png", "pdf", "svg", "html", "sixel", "kitty", "iterm", "text", "ascii", "auto"], default_value = "png", help = "Write memory_map.png, memory_map.pdf, memory_map.svg, or memory_map.html with every region linked to its row in a table and back (deep links such as #0x7f3a0000 find the region holding the address), print the map inline for a graphics capable terminal (auto detects one) or print it as colored text")]
+    pub format: String,
+    #[arg(long, help = "Open the written image or PDF in the default viewer; with serve, open the live page in the browser")]
+    pub open: bool,
+    #[arg(long, conflicts_with_all = ["open", "no_image", "tile_regions"], help = "Write the encoded PNG or PDF to stdout instead of memory_map.png, printing the summaries to stderr")]
+    pub stdout: bool,
+    #[arg(long, conflicts_with_all = ["no_image", "tile_regions"], help = "Also copy the rendered PNG to the system clipboard")]
+    pub clipboard: bool,
+    #[arg(long, value_name = "KIND:ARG", conflicts_with_all = ["pid", "container", "adb", "all", "agent", "history", "serve_metrics"], help = "Read the map from a named source: procfs:PID, file:PATH, strace:LOG, perf:FILE, qmp:SOCKET or mtree:FILE for a QEMU guest, gdb-remote:HOST:PORT for a chip behind OpenOCD or gdbserver, esp-idf:COMPONENTS.json[,SUMMARY.json] for an ESP-IDF size report, cortex-m:PRESET[,FILE.svd] for a Cortex-M reference map (cortex-m, stm32f407, nrf52840, rp2040) with SVD peripherals, vmcore:FILE for a crashed kernel's physical memory from an ELF or kdump-compressed dump, gpu: for VRAM and GTT per client from DRM fdinfo (or gpu:FILE for saved amdgpu_gem_info or nvidia-smi --query-compute-apps CSV), v8-heap:FILE for a Node or Chrome heap's spaces from a .heapsnapshot or heap space statistics, windows:PID or windows:NAME for a Windows process from inside WSL2, walked through interop, or one registered by an embedding crate")]
+    pub source: Option<String>,
+    #[arg(long, conflicts_with_all = ["pid", "container", "adb", "binary", "aslr"], help = "Draw every readable process as one strip, largest RSS first, to memory_overview.png")]
+    pub all: bool,
+    #[arg(long, requires = "all", help = "With --all, also render each process to memory_map_<pid>.png")]
+    pub per_process: bool,
+    #[arg(long, value_name = "N", num_args = 0..=1, require_equals = true, default_missing_value = "30", requires = "all", help = "With --all, sum PSS per mapped file across every process and rank the N most expensive, drawn to library_pss.png (reads smaps)")]
+    pub by_library: Option<usize>,
+    #[arg(long, short = 'j', value_name = "N", help = "Worker threads for --all (defaults to one per CPU)")]
+    pub jobs: Option<NonZeroUsize>,
+    #[arg(long, alias = "elf", value_name = "FILE", conflicts_with_all = ["pid", "package", "aslr"], help = "Visualize the segments and sections of an ELF, PE or Mach-O file instead of a process")]
+    pub binary: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/proc/modules", conflicts_with_all = ["pid", "package", "binary", "aslr", "all"], help = "Visualize where loaded kernel modules sit, from /proc/modules or FILE (needs root for addresses)")]
+    pub kernel_modules: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/proc/vmallocinfo", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules"], help = "Visualize the kernel's vmalloc area from /proc/vmallocinfo or FILE, colored by caller (needs root)")]
+    pub vmallocinfo: Option<String>,
+    #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true, default_missing_value = "/proc", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo"], help = "Draw physical memory by frame state from kpageflags and kpagecount in DIR to physical_memory.png (needs root)")]
+    pub physical: Option<String>,
+    #[arg(long, conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical"], help = "List /dev/shm objects and System V segments with the processes mapping each, drawn to shared_memory.png")]
+    pub shm: bool,
+    #[arg(long, value_name = "PIDS", value_delimiter = ',', conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm"], help = "Across the comma separated processes, report how much of each mapped file is shared (RSS against PSS) and how much is spent on duplicate copies such as deleted old versions, drawn to library_sharing.png")]
+    pub library_sharing: Vec<u32>,
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm"], help = "Draw DRAM banks and U-Boot's relocated code, malloc area, stack and device tree from a console capture of bdinfo and meminfo, with the load addresses from printenv marked, and report loads that land on U-Boot")]
+    pub uboot: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/sys/kernel/debug/page_owner", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm", "uboot"], help = "Rank who holds physical memory by the allocating stack's first frame past the allocator, or its module, from a page_owner dump or FILE, and draw the frames colored by owner to page_owner.png; --top sets how many owners to list (20)")]
+    pub page_owner: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/proc/slabinfo", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm", "uboot", "page_owner"], help = "List slab caches by size with object counts and how full they are, from /proc/slabinfo (or /sys/kernel/slab without root), a slabinfo FILE or a slab directory, and draw them as a treemap to slab_caches.png; --top sets how many to list (20)")]
+    pub slabinfo: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/proc/buddyinfo", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm", "uboot", "page_owner", "slabinfo"], help = "Print and draw to buddyinfo.png the free blocks per order of each zone from /proc/buddyinfo or FILE, with the unusable free space index for a hugepage; under watch, sampled every --interval with the index drawn over time")]
+    pub buddyinfo: Option<String>,
+    #[arg(long, value_name = "ORDER", requires = "buddyinfo", help = "Order the unusable index is computed for (default: the transparent hugepage order)")]
+    pub buddy_order: Option<usize>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "/proc/zoneinfo", conflicts_with_all = ["pid", "package", "binary", "aslr", "all", "kernel_modules", "vmallocinfo", "physical", "shm", "uboot", "page_owner", "slabinfo", "buddyinfo"], help = "Print and draw to zoneinfo.png each zone's free pages against its min, low and high watermarks from /proc/zoneinfo or FILE, with what reclaim does at that level; under watch, sampled every --interval with the headroom drawn over time")]
+    pub zoneinfo: Option<String>,
+    #[arg(long, requires = "kernel_modules", help = "Split each module into its sections, from /sys/module/*/sections")]
+    pub module_sections: bool,
+    #[arg(long, value_name = "DEVICE", requires = "binary", help = "Print and draw to firmware_usage.png how much of the device's flash, RAM and EEPROM the firmware takes, per section; DEVICE is flash=SIZE,ram=SIZE[,eeprom=SIZE] or a preset such as atmega328p")]
+    pub mcu: Option<String>,
+    #[arg(long, value_name = "RUNS", help = "Launch COMMAND this many times and report the ASLR entropy of its mappings")]
+    pub aslr: Option<usize>,
+    #[arg(long, default_value = "200ms", value_parser = parse_duration, help = "How long each launched process runs before its maps are read")]
+    pub aslr_delay: Duration,
+    #[arg(long, conflicts_with = "adb", help = "Keep parsed region lists in ~/.cache/memory-map-visualizer and reuse them while the process's maps are unchanged")]
+    pub cache: bool,
+    #[arg(long, value_name = "DURATION", default_value = "5s", requires = "cache", value_parser = parse_duration, help = "How long cached smaps figures such as RSS are reused, since they change without the maps changing")]
+    pub cache_max_age: Duration,
+    #[arg(long, help = "Read the cgroup's memory usage and limit into the header, with a gauge")]
+    pub cgroup: bool,
+    #[arg(long, conflicts_with = "source", help = "Print RSS, PSS, swap and locked totals from smaps_rollup as tab separated bytes and exit, without reading the regions; with --all, one row per process")]
+    pub summary: bool,
+    #[arg(long, help = "Add VmPeak, VmHWM, the thread count and context switches from /proc/PID/status to the header")]
+    pub proc_status: bool,
+    #[arg(long, help = "Add the machine's MemTotal, MemAvailable, swap and Committed_AS from /proc/meminfo to the header; snapshots record it too")]
+    pub meminfo: bool,
+    #[arg(long, help = "Add a side panel stacking the cgroup's memory.stat: anon, file, slab, other kernel memory, sock and swap against its limit")]
+    pub cgroup_panel: bool,
+    #[arg(long, value_name = "FILE", help = "Add a side panel stacking the heap's composition over time by allocation site from a Valgrind massif.out file, and print the peak; under watch the file is reread each frame, with the frame's time marked when massif ran with --time-unit=ms")]
+    pub massif: Option<String>,
+    #[arg(long, value_name = "N", help = "Print the N largest regions")]
+    pub top: Option<usize>,
+    #[arg(long, help = "Also draw memory_ranked.png, one bar per region sized by --sort (size unless rss), limited by --top or 50")]
+    pub ranked_chart: bool,
+    #[arg(long, help = "Also draw memory_summary.png, mapped bytes and RSS as stacked bars by category: file-backed code and data, heap, private and shared anonymous memory, stacks (reads smaps)")]
+    pub summary_chart: bool,
+    #[arg(long, value_parser = ["strip", "icicle"], default_value = "strip", help = "Draw the address-space strip, or memory_icicle.png instead: mapped bytes nested by path component, file and segment, sized by RSS unless --size-metric says pss, swap or dirty (reads smaps)")]
+    pub layout: String,
+    #[arg(long, value_parser = ["virtual", "rss", "pss", "swap", "dirty"], default_value = "virtual", help = "Metric that ranks the --top regions (all but virtual read smaps)")]
+    pub top_by: String,
+    #[arg(long, help = "Print free gap statistics and a gap-size histogram")]
+    pub fragmentation: bool,
+    #[arg(long, help = "Draw the gap-size histogram as a side panel on the image")]
+    pub fragmentation_panel: bool,
+    #[arg(long, help = "Print how many regions fall in each power-of-two size bucket, with their bytes and the cumulative bytes")]
+    pub size_histogram: bool,
+    #[arg(long, help = "Draw the region-size histogram as a side panel on the image")]
+    pub size_histogram_panel: bool,
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help = "Find and highlight free ranges that fit an allocation of SIZE (e.g. 256M)")]
+    pub find_hole: Option<u64>,
+    #[arg(long, value_name = "SIZE", default_value = "4K", requires = "find_hole", value_parser = parse_size, help = "Alignment the --find-hole allocation needs")]
+    pub align: u64,
+    #[arg(long, conflicts_with = "adb", help = "Band the pages KSM has merged and report what it saves, from pagemap and kpageflags (needs root)")]
+    pub ksm: bool,
+    #[arg(long, conflicts_with = "adb", help = "Sample pages of each readable region through process_vm_readv and add a strip shaded by their entropy, to spot zero-filled, packed or encrypted memory; needs ptrace access")]
+    pub entropy: bool,
+    #[arg(long, conflicts_with = "adb", help = "Read the first 64 bytes of each readable region and show them as a hexdump in the tooltips of serve's page and --gui, and keep them in saved snapshots; needs ptrace access")]
+    pub hex_preview: bool,
+    #[arg(long, conflicts_with = "adb", help = "Color anonymous memory by PMD mapped THP, PTE mapped THP and base pages, from pagemap and kpageflags (needs root; falls back to --hugepages)")]
+    pub thp: bool,
+    #[arg(long, help = "Read numa_maps and add a side panel of the bytes on each NUMA node, the imbalance between them and how much is remote to where the process runs")]
+    pub numa_panel: bool,
+    #[arg(long, help = "Report how much of each stack is used against RLIMIT_STACK or its allocation, and split its bar into used and headroom (reads smaps)")]
+    pub stack_headroom: bool,
+    #[arg(long, value_name = "PERCENT", default_value = "80", help = "Warn about and mark stacks that have used this many percent of their reserve")]
+    pub stack_threshold: f64,
+    #[arg(long, value_name = "ADDRESS[-END]", value_parser = parse_range, help = "Mark an address or shade a range, and print the regions it falls in")]
+    pub highlight: Vec<(usize, usize)>,
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, help = "Resolve an address to its function and mark it on the image")]
+    pub annotate: Vec<usize>,
+    #[arg(long, conflicts_with = "adb", help = "Attach gdb to mark every thread's pc and sp and the malloc arenas; the process is stopped meanwhile")]
+    pub gdb: bool,
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["gdb", "adb"], help = "Take the same markers from a gdb already attached, whose MI stream is served on this socket")]
+    pub gdb_mi: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "gdb", help = "Mark in-use and free bytes of each glibc arena, from malloc_info() XML in FILE or run through --gdb/--gdb-mi")]
+    pub malloc_info: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Print jemalloc's size class utilization from malloc_stats_print() output and mark its totals")]
+    pub jemalloc_stats: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Print the V8 heap's spaces from a .heapsnapshot (estimated) or v8.getHeapSpaceStatistics() JSON, mark them on the process's V8 pages and report how much of its anonymous memory they account for")]
+    pub v8_heap: Option<String>,
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "jcmd", help = "Name the JVM's anonymous mappings after their Native Memory Tracking category (Java Heap, Metaspace, Code, Thread Stack, GC...) from jcmd PID VM.native_memory detail, run here or saved to FILE, and print the categories; needs -XX:NativeMemoryTracking=detail")]
+    pub jvm_nmt: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Name a Go process's heap arenas, their reserved space and (live) the pages released or never touched, and reconcile them with runtime.MemStats from FILE: JSON, expvar's /debug/vars or /debug/pprof/heap?debug=1")]
+    pub go_memstats: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Print the call stacks holding the most heap at its peak from heaptrack data (.zst, .gz or interpreted text) or DHAT JSON, and mark them on the heap, or on the largest anonymous mapping for allocations past the mmap threshold; --top sets how many (5)")]
+    pub alloc_profile: Option<String>,
+    #[arg(long, value_name = "EXPR", help = "Exit with status 3 if e.g. rss>2G or wx-regions>0 holds for the drawn regions, or a count alone such as deleted-mappings is nonzero; metrics are mapped, rss, pss, swap, locked, largest, regions, wx-regions and deleted-mappings")]
+    pub fail_if: Vec<Assertion>,
+    #[arg(long, value_name = "PATH", default_value = "memory_map.json", help = "Where --audit writes its JSON report")]
+    pub report_json: String,
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["agent", "history"], help = "Sample the process every --interval and expose Prometheus gauges on http://ADDR/metrics")]
+    pub serve_metrics: Option<String>,
+    #[arg(long, conflicts_with = "history", help = "Keep sampling the process and record category bytes in the history store")]
+    pub agent: bool,
+    #[arg(long, value_name = "PATH", requires = "agent", help = "Append a row per agent sample to PATH: timestamp, mapped bytes, RSS, region count, heap and stack size")]
+    pub csv: Option<String>,
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, help = "Render a timeline chart of the recorded history, e.g. 24h")]
+    pub history: Option<Duration>,
+    #[arg(long, default_value = "memory_history.db", help = "History store used by --agent and --history")]
+    pub store: String,
+    #[arg(long, default_value = "10s", value_parser = sampling_interval, help = "Sampling interval in agent, metrics and watch mode, at least 1s")]
+    pub interval: Duration,
+    #[arg(long, default_value = "24h", value_parser = parse_duration, help = "How long the agent keeps samples")]
+    pub retention: Duration,
+    #[cfg(feature = "gui")]
+    #[arg(long, help = "Open an interactive window with zoom, pan and filters instead of writing a PNG")]
+    pub gui: bool,
+    #[cfg(feature = "self-update")]
+    #[arg(long, help = "Check the release feed for a newer version")]
+    pub check_update: bool,
+}
+
+fn fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err("The fraction must be in (0, 1]".to_string()),
+    }
+}
+
+fn factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(factor),
+        _ => Err("The factor must be a positive number".to_string()),
+    }
+}
+
+fn percentage(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err("The percentage must be from 0 to 100".to_string()),
+    }
+}
+
+fn size(s: &str) -> Result<usize, String> {
+    parse_size(s).map(|size| size as usize)
+}
+
+// The agent's history store keys samples by the second, so a shorter
+// interval would overwrite them, and 0 would spin.
+#[cfg(not(target_arch = "wasm32"))]
+fn sampling_interval(s: &str) -> Result<Duration, String> {
+    let interval = parse_duration(s)?;
+    match interval < Duration::from_secs(1) {
+        true => Err("The interval must be at least 1s".to_string()),
+        false => Ok(interval),
+    }
+}
+
+// A number with an optional ms, s, m, h or d unit, seconds without one.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number.parse().map_err(|_| format!("Invalid duration: {}", s))?;
+    let seconds = match unit {
+        "ms" => return Ok(Duration::from_millis(number)),
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        "d" => number * 60 * 60 * 24,
+        _ => return Err(format!("Invalid duration unit: {}", unit)),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+// A group of arguments as if none were given, for the commands that don't
+// take it and for callers without a command line.
+pub fn defaults<T: Args + FromArgMatches>() -> T {
+    let matches = T::augment_args(clap::Command::new("memlayout")).get_matches_from(["memlayout"]);
+    T::from_arg_matches(&matches).expect("Defaults must parse")
+}
+
+// What run() works from: the process named on the command line, the
+// options of the drawing pipeline, and what to do with its result.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::large_enum_variant)]
+pub enum Mode {
+    Render,
+    Report,
+    Watch,
+    Serve { listen: String, refresh: Duration, token: Option<String> },
+    Record(RecordArgs),
+    Animate(AnimateArgs),
+    Replay(ReplayArgs),
+    Diff(DiffArgs),
+    SnapshotSave(String),
+    SnapshotRender(String),
+    Completions(String),
+    #[cfg(feature = "self-update")]
+    SelfUpdate,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Invocation {
+    pub process: Option<String>,
+    // What --aslr launches.
+    pub command: Vec<String>,
+    pub args: RenderArgs,
+    pub mode: Mode,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Cli {
+    // The top-level arguments are render's, so they can't come before a
+    // subcommand; only the global ones, --config and logging, are shared.
+    // clap's args_conflicts_with_subcommands would rule those out as well.
+    pub fn parse_args(args: Vec<String>) -> Cli {
+        let mut command = Cli::command();
+        let matches = command.clone().get_matches_from(args);
+        if matches.subcommand().is_some() {
+            let early = matches
+                .ids()
+                .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+                .filter_map(|id| command.get_arguments().find(|arg| arg.get_id() == id))
+                .find(|arg| !arg.is_global_set());
+            if let Some(arg) = early {
+                let name = match (arg.get_long(), arg.get_value_names()) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some(names)) => names[0].to_string(),
+                    (None, None) => arg.get_id().to_string(),
+                };
+                command.error(ErrorKind::ArgumentConflict, format!("{} goes after the subcommand", name)).exit();
+            }
+        }
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn invocation(self) -> Invocation {
+        let render = |command: RenderCommand, mode| Invocation { process: command.process, command: command.command, args: command.render, mode };
+        let without = |process, args, mode| Invocation { process, command: Vec::new(), args, mode };
+        match self.command {
+            None => render(self.default, Mode::Render),
+            Some(Command::Render(command)) => render(command, Mode::Render),
+            Some(Command::Report(command)) => render(command, Mode::Report),
+            Some(Command::Watch(command)) => render(command, Mode::Watch),
+            Some(Command::Serve(serve)) => without(serve.process, serve.render, Mode::Serve { listen: serve.listen, refresh: serve.refresh, token: serve.token }),
+            Some(Command::Record(record)) => {
+                let args = RenderArgs { target: record.target.clone(), hex_preview: record.hex_preview, ..defaults() };
+                without(record.process.clone(), args, Mode::Record(record))
+            }
+            Some(Command::Animate(animate)) => without(None, RenderArgs { draw: animate.draw.clone(), ..defaults() }, Mode::Animate(animate)),
+            Some(Command::Replay(replay)) => without(None, RenderArgs { draw: replay.draw.clone(), export: replay.export.clone(), ..defaults() }, Mode::Replay(replay)),
+            Some(Command::Diff(diff)) => without(None, RenderArgs { draw: diff.draw.clone(), filter: diff.filter.clone(), ..defaults() }, Mode::Diff(diff)),
+            Some(Command::Snapshot { command: SnapshotCommand::Save(save) }) => {
+                let args = RenderArgs { target: save.target, hex_preview: save.hex_preview, ..defaults() };
+                without(save.process, args, Mode::SnapshotSave(save.output))
+            }
+            Some(Command::Snapshot { command: SnapshotCommand::Render(render) }) => without(None, render.render, Mode::SnapshotRender(render.file)),
+            Some(Command::Completions { shell }) => without(None, defaults(), Mode::Completions(shell)),
+            #[cfg(feature = "self-update")]
+            Some(Command::SelfUpdate) => without(None, defaults(), Mode::SelfUpdate),
+        }
+    }
+}
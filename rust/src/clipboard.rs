@@ -0,0 +1,10 @@
+use image::RgbImage;
+
+// Puts the map on the system clipboard, for pasting into a chat or ticket.
+// X11 serves the clipboard from its owner, so when memlayout exits the image
+// is handed to the clipboard manager; without one running it goes with us.
+pub fn copy_image(image: &RgbImage) -> Result<(), arboard::Error> {
+    let rgba: Vec<u8> = image.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect();
+    let data = arboard::ImageData { width: image.width() as usize, height: image.height() as usize, bytes: rgba.into() };
+    arboard::Clipboard::new()?.set_image(data)
+}
@@ -0,0 +1,36 @@
+use crate::cli::Cli;
+use crate::theme;
+use clap::CommandFactory;
+use clap_complete::{generate as write_script, Shell};
+
+// The scripts clap writes, with --theme offering the built-in names as well
+// as JSON files. Values with a fixed list, --format among them, are already
+// completed from the possible values the command line declares.
+pub fn generate(shell: &str) -> String {
+    let shell: Shell = shell.parse().expect("Unknown shell");
+    let mut script = Vec::new();
+    write_script(shell, &mut Cli::command(), "memlayout", &mut script);
+    let script = String::from_utf8(script).expect("Completion script is not UTF-8");
+    let themes = theme::BUILTIN.join(" ");
+    match shell {
+        // The theme case sets IFS to a newline around its completion, so
+        // the names are given one per line.
+        Shell::Bash => {
+            let completion = format!("COMPREPLY=($(compgen -W $'{}' -- \"${{cur}}\") $(compgen -o plusdirs -f -X '!*.json' -- \"${{cur}}\"))", theme::BUILTIN.join("\\n"));
+            let mut parts = script.split("--theme)\n");
+            let first = parts.next().unwrap_or_default().to_string();
+            parts.fold(first, |script, part| script + "--theme)\n" + &part.replacen("COMPREPLY=($(compgen -f \"${cur}\"))", &completion, 1))
+        }
+        Shell::Zsh => {
+            let helper = format!("(( $+functions[_memlayout_themes] )) ||\n_memlayout_themes() {{\n    _alternative 'themes:theme:({})' 'files:theme file:_files -g \"*.json\"'\n}}\n\n_memlayout \"$@\"", themes);
+            script.replace(":NAME|FILE:_files'", ":NAME|FILE:_memlayout_themes'").replace("\n_memlayout \"$@\"", &format!("\n{}", helper))
+        }
+        _ => script
+            .lines()
+            .map(|line| match line.contains(" -l theme ") {
+                true => format!("{} -a \"{}\"\n", line, themes),
+                false => format!("{}\n", line),
+            })
+            .collect(),
+    }
+}
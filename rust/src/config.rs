@@ -0,0 +1,198 @@
+use clap::{Arg, Command};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const CONFIG_FILE: &str = "memory-map-visualizer/config.toml";
+
+// The subset of TOML a flat list of defaults needs: top-level keys with
+// string, number, boolean or one-line array values.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join(CONFIG_FILE))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_scalar(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        return Ok(inner.to_string());
+    }
+    if let Some(inner) = raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        let mut text = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(c @ ('"' | '\\')) => text.push(c),
+                _ => return Err(format!("Unsupported escape in {}", raw)),
+            }
+        }
+        return Ok(text);
+    }
+    // Bare numbers, which may use TOML's underscores as separators.
+    if !raw.is_empty() && raw.chars().all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c)) {
+        return Ok(raw.replace('_', ""));
+    }
+    Err(format!("Unsupported value: {}", raw))
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    match raw.trim() {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        raw if raw.starts_with('[') => {
+            let inner = raw.strip_prefix('[').and_then(|s| s.strip_suffix(']')).ok_or_else(|| format!("Arrays must fit on one line: {}", raw))?;
+            let items = inner.split(',').map(str::trim).filter(|item| !item.is_empty());
+            Ok(Value::List(items.map(parse_scalar).collect::<Result<_, _>>()?))
+        }
+        raw => parse_scalar(raw).map(Value::Text),
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!("line {}: tables are not supported, put options at the top level", number + 1));
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = parse_value(value).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+// Finds the file named by --config, or else the per-user one if it exists.
+fn locate(args: &[String]) -> Option<PathBuf> {
+    let explicit = args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix("--config") {
+        Some("") => args.get(i + 1).cloned(),
+        Some(value) => value.strip_prefix('=').map(str::to_string),
+        None => None,
+    });
+    match explicit {
+        Some(path) => Some(PathBuf::from(path)),
+        None => default_path().filter(|path| path.exists()),
+    }
+}
+
+// The subcommand the command line runs, as the commands from the top one
+// down, and the index its own arguments start at.
+fn subcommand_path<'a>(app: &'a Command, args: &[String]) -> (Vec<&'a Command>, usize) {
+    let mut path = vec![app];
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--config" {
+            i += 2;
+            continue;
+        }
+        if arg.starts_with("--config=") {
+            i += 1;
+            continue;
+        }
+        match path[path.len() - 1].find_subcommand(arg) {
+            Some(subcommand) => path.push(subcommand),
+            None => break,
+        }
+        i += 1;
+    }
+    (path, i)
+}
+
+fn find_arg<'a>(command: &'a Command, key: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|arg| key != "config" && (arg.get_long() == Some(key) || arg.get_all_aliases().is_some_and(|aliases| aliases.contains(&key))))
+}
+
+fn known_anywhere(command: &Command, key: &str) -> bool {
+    find_arg(command, key).is_some() || command.get_subcommands().any(|subcommand| known_anywhere(subcommand, key))
+}
+
+// Each key names a long option of some command. Keys are turned into
+// arguments placed right after the subcommand being run, except for options
+// the command line already gives, so flags always override the file. Keys
+// of other subcommands are left for when those run.
+pub fn apply(app: &Command, mut args: Vec<String>) -> Result<Vec<String>, String> {
+    let path = match locate(&args) {
+        Some(found) => found,
+        None => return Ok(args),
+    };
+    let text = fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let entries = parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // Global options only reach the subcommands once the command is built.
+    let mut app = app.clone();
+    app.build();
+    let (commands, at) = subcommand_path(&app, &args);
+    let command = commands[commands.len() - 1];
+
+    let mut defaults = Vec::new();
+    for (key, value) in entries {
+        let arg = match find_arg(command, &key) {
+            Some(arg) => arg,
+            None if known_anywhere(&app, &key) => continue,
+            None => return Err(format!("{}: unknown option {}", path.display(), key)),
+        };
+        // Aliases count too, or a default and an aliased flag would collide.
+        let names: Vec<String> = arg.get_long().into_iter().chain(arg.get_all_aliases().unwrap_or_default()).map(|name| format!("--{}", name)).collect();
+        let long = names[0].clone();
+        let short = arg.get_short().map(|c| format!("-{}", c));
+        let given = args.iter().skip(at).any(|a| names.iter().any(|name| a == name || a.starts_with(&format!("{}=", name))) || Some(a) == short.as_ref());
+        if given {
+            continue;
+        }
+        let takes_value = arg.get_action().takes_values();
+        match value {
+            Value::Bool(set) if !takes_value => {
+                if set {
+                    defaults.push(long);
+                }
+            }
+            _ if !takes_value => return Err(format!("{}: {} is a flag and takes true or false", path.display(), key)),
+            Value::Bool(set) => defaults.push(format!("{}={}", long, set)),
+            Value::Text(text) => defaults.push(format!("{}={}", long, text)),
+            Value::List(items) => defaults.extend(items.iter().map(|item| format!("{}={}", long, item))),
+        }
+    }
+    args.splice(at..at, defaults);
+    Ok(args)
+}
@@ -0,0 +1,59 @@
+use crate::namespace;
+use std::fs;
+use std::process::Command;
+
+// Docker and Podman both answer inspect with the host PID of the container's
+// init process, already translated out of the container's PID namespace.
+pub fn resolve_pid(container: &str) -> Result<u32, String> {
+    let mut errors = Vec::new();
+    for runtime in ["docker", "podman"] {
+        match inspect(runtime, container) {
+            Ok(pid) => return Ok(pid),
+            Err(e) => errors.push(e),
+        }
+    }
+    match scan_cgroups(container) {
+        Ok(pid) => Ok(pid),
+        Err(e) => {
+            errors.push(e);
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+fn inspect(runtime: &str, container: &str) -> Result<u32, String> {
+    let output = Command::new(runtime)
+        .args(["inspect", "--format", "{{.State.Pid}}", container])
+        .output()
+        .map_err(|e| format!("Unable to run {}: {}", runtime, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Err(format!("{} inspect {} failed: {}", runtime, container, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    match stdout.trim().parse::<u32>() {
+        Ok(0) => Err(format!("Container {} is not running", container)),
+        Ok(pid) => Ok(pid),
+        Err(_) => Err(format!("{} inspect {} returned no PID: {}", runtime, container, stdout.trim())),
+    }
+}
+
+// Without a runtime to ask, a container ID still shows up in the cgroup paths
+// of its processes (/docker/<id>, docker-<id>.scope, libpod-<id>.scope). Its
+// init is the one that is PID 1 in its own namespace.
+fn scan_cgroups(id: &str) -> Result<u32, String> {
+    if id.len() < 12 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("{} is not a container ID, so its cgroups can't be searched", id));
+    }
+    let entries = fs::read_dir("/proc").map_err(|e| format!("Unable to list /proc: {}", e))?;
+    let mut candidates: Vec<u32> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| fs::read_to_string(format!("/proc/{}/cgroup", pid)).is_ok_and(|cgroup| cgroup.contains(id)))
+        .collect();
+    candidates.sort_unstable();
+    candidates
+        .iter()
+        .find(|pid| namespace::innermost_pid(**pid) == Some(1))
+        .or(candidates.first())
+        .copied()
+        .ok_or_else(|| format!("No process belongs to a cgroup of container {}", id))
+}
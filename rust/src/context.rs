@@ -0,0 +1,185 @@
+use crate::{draw_hatch, fit_text, format_size, region_color, scaled, MemoryRegion, RenderOptions};
+use plotters::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+// Logical pixels of the column the bar and its labels take.
+pub const COLUMN_WIDTH: u32 = 70;
+const BAR_WIDTH: i32 = 14;
+// Share of the bar given to the user half; the kernel half is only a
+// reference, and the non-canonical hole between them a fixed break.
+const USER_SHARE: f64 = 0.85;
+const HOLE_HEIGHT: i32 = 12;
+const NEAR_BOUNDARY: u128 = 1 << 20;
+pub const BOUNDARY_COLOR: RGBColor = RGBColor(150, 40, 210);
+
+// Where user space ends and the kernel half begins, and the lowest address
+// a process may map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddressSpace {
+    // The user half is 1 << user_bits bytes and the kernel half mirrors it
+    // at the top, leaving the non-canonical hole between them.
+    pub user_bits: u32,
+    // vm.mmap_min_addr, known only for this machine's processes.
+    pub mmap_min_addr: Option<usize>,
+}
+
+// Splits in use: 39 and 42 bits on arm64 with 4 KiB and 64 KiB pages, 47
+// with 4-level paging on x86-64, 48 and 52 on arm64, 56 with 5-level paging.
+const USER_BITS: [u32; 6] = [39, 42, 47, 48, 52, 56];
+
+// The smallest split that holds every user mapping. Kernel addresses such
+// as [vsyscall] live in the upper half and don't count.
+fn bits_from_regions(memory_regions: &[MemoryRegion]) -> u32 {
+    let highest = memory_regions.iter().filter(|region| region.attributes.allocated && region.end <= 1 << 63).map(|region| region.end).max().unwrap_or(0);
+    USER_BITS.into_iter().find(|bits| highest <= 1 << bits).unwrap_or(63)
+}
+
+// On x86-64 the kernel drops the la57 flag from cpuinfo unless it runs with
+// 5-level paging. Elsewhere the split is read off the mappings, the stack
+// sitting close to the top.
+#[cfg(not(target_arch = "wasm32"))]
+fn machine_bits() -> Option<u32> {
+    if std::env::consts::ARCH != "x86_64" {
+        return None;
+    }
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    let flags = cpuinfo.lines().find(|line| line.starts_with("flags"))?;
+    Some(if flags.split_whitespace().any(|flag| flag == "la57") { 56 } else { 47 })
+}
+
+impl AddressSpace {
+    // From the mappings alone, for processes captured elsewhere.
+    pub fn infer(memory_regions: &[MemoryRegion]) -> Self {
+        AddressSpace { user_bits: bits_from_regions(memory_regions), mmap_min_addr: None }
+    }
+
+    // What this machine's kernel uses, when the process runs here.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn detect(memory_regions: &[MemoryRegion], local: bool) -> Self {
+        if !local {
+            return Self::infer(memory_regions);
+        }
+        let user_bits = machine_bits().unwrap_or(0).max(bits_from_regions(memory_regions));
+        let mmap_min_addr = fs::read_to_string("/proc/sys/vm/mmap_min_addr").ok().and_then(|value| value.trim().parse().ok());
+        AddressSpace { user_bits, mmap_min_addr }
+    }
+
+    pub fn user_end(&self) -> u128 {
+        1 << self.user_bits
+    }
+
+    pub fn kernel_start(&self) -> u128 {
+        (1 << 64) - self.user_end()
+    }
+
+    // The lines worth drawing across the map, by address.
+    pub fn boundaries(&self) -> Vec<(u128, String)> {
+        let mut boundaries = Vec::new();
+        if let Some(min) = self.mmap_min_addr.filter(|min| *min > 0) {
+            boundaries.push((min as u128, format!("mmap_min_addr {}", format_size(min))));
+        }
+        boundaries.push((self.user_end(), format!("{}-bit user space ends", self.user_bits)));
+        boundaries.push((self.kernel_start(), format!("kernel {:#x}", self.kernel_start())));
+        boundaries
+    }
+
+    // Mappings that sit where a normal process doesn't put them: under
+    // mmap_min_addr, which takes CAP_SYS_RAWIO, in the hole, or flush
+    // against the top of user space, where only a stack without ASLR ends.
+    pub fn anomalies<'a>(&self, memory_regions: &'a [MemoryRegion]) -> Vec<(&'a MemoryRegion, &'static str)> {
+        memory_regions
+            .iter()
+            .filter(|region| region.attributes.allocated)
+            .filter_map(|region| {
+                let (start, end) = (region.start as u128, region.end as u128);
+                let reason = if self.mmap_min_addr.is_some_and(|min| start < min as u128) {
+                    "below mmap_min_addr"
+                } else if start < self.kernel_start() && end > self.user_end() {
+                    "in the non-canonical hole"
+                } else if end <= self.user_end() && self.user_end() - end < NEAR_BOUNDARY {
+                    "within 1 MiB of the user/kernel boundary"
+                } else {
+                    return None;
+                };
+                Some((region, reason))
+            })
+            .collect()
+    }
+}
+
+// A thin bar of the whole canonical address space, low addresses at the
+// top like the map: every mapping as a tick at least two pixels tall and
+// the span the map shows bracketed, then the hole and the kernel half.
+pub fn draw_context_bar<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, memory_regions: &[MemoryRegion], x: i32, height: i32, options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 9.0 * options.scale_factor, FontStyle::Normal);
+    let space = options.address_space.unwrap_or_else(|| AddressSpace::infer(memory_regions));
+    let (bits, user_size, kernel_start) = (space.user_bits, space.user_end(), space.kernel_start());
+    let (top, bottom) = (px(20), height - px(10));
+    let user_bottom = top + ((bottom - top - px(HOLE_HEIGHT)) as f64 * USER_SHARE) as i32;
+    let kernel_top = user_bottom + px(HOLE_HEIGHT);
+    let bar_right = x + px(BAR_WIDTH);
+    let y = |address: usize| -> i32 {
+        let address = address as u128;
+        if address < user_size {
+            top + (address as f64 / user_size as f64 * (user_bottom - top) as f64) as i32
+        } else {
+            let into_kernel = address.saturating_sub(kernel_start) as f64 / user_size as f64;
+            kernel_top + (into_kernel * (bottom - kernel_top) as f64) as i32
+        }
+    };
+
+    root.draw(&Text::new(format!("{}-bit", bits), (x, top - px(14)), font.color(&ink)))?;
+    let gap = options.theme.gap;
+    root.draw(&Rectangle::new([(x, top), (bar_right, user_bottom)], RGBColor(gap[0], gap[1], gap[2]).filled()))?;
+    draw_hatch(root, (x, user_bottom + px(2)), (bar_right, kernel_top - px(2)), &RGBColor(150, 150, 150), px(3))?;
+    root.draw(&Rectangle::new([(x, kernel_top), (bar_right, bottom)], RGBColor(90, 90, 90).filled()))?;
+
+    for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+        let color = region_color(region, options.color_by, &options.theme);
+        let (y0, y1) = (y(region.start), y(region.end.saturating_sub(1)));
+        root.draw(&Rectangle::new([(x, y0), (bar_right, y1.max(y0 + px(2)))], RGBColor(color[0], color[1], color[2]).filled()))?;
+    }
+
+    // Collapsed gaps are drawn, but aren't what the map is showing.
+    let mut shown = memory_regions.iter().filter(|region| !options.is_collapsed(region));
+    if let Some(first) = shown.next() {
+        let last = shown.next_back().unwrap_or(first);
+        let (y0, y1) = (y(first.start), y(last.end.saturating_sub(1)));
+        let (y0, y1) = (y0 - px(1), y1.max(y0 + px(2)) + px(1));
+        root.draw(&Rectangle::new([(x - px(3), y0), (bar_right + px(3), y1)], RED.stroke_width(px(2) as u32)))?;
+    }
+
+    let label_x = bar_right + px(5);
+    root.draw(&Text::new("0", (label_x, top), font.color(&ink)))?;
+    root.draw(&Text::new(format_size((user_size - 1) as usize), (label_x, user_bottom - px(10)), font.color(&ink)))?;
+    root.draw(&Text::new("kernel", (label_x, kernel_top), font.color(&ink)))?;
+    Ok(())
+}
+
+// A dashed line across the bars with its label at the right end, above the
+// line when there is room.
+pub fn draw_boundary<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, y: i32, label: &str, (x0, x1): (i32, i32), scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, scale);
+    let mut x = x0;
+    while x < x1 {
+        root.draw(&PathElement::new(vec![(x, y), ((x + px(6)).min(x1), y)], BOUNDARY_COLOR.stroke_width(px(2) as u32)))?;
+        x += px(10);
+    }
+    let font = FontDesc::new(FontFamily::SansSerif, 9.0 * scale, FontStyle::Bold);
+    let Some(label) = fit_text(root, label, &font, x1 - x0 - px(8))? else { return Ok(()) };
+    let (w, h) = root.estimate_text_size(&label, &TextStyle::from(font.clone()))?;
+    let top = if y - px(2) - h as i32 >= 0 { y - px(2) - h as i32 } else { y + px(3) };
+    let left = x1 - px(4) - w as i32;
+    root.draw(&Rectangle::new([(left - px(1), top), (left + w as i32 + px(1), top + h as i32)], WHITE.mix(0.85).filled()))?;
+    root.draw(&Text::new(label, (left, top), font.color(&BOUNDARY_COLOR)))?;
+    Ok(())
+}
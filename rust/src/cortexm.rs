@@ -0,0 +1,162 @@
+use crate::gdbremote::number;
+use crate::mallocinfo::attribute;
+use crate::{carve, MemoryAttributes, MemoryRegion};
+use std::fs;
+
+// Start, size, name, permissions; device memory is drawn shared.
+type Block = (u64, u64, &'static str, &'static str);
+
+// The architectural map every ARMv6-M, ARMv7-M and ARMv8-M core shares.
+const ARCHITECTURE: [Block; 7] = [
+    (0x0000_0000, 0x2000_0000, "Code", "r-x"),
+    (0x2000_0000, 0x2000_0000, "SRAM", "rwx"),
+    (0x4000_0000, 0x2000_0000, "Peripheral", "rw-s"),
+    (0x6000_0000, 0x4000_0000, "External RAM", "rwx"),
+    (0xA000_0000, 0x4000_0000, "External device", "rw-s"),
+    (0xE000_0000, 0x0010_0000, "Private peripheral bus", "rw-s"),
+    (0xE010_0000, 0x1FF0_0000, "Vendor system", "rw-s"),
+];
+
+// Memories and buses as the reference manuals lay them out.
+const STM32F407: [Block; 10] = [
+    (0x0800_0000, 0x0010_0000, "Flash", "r-x"),
+    (0x1000_0000, 0x0001_0000, "CCM RAM", "rw-"),
+    (0x1FFF_0000, 0x0000_7800, "System memory", "r-x"),
+    (0x2000_0000, 0x0001_C000, "SRAM1", "rwx"),
+    (0x2001_C000, 0x0000_4000, "SRAM2", "rwx"),
+    (0x4000_0000, 0x0000_8000, "APB1", "rw-s"),
+    (0x4001_0000, 0x0000_6C00, "APB2", "rw-s"),
+    (0x4002_0000, 0x0006_0000, "AHB1", "rw-s"),
+    (0x5000_0000, 0x0006_0C00, "AHB2", "rw-s"),
+    (0xE000_0000, 0x0010_0000, "Private peripheral bus", "rw-s"),
+];
+
+const NRF52840: [Block; 6] = [
+    (0x0000_0000, 0x0010_0000, "Flash", "r-x"),
+    (0x0080_0000, 0x0004_0000, "Code RAM", "rwx"),
+    (0x1000_0000, 0x0000_1000, "FICR", "r--"),
+    (0x2000_0000, 0x0004_0000, "RAM", "rwx"),
+    (0x4000_0000, 0x2000_0000, "Peripherals", "rw-s"),
+    (0xE000_0000, 0x0010_0000, "Private peripheral bus", "rw-s"),
+];
+
+const RP2040: [Block; 7] = [
+    (0x0000_0000, 0x0000_4000, "ROM", "r-x"),
+    (0x1000_0000, 0x0100_0000, "XIP flash", "r-x"),
+    (0x2000_0000, 0x0004_2000, "SRAM", "rwx"),
+    (0x4000_0000, 0x0007_0000, "APB peripherals", "rw-s"),
+    (0x5000_0000, 0x0040_0000, "AHB-Lite peripherals", "rw-s"),
+    (0xD000_0000, 0x0000_1000, "SIO", "rw-s"),
+    (0xE000_0000, 0x0010_0000, "Private peripheral bus", "rw-s"),
+];
+
+pub const PRESETS: [&str; 4] = ["cortex-m", "stm32f407", "nrf52840", "rp2040"];
+
+fn preset(name: &str) -> Result<&'static [Block], String> {
+    match name {
+        "" | "cortex-m" | "armv6m" | "armv7m" | "armv8m" => Ok(&ARCHITECTURE),
+        "stm32f407" => Ok(&STM32F407),
+        "nrf52840" => Ok(&NRF52840),
+        "rp2040" => Ok(&RP2040),
+        _ => Err(format!("Unknown Cortex-M preset {}; known presets are {}", name, PRESETS.join(", "))),
+    }
+}
+
+// Named like [heap] rather than [anon:...], which would make the code
+// regions JIT code.
+fn region(start: usize, end: usize, name: &str, perms: &str) -> MemoryRegion {
+    let flag = |index: usize, letter: char| perms.chars().nth(index) == Some(letter);
+    MemoryRegion {
+        start,
+        end,
+        size: end - start,
+        attributes: MemoryAttributes { readable: flag(0, 'r'), writable: flag(1, 'w'), executable: flag(2, 'x'), shared: flag(3, 's'), allocated: true },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(format!("[{}]", name)),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim())
+}
+
+// Drops every <tag>...</tag>, so the names of registers and interrupts
+// aren't taken for the peripheral's own.
+fn without<'a>(xml: &'a str, tag: &str) -> String {
+    let mut rest: &'a str = xml;
+    let mut kept = String::new();
+    while let Some(start) = rest.find(&format!("<{}>", tag)) {
+        kept += &rest[..start];
+        let close = format!("</{}>", tag);
+        rest = rest[start..].find(&close).map_or("", |end| &rest[start + end + close.len()..]);
+    }
+    kept + rest
+}
+
+// The <peripheral> blocks of a CMSIS-SVD file, each <addressBlock> at
+// baseAddress + offset. Derived peripherals repeat the blocks of the one
+// they're derived from at their own base.
+pub fn parse_svd(xml: &str) -> Result<Vec<(usize, usize, String)>, String> {
+    let mut blocks: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+    let mut peripherals = Vec::new();
+    for part in xml.split("<peripheral").skip(1) {
+        if !part.starts_with(['>', ' ']) {
+            continue;
+        }
+        let (tag, body) = part.split_once('>').ok_or("Unterminated peripheral")?;
+        let body = body.split("</peripheral>").next().unwrap_or(body);
+        let body = without(&without(&without(body, "registers"), "interrupt"), "cluster");
+        let name = element(&body, "name").ok_or("A peripheral has no name")?.to_string();
+        let base = number(element(&body, "baseAddress").ok_or_else(|| format!("{} has no baseAddress", name))?)?;
+        let mut own = Vec::new();
+        for block in body.split("<addressBlock>").skip(1) {
+            let (offset, size) = (element(block, "offset").unwrap_or("0"), element(block, "size").ok_or_else(|| format!("An address block of {} has no size", name))?);
+            own.push((number(offset)?, number(size)?));
+        }
+        if own.is_empty() {
+            let parent = attribute(tag, "derivedFrom").ok_or_else(|| format!("{} has no addressBlock", name))?;
+            own = blocks.iter().find(|(block_name, _)| block_name == parent).map(|(_, own)| own.clone()).ok_or_else(|| format!("{} is derived from unknown {}", name, parent))?;
+        }
+        for (offset, size) in &own {
+            peripherals.push((base + offset, base + offset + size, name.clone()));
+        }
+        blocks.push((name, own));
+    }
+    if peripherals.is_empty() {
+        return Err("No peripherals in the SVD file".to_string());
+    }
+    Ok(peripherals)
+}
+
+// The preset with the SVD's peripherals cut into it. A peripheral that
+// overlaps the one below it, as aliases sometimes do, is left out.
+pub fn read(preset_name: &str, svd: Option<&str>) -> Result<Vec<MemoryRegion>, String> {
+    let mut peripherals: Vec<MemoryRegion> = Vec::new();
+    if let Some(path) = svd {
+        let xml = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        let mut blocks = parse_svd(&xml)?;
+        blocks.sort_by_key(|block| block.0);
+        for (start, end, name) in blocks {
+            if peripherals.last().is_some_and(|previous| previous.end > start) {
+                tracing::debug!(peripheral = name, "overlaps the one before, left out");
+                continue;
+            }
+            peripherals.push(region(start, end, &name, "rw-s"));
+        }
+    }
+    let mut memory_regions = Vec::new();
+    for (start, size, name, perms) in preset(preset_name)? {
+        memory_regions.extend(carve(&region(*start as usize, (*start + *size) as usize, name, perms), &peripherals));
+    }
+    memory_regions.extend(peripherals);
+    memory_regions.sort_by_key(|region| region.start);
+    Ok(memory_regions)
+}
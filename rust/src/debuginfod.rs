@@ -0,0 +1,44 @@
+use object::Object;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+pub fn build_id(data: &[u8]) -> Option<String> {
+    let file = object::File::parse(data).ok()?;
+    let id = file.build_id().ok()??;
+    Some(id.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Same layout debuginfod-find uses, so an existing client cache is reused.
+fn cache_dir() -> Option<PathBuf> {
+    if let Ok(path) = env::var("DEBUGINFOD_CACHE_PATH") {
+        return Some(PathBuf::from(path));
+    }
+    let base = env::var("XDG_CACHE_HOME").map(PathBuf::from).or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".cache")));
+    base.ok().map(|base| base.join("debuginfod_client"))
+}
+
+fn download(server: &str, build_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let url = format!("{}/buildid/{}/debuginfo", server.trim_end_matches('/'), build_id);
+    let mut data = Vec::new();
+    ureq::get(&url).set("User-Agent", "memlayout").call()?.into_reader().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Returns the path of the separate debug file for a build id, downloading it
+// from the first DEBUGINFOD_URLS server that has it when it isn't cached.
+pub fn debuginfo(build_id: &str) -> Option<PathBuf> {
+    let cached = cache_dir()?.join(build_id).join("debuginfo");
+    if cached.is_file() {
+        return Some(cached);
+    }
+
+    let urls = env::var("DEBUGINFOD_URLS").ok()?;
+    let data = urls.split_whitespace().find_map(|server| download(server, build_id).ok())?;
+    fs::create_dir_all(cached.parent()?).ok()?;
+    let partial = cached.with_extension("partial");
+    fs::write(&partial, data).ok()?;
+    fs::rename(&partial, &cached).ok()?;
+    Some(cached)
+}
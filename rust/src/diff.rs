@@ -0,0 +1,474 @@
+use crate::report::{hex, RegionRecord};
+use crate::snapshot::Snapshot;
+use crate::{animate, audit, display_name, fit_text, format_size, insert_gap_memory_regions, layout, luminance, place_labels, region_color, scaled, source, MemoryRegion, RenderOptions, BREAK_HEIGHT, LEGEND_WIDTH};
+use plotters::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const TITLE_HEIGHT: i32 = 24;
+const LANE_WIDTH: i32 = 240;
+const LANE_GAP: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+    Permissions,
+    // Still mapped, but from another file.
+    Replaced,
+}
+
+impl Change {
+    fn of(before: &MemoryRegion, after: &MemoryRegion) -> Self {
+        match (before.attributes.allocated, after.attributes.allocated) {
+            (false, false) => Change::Same,
+            (false, true) => Change::Added,
+            (true, false) => Change::Removed,
+            _ if before.file_name != after.file_name || before.inode != after.inode => Change::Replaced,
+            _ if before.attributes != after.attributes => Change::Permissions,
+            _ => Change::Same,
+        }
+    }
+
+    fn color(self) -> RGBColor {
+        match self {
+            Change::Same => RGBColor(160, 160, 160),
+            Change::Added => RGBColor(0, 170, 60),
+            Change::Removed => RGBColor(220, 30, 30),
+            Change::Permissions => RGBColor(255, 150, 0),
+            Change::Replaced => RGBColor(150, 60, 200),
+        }
+    }
+}
+
+// A .mmsnap snapshot, a maps or smaps file, or any --source spec such as
+// procfs:PID for the state right now.
+pub fn load(path: &str) -> Result<Vec<MemoryRegion>, String> {
+    if path.ends_with(".mmsnap") {
+        return Snapshot::load(path).map(|snapshot| snapshot.regions).map_err(|e| e.to_string());
+    }
+    let spec = if Path::new(path).exists() { format!("file:{}", path) } else { path.to_string() };
+    source::load(source::open(&spec, true)?.as_ref()).map(|(memory_regions, _)| memory_regions)
+}
+
+// Both captures split at each other's boundaries, as (before, after) pairs
+// over the same intervals with the gaps between them, so an address sits
+// at the same height in both lanes.
+pub fn pair(before: Vec<MemoryRegion>, after: Vec<MemoryRegion>) -> Vec<(MemoryRegion, MemoryRegion)> {
+    let mut aligned = animate::align_frames(&[before, after]).into_iter();
+    let (before, after) = (aligned.next().unwrap(), aligned.next().unwrap());
+    let mut pairs = Vec::new();
+    let mut end = 0;
+    for (before, after) in before.into_iter().zip(after) {
+        if before.start > end {
+            pairs.push((MemoryRegion::gap(end, before.start), MemoryRegion::gap(end, before.start)));
+        }
+        end = before.end;
+        pairs.push((before, after));
+    }
+    pairs
+}
+
+// Runs of adjacent intervals with the same change, as (first, last) pair
+// indices.
+pub fn changed_runs(pairs: &[(MemoryRegion, MemoryRegion)]) -> Vec<(usize, usize, Change)> {
+    let mut runs: Vec<(usize, usize, Change)> = Vec::new();
+    for (i, (before, after)) in pairs.iter().enumerate() {
+        let change = Change::of(before, after);
+        match runs.last_mut() {
+            Some((_, last, kind)) if *last + 1 == i && *kind == change && pairs[*last].1.end == after.start => *last = i,
+            _ if change == Change::Same => {}
+            _ => runs.push((i, i, change)),
+        }
+    }
+    runs
+}
+
+fn name(region: &MemoryRegion) -> String {
+    display_name(region).unwrap_or_else(|| "anon".to_string())
+}
+
+pub fn describe_run(pairs: &[(MemoryRegion, MemoryRegion)], (first, last, change): (usize, usize, Change)) -> String {
+    let (before, after) = (&pairs[first].0, &pairs[first].1);
+    let size = format_size(pairs[last].1.end - after.start);
+    match change {
+        Change::Added => format!("+ {} ({})", name(after), size),
+        Change::Removed => format!("- {} ({})", name(before), size),
+        Change::Replaced => format!("{} -> {} ({})", name(before), name(after), size),
+        _ => {
+            let warning = if audit::is_writable_executable(after) && !audit::is_writable_executable(before) { "W+X " } else { "" };
+            format!("{}{} {} -> {} ({})", warning, name(after), before.attributes.perms(), after.attributes.perms(), size)
+        }
+    }
+}
+
+// Bump on any change that isn't a pure addition, like the region export.
+pub const DIFF_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Resize {
+    pub before: RegionRecord,
+    pub after: RegionRecord,
+}
+
+#[derive(Serialize)]
+pub struct PermissionChange {
+    #[serde(serialize_with = "hex")]
+    pub start: usize,
+    #[serde(serialize_with = "hex")]
+    pub end: usize,
+    pub size: usize,
+    pub path: Option<String>,
+    pub before: String,
+    pub after: String,
+    pub writable_executable: bool,
+}
+
+#[derive(Serialize)]
+pub struct DiffReport {
+    pub schema_version: u32,
+    pub before: String,
+    pub after: String,
+    pub added: Vec<RegionRecord>,
+    pub removed: Vec<RegionRecord>,
+    pub resized: Vec<Resize>,
+    pub permission_changed: Vec<PermissionChange>,
+    // Regions that are writable and executable now but weren't, whether
+    // added so or changed to it: the count a CI check fails on.
+    pub new_writable_executable: usize,
+}
+
+fn same_backing(a: &MemoryRegion, b: &MemoryRegion) -> bool {
+    a.file_name == b.file_name && a.inode == b.inode
+}
+
+fn overlaps(a: &MemoryRegion, start: usize, end: usize) -> bool {
+    a.start < end && start < a.end
+}
+
+// Grown or shrunk in place: the same mapping keeps one of its ends, and
+// what one side has past the other's is unmapped on that other side, not
+// split off with other permissions.
+fn resized(before: &MemoryRegion, after: &MemoryRegion, before_regions: &[&MemoryRegion], after_regions: &[&MemoryRegion]) -> bool {
+    if !same_backing(before, after) || before.attributes != after.attributes || before.size == after.size || (before.start != after.start && before.end != after.end) {
+        return false;
+    }
+    let (start, end) = if before.start == after.start { (before.end.min(after.end), before.end.max(after.end)) } else { (before.start.min(after.start), before.start.max(after.start)) };
+    let other = if after.size > before.size { before_regions } else { after_regions };
+    !other.iter().any(|region| overlaps(region, start, end))
+}
+
+// Region by region for added, removed and resized; permission changes come
+// from the intervals, since mprotect() on part of a region splits it.
+pub fn report(before: &[MemoryRegion], after: &[MemoryRegion], pairs: &[(MemoryRegion, MemoryRegion)], names: (&str, &str)) -> DiffReport {
+    let before_regions: Vec<&MemoryRegion> = before.iter().filter(|region| region.attributes.allocated).collect();
+    let after_regions: Vec<&MemoryRegion> = after.iter().filter(|region| region.attributes.allocated).collect();
+    let mut resizes = Vec::new();
+    for old in &before_regions {
+        for new in after_regions.iter().filter(|new| resized(old, new, &before_regions, &after_regions)) {
+            resizes.push(Resize { before: RegionRecord::from(*old), after: RegionRecord::from(*new) });
+        }
+    }
+    let unmatched = |region: &MemoryRegion, others: &[&MemoryRegion]| !others.iter().any(|other| same_backing(region, other) && overlaps(other, region.start, region.end));
+    let added: Vec<&MemoryRegion> = after_regions.iter().copied().filter(|region| unmatched(region, &before_regions)).collect();
+    let removed: Vec<&MemoryRegion> = before_regions.iter().copied().filter(|region| unmatched(region, &after_regions)).collect();
+
+    let permission_changed: Vec<PermissionChange> = changed_runs(pairs)
+        .into_iter()
+        .filter(|run| run.2 == Change::Permissions)
+        .map(|(first, last, _)| {
+            let (old, new) = (&pairs[first].0, &pairs[first].1);
+            PermissionChange {
+                start: new.start,
+                end: pairs[last].1.end,
+                size: pairs[last].1.end - new.start,
+                path: new.file_name.clone(),
+                before: old.attributes.perms(),
+                after: new.attributes.perms(),
+                writable_executable: audit::is_writable_executable(new) && !audit::is_writable_executable(old),
+            }
+        })
+        .collect();
+    let new_writable_executable = added.iter().filter(|region| audit::is_writable_executable(region)).count() + permission_changed.iter().filter(|change| change.writable_executable).count();
+    DiffReport {
+        schema_version: DIFF_SCHEMA_VERSION,
+        before: names.0.to_string(),
+        after: names.1.to_string(),
+        added: added.into_iter().map(RegionRecord::from).collect(),
+        removed: removed.into_iter().map(RegionRecord::from).collect(),
+        resized: resizes,
+        permission_changed,
+        new_writable_executable,
+    }
+}
+
+// The regions of one side; those the change list marks for this side get
+// outlined in the change's color.
+fn draw_lane<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, regions: &[&MemoryRegion], changes: &[Change], extents: &[(i32, i32)], x: i32, outlined: &[Change], options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let right = x + px(LANE_WIDTH);
+    for ((region, change), &(y, height)) in regions.iter().zip(changes).zip(extents) {
+        let color = region_color(region, options.color_by, &options.theme);
+        root.draw(&Rectangle::new([(x, y), (right, y + height)], RGBColor(color[0], color[1], color[2]).filled()))?;
+        if height >= px(11) {
+            if let Some(label) = display_name(region).map(|name| format!("{} {}", name, region.attributes.perms())) {
+                if let Some(label) = fit_text(root, &label, &font, px(LANE_WIDTH - 6))? {
+                    let ink = if luminance(color) > 128.0 { &BLACK } else { &WHITE };
+                    root.draw(&Text::new(label, (x + px(3), y + px(1)), font.color(ink)))?;
+                }
+            }
+        }
+        if outlined.contains(change) {
+            root.draw(&Rectangle::new([(x + px(1), y), (right - px(1), y + height.max(px(3)))], change.color().stroke_width(px(3) as u32)))?;
+        }
+    }
+    Ok(())
+}
+
+// Before, the changes between, and after, side by side on one address scale:
+// added regions outlined green in the after lane, removed ones red in the
+// before lane, and permission changes orange in both.
+pub fn draw_diff(pairs: &[(MemoryRegion, MemoryRegion)], titles: (&str, &str), options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let legend_width = px(LEGEND_WIDTH as i32);
+    let width = legend_width + 3 * px(LANE_WIDTH) + 3 * px(LANE_GAP);
+    let map_height = px(options.height as i32);
+    let root = BitMapBackend::new(path, (width as u32, (map_height + px(TITLE_HEIGHT)) as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let (title, map) = root.split_vertically(px(TITLE_HEIGHT));
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+
+    // One layout for both lanes: an interval mapped on either side is a
+    // region, so only gaps on both sides collapse.
+    let union: Vec<MemoryRegion> = pairs.iter().map(|(before, after)| if after.attributes.allocated { after.clone() } else { before.clone() }).collect();
+    let extents = layout(&union, map_height as u32, px(BREAK_HEIGHT), options);
+    let changes: Vec<Change> = pairs.iter().map(|(before, after)| Change::of(before, after)).collect();
+    let runs = changed_runs(pairs);
+    let lane_x = |lane: i32| legend_width + lane * (px(LANE_WIDTH) + px(LANE_GAP));
+
+    let counts: Vec<String> = [(Change::Added, "added"), (Change::Removed, "removed"), (Change::Permissions, "permission changes"), (Change::Replaced, "replaced")]
+        .into_iter()
+        .map(|(kind, what)| (runs.iter().filter(|run| run.2 == kind).count(), what))
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+    let summary = if counts.is_empty() { "no changes".to_string() } else { counts.join(", ") };
+    let title_font = FontDesc::new(FontFamily::SansSerif, 11.0 * options.scale_factor, FontStyle::Bold);
+    for (lane, text) in [(0, format!("before: {}", titles.0)), (1, summary), (2, format!("after: {}", titles.1))] {
+        if let Some(text) = fit_text(&title, &text, &title_font, px(LANE_WIDTH))? {
+            title.draw(&Text::new(text, (lane_x(lane), px(6)), title_font.color(&ink)))?;
+        }
+    }
+
+    let before: Vec<&MemoryRegion> = pairs.iter().map(|(before, _)| before).collect();
+    let after: Vec<&MemoryRegion> = pairs.iter().map(|(_, after)| after).collect();
+    draw_lane(&map, &before, &changes, &extents, lane_x(0), &[Change::Removed, Change::Permissions, Change::Replaced], options)?;
+    draw_lane(&map, &after, &changes, &extents, lane_x(2), &[Change::Added, Change::Permissions, Change::Replaced], options)?;
+
+    let middle = lane_x(1);
+    for (region, &(y, height)) in union.iter().zip(&extents) {
+        if options.is_collapsed(region) {
+            let gap = options.theme.gap;
+            map.draw(&Rectangle::new([(middle, y), (middle + px(LANE_WIDTH), y + height)], RGBColor(gap[0], gap[1], gap[2]).mix(0.5).filled()))?;
+            let label = format!("{} unmapped", format_size(region.size));
+            map.draw(&Text::new(label, (middle + px(3), y + (height - px(10)) / 2), font.color(&ink)))?;
+        }
+    }
+    for &(first, last, change) in &runs {
+        let (y, _) = extents[first];
+        let bottom = extents[last].0 + extents[last].1.max(px(3));
+        map.draw(&Rectangle::new([(middle, y), (middle + px(LANE_WIDTH), bottom)], change.color().mix(0.8).filled()))?;
+        if bottom - y >= px(11) {
+            if let Some(label) = fit_text(&map, &describe_run(pairs, (first, last, change)), &font, px(LANE_WIDTH - 6))? {
+                map.draw(&Text::new(label, (middle + px(3), y + px(1)), font.color(&BLACK)))?;
+            }
+        }
+        for lane in [0, 1] {
+            map.draw(&PathElement::new(vec![(lane_x(lane) + px(LANE_WIDTH), y), (lane_x(lane + 1), y)], change.color().stroke_width(px(1) as u32)))?;
+        }
+    }
+
+    // The start of every change, in the address column.
+    let label_height = px(12);
+    let wanted: Vec<i32> = runs.iter().map(|run| extents[run.0].0).collect();
+    for (run, top) in runs.iter().zip(place_labels(&wanted, label_height, map_height, options.label_placement)) {
+        let Some(top) = top else { continue };
+        let y = extents[run.0].0;
+        if top != y {
+            map.draw(&PathElement::new(vec![(legend_width - px(28), top + label_height / 2), (legend_width - px(2), y)], ink))?;
+        }
+        map.draw(&Text::new(format!("{:#x}", pairs[run.0].1.start), (px(5), top), font.color(&run.2.color())))?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+// Two live processes rather than one process twice: their addresses have
+// nothing to do with each other, so only the objects mapped can be matched.
+pub fn different_processes(before: &str, after: &str) -> bool {
+    let pid = |spec: &str| spec.strip_prefix("procfs:").and_then(|pid| pid.parse::<u32>().ok());
+    matches!((pid(before), pid(after)), (Some(a), Some(b)) if a != b)
+}
+
+// What a region is an instance of in any process: its file, or a name such
+// as [heap] or [anon:NAME]. Plain anonymous memory and thread stacks are
+// the process's own.
+fn object(region: &MemoryRegion) -> Option<&str> {
+    if !region.attributes.allocated || region.thread_id.is_some() {
+        return None;
+    }
+    region.file_name.as_deref()
+}
+
+#[derive(Default)]
+struct Totals {
+    start: usize,
+    mapped: usize,
+    rss: usize,
+    private_dirty: usize,
+    // Indices of the object's regions, first and last.
+    first: usize,
+    last: usize,
+}
+
+fn objects(regions: &[MemoryRegion]) -> BTreeMap<&str, Totals> {
+    let mut objects: BTreeMap<&str, Totals> = BTreeMap::new();
+    for (index, region) in regions.iter().enumerate() {
+        let Some(name) = object(region) else { continue };
+        let totals = objects.entry(name).or_insert_with(|| Totals { start: region.start, first: index, ..Totals::default() });
+        totals.mapped += region.size;
+        totals.rss += region.smaps.as_ref().map_or(0, |smaps| smaps.rss);
+        totals.private_dirty += region.smaps.as_ref().map_or(0, |smaps| smaps.private_dirty);
+        totals.last = index;
+    }
+    objects
+}
+
+fn private_anonymous(regions: &[MemoryRegion]) -> (usize, usize) {
+    let anonymous: Vec<&MemoryRegion> = regions.iter().filter(|region| region.attributes.allocated && object(region).is_none()).collect();
+    (anonymous.iter().map(|region| region.size).sum(), anonymous.iter().filter_map(|region| region.smaps.as_ref()).map(|smaps| smaps.rss).sum())
+}
+
+fn signed_size(bytes: i64) -> String {
+    format!("{}{}", if bytes < 0 { "-" } else { "+" }, format_size(bytes.unsigned_abs() as usize))
+}
+
+// Where two processes differ object by object, largest RSS difference
+// first, then what each has that matches nothing in the other.
+pub fn print_objects(before: &[MemoryRegion], after: &[MemoryRegion], names: (&str, &str), limit: usize) {
+    let (a, b) = (objects(before), objects(after));
+    let mut shared: Vec<(&str, &Totals, &Totals)> = a.iter().filter_map(|(name, left)| Some((*name, left, b.get(name)?))).collect();
+    shared.sort_by_key(|(_, left, right)| std::cmp::Reverse((right.rss as i64 - left.rss as i64).abs()));
+    println!("A is {}, B is {}", names.0, names.1);
+    println!("{:>10} {:>10} {:>10} {:>10}  object", "RSS A", "RSS B", "difference", "dirty B");
+    for (name, left, right) in shared.iter().take(limit) {
+        println!("{:>10} {:>10} {:>10} {:>10}  {}", format_size(left.rss), format_size(right.rss), signed_size(right.rss as i64 - left.rss as i64), format_size(right.private_dirty), name);
+    }
+    let ((anon_a, anon_rss_a), (anon_b, anon_rss_b)) = (private_anonymous(before), private_anonymous(after));
+    println!("{:>10} {:>10} {:>10} {:>10}  anonymous and thread stacks ({} and {} mapped)", format_size(anon_rss_a), format_size(anon_rss_b), signed_size(anon_rss_b as i64 - anon_rss_a as i64), "", format_size(anon_a), format_size(anon_b));
+    for (side, only, other) in [("A", &a, &b), ("B", &b, &a)] {
+        let unmatched: Vec<(&&str, &Totals)> = only.iter().filter(|(name, _)| !other.contains_key(*name)).collect();
+        if !unmatched.is_empty() {
+            let bytes: usize = unmatched.iter().map(|(_, totals)| totals.rss).sum();
+            println!("only in {}: {} objects, {} RSS", side, unmatched.len(), format_size(bytes));
+        }
+    }
+    let moved = shared.iter().filter(|(_, left, right)| left.start != right.start).count();
+    println!("{} of {} shared objects at different addresses", moved, shared.len());
+}
+
+// The two processes in lanes of their own scale, with a connector from an
+// object in one to the same object in the other: its color says which side
+// has more of it resident, and the objects only one side maps are outlined,
+// green for B and red for A. Base addresses run down both outer edges.
+pub fn draw_by_object(before: &[MemoryRegion], after: &[MemoryRegion], titles: (&str, &str), options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let legend_width = px(LEGEND_WIDTH as i32);
+    let width = 2 * legend_width + 3 * px(LANE_WIDTH) + 2 * px(LANE_GAP);
+    let map_height = px(options.height as i32);
+    let root = BitMapBackend::new(path, (width as u32, (map_height + px(TITLE_HEIGHT)) as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let (title, map) = root.split_vertically(px(TITLE_HEIGHT));
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let lane_x = |lane: i32| legend_width + lane * (px(LANE_WIDTH) + px(LANE_GAP));
+
+    let (left, right) = (insert_gap_memory_regions(before), insert_gap_memory_regions(after));
+    let (left_extents, right_extents) = (layout(&left, map_height as u32, px(BREAK_HEIGHT), options), layout(&right, map_height as u32, px(BREAK_HEIGHT), options));
+    let (a, b) = (objects(&left), objects(&right));
+    let change = |region: &MemoryRegion, other: &BTreeMap<&str, Totals>, unmatched: Change| match object(region) {
+        Some(name) if !other.contains_key(name) => unmatched,
+        _ => Change::Same,
+    };
+    let left_changes: Vec<Change> = left.iter().map(|region| change(region, &b, Change::Removed)).collect();
+    let right_changes: Vec<Change> = right.iter().map(|region| change(region, &a, Change::Added)).collect();
+
+    let shared = a.keys().filter(|name| b.contains_key(*name)).count();
+    let summary = format!("{} shared objects, {} only in A, {} only in B", shared, a.len() - shared, b.len() - shared);
+    let title_font = FontDesc::new(FontFamily::SansSerif, 11.0 * options.scale_factor, FontStyle::Bold);
+    for (lane, text) in [(0, format!("A: {}", titles.0)), (1, summary), (2, format!("B: {}", titles.1))] {
+        if let Some(text) = fit_text(&title, &text, &title_font, px(LANE_WIDTH))? {
+            title.draw(&Text::new(text, (lane_x(lane), px(6)), title_font.color(&ink)))?;
+        }
+    }
+
+    let left_refs: Vec<&MemoryRegion> = left.iter().collect();
+    let right_refs: Vec<&MemoryRegion> = right.iter().collect();
+    draw_lane(&map, &left_refs, &left_changes, &left_extents, lane_x(0), &[Change::Removed], options)?;
+    draw_lane(&map, &right_refs, &right_changes, &right_extents, lane_x(2), &[Change::Added], options)?;
+
+    // The middle of an object's span in a lane, from its first region to
+    // its last.
+    let middle = |totals: &Totals, extents: &[(i32, i32)]| (extents[totals.first].0 + extents[totals.last].0 + extents[totals.last].1) / 2;
+    let mut differences: Vec<(i32, String, RGBColor)> = Vec::new();
+    for (name, from) in &a {
+        let Some(to) = b.get(name) else { continue };
+        let (y0, y1) = (middle(from, &left_extents), middle(to, &right_extents));
+        let difference = to.rss as i64 - from.rss as i64;
+        // Differences under a quarter of the smaller side, or a MiB, are
+        // noise between replicas.
+        let notable = difference.unsigned_abs() as usize > (from.rss.min(to.rss) / 4).max(1 << 20);
+        let color = match difference {
+            _ if !notable => RGBColor(170, 170, 170),
+            difference if difference > 0 => RGBColor(220, 30, 30),
+            _ => RGBColor(0, 150, 60),
+        };
+        map.draw(&PathElement::new(vec![(lane_x(0) + px(LANE_WIDTH), y0), (lane_x(2), y1)], color.mix(if notable { 0.9 } else { 0.35 }).stroke_width(px(1) as u32)))?;
+        if notable {
+            differences.push(((y0 + y1) / 2, format!("{} {}", signed_size(difference), display_name(&left[from.first]).unwrap_or_default()), color));
+        }
+    }
+    differences.sort_by_key(|(y, _, _)| *y);
+    let label_height = px(12);
+    let wanted: Vec<i32> = differences.iter().map(|(y, _, _)| *y - label_height / 2).collect();
+    for ((_, text, color), top) in differences.iter().zip(place_labels(&wanted, label_height, map_height, options.label_placement)) {
+        let Some(top) = top else { continue };
+        let Some(text) = fit_text(&map, text, &font, px(LANE_WIDTH - 20))? else { continue };
+        let (text_width, _) = map.estimate_text_size(&text, &TextStyle::from(font.clone()))?;
+        let x = lane_x(1) + (px(LANE_WIDTH) - text_width as i32) / 2;
+        map.draw(&Rectangle::new([(x - px(2), top), (x + text_width as i32 + px(2), top + label_height)], options.background().filled()))?;
+        map.draw(&Text::new(text, (x, top), font.color(color)))?;
+    }
+
+    // Where each mapped file starts, the ASLR slide of the two processes.
+    for (objects, extents, x) in [(&a, &left_extents, px(5)), (&b, &right_extents, lane_x(2) + px(LANE_WIDTH) + px(8))] {
+        let files: Vec<&Totals> = objects.iter().filter(|(name, _)| name.starts_with('/')).map(|(_, totals)| totals).collect();
+        let wanted: Vec<i32> = files.iter().map(|totals| extents[totals.first].0).collect();
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.sort_by_key(|&i| wanted[i]);
+        let sorted: Vec<i32> = order.iter().map(|&i| wanted[i]).collect();
+        for (&i, top) in order.iter().zip(place_labels(&sorted, label_height, map_height, options.label_placement)) {
+            let Some(top) = top else { continue };
+            map.draw(&Text::new(format!("{:#x}", files[i].start), (x, top), font.color(&ink)))?;
+        }
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::{region_category, MemoryRegion};
+use image::Rgb;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Kind(String),
+    Perm(String),
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s.split_once('=').ok_or_else(|| format!("Expected key=value selector: {}", s))?;
+        match key {
+            "kind" => {
+                let kind = match value {
+                    "file-backed" => "file",
+                    "anonymous" => "anon",
+                    other => other,
+                };
+                Ok(Selector::Kind(kind.replace('-', " ")))
+            }
+            "perm" if (3..=4).contains(&value.len()) => Ok(Selector::Perm(value.to_string())),
+            "perm" => Err(format!("Invalid permission selector: {}", value)),
+            _ => Err(format!("Unknown selector key: {}", key)),
+        }
+    }
+}
+
+impl Selector {
+    pub fn matches(&self, region: &MemoryRegion) -> bool {
+        match self {
+            Selector::Kind(kind) => region_category(region) == kind,
+            Selector::Perm(perm) => region.attributes.perms().starts_with(perm.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Emphasis {
+    pub dim: Vec<Selector>,
+    pub emphasize: Vec<Selector>,
+    pub dim_opacity: f64,
+}
+
+fn blend_with_white(color: Rgb<u8>, opacity: f64) -> Rgb<u8> {
+    let blend = |c: u8| (255.0 - (255.0 - c as f64) * opacity).round() as u8;
+    Rgb([blend(color[0]), blend(color[1]), blend(color[2])])
+}
+
+fn saturate(color: Rgb<u8>, amount: f64) -> Rgb<u8> {
+    let gray = (color[0] as f64 + color[1] as f64 + color[2] as f64) / 3.0;
+    let push = |c: u8| (gray + (c as f64 - gray) * amount).clamp(0.0, 255.0).round() as u8;
+    Rgb([push(color[0]), push(color[1]), push(color[2])])
+}
+
+impl Emphasis {
+    // Returns the adjusted color and whether the region should be outlined.
+    // Once anything is emphasized, everything else recedes as if dimmed.
+    pub fn apply(&self, region: &MemoryRegion, color: Rgb<u8>) -> (Rgb<u8>, bool) {
+        if self.emphasize.iter().any(|selector| selector.matches(region)) {
+            return (saturate(color, 1.3), true);
+        }
+        if self.dim.iter().any(|selector| selector.matches(region)) || !self.emphasize.is_empty() {
+            return (blend_with_white(color, self.dim_opacity), false);
+        }
+        (color, false)
+    }
+}
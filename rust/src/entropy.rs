@@ -0,0 +1,107 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::MemoryRegion;
+#[cfg(not(target_arch = "wasm32"))]
+use std::ffi::c_void;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::os::raw::{c_int, c_ulong};
+
+// Pages read per region, spread evenly over it.
+#[cfg(not(target_arch = "wasm32"))]
+const SAMPLES: usize = 16;
+#[cfg(not(target_arch = "wasm32"))]
+const SAMPLE_SIZE: usize = 4096;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[repr(C)]
+struct IoVec {
+    base: *mut c_void,
+    len: usize,
+}
+
+extern "C" {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn process_vm_readv(pid: c_int, local: *const IoVec, local_count: c_ulong, remote: *const IoVec, remote_count: c_ulong, flags: c_ulong) -> isize;
+}
+
+// Reads what it can of `len` bytes at `address` in the process.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_remote(pid: u32, address: usize, buffer: &mut [u8]) -> io::Result<usize> {
+    let local = IoVec { base: buffer.as_mut_ptr() as *mut c_void, len: buffer.len() };
+    let remote = IoVec { base: address as *mut c_void, len: buffer.len() };
+    let read = unsafe { process_vm_readv(pid as c_int, &local, 1, &remote, 1, 0) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(read as usize)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn denied_error(pid: u32, e: &io::Error) -> String {
+    format!("Unable to read the memory of pid {}: {} (needs ptrace access: the same user with kernel.yama.ptrace_scope 0, or CAP_SYS_PTRACE)", pid, e)
+}
+
+// Shannon entropy in bits per byte: 0 for a page of zeros, close to 8 for
+// compressed or encrypted data, 4 to 6 for code and text.
+#[cfg(not(target_arch = "wasm32"))]
+fn shannon(counts: &[u64; 256]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts.iter().filter(|count| **count > 0).map(|count| *count as f64 / total as f64).map(|p| -p * p.log2()).sum()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn class(bits: f64) -> &'static str {
+    match bits {
+        bits if bits < 0.5 => "zero-filled",
+        bits if bits < 7.2 => "",
+        _ => "compressed or encrypted",
+    }
+}
+
+// [vvar] faults on every read and [vsyscall] isn't the process's to read.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn candidate(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && region.attributes.readable && !matches!(region.file_name.as_deref(), Some("[vvar]" | "[vvar_vclock]" | "[vsyscall]"))
+}
+
+// Entropy of samples from every readable region, by region start. Without
+// ptrace access to the process nothing is readable, which is an error
+// rather than a map of zeros; a region that can't be read is left out.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sample(pid: u32, memory_regions: &[MemoryRegion]) -> Result<Vec<(usize, f64)>, String> {
+    let mut entropies = Vec::new();
+    let mut buffer = vec![0u8; SAMPLE_SIZE];
+    let mut denied = None;
+    for region in memory_regions.iter().filter(|region| candidate(region)) {
+        let mut counts = [0u64; 256];
+        let samples = SAMPLES.min(region.size.div_ceil(SAMPLE_SIZE));
+        let stride = region.size / samples;
+        for i in 0..samples {
+            let address = region.start + i * stride;
+            let len = SAMPLE_SIZE.min(region.end - address);
+            match read_remote(pid, address, &mut buffer[..len]) {
+                Ok(read) => buffer[..read].iter().for_each(|byte| counts[*byte as usize] += 1),
+                Err(e) if matches!(e.raw_os_error(), Some(1) | Some(3)) => denied = Some(e),
+                Err(_) => {}
+            }
+        }
+        if counts.iter().any(|count| *count > 0) {
+            entropies.push((region.start, shannon(&counts)));
+        }
+    }
+    match denied {
+        Some(e) if entropies.is_empty() => Err(denied_error(pid, &e)),
+        _ => Ok(entropies),
+    }
+}
+
+// Dark blue for zeros through green to red for random looking bytes.
+pub fn color(bits: f64) -> [u8; 3] {
+    let t = (bits / 8.0).clamp(0.0, 1.0);
+    let (r, g, b) = if t < 0.5 { (0.0, t * 2.0, 1.0 - t * 2.0) } else { ((t - 0.5) * 2.0, 1.0 - (t - 0.5) * 2.0, 0.0) };
+    [(40.0 + r * 215.0) as u8, (40.0 + g * 180.0) as u8, (80.0 + b * 175.0) as u8]
+}
@@ -0,0 +1,151 @@
+use crate::{MemoryAttributes, MemoryRegion};
+use serde_json::Value;
+use std::fs;
+
+// The memory types an ESP32 image is sized against, in the order they are
+// drawn, with the permissions their contents run with.
+const GROUPS: [(&str, (bool, bool, bool)); 5] = [
+    ("IRAM", (true, false, true)),
+    ("DIRAM", (true, true, true)),
+    ("DRAM", (true, true, false)),
+    ("Flash code", (true, false, true)),
+    ("Flash data", (true, false, false)),
+];
+
+// Groups start on this boundary with at least this much space between
+// them, so each shows as its own block.
+const GROUP_ALIGN: usize = 0x10000;
+
+// Per section keys such as iram_text, dram_bss, diram_data, flash_text
+// and flash_rodata, as both `idf.py size --format json` and
+// `idf.py size-components --format json` name them.
+fn group_of(key: &str) -> Option<usize> {
+    // dram_total, dram_remain and used_dram_ratio describe the type.
+    if key.ends_with("_total") || key.ends_with("_remain") || key.ends_with("_ratio") {
+        None
+    } else if key.starts_with("diram") {
+        Some(1)
+    } else if key.starts_with("iram") {
+        Some(0)
+    } else if key.starts_with("dram") || key == "data" || key == "bss" {
+        Some(2)
+    } else if key == "flash_text" || key == "flash_code" {
+        Some(3)
+    } else if key.starts_with("flash_rodata") || key == "flash_data" {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+// What each memory type holds in total, from a size summary: iram_total
+// and friends in the old format, memory_types in esp-idf-size's json2.
+fn group_totals(summary: &Value) -> [Option<usize>; 5] {
+    let mut totals = [None; 5];
+    if let Some(types) = summary["memory_types"].as_object() {
+        for (name, memory) in types {
+            if let Some(group) = GROUPS.iter().position(|(group, _)| group.eq_ignore_ascii_case(name)) {
+                totals[group] = memory["size"].as_u64().map(|size| size as usize);
+            }
+        }
+    }
+    for (group, total) in totals.iter_mut().enumerate() {
+        let key = format!("{}_total", GROUPS[group].0.to_lowercase().replace(' ', "_"));
+        if let Some(size) = summary[key.as_str()].as_u64() {
+            *total = Some(size as usize);
+        }
+    }
+    totals
+}
+
+// Bytes per group and component. A summary without components counts as a
+// single component, "used".
+fn usage(json: &Value) -> Vec<(String, [usize; 5])> {
+    let Some(object) = json.as_object() else { return Vec::new() };
+    let sizes = |sections: &serde_json::Map<String, Value>| {
+        let mut used = [0; 5];
+        for (key, size) in sections {
+            if let (Some(group), Some(size)) = (group_of(key), size.as_u64()) {
+                used[group] += size as usize;
+            }
+        }
+        used
+    };
+    if object.values().any(Value::is_object) && !object.contains_key("memory_types") {
+        object.iter().filter_map(|(component, sections)| Some((component.clone(), sizes(sections.as_object()?)))).collect()
+    } else if let Some(types) = object.get("memory_types").and_then(Value::as_object) {
+        let mut used = [0; 5];
+        for (name, memory) in types {
+            if let Some(group) = GROUPS.iter().position(|(group, _)| group.eq_ignore_ascii_case(name)) {
+                used[group] = memory["used"].as_u64().unwrap_or(0) as usize;
+            }
+        }
+        vec![("used".to_string(), used)]
+    } else {
+        vec![("used".to_string(), sizes(object))]
+    }
+}
+
+fn read_json(path: &str) -> Result<Value, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not JSON: {}", path, e))
+}
+
+// The address space is made up: each memory type is a block of its size,
+// holding its components largest first, with what is left shown free.
+// Flash is as large as what is in it unless the summary says otherwise.
+pub fn read(components: &str, summary: Option<&str>) -> Result<Vec<MemoryRegion>, String> {
+    let json = read_json(components)?;
+    let usage = usage(&json);
+    // A summary given alone has its own totals.
+    let totals = group_totals(&summary.map(read_json).transpose()?.unwrap_or(json));
+    let mut memory_regions = Vec::new();
+    let mut base = 0;
+    for (group, (name, (readable, writable, executable))) in GROUPS.iter().enumerate() {
+        let mut components: Vec<(&str, usize)> = usage.iter().map(|(component, used)| (component.as_str(), used[group])).filter(|(_, used)| *used > 0).collect();
+        if components.is_empty() {
+            continue;
+        }
+        components.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let mut start = base;
+        for (component, size) in components {
+            memory_regions.push(MemoryRegion {
+                start,
+                end: start + size,
+                size,
+                attributes: MemoryAttributes { readable: *readable, writable: *writable, executable: *executable, shared: false, allocated: true },
+                offset: 0,
+                device: (0, 0),
+                inode: 0,
+                file_name: Some(format!("{}: {}", name, component)),
+                thread_id: None,
+                guard: false,
+                smaps: None,
+                mappings: 1,
+            });
+            start += size;
+        }
+        let end = totals[group].map_or(start, |total| start.max(base + total));
+        if end > start {
+            memory_regions.push(MemoryRegion {
+                start,
+                end,
+                size: end - start,
+                attributes: MemoryAttributes { readable: false, writable: false, executable: false, shared: false, allocated: false },
+                offset: 0,
+                device: (0, 0),
+                inode: 0,
+                file_name: Some(format!("{}: free", name)),
+                thread_id: None,
+                guard: false,
+                smaps: None,
+                mappings: 1,
+            });
+        }
+        base = end.next_multiple_of(GROUP_ALIGN) + GROUP_ALIGN;
+    }
+    if memory_regions.is_empty() {
+        return Err(format!("{} has no IRAM, DRAM or flash sizes", components));
+    }
+    Ok(memory_regions)
+}
@@ -0,0 +1,125 @@
+use crate::pattern::Regex;
+use crate::sanitizer;
+use crate::smaps::{describe_flag, VM_FLAGS};
+use crate::MemoryRegion;
+use std::str::FromStr;
+
+// Permission letters a region must have, and those a "-" in front of a
+// letter says it must not: "w-x" is writable but not executable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermFilter {
+    required: Vec<char>,
+    forbidden: Vec<char>,
+}
+
+impl FromStr for PermFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = PermFilter { required: Vec::new(), forbidden: Vec::new() };
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            let (letter, list) = match c {
+                '-' => (chars.next().ok_or_else(|| format!("Dangling - in permission filter: {}", s))?, &mut filter.forbidden),
+                c => (c, &mut filter.required),
+            };
+            if !"rwxps".contains(letter) {
+                return Err(format!("Unknown permission {} in {}, expected r, w, x, p or s", letter, s));
+            }
+            list.push(letter);
+        }
+        if filter.required.is_empty() && filter.forbidden.is_empty() {
+            return Err("Empty permission filter".to_string());
+        }
+        Ok(filter)
+    }
+}
+
+impl PermFilter {
+    pub fn matches(&self, region: &MemoryRegion) -> bool {
+        let attributes = &region.attributes;
+        let has = |letter: &char| match letter {
+            'r' => attributes.readable,
+            'w' => attributes.writable,
+            'x' => attributes.executable,
+            's' => attributes.shared,
+            _ => !attributes.shared,
+        };
+        self.required.iter().all(has) && !self.forbidden.iter().any(has)
+    }
+}
+
+// VmFlags codes from smaps a region must have, comma separated, with a "-"
+// in front of those it must not: "dd,-ht" is left out of core dumps and
+// not backed by hugetlb pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagFilter {
+    required: Vec<String>,
+    forbidden: Vec<String>,
+}
+
+impl FromStr for FlagFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = FlagFilter { required: Vec::new(), forbidden: Vec::new() };
+        for flag in s.split(',').map(str::trim).filter(|flag| !flag.is_empty()) {
+            let (code, list) = match flag.strip_prefix('-') {
+                Some(code) => (code, &mut filter.forbidden),
+                None => (flag, &mut filter.required),
+            };
+            if describe_flag(code).is_none() {
+                let known: Vec<&str> = VM_FLAGS.iter().map(|(code, _)| *code).collect();
+                return Err(format!("Unknown VmFlags code {} in {}, expected one of {}", code, s, known.join(" ")));
+            }
+            list.push(code.to_string());
+        }
+        if filter.required.is_empty() && filter.forbidden.is_empty() {
+            return Err("Empty VmFlags filter".to_string());
+        }
+        Ok(filter)
+    }
+}
+
+impl FlagFilter {
+    // Regions without smaps have no flags at all.
+    pub fn matches(&self, region: &MemoryRegion) -> bool {
+        let has = |code: &String| region.smaps.as_ref().is_some_and(|smaps| smaps.has_flag(code));
+        self.required.iter().all(has) && !self.forbidden.iter().any(has)
+    }
+}
+
+// Decides which captured regions get drawn. The address ranges of the
+// others are kept so they can be collapsed like large gaps.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    pub include: Vec<Regex>,
+    pub exclude: Vec<Regex>,
+    // A region passes if it matches any of these.
+    pub perms: Vec<PermFilter>,
+    // Likewise any of these.
+    pub vm_flags: Vec<FlagFilter>,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    pub exclude_shadow: bool,
+}
+
+impl Filters {
+    // Paths match the pathname column of maps, pseudo-paths like [heap]
+    // included; regions without one only pass when nothing is included.
+    pub fn keeps(&self, region: &MemoryRegion) -> bool {
+        let path = region.file_name.as_deref();
+        let included = self.include.is_empty() || path.is_some_and(|path| self.include.iter().any(|regex| regex.is_match(path)));
+        let excluded = path.is_some_and(|path| self.exclude.iter().any(|regex| regex.is_match(path)));
+        let permitted = self.perms.is_empty() || self.perms.iter().any(|filter| filter.matches(region));
+        let flagged = self.vm_flags.is_empty() || self.vm_flags.iter().any(|filter| filter.matches(region));
+        let sized = self.min_size.is_none_or(|min| region.size >= min) && self.max_size.is_none_or(|max| region.size <= max);
+        let unshadowed = !self.exclude_shadow || !sanitizer::is_shadow(region);
+        included && !excluded && permitted && flagged && sized && unshadowed
+    }
+
+    pub fn apply(&self, memory_regions: Vec<MemoryRegion>) -> (Vec<MemoryRegion>, Vec<(usize, usize)>) {
+        let (kept, removed): (Vec<MemoryRegion>, Vec<MemoryRegion>) = memory_regions.into_iter().partition(|region| self.keeps(region));
+        (kept, removed.iter().map(|region| (region.start, region.end)).collect())
+    }
+}
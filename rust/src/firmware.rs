@@ -0,0 +1,159 @@
+use crate::fragmentation::parse_size;
+use crate::{fit_text, format_size, name_color, RenderOptions};
+use object::elf::SHF_ALLOC;
+use object::{Object, ObjectSection, SectionFlags, SectionKind};
+use plotters::prelude::*;
+use std::fs;
+
+const CHART_WIDTH: u32 = 800;
+const ROW_HEIGHT: i32 = 90;
+
+// Flash, RAM and EEPROM sizes in bytes, from a preset or "flash=32K,ram=2K"
+// with an optional eeprom.
+pub struct Device {
+    pub name: String,
+    pub memories: [u64; 3],
+}
+
+pub const MEMORIES: [&str; 3] = ["flash", "ram", "eeprom"];
+
+const PRESETS: [(&str, [u64; 3]); 9] = [
+    ("atmega328p", [32 << 10, 2 << 10, 1 << 10]),
+    ("atmega32u4", [32 << 10, 2560, 1 << 10]),
+    ("atmega2560", [256 << 10, 8 << 10, 4 << 10]),
+    ("attiny85", [8 << 10, 512, 512]),
+    ("stm32f103c8", [64 << 10, 20 << 10, 0]),
+    ("stm32f407vg", [1 << 20, 192 << 10, 0]),
+    ("nrf52840", [1 << 20, 256 << 10, 0]),
+    ("rp2040", [2 << 20, 264 << 10, 0]),
+    ("esp32", [4 << 20, 320 << 10, 0]),
+];
+
+pub fn device(spec: &str) -> Result<Device, String> {
+    if let Some((name, memories)) = PRESETS.iter().find(|(name, _)| name.eq_ignore_ascii_case(spec)) {
+        return Ok(Device { name: name.to_string(), memories: *memories });
+    }
+    if !spec.contains('=') {
+        let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+        return Err(format!("Unknown device {}; give flash=SIZE,ram=SIZE[,eeprom=SIZE] or one of {}", spec, names.join(", ")));
+    }
+    let mut memories = [0; 3];
+    for part in spec.split(',') {
+        let (memory, size) = part.split_once('=').ok_or_else(|| format!("Invalid device memory: {}", part))?;
+        let index = MEMORIES.iter().position(|name| *name == memory.trim()).ok_or_else(|| format!("Unknown device memory {}; expected flash, ram or eeprom", memory))?;
+        memories[index] = parse_size(size)?;
+    }
+    Ok(Device { name: spec.to_string(), memories })
+}
+
+// Bytes each allocated section takes of each memory. Initialized data is
+// stored in flash and copied to RAM at startup, so it counts in both, as
+// avr-size counts it; .bss and .noinit only take RAM.
+pub fn usage(path: &str) -> Result<[Vec<(String, u64)>; 3], String> {
+    let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let file = object::File::parse(&*data).map_err(|e| format!("Unable to parse {}: {}", path, e))?;
+    let mut usage: [Vec<(String, u64)>; 3] = Default::default();
+    for section in file.sections() {
+        let SectionFlags::Elf { sh_flags } = section.flags() else { continue };
+        let name = section.name().unwrap_or("?").to_string();
+        if sh_flags & SHF_ALLOC as u64 == 0 || section.size() == 0 || section.kind() == SectionKind::UninitializedTls {
+            continue;
+        }
+        let size = section.size();
+        if name.starts_with(".eeprom") {
+            usage[2].push((name, size));
+        } else if section.kind() == SectionKind::UninitializedData || name.starts_with(".noinit") {
+            usage[1].push((name, size));
+        } else if section.kind() == SectionKind::Data || section.kind() == SectionKind::Tls {
+            usage[0].push((name.clone(), size));
+            usage[1].push((name, size));
+        } else {
+            usage[0].push((name, size));
+        }
+    }
+    if usage.iter().all(Vec::is_empty) {
+        return Err(format!("{} has no allocated ELF sections", path));
+    }
+    Ok(usage)
+}
+
+// In the manner of avr-size -C: used, capacity, percentage and the
+// sections that make it up.
+pub fn print_usage(device: &Device, usage: &[Vec<(String, u64)>; 3]) {
+    println!("Device: {}", device.name);
+    for (index, sections) in usage.iter().enumerate() {
+        let (used, total) = (sections.iter().map(|(_, size)| size).sum::<u64>(), device.memories[index]);
+        if used == 0 && total == 0 {
+            continue;
+        }
+        let percent = if total > 0 { format!("{:.1}% full", used as f64 * 100.0 / total as f64) } else { "no capacity given".to_string() };
+        let names: Vec<&str> = sections.iter().map(|(name, _)| name.as_str()).collect();
+        println!("{:<8} {:>10} of {:>10} ({})  {}", MEMORIES[index], format_size(used as usize), format_size(total as usize), percent, names.join(" + "));
+    }
+}
+
+// A gauge per memory: the sections stacked from the left in their own
+// hues, the free rest in the gap color, and a red mark at the capacity
+// when the sections overflow it.
+pub fn draw_usage(device: &Device, usage: &[Vec<(String, u64)>; 3], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let rows: Vec<usize> = (0..3).filter(|index| !usage[*index].is_empty() || device.memories[*index] > 0).collect();
+    let height = (rows.len() as i32 * ROW_HEIGHT + 40) as u32;
+    let root = BitMapBackend::new(path, (CHART_WIDTH, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 11.0, FontStyle::Normal);
+    let bold = FontDesc::new(FontFamily::SansSerif, 12.0, FontStyle::Bold);
+    root.draw(&Text::new(format!("{} memory usage", device.name), (10, 8), FontDesc::new(FontFamily::SansSerif, 14.0, FontStyle::Bold).color(&ink)))?;
+
+    let (left, right) = (10, CHART_WIDTH as i32 - 60);
+    for (row, index) in rows.into_iter().enumerate() {
+        let y = 36 + row as i32 * ROW_HEIGHT;
+        let used: u64 = usage[index].iter().map(|(_, size)| size).sum();
+        let total = device.memories[index];
+        let scale = total.max(used).max(1) as f64;
+        let title = match total {
+            0 => format!("{}: {} (no capacity given)", MEMORIES[index], format_size(used as usize)),
+            _ => format!("{}: {} of {}, {:.1}% used, {} free", MEMORIES[index], format_size(used as usize), format_size(total as usize), used as f64 * 100.0 / total as f64, format_size(total.saturating_sub(used) as usize)),
+        };
+        root.draw(&Text::new(title, (left, y), bold.color(&ink)))?;
+
+        let (bar_top, bar_bottom) = (y + 18, y + 44);
+        let x = |bytes: u64| left + ((bytes as f64 / scale) * (right - left) as f64).round() as i32;
+        let gap = options.theme.gap.0;
+        root.draw(&Rectangle::new([(left, bar_top), (right, bar_bottom)], RGBColor(gap[0], gap[1], gap[2]).filled()))?;
+        let mut offset = 0;
+        for (name, size) in &usage[index] {
+            let color = name_color(name).0;
+            let (x0, x1) = (x(offset), x(offset + size).max(x(offset) + 1));
+            root.draw(&Rectangle::new([(x0, bar_top), (x1, bar_bottom)], RGBColor(color[0], color[1], color[2]).filled()))?;
+            offset += size;
+        }
+        // Largest first, as many as fit on the line.
+        let mut legend: Vec<&(String, u64)> = usage[index].iter().collect();
+        legend.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        let mut legend_x = left;
+        for (name, size) in legend {
+            let color = name_color(name).0;
+            let label = format!("{} {}", name, format_size(*size as usize));
+            let width = root.estimate_text_size(&label, &font.clone().into())?.0 as i32 + 22;
+            if legend_x + width >= CHART_WIDTH as i32 {
+                break;
+            }
+            root.draw(&Rectangle::new([(legend_x, bar_bottom + 8), (legend_x + 10, bar_bottom + 18)], RGBColor(color[0], color[1], color[2]).filled()))?;
+            root.draw(&Text::new(label, (legend_x + 14, bar_bottom + 7), font.color(&ink)))?;
+            legend_x += width;
+        }
+        if total > 0 && used > total {
+            root.draw(&Rectangle::new([(x(total), bar_top - 3), (x(total) + 2, bar_bottom + 3)], RED.filled()))?;
+        }
+        root.draw(&Rectangle::new([(left, bar_top), (right, bar_bottom)], ink))?;
+        if total > 0 {
+            let percent = format!("{:.0}%", used as f64 * 100.0 / total as f64);
+            if let Some(percent) = fit_text(&root, &percent, &bold, 50)? {
+                root.draw(&Text::new(percent, (right + 8, bar_top + 6), bold.color(if used > total { &RED } else { &ink })))?;
+            }
+        }
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,211 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::context::AddressSpace;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{format_size, MemoryRegion};
+#[cfg(not(target_arch = "wasm32"))]
+use plotters::prelude::*;
+
+// Upper bounds of the gap-size histogram buckets.
+#[cfg(not(target_arch = "wasm32"))]
+const BUCKETS: [(u64, &str); 7] = [
+    (1 << 16, "< 64K"),
+    (1 << 20, "< 1M"),
+    (1 << 24, "< 16M"),
+    (1 << 28, "< 256M"),
+    (1 << 32, "< 4G"),
+    (1 << 36, "< 64G"),
+    (u64::MAX, ">= 64G"),
+];
+
+// The default vm.mmap_min_addr, for processes captured elsewhere.
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_MMAP_MIN_ADDR: u64 = 0x10000;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub struct Fragmentation {
+    pub gaps: usize,
+    pub total_free: u64,
+    pub largest_free: u64,
+    pub histogram: Vec<(&'static str, usize)>,
+    pub index: f64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Hole {
+    pub start: u64,
+    pub end: u64,
+    pub lowest_fit: u64,
+    pub highest_fit: u64,
+}
+
+// The end of user space and the lowest address a process may map. Mappings
+// past the end, such as [vsyscall] in the kernel half, would turn the whole
+// non-canonical range into one giant free block.
+#[cfg(not(target_arch = "wasm32"))]
+fn user_bounds(space: &AddressSpace) -> (u64, u64) {
+    let end = space.user_end().min(u64::MAX as u128) as u64;
+    (space.mmap_min_addr.map_or(DEFAULT_MMAP_MIN_ADDR, |min| min as u64), end)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn user_mappings(memory_regions: &[MemoryRegion], user_end: u64) -> Vec<(u64, u64)> {
+    let mut mapped: Vec<(u64, u64)> = memory_regions
+        .iter()
+        .filter(|region| region.attributes.allocated && (region.start as u64) < user_end)
+        .map(|region| (region.start as u64, region.end as u64))
+        .collect();
+    mapped.sort_unstable();
+    mapped
+}
+
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).map_err(|_| format!("Invalid size: {}", s));
+    }
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number.parse().map_err(|_| format!("Invalid size: {}", s))?;
+    let shift = match unit.trim_end_matches(['B', 'b', 'i']) {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        _ => return Err(format!("Invalid size unit: {}", unit)),
+    };
+    number.checked_shl(shift).filter(|bytes| bytes >> shift == number).ok_or_else(|| format!("Size too large: {}", s))
+}
+
+// Free ranges, from mmap_min_addr up to the end of the user address space,
+// that can hold `size` bytes at an `align` boundary. The kernel places
+// mappings top-down, so the highest fit is where a hint-less mmap would go.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find_holes(memory_regions: &[MemoryRegion], space: &AddressSpace, size: u64, align: u64) -> Vec<Hole> {
+    let align = align.max(1);
+    let (min_addr, user_end) = user_bounds(space);
+    let mapped = user_mappings(memory_regions, user_end);
+    let mut bounds = vec![(0, min_addr)];
+    bounds.extend(mapped);
+    bounds.push((user_end, user_end));
+
+    bounds
+        .windows(2)
+        .filter_map(|pair| {
+            let (start, end) = (pair[0].1.max(min_addr), pair[1].0);
+            let lowest_fit = start.checked_next_multiple_of(align)?;
+            if end < size || lowest_fit > end - size {
+                return None;
+            }
+            let highest_fit = (end - size) / align * align;
+            Some(Hole { start, end, lowest_fit, highest_fit })
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fragmentation(memory_regions: &[MemoryRegion], space: &AddressSpace) -> Fragmentation {
+    let mapped = user_mappings(memory_regions, user_bounds(space).1);
+    let holes: Vec<u64> = mapped.windows(2).filter(|pair| pair[1].0 > pair[0].1).map(|pair| pair[1].0 - pair[0].1).collect();
+    let mut histogram: Vec<(&'static str, usize)> = BUCKETS.iter().map(|(_, label)| (*label, 0)).collect();
+    for hole in &holes {
+        let bucket = BUCKETS.iter().position(|(bound, _)| hole < bound).unwrap_or(BUCKETS.len() - 1);
+        histogram[bucket].1 += 1;
+    }
+
+    let total_free: u64 = holes.iter().sum();
+    let largest_free = holes.iter().copied().max().unwrap_or(0);
+    Fragmentation {
+        gaps: holes.len(),
+        total_free,
+        largest_free,
+        histogram,
+        // 0 when all free space is one hole, approaching 1 as it splinters.
+        index: if total_free == 0 { 0.0 } else { 1.0 - largest_free as f64 / total_free as f64 },
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Fragmentation {
+    pub fn print(&self) {
+        println!("Free gaps: {}", self.gaps);
+        println!("Free bytes: {}", format_size(self.total_free as usize));
+        println!("Largest free block: {}", format_size(self.largest_free as usize));
+        println!("Fragmentation index: {:.3}", self.index);
+        for (label, count) in &self.histogram {
+            println!("  {:<8} {}", label, count);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn draw_histogram_panel(fragmentation: &Fragmentation, width: u32, height: u32) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    {
+        let root = BitMapBackend::with_buffer(&mut imgbuf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let (chart_area, _) = root.split_vertically(320);
+
+        let max_count = fragmentation.histogram.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+        let mut chart = ChartBuilder::on(&chart_area)
+            .caption(format!("Free gaps (index {:.2})", fragmentation.index), ("sans-serif", 13))
+            .margin(8)
+            .x_label_area_size(25)
+            .y_label_area_size(50)
+            .build_cartesian_2d(0..max_count + 1, (0..fragmentation.histogram.len()).into_segmented())?;
+
+        chart
+            .configure_mesh()
+            .disable_y_mesh()
+            .y_label_formatter(&|value| match value {
+                SegmentValue::CenterOf(i) => fragmentation.histogram.get(*i).map(|(label, _)| label.to_string()).unwrap_or_default(),
+                _ => String::new(),
+            })
+            .draw()?;
+
+        chart.draw_series(fragmentation.histogram.iter().enumerate().map(|(i, (_, count))| {
+            Rectangle::new([(0, SegmentValue::Exact(i)), (*count, SegmentValue::Exact(i + 1))], BLUE.mix(0.7).filled())
+        }))?;
+
+        let font = ("sans-serif", 11).into_font();
+        root.draw(&Text::new(format!("largest: {}", format_size(fragmentation.largest_free as usize)), (10, 325), font.clone()))?;
+        root.draw(&Text::new(format!("free: {}", format_size(fragmentation.total_free as usize)), (10, 340), font))?;
+    }
+    Ok(imgbuf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapped(start: usize, end: usize) -> MemoryRegion {
+        MemoryRegion { start, end, size: end - start, ..format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end).parse().unwrap() }
+    }
+
+    #[test]
+    fn holes_reach_the_detected_end_of_user_space() {
+        let memory_regions = [mapped(0x400000, 0x500000), mapped(0x7ff000000000, 0x7fff00000000)];
+        let four_level = AddressSpace { user_bits: 47, mmap_min_addr: Some(0x10000) };
+        let five_level = AddressSpace { user_bits: 56, mmap_min_addr: Some(0x10000) };
+        let top = |space: &AddressSpace| find_holes(&memory_regions, space, 1 << 30, 1 << 21).last().map(|hole| hole.end);
+        assert_eq!(top(&four_level), Some(1 << 47));
+        assert_eq!(top(&five_level), Some(1 << 56));
+    }
+
+    #[test]
+    fn holes_start_at_mmap_min_addr() {
+        let memory_regions = [mapped(0x400000, 0x500000)];
+        let space = AddressSpace { user_bits: 47, mmap_min_addr: Some(0x1000) };
+        assert_eq!(find_holes(&memory_regions, &space, 0x1000, 0x1000)[0].start, 0x1000);
+        // Unknown, as for a process captured elsewhere: the usual default.
+        let space = AddressSpace { mmap_min_addr: None, ..space };
+        assert_eq!(find_holes(&memory_regions, &space, 0x1000, 0x1000)[0].start, 0x10000);
+    }
+
+    #[test]
+    fn mappings_past_user_space_are_not_fragmentation() {
+        let memory_regions = [mapped(0x400000, 0x500000), mapped(0x600000, 0x700000), mapped(0xffffffffff600000, 0xffffffffff601000)];
+        let fragmentation = fragmentation(&memory_regions, &AddressSpace { user_bits: 47, mmap_min_addr: None });
+        assert_eq!((fragmentation.gaps, fragmentation.largest_free), (1, 0x100000));
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+// Arenas are a ring starting at main_arena; this only bounds a corrupt one.
+const MAX_ARENAS: usize = 256;
+
+// A GDB conversation over the machine interface (gdb --interpreter=mi2),
+// either with a gdb we start and attach, or with one already driving the
+// process whose MI stream is exposed on a socket (e.g. through socat).
+pub struct Session {
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+    token: u32,
+    // Only set for a gdb we attached ourselves, which also detaches.
+    child: Option<Child>,
+}
+
+impl Session {
+    pub fn attach(pid: u32) -> Result<Self, String> {
+        let mut child = Command::new("gdb")
+            .args(["--nx", "--quiet", "--interpreter=mi2"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Unable to run gdb: {}", e))?;
+        let (stdin, stdout) = (child.stdin.take().unwrap(), child.stdout.take().unwrap());
+        let mut session = Session { reader: Box::new(BufReader::new(stdout)), writer: Box::new(stdin), token: 0, child: Some(child) };
+        session.command(&format!("-target-attach {}", pid))?;
+        Ok(session)
+    }
+
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("Unable to connect to gdb at {}: {}", address, e))?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Session { reader: Box::new(BufReader::new(stream)), writer: Box::new(writer), token: 0, child: None })
+    }
+
+    // Sends one command and returns the results of its ^done, skipping the
+    // console, log and async records gdb interleaves.
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        self.token += 1;
+        writeln!(self.writer, "{}{}", self.token, command).and_then(|_| self.writer.flush()).map_err(|e| format!("Unable to talk to gdb: {}", e))?;
+        let token = self.token.to_string();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).map_err(|e| format!("Unable to read from gdb: {}", e))? == 0 {
+                return Err(format!("gdb exited during {}", command));
+            }
+            let Some(record) = line.trim_end().strip_prefix(token.as_str()) else { continue };
+            if let Some(results) = record.strip_prefix("^done").or_else(|| record.strip_prefix("^running")) {
+                return Ok(results.trim_start_matches(',').to_string());
+            }
+            if let Some(error) = record.strip_prefix("^error") {
+                return Err(format!("{}: {}", command, field(error, "msg").unwrap_or(error)));
+            }
+        }
+    }
+
+    // An expression's value as an address; gdb prints pointers as
+    // "0x7ffd3c8e1a10" or "(void *) 0x... <symbol>".
+    fn address(&mut self, expression: &str) -> Result<usize, String> {
+        let results = self.command(&format!("-data-evaluate-expression \"{}\"", expression))?;
+        let value = field(&results, "value").ok_or_else(|| format!("gdb gave no value for {}", expression))?;
+        let token = value.split_whitespace().find(|token| token.starts_with("0x")).or_else(|| value.split_whitespace().next()).unwrap_or("");
+        match token.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => token.parse().ok(),
+        }
+        .ok_or_else(|| format!("{} is not an address: {}", expression, value))
+    }
+
+    // Every thread, and the one selected now.
+    fn threads(&mut self) -> Result<(Vec<String>, Option<String>), String> {
+        let results = self.command("-thread-list-ids")?;
+        let ids = results.split_once("thread-ids={").map_or("", |(_, rest)| rest.split('}').next().unwrap_or(""));
+        let ids = ids.split("thread-id=\"").skip(1).filter_map(|rest| rest.split('"').next()).map(str::to_string).collect();
+        Ok((ids, field(&results, "current-thread-id").map(str::to_string)))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = self.command("-target-detach");
+            let _ = self.command("-gdb-exit");
+            let _ = child.wait();
+        }
+    }
+}
+
+// The value of name="..." in an MI result list, with escapes left as they are.
+fn field<'a>(results: &'a str, name: &str) -> Option<&'a str> {
+    let start = results.find(&format!("{}=\"", name))? + name.len() + 2;
+    let rest = &results[start..];
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(&rest[..i]),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+// The program counter and stack pointer of every thread, and glibc's malloc
+// arenas when its symbols are available, as markers.
+pub fn annotations(session: &mut Session) -> Result<Vec<(usize, String)>, String> {
+    let mut annotations = Vec::new();
+    let (threads, current) = session.threads()?;
+    for id in &threads {
+        session.command(&format!("-thread-select {}", id))?;
+        annotations.push((session.address("$pc")?, format!("thread {} pc", id)));
+        annotations.push((session.address("$sp")?, format!("thread {} sp", id)));
+    }
+    // A shared session is left on the thread its user had selected.
+    if let Some(current) = current {
+        session.command(&format!("-thread-select {}", current))?;
+    }
+    for (index, arena) in arenas(session).into_iter().enumerate() {
+        annotations.push((arena, if index == 0 { "main_arena".to_string() } else { format!("arena {}", index) }));
+    }
+    Ok(annotations)
+}
+
+// glibc's arenas in ring order, which is also the order malloc_info()
+// numbers its heaps in. Without libc debug info there's no main_arena,
+// which isn't an error.
+pub fn arenas(session: &mut Session) -> Vec<usize> {
+    let Ok(main_arena) = session.address("(unsigned long)&main_arena") else { return Vec::new() };
+    let mut arenas = vec![main_arena];
+    while arenas.len() < MAX_ARENAS {
+        match session.address(&format!("(unsigned long)((struct malloc_state *){:#x})->next", arenas[arenas.len() - 1])) {
+            Ok(next) if next != main_arena && next != 0 => arenas.push(next),
+            _ => break,
+        }
+    }
+    arenas
+}
+
+// Has the process itself run malloc_info() into a file and reads it back
+// through /proc/PID/root, which is the same file for a process in another
+// mount namespace. libc's exported symbols carry no types, hence the casts.
+pub fn malloc_info(session: &mut Session, pid: u32) -> Result<String, String> {
+    let path = format!("/tmp/memlayout-malloc-info-{}.xml", pid);
+    let file = session.address(&format!("(unsigned long)((void *(*)(const char *, const char *))fopen)(\\\"{}\\\", \\\"w\\\")", path))?;
+    if file == 0 {
+        return Err(format!("The process couldn't open {}", path));
+    }
+    let written = session.address(&format!("((int (*)(int, void *))malloc_info)(0, (void *){:#x})", file));
+    session.address(&format!("((int (*)(void *))fclose)((void *){:#x})", file))?;
+    written?;
+    let local = format!("/proc/{}/root{}", pid, path);
+    let xml = std::fs::read_to_string(&local).map_err(|e| format!("Unable to read {}: {}", local, e));
+    let _ = std::fs::remove_file(&local);
+    xml
+}
@@ -0,0 +1,137 @@
+use crate::mallocinfo::attribute;
+use crate::{MemoryAttributes, MemoryRegion};
+use std::io::{BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// How much of the memory map XML is asked for per qXfer packet; stubs
+// answer with less when their packet buffer is smaller.
+const CHUNK: usize = 0x400;
+
+// A target behind a GDB remote serial protocol stub: OpenOCD (port 3333 by
+// default), gdbserver, pyOCD or a probe's built-in server. Connecting is
+// what gdb does, so OpenOCD halts the target as it would for gdb.
+pub struct Remote {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Remote {
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("Unable to connect to the gdb server at {}: {}", address, e))?;
+        stream.set_read_timeout(Some(Duration::from_secs(10))).map_err(|e| e.to_string())?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Remote { reader: BufReader::new(stream), writer })
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte).map_err(|e| format!("gdb server read failed: {}", e))?;
+        Ok(byte[0])
+    }
+
+    // "$data#checksum", acknowledged with '+'. Replies escape '}' '#' '$'
+    // and '*' as '}' followed by the byte xor 0x20, and may run-length
+    // encode as "c*n", repeating c n - 29 more times.
+    fn request(&mut self, packet: &str) -> Result<Vec<u8>, String> {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.writer, "${}#{:02x}", packet, checksum).map_err(|e| format!("gdb server write failed: {}", e))?;
+        // Acks and notifications ("%...") until the reply starts.
+        while self.byte()? != b'$' {}
+        let mut data = Vec::new();
+        loop {
+            match self.byte()? {
+                b'#' => break,
+                b'}' => {
+                    let escaped = self.byte()?;
+                    data.push(escaped ^ 0x20);
+                }
+                b'*' => {
+                    let count = self.byte()?.saturating_sub(29);
+                    let last = *data.last().ok_or("Run-length encoding without a character")?;
+                    data.extend(std::iter::repeat_n(last, count as usize));
+                }
+                byte => data.push(byte),
+            }
+        }
+        self.byte()?;
+        self.byte()?;
+        self.writer.write_all(b"+").map_err(|e| format!("gdb server write failed: {}", e))?;
+        Ok(data)
+    }
+
+    // qXfer:memory-map:read answers "m" with more to come or "l" for the
+    // last part; a stub without a map answers with nothing or an error.
+    pub fn memory_map(&mut self) -> Result<String, String> {
+        let supported = String::from_utf8_lossy(&self.request("qSupported:multiprocess+;qXfer:memory-map:read+")?).into_owned();
+        if !supported.split(';').any(|feature| feature == "qXfer:memory-map:read+") {
+            return Err("The gdb server doesn't offer a memory map (qXfer:memory-map:read)".to_string());
+        }
+        let mut xml = Vec::new();
+        loop {
+            let reply = self.request(&format!("qXfer:memory-map:read::{:x},{:x}", xml.len(), CHUNK))?;
+            match reply.split_first() {
+                Some((b'm', part)) => xml.extend_from_slice(part),
+                Some((b'l', part)) => {
+                    xml.extend_from_slice(part);
+                    break;
+                }
+                _ => return Err(format!("The gdb server refused the memory map: {}", String::from_utf8_lossy(&reply))),
+            }
+        }
+        String::from_utf8(xml).map_err(|_| "The memory map is not UTF-8".to_string())
+    }
+}
+
+// Numbers in the map are decimal unless they start with 0x.
+pub fn number(value: &str) -> Result<usize, String> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number in the memory map: {}", value))
+}
+
+// <memory type="flash" start="0x8000000" length="0x100000"> with a
+// blocksize property, or type ram or rom. Code runs from flash on most
+// MCUs, so that is drawn executable.
+pub fn parse_memory_map(xml: &str) -> Result<Vec<MemoryRegion>, String> {
+    if !xml.contains("<memory-map") {
+        return Err("Not a gdb memory map".to_string());
+    }
+    let mut memory_regions = Vec::new();
+    for tag in xml.split('<').skip(1).map(|tag| tag.split('>').next().unwrap_or("")) {
+        if !tag.starts_with("memory ") {
+            continue;
+        }
+        let kind = attribute(tag, "type").unwrap_or("ram");
+        let start = number(attribute(tag, "start").ok_or("A memory element has no start")?)?;
+        let length = number(attribute(tag, "length").ok_or("A memory element has no length")?)?;
+        let attributes = match kind {
+            "flash" => MemoryAttributes { readable: true, writable: false, executable: true, shared: false, allocated: true },
+            "rom" => MemoryAttributes { readable: true, writable: false, executable: false, shared: false, allocated: true },
+            _ => MemoryAttributes { readable: true, writable: true, executable: false, shared: false, allocated: true },
+        };
+        memory_regions.push(MemoryRegion {
+            start,
+            end: start.saturating_add(length),
+            size: length,
+            attributes,
+            offset: 0,
+            device: (0, 0),
+            inode: 0,
+            // Named like [heap] rather than [anon:...], which would make
+            // flash JIT code.
+            file_name: Some(format!("[{}]", kind)),
+            thread_id: None,
+            guard: false,
+            smaps: None,
+            mappings: 1,
+        });
+    }
+    if memory_regions.is_empty() {
+        return Err("The memory map has no regions".to_string());
+    }
+    memory_regions.sort_by_key(|region| region.start);
+    Ok(memory_regions)
+}
@@ -0,0 +1,218 @@
+use crate::{carve, format_size, pagemap, MemoryRegion};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// The runtime.MemStats fields that explain a Go process's memory, in bytes.
+pub struct MemStats {
+    pub sys: u64,
+    pub heap_sys: u64,
+    pub heap_alloc: u64,
+    pub heap_inuse: u64,
+    pub heap_idle: u64,
+    // Idle spans whose pages the scavenger has handed back with madvise.
+    pub heap_released: u64,
+    pub stack_sys: u64,
+}
+
+impl MemStats {
+    pub fn label(&self) -> String {
+        format!(
+            "Go heap: {} in use, {} idle, {} released of {}",
+            format_size(self.heap_inuse as usize),
+            format_size(self.heap_idle.saturating_sub(self.heap_released) as usize),
+            format_size(self.heap_released as usize),
+            format_size(self.heap_sys as usize)
+        )
+    }
+}
+
+// runtime.MemStats as JSON, on its own or under "memstats" as expvar's
+// /debug/vars serves it; otherwise text with "Name = N" (the "# runtime.
+// MemStats" trailer of /debug/pprof/heap?debug=1) or "Name:N" (fmt's %+v).
+pub fn parse(text: &str) -> Result<MemStats, String> {
+    let mut values: BTreeMap<String, u64> = BTreeMap::new();
+    if let Ok(json) = serde_json::from_str::<Value>(text) {
+        let object = json.get("memstats").unwrap_or(&json);
+        for (key, value) in object.as_object().into_iter().flatten() {
+            if let Some(value) = value.as_u64() {
+                values.insert(key.clone(), value);
+            }
+        }
+    } else {
+        for line in text.lines() {
+            // pprof prints stacks and the span and cache structures as
+            // "# Stack = 491520 / 491520", in use over obtained.
+            let pair = line.trim_start_matches(['#', ' ']).split_once(" = ").and_then(|(key, value)| Some((key, value.split_once(" / ")?)));
+            if let Some((key, (inuse, sys))) = pair {
+                for (suffix, value) in [("Inuse", inuse), ("Sys", sys)] {
+                    if let Ok(value) = value.trim().parse::<u64>() {
+                        values.insert(format!("{}{}", key, suffix), value);
+                    }
+                }
+                continue;
+            }
+            let line = line.replace(" = ", ":");
+            for token in line.split_whitespace() {
+                let Some((key, value)) = token.trim_matches(['{', '}', '#']).split_once(':') else { continue };
+                if let Ok(value) = value.parse::<u64>() {
+                    values.insert(key.to_string(), value);
+                }
+            }
+        }
+    }
+    let value = |key: &str| values.get(key).copied().ok_or_else(|| format!("The memory stats have no {}; expected runtime.MemStats as JSON, expvar or /debug/pprof/heap?debug=1", key));
+    Ok(MemStats {
+        sys: value("Sys")?,
+        heap_sys: value("HeapSys")?,
+        heap_alloc: value("HeapAlloc")?,
+        heap_inuse: value("HeapInuse")?,
+        heap_idle: value("HeapIdle")?,
+        heap_released: value("HeapReleased")?,
+        stack_sys: value("StackSys")?,
+    })
+}
+
+pub fn read(path: &str) -> Result<MemStats, String> {
+    parse(&std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?)
+}
+
+pub fn print(stats: &MemStats) {
+    let size = |bytes: u64| format_size(bytes as usize);
+    println!("Sys {}: heap {}, stacks {}, other runtime {}", size(stats.sys), size(stats.heap_sys), size(stats.stack_sys), size(stats.sys.saturating_sub(stats.heap_sys + stats.stack_sys)));
+    println!(
+        "HeapSys {} = in use {} ({} of live objects) + idle {}, of which {} released",
+        size(stats.heap_sys),
+        size(stats.heap_inuse),
+        size(stats.heap_alloc),
+        size(stats.heap_idle),
+        size(stats.heap_released)
+    );
+}
+
+// What the Go heap's mappings hold, for comparing with MemStats.
+#[derive(Default)]
+pub struct Arenas {
+    pub mapped: usize,
+    // Mapped pages with nothing behind them, by pagemap.
+    pub absent: usize,
+    // PROT_NONE address space reserved for arenas not yet in use.
+    pub reserved: usize,
+}
+
+// On 64-bit Linux the runtime places its heap at hints of the form
+// 0x00XXc000000000 (mallocinit in malloc.go), so its arenas are the
+// anonymous private mappings whose address bits 32 to 39 read 0xc0.
+fn in_arena(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && region.file_name.is_none() && region.thread_id.is_none() && !region.attributes.shared && (region.start >> 32) & 0xff == 0xc0
+}
+
+// Arena mappings named "[anon:Go heap]", with PROT_NONE reservations as
+// "[anon:Go heap, reserved]" and, for a live process, the runs pagemap
+// shows nothing behind carved out as "[anon:Go heap, not resident]": the
+// released spans, if the runtime used MADV_DONTNEED, and pages not touched
+// yet. Carved mappings lose their smaps, which can't be divided.
+pub fn attribute(memory_regions: Vec<MemoryRegion>, pid: Option<u32>, page_size: usize) -> (Vec<MemoryRegion>, Arenas) {
+    let mut arenas = Arenas::default();
+    let mut result = Vec::with_capacity(memory_regions.len());
+    for mut region in memory_regions {
+        if !in_arena(&region) {
+            result.push(region);
+            continue;
+        }
+        if !region.attributes.readable {
+            arenas.reserved += region.size;
+            region.file_name = Some("[anon:Go heap, reserved]".to_string());
+            result.push(region);
+            continue;
+        }
+        arenas.mapped += region.size;
+        region.file_name = Some("[anon:Go heap]".to_string());
+        let runs = pid.and_then(|pid| pagemap::absent(pid, &region, page_size).ok()).unwrap_or_default();
+        if runs.is_empty() {
+            result.push(region);
+            continue;
+        }
+        let overlays: Vec<MemoryRegion> = runs
+            .iter()
+            .map(|(start, end)| MemoryRegion { start: *start, end: *end, size: end - start, file_name: Some("[anon:Go heap, not resident]".to_string()), smaps: None, ..region.clone() })
+            .collect();
+        arenas.absent += overlays.iter().map(|overlay| overlay.size).sum::<usize>();
+        let mut pieces = carve(&MemoryRegion { smaps: None, ..region }, &overlays);
+        pieces.extend(overlays);
+        pieces.sort_by_key(|piece| piece.start);
+        result.extend(pieces);
+    }
+    (result, arenas)
+}
+
+// Reconciles the mappings with MemStats: the heap keeps at most HeapSys
+// less what was released resident. More than that means released pages
+// are still counted in RSS.
+pub fn explain(stats: &MemStats, arenas: &Arenas, measured: bool) {
+    println!("Go arenas: {} mapped, {} reserved", format_size(arenas.mapped), format_size(arenas.reserved));
+    if !measured {
+        return;
+    }
+    let resident = arenas.mapped - arenas.absent;
+    let expected = stats.heap_sys.saturating_sub(stats.heap_released) as usize;
+    println!("{} of the arenas resident, {} not; MemStats allows at most {}", format_size(resident), format_size(arenas.absent), format_size(expected));
+    let excess = resident.saturating_sub(expected);
+    if stats.heap_released > 0 && excess > (stats.heap_released as usize) / 4 {
+        println!(
+            "{} released to the OS is still resident: released with MADV_FREE (the default from Go 1.12 to 1.15), pages stay in RSS until the kernel needs them; GODEBUG=madvdontneed=1 drops them at once",
+            format_size(excess.min(stats.heap_released as usize))
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The trailer of /debug/pprof/heap?debug=1.
+    const PPROF: &str = "\
+heap profile: 1: 2048 [8: 16384] @ heap/1048576
+1: 2048 [8: 16384] @ 0x40e9b1 0x40e5e5 0x45cd81
+
+# runtime.MemStats
+# Alloc = 2437584
+# TotalAlloc = 2437584
+# Sys = 12699664
+# Lookups = 0
+# Mallocs = 8953
+# Frees = 417
+# HeapAlloc = 2437584
+# HeapSys = 7962624
+# HeapIdle = 4530176
+# HeapInuse = 3432448
+# HeapReleased = 4497408
+# HeapObjects = 8536
+# Stack = 491520 / 491520
+# MSpan = 73440 / 81600
+# MCache = 14400 / 15600
+# NextGC = 4194304
+";
+
+    #[test]
+    fn parses_the_pprof_trailer() {
+        let stats = parse(PPROF).unwrap();
+        assert_eq!((stats.sys, stats.heap_sys, stats.heap_alloc), (12699664, 7962624, 2437584));
+        assert_eq!((stats.heap_inuse, stats.heap_idle, stats.heap_released), (3432448, 4530176, 4497408));
+        assert_eq!(stats.stack_sys, 491520);
+    }
+
+    #[test]
+    fn parses_expvar_json() {
+        let vars = r#"{"cmdline": ["./server"], "memstats": {"Alloc": 1, "Sys": 100, "HeapSys": 80, "HeapAlloc": 40, "HeapInuse": 48, "HeapIdle": 32, "HeapReleased": 16, "StackSys": 8, "PauseNs": [0, 0]}}"#;
+        let stats = parse(vars).unwrap();
+        assert_eq!((stats.sys, stats.heap_idle, stats.heap_released, stats.stack_sys), (100, 32, 16, 8));
+        assert_eq!(stats.label(), "Go heap: 48 B in use, 16 B idle, 16 B released of 80 B");
+    }
+
+    #[test]
+    fn parses_printed_structs() {
+        let printed = "{Alloc:1 TotalAlloc:2 Sys:100 Lookups:0 Mallocs:3 Frees:1 HeapAlloc:40 HeapSys:80 HeapIdle:32 HeapInuse:48 HeapReleased:16 HeapObjects:2 StackInuse:8 StackSys:8}";
+        assert_eq!(parse(printed).unwrap().heap_sys, 80);
+        assert!(parse("{Alloc:1}").is_err_and(|e| e.contains("no Sys")));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{MemoryAttributes, MemoryRegion};
+use std::collections::BTreeMap;
+use std::fs;
+
+// Blocks of different memories start on this boundary with at least this
+// much space between them, as espidf.rs lays out its memory types.
+const BLOCK_ALIGN: usize = 1 << 20;
+
+// What one DRM client (or NVIDIA compute app) holds of each GPU memory,
+// "vram", "gtt" or whatever the driver calls its regions.
+pub struct Client {
+    pub pid: u32,
+    pub command: String,
+    pub memories: BTreeMap<String, usize>,
+}
+
+// "1234 KiB", "12 MiB" or bytes without a unit.
+fn amount(value: &str) -> Option<usize> {
+    let mut fields = value.split_whitespace();
+    let number: usize = fields.next()?.parse().ok()?;
+    let shift = match fields.next() {
+        None => 0,
+        Some("KiB") => 10,
+        Some("MiB") => 20,
+        Some("GiB") => 30,
+        Some(_) => return None,
+    };
+    Some(number << shift)
+}
+
+fn comm(pid: u32) -> String {
+    fs::read_to_string(format!("/proc/{}/comm", pid)).map(|comm| comm.trim().to_string()).unwrap_or_else(|_| "?".to_string())
+}
+
+// The DRM fdinfo every recent driver writes for each open render or card
+// node: drm-client-id, then drm-total-REGION (or the older
+// drm-memory-REGION) per memory. A client shared across fds and forks is
+// counted once, for the first process seen with it.
+pub fn from_fdinfo() -> Result<Vec<Client>, String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut clients = Vec::new();
+    let entries = fs::read_dir("/proc").map_err(|e| format!("Unable to read /proc: {}", e))?;
+    let mut pids: Vec<u32> = entries.flatten().filter_map(|entry| entry.file_name().to_str()?.parse().ok()).collect();
+    pids.sort_unstable();
+    for pid in pids {
+        let Ok(fds) = fs::read_dir(format!("/proc/{}/fdinfo", pid)) else { continue };
+        for fd in fds.flatten() {
+            let Ok(text) = fs::read_to_string(fd.path()) else { continue };
+            let value = |key: &str| text.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(':')).map(str::trim);
+            let Some(id) = value("drm-client-id") else { continue };
+            if !seen.insert((value("drm-pdev").unwrap_or("").to_string(), id.to_string())) {
+                continue;
+            }
+            let mut memories = BTreeMap::new();
+            for line in text.lines() {
+                let Some((key, amount_text)) = line.split_once(':') else { continue };
+                let region = key.strip_prefix("drm-total-").or_else(|| key.strip_prefix("drm-memory-"));
+                if let (Some(region), Some(bytes)) = (region, amount(amount_text.trim())) {
+                    // drm-total wins over drm-memory when a driver prints both.
+                    if key.starts_with("drm-total-") || !memories.contains_key(region) {
+                        memories.insert(region.to_string(), bytes);
+                    }
+                }
+            }
+            if memories.values().any(|bytes| *bytes > 0) {
+                clients.push(Client { pid, command: comm(pid), memories });
+            }
+        }
+    }
+    if clients.is_empty() {
+        return Err("No process holds GPU memory, or the driver has no DRM fdinfo".to_string());
+    }
+    Ok(clients)
+}
+
+// amdgpu_gem_info from /sys/kernel/debug/dri/N: a "pid 1234 command
+// Xorg:" line per client, then a line per buffer object such as
+// "0x00000001:      2097152 byte VRAM NO_CPU_ACCESS".
+pub fn parse_gem_info(text: &str) -> Vec<Client> {
+    let mut clients: Vec<Client> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(rest) = trimmed.strip_prefix("pid") {
+            let mut fields = rest.split_whitespace();
+            let pid = fields.next().and_then(|pid| pid.parse().ok()).unwrap_or(0);
+            let command = rest.split_once("command").map_or("?", |(_, command)| command.trim().trim_end_matches(':'));
+            clients.push(Client { pid, command: command.to_string(), memories: BTreeMap::new() });
+            continue;
+        }
+        let Some(client) = clients.last_mut() else { continue };
+        let fields: Vec<&str> = trimmed.split_whitespace().collect();
+        if let [handle, size, "byte", domain, ..] = fields.as_slice() {
+            if let (true, Ok(size)) = (handle.ends_with(':'), size.parse::<usize>()) {
+                *client.memories.entry(domain.to_lowercase()).or_insert(0) += size;
+            }
+        }
+    }
+    clients.retain(|client| !client.memories.is_empty());
+    clients
+}
+
+// nvidia-smi --query-compute-apps=pid,process_name,used_memory
+// --format=csv, whose header names the unit.
+pub fn parse_nvidia_smi(text: &str) -> Vec<Client> {
+    let mut lines = text.lines();
+    let unit = match lines.next() {
+        Some(header) if header.contains("[MiB]") => 20,
+        Some(header) if header.contains("[GiB]") => 30,
+        _ => 0,
+    };
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let pid = fields.first()?.parse().ok()?;
+            let used = fields.last()?.split_whitespace().next()?.parse::<usize>().ok()?;
+            let command = fields.get(1).filter(|_| fields.len() > 2).unwrap_or(&"?").to_string();
+            Some(Client { pid, command, memories: BTreeMap::from([("vram".to_string(), used << unit)]) })
+        })
+        .collect()
+}
+
+// The size of each memory for the free space after the clients: amdgpu's
+// mem_info_vram_total and mem_info_gtt_total of every card.
+fn totals() -> BTreeMap<String, usize> {
+    let mut totals = BTreeMap::new();
+    let Ok(cards) = fs::read_dir("/sys/class/drm") else { return totals };
+    for card in cards.flatten() {
+        let name = card.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("card") || name.contains('-') {
+            continue;
+        }
+        for memory in ["vram", "gtt"] {
+            if let Some(bytes) = fs::read_to_string(card.path().join(format!("device/mem_info_{}_total", memory))).ok().and_then(|total| total.trim().parse::<usize>().ok()) {
+                *totals.entry(memory.to_string()).or_insert(0) += bytes;
+            }
+        }
+    }
+    totals
+}
+
+fn region(start: usize, size: usize, name: String, allocated: bool) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end: start + size,
+        size,
+        attributes: MemoryAttributes { readable: allocated, writable: allocated, executable: false, shared: allocated, allocated },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// Like the ESP-IDF view the addresses are made up: a block per memory,
+// VRAM first, holding its clients largest first and then what is free.
+pub fn layout(clients: &[Client], totals: &BTreeMap<String, usize>) -> Vec<MemoryRegion> {
+    let mut memories: Vec<&String> = clients.iter().flat_map(|client| client.memories.keys()).collect();
+    memories.sort_by_key(|memory| (memory.as_str() != "vram", memory.as_str() != "gtt", memory.to_string()));
+    memories.dedup();
+    let mut memory_regions = Vec::new();
+    let mut base = 0;
+    for memory in memories {
+        let mut users: Vec<(&Client, usize)> = clients.iter().filter_map(|client| Some((client, *client.memories.get(memory)?))).filter(|(_, bytes)| *bytes > 0).collect();
+        if users.is_empty() {
+            continue;
+        }
+        users.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.pid.cmp(&b.0.pid)));
+        let label = memory.to_uppercase();
+        let mut start = base;
+        for (client, bytes) in users {
+            memory_regions.push(region(start, bytes, format!("{}: {} ({})", label, client.command, client.pid), true));
+            start += bytes;
+        }
+        let end = totals.get(memory).map_or(start, |total| start.max(base + total));
+        if end > start {
+            memory_regions.push(region(start, end - start, format!("{}: free", label), false));
+        }
+        base = end.next_multiple_of(BLOCK_ALIGN) + BLOCK_ALIGN;
+    }
+    memory_regions
+}
+
+// "gpu:" alone reads the fdinfo of every process, with this machine's
+// totals; "gpu:FILE" a saved amdgpu_gem_info or nvidia-smi CSV.
+pub fn read(arg: &str) -> Result<Vec<MemoryRegion>, String> {
+    if arg.is_empty() {
+        return Ok(layout(&from_fdinfo()?, &totals()));
+    }
+    let clients = {
+        let text = fs::read_to_string(arg).map_err(|e| format!("Unable to read {}: {}", arg, e))?;
+        let clients = if text.starts_with("pid,") { parse_nvidia_smi(&text) } else { parse_gem_info(&text) };
+        if clients.is_empty() {
+            return Err(format!("{} is neither amdgpu_gem_info nor nvidia-smi --query-compute-apps CSV", arg));
+        }
+        clients
+    };
+    Ok(layout(&clients, &BTreeMap::new()))
+}
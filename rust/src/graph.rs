@@ -0,0 +1,76 @@
+use crate::overview::ProcessSample;
+use crate::{format_size, MemoryRegion};
+use std::collections::BTreeMap;
+
+// What several processes can map at once: a file, or shared memory told
+// apart by its inode, since every memfd and SysV segment of a name is one
+// object only if the inode says so.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+struct Object {
+    path: String,
+    inode: u64,
+}
+
+fn is_shared_memory(region: &MemoryRegion) -> bool {
+    region.shared_memory_kind().is_some() || region.file_name.as_deref().is_some_and(|path| path.starts_with("/dev/shm/"))
+}
+
+fn object(region: &MemoryRegion) -> Option<Object> {
+    let path = region.file_name.as_deref().filter(|path| path.starts_with('/'))?;
+    region.attributes.allocated.then(|| Object { path: path.to_string(), inode: region.inode })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Graphviz of the processes and the objects they map, an edge from a
+// process to each object weighted by how much of it the process maps.
+// With several processes, only objects at least two of them map are
+// drawn: the topology is in what they share. Shared memory is a box.
+pub fn dot(processes: &[ProcessSample]) -> String {
+    let mut edges: BTreeMap<Object, Vec<(u32, usize)>> = BTreeMap::new();
+    let mut shared_memory: BTreeMap<Object, bool> = BTreeMap::new();
+    for process in processes {
+        let mut mapped: BTreeMap<Object, usize> = BTreeMap::new();
+        for region in &process.memory_regions {
+            let Some(object) = object(region) else { continue };
+            *shared_memory.entry(object.clone()).or_default() |= is_shared_memory(region);
+            *mapped.entry(object).or_default() += region.size;
+        }
+        for (object, bytes) in mapped {
+            edges.entry(object).or_default().push((process.pid, bytes));
+        }
+    }
+    let minimum = if processes.len() > 1 { 2 } else { 1 };
+    edges.retain(|_, mappers| mappers.len() >= minimum);
+    let heaviest = edges.values().flatten().map(|(_, bytes)| *bytes).max().unwrap_or(1).max(1) as f64;
+
+    let mut dot = String::from("graph memory {\n  graph [rankdir=LR, overlap=false];\n  node [fontname=\"sans-serif\", fontsize=10];\n  edge [fontname=\"sans-serif\", fontsize=8, color=\"#00000060\"];\n");
+    for process in processes {
+        // Without smaps a single process's RSS isn't known.
+        let rss = if process.rss > 0 { format!("\\nRSS {}", format_size(process.rss)) } else { String::new() };
+        dot.push_str(&format!("  p{} [label=\"{} ({}){}\", shape=box, style=\"rounded,filled\", fillcolor=\"#cfe2f3\"];\n", process.pid, escape(&process.name), process.pid, rss));
+    }
+    for (index, (object, mappers)) in edges.iter().enumerate() {
+        let name = object.path.rsplit('/').next().unwrap_or(&object.path);
+        let (shape, color) = if shared_memory[object] { ("box", "#f4cccc") } else { ("ellipse", "#eeeeee") };
+        dot.push_str(&format!(
+            "  o{} [label=\"{}\\n{} processes\", tooltip=\"{}\", shape={}, style=filled, fillcolor=\"{}\"];\n",
+            index,
+            escape(name),
+            mappers.len(),
+            escape(&object.path),
+            shape,
+            color
+        ));
+        for (pid, bytes) in mappers {
+            // Pen width by the share of the heaviest edge, on a log scale so
+            // a 4 KiB mapping still shows next to a 1 GiB one.
+            let width = 1.0 + 4.0 * ((*bytes as f64).ln_1p() / heaviest.ln_1p());
+            dot.push_str(&format!("  p{} -- o{} [label=\"{}\", weight={}, penwidth={:.1}];\n", pid, index, format_size(*bytes), (bytes >> 12).max(1), width));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
@@ -0,0 +1,66 @@
+use crate::{format_size, jit, MemoryRegion};
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_file(region: &MemoryRegion) -> bool {
+    region.file_name.as_deref().is_some_and(|path| path.starts_with('/'))
+}
+
+// A loader maps one object as a handful of adjacent mappings (headers, text,
+// rodata, data), so merging runs of the same path yields one block per
+// object. The block is sized by the bytes it maps, like the coalesced
+// placeholders, and takes the protection of its largest member, since the
+// union of text and data would read as rwx.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn group_by_file(memory_regions: Vec<MemoryRegion>, expand: &[&str]) -> Vec<MemoryRegion> {
+    group_runs(memory_regions, |block, region| {
+        let expanded = region.file_name.as_deref().is_some_and(|path| expand.iter().any(|pattern| path.contains(pattern)));
+        is_file(region) && !expanded && block.file_name == region.file_name
+    })
+}
+
+// A JIT allocates code space a chunk at a time, so a busy one leaves runs
+// of small executable mappings that say more as one block.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn group_jit(memory_regions: Vec<MemoryRegion>) -> Vec<MemoryRegion> {
+    group_runs(memory_regions, |block, region| jit::is_jit(block) && jit::is_jit(region) && block.file_name == region.file_name)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn group_runs<F: Fn(&MemoryRegion, &MemoryRegion) -> bool>(memory_regions: Vec<MemoryRegion>, joins: F) -> Vec<MemoryRegion> {
+    let mut grouped: Vec<MemoryRegion> = Vec::new();
+    let mut largest = 0;
+    for region in memory_regions {
+        match grouped.last_mut() {
+            Some(block) if joins(block, &region) => {
+                if region.size > largest {
+                    largest = region.size;
+                    block.attributes = region.attributes.clone();
+                }
+                block.end = region.end;
+                block.size += region.size;
+                block.mappings += region.mappings;
+                block.smaps = match (block.smaps.take(), &region.smaps) {
+                    (Some(mut total), Some(smaps)) => {
+                        total.add(smaps);
+                        Some(total)
+                    }
+                    _ => None,
+                };
+            }
+            _ => {
+                largest = region.size;
+                grouped.push(region);
+            }
+        }
+    }
+    grouped
+}
+
+pub fn group_label(region: &MemoryRegion) -> String {
+    let path = region.file_name.as_deref().unwrap_or(if jit::is_jit(region) { "JIT" } else { "anon" });
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match &region.smaps {
+        Some(smaps) => format!("{} x{} {}, RSS {}", name, region.mappings, format_size(region.size), format_size(smaps.rss)),
+        None => format!("{} x{} {}", name, region.mappings, format_size(region.size)),
+    }
+}
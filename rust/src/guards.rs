@@ -0,0 +1,49 @@
+use crate::MemoryRegion;
+
+fn is_prot_none(region: &MemoryRegion) -> bool {
+    let attributes = &region.attributes;
+    attributes.allocated && !attributes.readable && !attributes.writable && !attributes.executable
+}
+
+fn is_anon_data(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && region.attributes.readable && region.attributes.writable && region.file_name.is_none()
+}
+
+pub fn is_stack(region: &MemoryRegion) -> bool {
+    region.thread_id.is_some() || region.file_name.as_deref() == Some("[stack]")
+}
+
+// Stacks grow down, so their guard sits directly below them; glibc arenas
+// reserve PROT_NONE space directly above the part in use.
+pub fn mark_guard_pages(memory_regions: &mut [MemoryRegion]) {
+    for i in 0..memory_regions.len() {
+        if !is_prot_none(&memory_regions[i]) {
+            continue;
+        }
+        let guards_stack = memory_regions
+            .get(i + 1)
+            .is_some_and(|above| above.start == memory_regions[i].end && is_stack(above));
+        let guards_arena = i > 0 && memory_regions[i - 1].end == memory_regions[i].start && is_anon_data(&memory_regions[i - 1]);
+        memory_regions[i].guard = guards_stack || guards_arena;
+    }
+}
+
+// The main stack is protected by the kernel's stack_guard_gap instead of a
+// mapping, so unmapped space below it counts as a guard.
+pub fn unguarded_stacks(memory_regions: &[MemoryRegion]) -> Vec<&MemoryRegion> {
+    let mut unguarded = Vec::new();
+    for (i, region) in memory_regions.iter().enumerate() {
+        if !region.attributes.allocated || !is_stack(region) {
+            continue;
+        }
+        let below = if i > 0 { memory_regions[..i].iter().rev().find(|r| r.attributes.allocated) } else { None };
+        let guarded = match below {
+            Some(below) if below.end == region.start => below.guard,
+            Some(_) | None => region.file_name.as_deref() == Some("[stack]"),
+        };
+        if !guarded {
+            unguarded.push(region);
+        }
+    }
+    unguarded
+}
@@ -0,0 +1,211 @@
+use crate::preview;
+use crate::scale::Scale;
+use crate::smaps::describe_flag;
+use crate::theme::Theme;
+use crate::{display_name, format_size, insert_gap_memory_regions, luminance, region_category, region_color, region_weight, ColorBy, MemoryRegion, SizeMetric};
+use egui::{Align2, Color32, CtxRef, Pos2, Rect, Sense, TextStyle, Vec2};
+use std::collections::BTreeSet;
+
+const CATEGORIES: [&str; 13] = ["file", "anon", "jit", "heap", "stack", "special", "named anon", "shadow", "shared anon", "memfd", "sysv shm", "guard", "gap"];
+const ADDRESS_COLUMN: f32 = 190.0;
+// The smallest slice of the layout a zoom can show, so a bar is never
+// magnified past the point where it stops meaning anything.
+const MIN_SPAN: f64 = 1e-6;
+
+pub type Refresh = Box<dyn Fn() -> Result<Vec<MemoryRegion>, String>>;
+
+pub struct MemoryMapApp {
+    title: String,
+    captured: Vec<MemoryRegion>,
+    color_by: ColorBy,
+    theme: Theme,
+    size_metric: SizeMetric,
+    scale: Scale,
+    hidden: BTreeSet<&'static str>,
+    // Visible part of the layout, as fractions of the total height.
+    view: (f64, f64),
+    refresh: Option<Refresh>,
+    status: Option<String>,
+    // First bytes of readable regions by start, from --hex-preview.
+    previews: Vec<(usize, Vec<u8>)>,
+}
+
+impl MemoryMapApp {
+    pub fn new(title: String, captured: Vec<MemoryRegion>, color_by: ColorBy, theme: Theme, size_metric: SizeMetric, scale: Scale, refresh: Option<Refresh>) -> Self {
+        MemoryMapApp { title, captured, color_by, theme, size_metric, scale, hidden: BTreeSet::new(), view: (0.0, 1.0), refresh, status: None, previews: Vec::new() }
+    }
+
+    pub fn with_previews(mut self, previews: Vec<(usize, Vec<u8>)>) -> Self {
+        self.previews = previews;
+        self
+    }
+
+    // Same weights as the PNG, laid out as (region, top, bottom) in
+    // fractions of the total.
+    fn layout(&self) -> Vec<(MemoryRegion, f64, f64)> {
+        let shown: Vec<MemoryRegion> = self.captured.iter().filter(|region| !self.hidden.contains(region_category(region))).cloned().collect();
+        let mut regions = insert_gap_memory_regions(&shown);
+        if self.hidden.contains("gap") {
+            regions.retain(|region| region.attributes.allocated);
+        }
+        let total: f64 = regions.iter().map(|region| region_weight(region, self.size_metric, self.scale)).sum::<f64>().max(1.0);
+        let mut top = 0.0;
+        regions
+            .into_iter()
+            .filter_map(|region| {
+                let weight = region_weight(&region, self.size_metric, self.scale) / total;
+                if weight == 0.0 {
+                    return None;
+                }
+                let entry = (region, top, top + weight);
+                top += weight;
+                Some(entry)
+            })
+            .collect()
+    }
+
+    fn zoom(&mut self, factor: f64, anchor: f64) {
+        let (start, end) = self.view;
+        let span = ((end - start) * factor).clamp(MIN_SPAN, 1.0);
+        let center = start + anchor * (end - start);
+        let start = (center - anchor * span).clamp(0.0, 1.0 - span);
+        self.view = (start, start + span);
+    }
+
+    fn pan(&mut self, fraction: f64) {
+        let (start, end) = self.view;
+        let span = end - start;
+        let start = (start + fraction * span).clamp(0.0, 1.0 - span);
+        self.view = (start, start + span);
+    }
+
+    fn controls(&mut self, ctx: &CtxRef) {
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for category in CATEGORIES {
+                    let mut shown = !self.hidden.contains(category);
+                    if ui.checkbox(&mut shown, category).changed() {
+                        if shown {
+                            self.hidden.remove(category);
+                        } else {
+                            self.hidden.insert(category);
+                        }
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("size:");
+                ui.radio_value(&mut self.size_metric, SizeMetric::Virtual, "virtual");
+                ui.radio_value(&mut self.size_metric, SizeMetric::Rss, "rss");
+                ui.radio_value(&mut self.size_metric, SizeMetric::Pss, "pss");
+                ui.separator();
+                ui.label("scale:");
+                ui.radio_value(&mut self.scale, Scale::Log, "log");
+                ui.radio_value(&mut self.scale, Scale::Sqrt, "sqrt");
+                ui.radio_value(&mut self.scale, Scale::Linear, "linear");
+                ui.radio_value(&mut self.scale, Scale::Equal, "equal");
+                ui.separator();
+                if ui.button("Reset zoom").clicked() {
+                    self.view = (0.0, 1.0);
+                }
+                if let Some(refresh) = &self.refresh {
+                    if ui.button("Recapture").clicked() {
+                        match refresh() {
+                            Ok(captured) => {
+                                self.captured = captured;
+                                self.status = None;
+                            }
+                            Err(e) => self.status = Some(e),
+                        }
+                    }
+                }
+                if let Some(status) = &self.status {
+                    ui.colored_label(Color32::RED, status);
+                }
+            });
+        });
+    }
+}
+
+impl epi::App for MemoryMapApp {
+    fn name(&self) -> &str {
+        &self.title
+    }
+
+    fn update(&mut self, ctx: &CtxRef, _frame: &mut epi::Frame<'_>) {
+        self.controls(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::drag());
+            let rect = response.rect;
+            let height = rect.height().max(1.0) as f64;
+
+            if let Some(pointer) = response.hover_pos() {
+                let scroll = ui.input().scroll_delta.y as f64;
+                if scroll != 0.0 {
+                    self.zoom((-scroll * 0.002).exp(), (pointer.y - rect.top()) as f64 / height);
+                }
+            }
+            if response.dragged() {
+                self.pan(-response.drag_delta().y as f64 / height);
+            }
+
+            let (view_start, view_end) = self.view;
+            let to_y = |fraction: f64| rect.top() + ((fraction - view_start) / (view_end - view_start) * height) as f32;
+            let bar_left = rect.left() + ADDRESS_COLUMN;
+            let mut hovered = None;
+
+            for (region, top, bottom) in self.layout() {
+                if bottom < view_start || top > view_end {
+                    continue;
+                }
+                let (y0, y1) = (to_y(top).max(rect.top()), to_y(bottom).min(rect.bottom()));
+                let bar = Rect::from_min_max(Pos2::new(bar_left, y0), Pos2::new(rect.right(), y1.max(y0 + 1.0)));
+                let color = region_color(&region, self.color_by, &self.theme);
+                let fill = Color32::from_rgb(color[0], color[1], color[2]);
+                painter.rect_filled(bar, 0.0, fill);
+
+                if y1 - y0 >= 12.0 {
+                    painter.text(Pos2::new(rect.left() + 4.0, y0), Align2::LEFT_TOP, format!("{:#x} ({})", region.start, format_size(region.size)), TextStyle::Small, Color32::GRAY);
+                    if let Some(name) = display_name(&region) {
+                        let text_color = if luminance(color) > 128.0 { Color32::BLACK } else { Color32::WHITE };
+                        painter.text(Pos2::new(bar_left + 3.0, y0 + 1.0), Align2::LEFT_TOP, name, TextStyle::Small, text_color);
+                    }
+                }
+
+                if response.hover_pos().is_some_and(|pointer| pointer.y >= y0 && pointer.y < y1) {
+                    hovered = Some(region);
+                }
+            }
+
+            if let Some(region) = hovered {
+                egui::show_tooltip_at_pointer(ctx, egui::Id::new("region"), |ui| {
+                    ui.label(format!("{:#x}-{:#x}", region.start, region.end));
+                    ui.label(format!("{} ({:#x})", format_size(region.size), region.size));
+                    ui.label(format!("{} {}", region.attributes.perms(), region_category(&region)));
+                    if let Some(path) = &region.file_name {
+                        ui.label(path);
+                    }
+                    if let Some(smaps) = &region.smaps {
+                        ui.label(format!("RSS {} PSS {} swap {}", format_size(smaps.rss), format_size(smaps.pss), format_size(smaps.swap)));
+                        for flag in smaps.interesting_flags() {
+                            ui.label(format!("{}: {}", flag, describe_flag(flag).unwrap_or("unknown flag")));
+                        }
+                    }
+                    if let Some((_, bytes)) = self.previews.iter().find(|(start, _)| *start == region.start && region.attributes.allocated) {
+                        ui.monospace(preview::hexdump(bytes).join("\n"));
+                    }
+                });
+            }
+
+            if view_end - view_start < 1.0 {
+                painter.text(rect.right_top() + Vec2::new(-4.0, 4.0), Align2::RIGHT_TOP, format!("zoom x{:.0}", 1.0 / (view_end - view_start)), TextStyle::Small, Color32::RED);
+            }
+        });
+    }
+}
+
+pub fn run(app: MemoryMapApp) -> ! {
+    let options = epi::NativeOptions { initial_window_size: Some(Vec2::new(700.0, 900.0)), ..Default::default() };
+    egui_glium::run(Box::new(app), &options)
+}
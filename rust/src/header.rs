@@ -0,0 +1,149 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adb::AdbTarget;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture;
+use crate::cgroup::CgroupMemory;
+use crate::meminfo::SystemMemory;
+use crate::namespace::Namespace;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::snapshot::{self, Snapshot};
+use crate::status::ProcessStatus;
+use crate::{format_count, format_size};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{sanitizer, MemoryRegion};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// What a shared image needs to say about where it came from. Totals are
+// taken from the capture, before any grouping or truncation.
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub process: Option<String>,
+    pub pid: u32,
+    pub timestamp: u64,
+    pub hostname: String,
+    pub kernel: String,
+    pub mapped: usize,
+    pub rss: Option<usize>,
+    pub regions: usize,
+    // memfd, shared anonymous and System V mappings together.
+    pub shared_memory: usize,
+    // Sanitizer shadow, left out of mapped.
+    pub shadow: usize,
+    // Only read from live processes, and only when asked for.
+    pub cgroup: Option<CgroupMemory>,
+    pub namespace: Option<Namespace>,
+    // Locked bytes with --locked, and RLIMIT_MEMLOCK when it was read.
+    pub locked: Option<(usize, Option<usize>)>,
+    // /proc/meminfo at capture time with --meminfo, on a line of its own.
+    pub system: Option<SystemMemory>,
+    // Peaks, threads and context switches with --proc-status.
+    pub status: Option<ProcessStatus>,
+}
+
+impl Header {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture(pid: u32, adb: Option<&AdbTarget>, memory_regions: &[MemoryRegion]) -> Self {
+        let comm = match adb {
+            Some(adb) => adb.read_proc_file(pid, "comm"),
+            None => capture::read_proc_file(pid, "comm"),
+        };
+        let (hostname, kernel) = snapshot::host_info(adb);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut header = Header::new(comm.ok().map(|comm| comm.trim().to_string()), pid, timestamp, hostname, kernel, memory_regions);
+        if adb.is_none() {
+            header.namespace = Namespace::detect(pid);
+        }
+        header
+    }
+
+    // Snapshots don't record the process name.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Header::new(None, snapshot.pid, snapshot.timestamp, snapshot.hostname.clone(), snapshot.kernel.clone(), &snapshot.regions)
+    }
+
+    // Sources other than processes are named after themselves and have no
+    // host to describe.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_source(name: &str, pid: u32, memory_regions: &[MemoryRegion]) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Header::new(Some(name.to_string()), pid, timestamp, String::new(), String::new(), memory_regions)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(process: Option<String>, pid: u32, timestamp: u64, hostname: String, kernel: String, memory_regions: &[MemoryRegion]) -> Self {
+        let mapped = memory_regions.iter().filter(|r| r.attributes.allocated && !sanitizer::is_shadow(r)).map(|r| r.size).sum();
+        let shadow = memory_regions.iter().filter(|r| sanitizer::is_shadow(r)).map(|r| r.size).sum();
+        let with_smaps: Vec<usize> = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).collect();
+        let rss = if with_smaps.is_empty() { None } else { Some(with_smaps.iter().sum()) };
+        let regions = memory_regions.iter().filter(|r| r.attributes.allocated).count();
+        let shared_memory = memory_regions.iter().filter(|r| r.shared_memory_kind().is_some()).map(|r| r.size).sum();
+        Header { process: process.filter(|name| !name.is_empty()), pid, timestamp, hostname, kernel, mapped, rss, regions, shared_memory, shadow, cgroup: None, namespace: None, locked: None, system: None, status: None }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let pid = match &self.namespace {
+            Some(namespace) => format!("pid {}; {}", self.pid, namespace.label()),
+            None => format!("pid {}", self.pid),
+        };
+        let title = match &self.process {
+            Some(process) if self.pid == 0 => process.clone(),
+            Some(process) => format!("{} ({})", process, pid),
+            None => pid,
+        };
+        let mut captured = format!("captured {}", format_timestamp(self.timestamp));
+        if !self.hostname.is_empty() {
+            captured.push_str(&format!(" on {}", self.hostname));
+        }
+        if !self.kernel.is_empty() {
+            captured.push_str(&format!(", kernel {}", self.kernel));
+        }
+        let mut totals = format!("{} mapped", format_size(self.mapped));
+        if self.shadow > 0 {
+            totals.push_str(&format!(" (and {} sanitizer shadow)", format_size(self.shadow)));
+        }
+        if let Some(rss) = self.rss {
+            totals.push_str(&format!(", {} RSS", format_size(rss)));
+        }
+        totals.push_str(&format!(", {} regions", format_count(self.regions)));
+        if self.shared_memory > 0 {
+            totals.push_str(&format!(", {} shared memory", format_size(self.shared_memory)));
+        }
+        if let Some(cgroup) = &self.cgroup {
+            match cgroup.limit {
+                Some(limit) => totals.push_str(&format!(", cgroup {} of {} ({:.0}%)", format_size(cgroup.current), format_size(limit), cgroup.fraction().unwrap() * 100.0)),
+                None => totals.push_str(&format!(", cgroup {} (no limit)", format_size(cgroup.current))),
+            }
+        }
+        match self.locked {
+            Some((locked, Some(limit))) => totals.push_str(&format!(", {} locked of {} RLIMIT_MEMLOCK", format_size(locked), format_size(limit))),
+            Some((locked, None)) => totals.push_str(&format!(", {} locked", format_size(locked))),
+            None => {}
+        }
+        let mut lines = vec![title, captured, totals];
+        if let Some(status) = &self.status {
+            lines.push(status.label());
+        }
+        if let Some(system) = &self.system {
+            lines.push(system.label());
+        }
+        lines
+    }
+}
+
+// Seconds since the epoch as a UTC date and time, using the days-to-civil
+// conversion so no calendar crate is needed.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = ((timestamp / 86400) as i64, timestamp % 86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
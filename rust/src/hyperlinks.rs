@@ -0,0 +1,99 @@
+use crate::{bar_boxes, describe_region, display_name, format_size, preview, MemoryRegion, RenderOptions};
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// A region's id, its start as the address one would paste: "0x7f3a0000".
+fn id(region: &MemoryRegion) -> String {
+    format!("{:#x}", region.start)
+}
+
+// A transparent box over every mapped region's bar, on top of what plotters
+// drew, with the region for its tooltip (and the first bytes under
+// --hex-preview). In the HTML page the box links to the region's row and
+// is what the row links back to; in a bare SVG it carries the address, so
+// memory_map.svg#0x7f3a0000 points at the bar.
+pub fn anchor_svg(svg: String, memory_regions: &[MemoryRegion], size: (u32, u32), options: &RenderOptions, table: bool) -> String {
+    let Some(end) = svg.rfind("</svg>") else { return svg };
+    let mut boxes = String::from("<style>a rect:hover, rect:target { fill: rgba(255, 200, 0, 0.4); }</style>\n");
+    for (region, ((left, top), (right, bottom))) in memory_regions.iter().zip(bar_boxes(memory_regions, size, options)) {
+        if !region.attributes.allocated {
+            continue;
+        }
+        let preview = options.previews.iter().find(|(start, _)| *start == region.start).map(|(_, bytes)| preview::hexdump(bytes)).unwrap_or_default();
+        let title = std::iter::once(describe_region(region)).chain(preview).collect::<Vec<_>>().join("\n");
+        let rect = format!("x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"transparent\"><title>{}</title></rect>", left, top, right - left, (bottom - top).max(1), escape(&title));
+        if table {
+            boxes.push_str(&format!("<a href=\"#{0}\"><rect id=\"map-{0}\" {1}</a>\n", id(region), rect));
+        } else {
+            boxes.push_str(&format!("<rect id=\"{}\" {}\n", id(region), rect));
+        }
+    }
+    format!("{}{}{}", &svg[..end], boxes, &svg[end..])
+}
+
+// Deep links name any address, not only a region's start: the row of the
+// region holding it takes over the fragment.
+const SCRIPT: &str = r##"function follow() {
+  const hash = decodeURIComponent(location.hash.slice(1));
+  if (!/^0x[0-9a-f]+$/i.test(hash) || document.getElementById(hash)) return;
+  const address = BigInt(hash);
+  for (const row of document.querySelectorAll("tr[data-end]")) {
+    if (BigInt(row.id) <= address && address < BigInt(row.dataset.end)) {
+      location.replace("#" + row.id);
+      return;
+    }
+  }
+}
+window.addEventListener("hashchange", follow);
+follow();"##;
+
+// The map with its bars linked both ways to a table of every region, one
+// row each with the region's start as its id.
+pub fn html(svg: String, memory_regions: &[MemoryRegion], size: (u32, u32), options: &RenderOptions, title: &str) -> String {
+    let svg = anchor_svg(svg, memory_regions, size, options, true);
+    let mut rows = String::new();
+    for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+        let rss = region.smaps.as_ref().map(|smaps| format_size(smaps.rss)).unwrap_or_default();
+        let path = region.file_name.clone().or_else(|| display_name(region)).unwrap_or_default();
+        rows.push_str(&format!(
+            "<tr id=\"{0}\" data-end=\"{1:#x}\"><td><a href=\"#map-{0}\">{0}</a></td><td>{1:#x}</td><td>{2}</td><td>{3}</td><td>{4}</td><td>{5}</td></tr>\n",
+            id(region),
+            region.end,
+            format_size(region.size),
+            region.attributes.perms(),
+            rss,
+            escape(&path)
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 1em; }}
+table {{ border-collapse: collapse; font-size: 13px; }}
+th, td {{ padding: 1px 8px; text-align: left; }}
+td:nth-child(-n+3) {{ font-family: monospace; }}
+tr:target {{ background: #ffe680; }}
+</style>
+</head>
+<body>
+<h3>{title}</h3>
+{svg}
+<table>
+<tr><th>start</th><th>end</th><th>size</th><th>perms</th><th>RSS</th><th>mapping</th></tr>
+{rows}</table>
+<script>
+{script}
+</script>
+</body>
+</html>
+"#,
+        title = escape(title),
+        svg = svg,
+        rows = rows,
+        script = SCRIPT
+    )
+}
@@ -0,0 +1,126 @@
+use crate::{display_name, fit_text, format_size, luminance, region_color, MemoryRegion, RenderOptions};
+use image::Rgb;
+use plotters::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const WIDTH: u32 = 1200;
+const ROW_HEIGHT: i32 = 20;
+const TITLE_HEIGHT: i32 = 30;
+
+// A path component, a file, or one of its segments, with the bytes under
+// it. Only segments have a color of their own, their region's.
+pub struct Node {
+    name: String,
+    pub bytes: usize,
+    children: Vec<Node>,
+    color: Option<Rgb<u8>>,
+}
+
+impl Node {
+    fn new(name: &str) -> Self {
+        Node { name: name.to_string(), bytes: 0, children: Vec::new(), color: None }
+    }
+
+    fn child(&mut self, name: &str) -> &mut Node {
+        let index = match self.children.iter().position(|child| child.name == name) {
+            Some(index) => index,
+            None => {
+                self.children.push(Node::new(name));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index]
+    }
+
+    fn depth(&self) -> usize {
+        1 + self.children.iter().map(Node::depth).max().unwrap_or(0)
+    }
+
+    // Largest first, so what takes the memory is on the left.
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+        self.children.iter_mut().for_each(Node::sort);
+    }
+}
+
+// Where a region sits in the hierarchy: the directories of its file, the
+// file and the segment by permissions, or for memory without a file its
+// name ([heap], anon, a thread's stack) and permissions.
+fn components(region: &MemoryRegion) -> Vec<String> {
+    let segment = region.attributes.perms();
+    match region.file_name.as_deref() {
+        Some(path) if path.starts_with('/') && region.thread_id.is_none() => path.trim_start_matches('/').split('/').map(str::to_string).chain(std::iter::once(segment)).collect(),
+        _ => vec![display_name(region).unwrap_or_else(|| "anon".to_string()), segment],
+    }
+}
+
+pub fn tree(memory_regions: &[MemoryRegion], value: impl Fn(&MemoryRegion) -> usize, root: &str, options: &RenderOptions) -> Node {
+    let mut tree = Node::new(root);
+    for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+        let bytes = value(region);
+        if bytes == 0 {
+            continue;
+        }
+        tree.bytes += bytes;
+        let mut node = &mut tree;
+        for component in components(region) {
+            node = node.child(&component);
+            node.bytes += bytes;
+        }
+        node.color.get_or_insert(region_color(region, options.color_by, &options.theme));
+    }
+    tree.sort();
+    tree
+}
+
+// Directories and files in the warm hues of a flamegraph, steady for a
+// name from one run to the next; segments in their permission colors.
+fn color(node: &Node) -> Rgb<u8> {
+    if let Some(color) = node.color {
+        return color;
+    }
+    let mut hasher = DefaultHasher::new();
+    node.name.hash(&mut hasher);
+    let hash = hasher.finish();
+    Rgb([205 + (hash % 50) as u8, 90 + (hash >> 8) as u8 % 130, 40 + (hash >> 16) as u8 % 55])
+}
+
+fn draw_node<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, node: &Node, (left, right): (f64, f64), row: i32, font: &FontDesc) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let y = TITLE_HEIGHT + row * ROW_HEIGHT;
+    let (x0, x1) = (left.round() as i32, right.round() as i32);
+    if x1 - x0 < 1 {
+        return Ok(());
+    }
+    let fill = color(node);
+    root.draw(&Rectangle::new([(x0, y), (x1, y + ROW_HEIGHT - 1)], RGBColor(fill[0], fill[1], fill[2]).filled()))?;
+    if x1 - x0 >= 24 {
+        if let Some(label) = fit_text(root, &format!("{} ({})", node.name, format_size(node.bytes)), font, x1 - x0 - 6)? {
+            let ink = if luminance(fill) > 128.0 { &BLACK } else { &WHITE };
+            root.draw(&Text::new(label, (x0 + 3, y + 4), font.color(ink)))?;
+        }
+    }
+    let mut x = left;
+    for child in &node.children {
+        let width = (right - left) * child.bytes as f64 / node.bytes.max(1) as f64;
+        draw_node(root, child, (x, x + width), row + 1, font)?;
+        x += width;
+    }
+    Ok(())
+}
+
+// The tree top down, each node as wide as its share of its parent.
+pub fn draw_icicle(tree: &Node, title: &str, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let height = TITLE_HEIGHT + tree.depth() as i32 * ROW_HEIGHT + 10;
+    let root = BitMapBackend::new(path, (WIDTH, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    root.draw(&Text::new(title.to_string(), (10, 8), FontDesc::new(FontFamily::SansSerif, 14.0, FontStyle::Bold).color(&ink)))?;
+    let font = FontDesc::new(FontFamily::SansSerif, 11.0, FontStyle::Normal);
+    draw_node(&root, tree, (10.0, WIDTH as f64 - 10.0), 0, &font)?;
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use crate::{format_size, MemoryRegion};
+use serde_json::Value;
+
+// A small size class: its slabs hold nregs regions of size bytes each.
+pub struct Bin {
+    pub size: u64,
+    pub regions: u64,
+    pub capacity: u64,
+    pub slabs: u64,
+}
+
+impl Bin {
+    pub fn utilization(&self) -> f64 {
+        if self.capacity == 0 { 0.0 } else { self.regions as f64 / self.capacity as f64 }
+    }
+}
+
+#[derive(Default)]
+pub struct Stats {
+    pub version: Option<String>,
+    pub allocated: u64,
+    pub active: u64,
+    pub metadata: u64,
+    pub resident: u64,
+    pub mapped: u64,
+    pub retained: u64,
+    pub bins: Vec<Bin>,
+    // Large size classes with live extents, as (size, extents).
+    pub large: Vec<(u64, u64)>,
+}
+
+impl Stats {
+    // Regions in use over the regions the current slabs could hold.
+    pub fn slab_utilization(&self) -> Option<f64> {
+        let capacity: u64 = self.bins.iter().map(|bin| bin.capacity).sum();
+        (capacity > 0).then(|| self.bins.iter().map(|bin| bin.regions).sum::<u64>() as f64 / capacity as f64)
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = format!("jemalloc: {} allocated, {} active of {} mapped", format_size(self.allocated as usize), format_size(self.active as usize), format_size(self.mapped as usize));
+        if let Some(utilization) = self.slab_utilization() {
+            label += &format!(", slabs {:.0}% used", utilization * 100.0);
+        }
+        label
+    }
+}
+
+fn number(value: &Value, name: &str) -> u64 {
+    value.get(name).and_then(Value::as_u64).unwrap_or(0)
+}
+
+// malloc_stats_print() with the "J" option, e.g. from
+// MALLOC_CONF=stats_print:true,stats_print_opts:J at exit. Bins and large
+// extents come from the merged arena, which the "m" option leaves out.
+fn parse_json(json: &Value) -> Stats {
+    let json = &json["jemalloc"];
+    let totals = &json["stats"];
+    let merged = &json["stats.arenas"]["merged"];
+    let classes = json["arenas"]["bin"].as_array().map_or(&[][..], Vec::as_slice);
+    let bins = merged["bins"].as_array().map_or(&[][..], Vec::as_slice);
+    let large_classes = json["arenas"]["lextent"].as_array().map_or(&[][..], Vec::as_slice);
+    let large = merged["lextents"].as_array().map_or(&[][..], Vec::as_slice);
+    Stats {
+        version: json["version"].as_str().map(str::to_string),
+        allocated: number(totals, "allocated"),
+        active: number(totals, "active"),
+        metadata: number(totals, "metadata"),
+        resident: number(totals, "resident"),
+        mapped: number(totals, "mapped"),
+        retained: number(totals, "retained"),
+        bins: classes
+            .iter()
+            .zip(bins)
+            .map(|(class, bin)| Bin { size: number(class, "size"), regions: number(bin, "curregs"), capacity: number(bin, "curslabs") * number(class, "nregs"), slabs: number(bin, "curslabs") })
+            .filter(|bin| bin.slabs > 0)
+            .collect(),
+        large: large_classes.iter().zip(large).map(|(class, extents)| (number(class, "size"), number(extents, "curlextents"))).filter(|(_, count)| *count > 0).collect(),
+    }
+}
+
+// The plain text report only gives totals here, from its
+// "Allocated: N, active: N, metadata: N ..." line.
+fn parse_text(text: &str) -> Option<Stats> {
+    let line = text.lines().find(|line| line.starts_with("Allocated: "))?;
+    let mut stats = Stats { version: text.lines().find_map(|line| line.strip_prefix("Version: ")).map(|version| version.trim_matches('"').to_string()), ..Stats::default() };
+    for field in line.split(", ") {
+        let (name, value) = field.split_once(": ")?;
+        let value = value.split_whitespace().next()?.parse().ok()?;
+        match name.to_lowercase().as_str() {
+            "allocated" => stats.allocated = value,
+            "active" => stats.active = value,
+            "metadata" => stats.metadata = value,
+            "resident" => stats.resident = value,
+            "mapped" => stats.mapped = value,
+            "retained" => stats.retained = value,
+            _ => {}
+        }
+    }
+    Some(stats)
+}
+
+pub fn parse(text: &str) -> Result<Stats, String> {
+    match serde_json::from_str::<Value>(text) {
+        Ok(json) if json.get("jemalloc").is_some() => Ok(parse_json(&json)),
+        Ok(_) => Err("JSON without a \"jemalloc\" object isn't jemalloc stats".to_string()),
+        Err(_) => parse_text(text).ok_or_else(|| "Not jemalloc stats: expected malloc_stats_print() output".to_string()),
+    }
+}
+
+// jemalloc's extents are unnamed anonymous mappings and its stats carry no
+// addresses, so the summary goes on the largest private writable one.
+pub fn anchor(memory_regions: &[MemoryRegion]) -> Option<usize> {
+    memory_regions
+        .iter()
+        .filter(|region| region.attributes.allocated && region.file_name.is_none() && region.attributes.writable && !region.attributes.shared)
+        .max_by_key(|region| region.end - region.start)
+        .map(|region| region.start)
+}
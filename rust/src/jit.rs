@@ -0,0 +1,51 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{format_count, format_size};
+use crate::MemoryRegion;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+pub const JIT_COLOR: [u8; 3] = [240, 60, 170];
+
+// Executable memory no file backs is code some runtime wrote itself: V8's
+// code space, the JVM's code cache, ART's [anon:dalvik-jit-code-cache],
+// LuaJIT's mcode areas. Shared mappings are left to their own kinds, and
+// the kernel's [vdso] and [vsyscall] have names of their own.
+pub fn is_jit(region: &MemoryRegion) -> bool {
+    region.attributes.allocated
+        && region.attributes.executable
+        && !region.attributes.shared
+        && !region.guard
+        && region.thread_id.is_none()
+        && (region.file_name.is_none() || region.anon_name().is_some())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn summary(memory_regions: &[MemoryRegion]) -> Option<String> {
+    let jit: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| is_jit(region)).collect();
+    let bytes: usize = jit.iter().map(|region| region.size).sum();
+    let count: usize = jit.iter().map(|region| region.mappings).sum();
+    (count > 0).then(|| format!("JIT code: {} in {} mappings", format_size(bytes), format_count(count)))
+}
+
+// The JIT mappings, to compare against the next sample.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ranges(memory_regions: &[MemoryRegion]) -> Vec<(usize, usize)> {
+    memory_regions.iter().filter(|region| is_jit(region)).map(|region| (region.start, region.end)).collect()
+}
+
+// Bytes of JIT code mapped or unmapped per second between two samples. A
+// code space that grew or shrank in place counts the difference; one that
+// moved counts both its old and new size.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn churn(previous: &[(usize, usize)], current: &[(usize, usize)], elapsed: Duration) -> usize {
+    let changed = |from: &[(usize, usize)], to: &[(usize, usize)]| -> usize {
+        from.iter()
+            .map(|(start, end)| match to.iter().find(|(other_start, _)| other_start == start) {
+                Some((_, other_end)) => end.saturating_sub(*other_end),
+                None => end - start,
+            })
+            .sum()
+    };
+    let bytes = changed(previous, current) + changed(current, previous);
+    (bytes as f64 / elapsed.as_secs_f64().max(1e-3)) as usize
+}
@@ -0,0 +1,113 @@
+use crate::symbols::parse_address;
+use crate::{MemoryAttributes, MemoryRegion};
+use std::fs;
+use std::path::Path;
+
+pub struct Module {
+    pub name: String,
+    pub size: usize,
+    pub address: usize,
+    pub state: String,
+}
+
+// Lines look like "nf_tables 307200 3 nft_chain_nat, Live 0xffffffffc0a00000".
+// Without CAP_SYSLOG the kernel prints every address as zero.
+pub fn read_modules(path: &str) -> Result<Vec<Module>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut modules = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let size = fields[1].parse().map_err(|_| format!("Invalid module size in {}: {}", path, line))?;
+        let address = parse_address(fields[5])?;
+        modules.push(Module { name: fields[0].to_string(), size, address, state: fields[4].to_string() });
+    }
+    if !modules.is_empty() && modules.iter().all(|module| module.address == 0) {
+        return Err(format!("{} lists no addresses; run as root or lower kernel.kptr_restrict", path));
+    }
+    modules.sort_by_key(|module| module.address);
+    Ok(modules)
+}
+
+fn module_region(start: usize, end: usize, name: String, (readable, writable, executable): (bool, bool, bool)) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end,
+        size: end - start,
+        attributes: MemoryAttributes { readable, writable, executable, shared: false, allocated: true },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// The kernel doesn't export section permissions, but the names follow
+// the usual conventions.
+fn section_perms(section: &str) -> (bool, bool, bool) {
+    if section.contains("text") {
+        (true, false, true)
+    } else if section.contains("data") || section.contains("bss") || section.starts_with("__param") {
+        (true, true, false)
+    } else {
+        (true, false, false)
+    }
+}
+
+// Each section runs to the next one's start. Newer kernels place text,
+// data and init in separate allocations, so a section can start outside
+// the module's own range; those are shown a page long.
+fn section_regions(module: &Module, sections_root: &Path) -> Option<Vec<MemoryRegion>> {
+    let entries = fs::read_dir(sections_root.join(&module.name).join("sections")).ok()?;
+    let mut sections: Vec<(usize, String)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_str()?.to_string();
+            let address = parse_address(&fs::read_to_string(entry.path()).ok()?).ok()?;
+            (address != 0).then_some((address, name))
+        })
+        .collect();
+    if sections.is_empty() {
+        return None;
+    }
+    sections.sort();
+    let module_end = module.address + module.size;
+    let mut regions = Vec::new();
+    for (i, (start, section)) in sections.iter().enumerate() {
+        let limit = if *start >= module.address && *start < module_end { module_end } else { start + 4096 };
+        let end = sections.get(i + 1).map_or(limit, |(next, _)| (*next).min(limit));
+        if end > *start {
+            regions.push(module_region(*start, end, format!("{}:{}", module.name, section), section_perms(section)));
+        }
+    }
+    Some(regions)
+}
+
+// Modules are drawn as executable code unless their sections are read,
+// which splits them into text, data and read-only parts.
+pub fn module_regions(modules: &[Module], sections_root: Option<&Path>) -> Vec<MemoryRegion> {
+    let mut regions = Vec::new();
+    for module in modules.iter().filter(|module| module.size > 0) {
+        match sections_root.and_then(|root| section_regions(module, root)) {
+            Some(sections) => regions.extend(sections),
+            None => regions.push(module_region(module.address, module.address + module.size, module.name.clone(), (true, false, true))),
+        }
+    }
+    regions.sort_by_key(|region| region.start);
+    // Sections from separate allocations can land inside a neighbour.
+    let mut cursor = 0;
+    regions.retain(|region| {
+        let keep = region.start >= cursor;
+        if keep {
+            cursor = region.end;
+        }
+        keep
+    });
+    regions
+}
@@ -0,0 +1,54 @@
+use crate::{pagemap, MemoryRegion};
+use std::fs;
+
+// From include/uapi/linux/kernel-page-flags.h.
+const KPF_KSM: u64 = 1 << 21;
+
+// KSM only merges private anonymous pages, so shared regions and those
+// with nothing resident are skipped without reading their pagemap.
+fn candidate(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && !region.attributes.shared && region.smaps.as_ref().is_none_or(|smaps| smaps.rss > 0)
+}
+
+// The address ranges of pages KSM has merged, with runs of adjacent pages
+// joined.
+pub fn merged_ranges(pid: u32, memory_regions: &[MemoryRegion], page_size: usize) -> Result<Vec<(usize, usize)>, String> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    pagemap::walk(pid, memory_regions.iter().filter(|region| candidate(region)), page_size, |address, flags| {
+        if flags & KPF_KSM == 0 {
+            return;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if *end == address => *end += page_size,
+            _ => ranges.push((address, address + page_size)),
+        }
+    })?;
+    Ok(ranges)
+}
+
+fn read_counter(name: &str) -> Option<u64> {
+    fs::read_to_string(format!("/sys/kernel/mm/ksm/{}", name)).ok()?.trim().parse().ok()
+}
+
+// Machine-wide: pages_shared are the kept copies, pages_sharing the
+// mappings of them that would otherwise each be a page of their own.
+pub struct SystemStats {
+    pub running: bool,
+    pub pages_shared: u64,
+    pub pages_sharing: u64,
+    // Savings minus the rmap items KSM spends tracking pages, since 6.1.
+    pub general_profit: Option<i64>,
+}
+
+pub fn system_stats() -> Option<SystemStats> {
+    let general_profit = fs::read_to_string("/sys/kernel/mm/ksm/general_profit").ok().and_then(|profit| profit.trim().parse().ok());
+    Some(SystemStats { running: read_counter("run")? == 1, pages_shared: read_counter("pages_shared")?, pages_sharing: read_counter("pages_sharing")?, general_profit })
+}
+
+// ksm_merging_pages and ksm_process_profit from /proc/PID/ksm_stat, on
+// kernels that have it.
+pub fn process_stats(pid: u32) -> Option<(u64, Option<i64>)> {
+    let text = fs::read_to_string(format!("/proc/{}/ksm_stat", pid)).ok()?;
+    let value = |name: &str| text.lines().find_map(|line| line.strip_prefix(name)).and_then(|value| value.trim().parse::<i64>().ok());
+    Some((value("ksm_merging_pages")? as u64, value("ksm_process_profit")))
+}
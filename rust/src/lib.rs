@@ -0,0 +1,4015 @@
+#[cfg(not(target_arch = "wasm32"))]
+use clap::CommandFactory;
+use cli::DrawArgs;
+#[cfg(not(target_arch = "wasm32"))]
+use cli::{ExportArgs, Mode};
+use image::Rgb;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod adb;
+#[cfg(not(target_arch = "wasm32"))]
+mod agent;
+#[cfg(not(target_arch = "wasm32"))]
+mod allocsites;
+#[cfg(not(target_arch = "wasm32"))]
+mod animate;
+#[cfg(not(target_arch = "wasm32"))]
+mod aslr;
+#[cfg(not(target_arch = "wasm32"))]
+mod assertions;
+mod audit;
+#[cfg(not(target_arch = "wasm32"))]
+mod binary;
+#[cfg(not(target_arch = "wasm32"))]
+mod buddy;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+#[cfg(feature = "capi")]
+mod capi;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod cgroup;
+mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+mod clipboard;
+#[cfg(not(target_arch = "wasm32"))]
+mod completions;
+#[cfg(not(target_arch = "wasm32"))]
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+mod container;
+mod context;
+#[cfg(not(target_arch = "wasm32"))]
+mod cortexm;
+#[cfg(feature = "debuginfod")]
+mod debuginfod;
+#[cfg(not(target_arch = "wasm32"))]
+mod diff;
+mod emphasis;
+mod entropy;
+#[cfg(not(target_arch = "wasm32"))]
+mod espidf;
+#[cfg(not(target_arch = "wasm32"))]
+mod filter;
+#[cfg(not(target_arch = "wasm32"))]
+mod firmware;
+mod fragmentation;
+#[cfg(not(target_arch = "wasm32"))]
+mod gdb;
+#[cfg(not(target_arch = "wasm32"))]
+mod gdbremote;
+#[cfg(not(target_arch = "wasm32"))]
+mod gomem;
+#[cfg(not(target_arch = "wasm32"))]
+mod graph;
+#[cfg(not(target_arch = "wasm32"))]
+mod gpu;
+mod grouping;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(not(target_arch = "wasm32"))]
+mod guards;
+mod header;
+#[cfg(not(target_arch = "wasm32"))]
+mod hyperlinks;
+#[cfg(not(target_arch = "wasm32"))]
+mod icicle;
+#[cfg(not(target_arch = "wasm32"))]
+mod jemalloc;
+mod jit;
+#[cfg(not(target_arch = "wasm32"))]
+mod kmodules;
+#[cfg(not(target_arch = "wasm32"))]
+mod libraries;
+#[cfg(not(target_arch = "wasm32"))]
+mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+mod ksm;
+#[cfg(not(target_arch = "wasm32"))]
+mod massif;
+#[cfg(not(target_arch = "wasm32"))]
+mod mallocinfo;
+mod meminfo;
+#[cfg(not(target_arch = "wasm32"))]
+mod metrics;
+mod namespace;
+#[cfg(not(target_arch = "wasm32"))]
+mod nmt;
+#[cfg(not(target_arch = "wasm32"))]
+mod numa;
+#[cfg(not(target_arch = "wasm32"))]
+mod overview;
+#[cfg(not(target_arch = "wasm32"))]
+mod pattern;
+#[cfg(not(target_arch = "wasm32"))]
+mod pagemap;
+#[cfg(not(target_arch = "wasm32"))]
+mod pdf;
+#[cfg(not(target_arch = "wasm32"))]
+mod perf;
+#[cfg(not(target_arch = "wasm32"))]
+mod pageowner;
+#[cfg(not(target_arch = "wasm32"))]
+mod physical;
+#[cfg(not(target_arch = "wasm32"))]
+mod preview;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(not(target_arch = "wasm32"))]
+mod qemu;
+#[cfg(not(target_arch = "wasm32"))]
+mod ranked;
+#[cfg(not(target_arch = "wasm32"))]
+mod recorder;
+mod renderer;
+#[cfg(not(target_arch = "wasm32"))]
+mod replay;
+mod report;
+#[cfg(not(target_arch = "wasm32"))]
+mod rollup;
+mod sanitizer;
+mod scale;
+#[cfg(not(target_arch = "wasm32"))]
+mod serve;
+#[cfg(not(target_arch = "wasm32"))]
+mod shm;
+#[cfg(not(target_arch = "wasm32"))]
+mod sizes;
+#[cfg(not(target_arch = "wasm32"))]
+mod slab;
+mod smaps;
+#[cfg(not(target_arch = "wasm32"))]
+mod snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+mod source;
+mod stacks;
+mod status;
+#[cfg(not(target_arch = "wasm32"))]
+mod summary;
+#[cfg(not(target_arch = "wasm32"))]
+mod symbols;
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal;
+#[cfg(not(target_arch = "wasm32"))]
+mod text;
+mod theme;
+mod thp;
+#[cfg(not(target_arch = "wasm32"))]
+mod threads;
+#[cfg(not(target_arch = "wasm32"))]
+mod timeline;
+#[cfg(not(target_arch = "wasm32"))]
+mod uboot;
+#[cfg(feature = "self-update")]
+mod update;
+#[cfg(not(target_arch = "wasm32"))]
+mod utilization;
+#[cfg(not(target_arch = "wasm32"))]
+mod v8heap;
+mod validate;
+#[cfg(not(target_arch = "wasm32"))]
+mod viewer;
+#[cfg(not(target_arch = "wasm32"))]
+mod vmalloc;
+#[cfg(not(target_arch = "wasm32"))]
+mod vmcore;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+mod wsl;
+#[cfg(not(target_arch = "wasm32"))]
+mod zoneinfo;
+
+#[cfg(not(target_arch = "wasm32"))]
+use adb::AdbTarget;
+pub use smaps::SmapsInfo;
+#[cfg(not(target_arch = "wasm32"))]
+pub use source::{register_source, Capabilities, MemorySource, SourceFactory};
+use emphasis::Emphasis;
+use scale::Scale;
+use theme::Theme;
+pub use validate::{ParseWarning, Warnings};
+
+const LEGEND_WIDTH: u32 = 150;
+#[cfg(not(target_arch = "wasm32"))]
+const PANEL_WIDTH: u32 = 220;
+#[cfg(not(target_arch = "wasm32"))]
+const TEXT_LINES: u32 = 80;
+const BREAK_HEIGHT: i32 = 20;
+const LEGEND_COLUMN_WIDTH: u32 = 140;
+const HEADER_HEIGHT: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorBy {
+    Permissions,
+    Swap,
+    File,
+    Rss,
+}
+
+impl FromStr for ColorBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permissions" => Ok(ColorBy::Permissions),
+            "swap" => Ok(ColorBy::Swap),
+            "file" => Ok(ColorBy::File),
+            "rss" => Ok(ColorBy::Rss),
+            _ => Err(format!("Unknown color mode: {}", s)),
+        }
+    }
+}
+
+impl ColorBy {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn needs_smaps(self) -> bool {
+        matches!(self, ColorBy::Swap | ColorBy::Rss)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SizeMetric {
+    Virtual,
+    Rss,
+    Pss,
+    Swap,
+    Dirty,
+}
+
+impl FromStr for SizeMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "virtual" => Ok(SizeMetric::Virtual),
+            "rss" => Ok(SizeMetric::Rss),
+            "pss" => Ok(SizeMetric::Pss),
+            "swap" => Ok(SizeMetric::Swap),
+            "dirty" => Ok(SizeMetric::Dirty),
+            _ => Err(format!("Unknown size metric: {}", s)),
+        }
+    }
+}
+
+impl SizeMetric {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn needs_smaps(self) -> bool {
+        self != SizeMetric::Virtual
+    }
+
+    // Gaps and regions without smaps data have no resident cost.
+    fn value(self, region: &MemoryRegion) -> usize {
+        if self == SizeMetric::Virtual {
+            return region.size;
+        }
+        match &region.smaps {
+            Some(smaps) => match self {
+                SizeMetric::Virtual => region.size,
+                SizeMetric::Rss => smaps.rss,
+                SizeMetric::Pss => smaps.pss,
+                SizeMetric::Swap => smaps.swap,
+                SizeMetric::Dirty => smaps.shared_dirty + smaps.private_dirty,
+            },
+            None => 0,
+        }
+    }
+}
+
+fn region_weight(region: &MemoryRegion, metric: SizeMetric, scale: Scale) -> f64 {
+    scale.weight(metric.value(region))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Png,
+    Terminal(terminal::Protocol),
+    Text { unicode: bool },
+    Pdf,
+    Svg,
+    Html,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(OutputFormat::Png),
+            "sixel" => Ok(OutputFormat::Terminal(terminal::Protocol::Sixel)),
+            "kitty" => Ok(OutputFormat::Terminal(terminal::Protocol::Kitty)),
+            "iterm" => Ok(OutputFormat::Terminal(terminal::Protocol::Iterm)),
+            "pdf" => Ok(OutputFormat::Pdf),
+            "svg" => Ok(OutputFormat::Svg),
+            "html" => Ok(OutputFormat::Html),
+            "text" => Ok(OutputFormat::Text { unicode: true }),
+            "ascii" => Ok(OutputFormat::Text { unicode: false }),
+            "auto" => Ok(terminal::detect().map_or(OutputFormat::Png, OutputFormat::Terminal)),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+// What the animate and replay subcommands write; videos go through ffmpeg.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnimationFormat {
+    Gif,
+    Mp4,
+    Webm,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for AnimationFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(AnimationFormat::Gif),
+            "mp4" => Ok(AnimationFormat::Mp4),
+            "webm" => Ok(AnimationFormat::Webm),
+            _ => Err(format!("Unknown animation format: {}", s)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AnimationFormat {
+    // A default output path keeps its name and takes the format's extension.
+    fn output(self, path: &str, default: &str) -> String {
+        match self {
+            _ if path != default => path.to_string(),
+            AnimationFormat::Gif => path.to_string(),
+            AnimationFormat::Mp4 => path.replace(".gif", ".mp4"),
+            AnimationFormat::Webm => path.replace(".gif", ".webm"),
+        }
+    }
+}
+
+// The top and height of every region in a strip `height` units tall. Each
+// renderer lays out through this so the PNG, SVG and text views agree.
+// Collapsed gaps get `break_height` each and the rest share what is left.
+fn layout(memory_regions: &[MemoryRegion], height: u32, break_height: i32, options: &RenderOptions) -> Vec<(i32, i32)> {
+    // One pass over the regions decides both whether each is collapsed
+    // and what it weighs.
+    let mut collapsed = Vec::with_capacity(memory_regions.len());
+    let mut weights = Vec::with_capacity(memory_regions.len());
+    for region in memory_regions {
+        let is_collapsed = options.is_collapsed(region);
+        collapsed.push(is_collapsed);
+        weights.push(if is_collapsed { 0.0 } else { region_weight(region, options.size_metric, options.scale) });
+    }
+    cap_weights(&mut weights, options.max_region_fraction);
+
+    let breaks = collapsed.iter().filter(|collapsed| **collapsed).count() as u32 * break_height as u32;
+    let heights = allot_heights(&weights, height.saturating_sub(breaks), scaled(options.min_region_px as i32, options.scale_factor) as u32);
+    let mut y = 0;
+    heights
+        .into_iter()
+        .zip(collapsed)
+        .map(|(region_height, collapsed)| {
+            let region_height = if collapsed { break_height } else { region_height };
+            y += region_height;
+            (y - region_height, region_height)
+        })
+        .collect()
+}
+
+// Lowers the largest weights to the one cap at which each of them is exactly
+// `max_fraction` of the new total.
+fn cap_weights(weights: &mut [f64], max_fraction: f64) {
+    let mut sorted: Vec<f64> = weights.iter().copied().filter(|weight| *weight > 0.0).collect();
+    if max_fraction >= 1.0 || sorted.is_empty() {
+        return;
+    }
+    let max_fraction = max_fraction.max(1.0 / sorted.len() as f64);
+    sorted.sort_by(|a, b| b.total_cmp(a));
+
+    let mut capped = 0;
+    let mut rest: f64 = sorted.iter().sum();
+    let cap = loop {
+        if capped == sorted.len() {
+            break sorted[capped - 1];
+        }
+        let cap = max_fraction * rest / (1.0 - capped as f64 * max_fraction);
+        if sorted[capped] <= cap {
+            break cap;
+        }
+        rest -= sorted[capped];
+        capped += 1;
+    };
+    for weight in weights.iter_mut() {
+        *weight = weight.min(cap);
+    }
+}
+
+// Proportional heights, except that no region with any weight gets fewer
+// than `min_px`; the ones raised to the floor take their pixels from the rest.
+fn allot_heights(weights: &[f64], height: u32, min_px: u32) -> Vec<i32> {
+    let visible = weights.iter().filter(|weight| **weight > 0.0).count() as u32;
+    let min_px = height.checked_div(visible).map_or(0, |share| min_px.min(share)) as i32;
+    let mut floored = vec![false; weights.len()];
+    loop {
+        let fixed = floored.iter().filter(|floored| **floored).count() as f64 * min_px as f64;
+        let total = weights.iter().zip(&floored).filter(|(_, floored)| !**floored).map(|(weight, _)| weight).sum::<f64>().max(1.0);
+        let heights: Vec<i32> = weights
+            .iter()
+            .zip(&floored)
+            .map(|(weight, floored)| match (*weight > 0.0, floored) {
+                (false, _) => 0,
+                (true, true) => min_px,
+                (true, false) => ((weight / total) * (height as f64 - fixed)) as i32,
+            })
+            .collect();
+
+        let mut changed = false;
+        for (i, region_height) in heights.iter().enumerate() {
+            if weights[i] > 0.0 && !floored[i] && *region_height < min_px {
+                floored[i] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AxisLabels {
+    Hex,
+    // Human-readable distance into the surrounding cluster of mappings.
+    Offset,
+    // The old start and size printed beside every region, without ticks.
+    Regions,
+}
+
+impl FromStr for AxisLabels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(AxisLabels::Hex),
+            "offset" => Ok(AxisLabels::Offset),
+            "regions" => Ok(AxisLabels::Regions),
+            _ => Err(format!("Unknown axis label format: {}", s)),
+        }
+    }
+}
+
+// The attribute drawn as a fill pattern over the region color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PatternBy {
+    Shared,
+    Private,
+    FileBacked,
+    Anonymous,
+}
+
+impl FromStr for PatternBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(PatternBy::Shared),
+            "private" => Ok(PatternBy::Private),
+            "file-backed" => Ok(PatternBy::FileBacked),
+            "anonymous" => Ok(PatternBy::Anonymous),
+            _ => Err(format!("Unknown pattern attribute: {}", s)),
+        }
+    }
+}
+
+impl PatternBy {
+    fn label(self) -> &'static str {
+        match self {
+            PatternBy::Shared => "shared",
+            PatternBy::Private => "private",
+            PatternBy::FileBacked => "file-backed",
+            PatternBy::Anonymous => "anonymous",
+        }
+    }
+
+    fn matches(self, region: &MemoryRegion) -> bool {
+        let file_backed = region.file_name.as_deref().is_some_and(|name| name.starts_with('/'));
+        region.attributes.allocated
+            && match self {
+                PatternBy::Shared => region.attributes.shared,
+                PatternBy::Private => !region.attributes.shared,
+                PatternBy::FileBacked => file_backed,
+                PatternBy::Anonymous => !file_backed,
+            }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pattern {
+    Stripes,
+    Dots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LabelPlacement {
+    // Push a label below the one it would cover, with a leader line back.
+    Stack,
+    Skip,
+    Overlap,
+}
+
+impl FromStr for LabelPlacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stack" => Ok(LabelPlacement::Stack),
+            "skip" => Ok(LabelPlacement::Skip),
+            "overlap" => Ok(LabelPlacement::Overlap),
+            _ => Err(format!("Unknown label placement: {}", s)),
+        }
+    }
+}
+
+// Tops for labels wanted at `wanted` (ascending), each `height` tall. A
+// stacked label may move down by a few label heights; one that would move
+// further, or past `bottom`, is left out.
+fn place_labels(wanted: &[i32], height: i32, bottom: i32, placement: LabelPlacement) -> Vec<Option<i32>> {
+    let max_shift = match placement {
+        LabelPlacement::Stack => height * 4,
+        LabelPlacement::Skip => 0,
+        LabelPlacement::Overlap => return wanted.iter().map(|y| Some(*y)).collect(),
+    };
+    let mut next_free = i32::MIN;
+    wanted
+        .iter()
+        .map(|&y| {
+            let top = y.max(next_free);
+            if top - y > max_shift || top + height > bottom {
+                return None;
+            }
+            next_free = top + height;
+            Some(top)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LegendPosition {
+    BottomLeft,
+    TopLeft,
+    // A column of its own to the right of the bar.
+    Right,
+}
+
+impl FromStr for LegendPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bottom-left" => Ok(LegendPosition::BottomLeft),
+            "top-left" => Ok(LegendPosition::TopLeft),
+            "right" => Ok(LegendPosition::Right),
+            _ => Err(format!("Unknown legend position: {}", s)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortOrder {
+    Address,
+    Size,
+    Rss,
+    Path,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(SortOrder::Address),
+            "size" => Ok(SortOrder::Size),
+            "rss" => Ok(SortOrder::Rss),
+            "path" => Ok(SortOrder::Path),
+            _ => Err(format!("Unknown sort order: {}", s)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SortOrder {
+    // Largest first for the sizes; paths alphabetically, unnamed regions last.
+    fn sort(self, memory_regions: &mut [&MemoryRegion]) {
+        let rss = |region: &MemoryRegion| region.smaps.as_ref().map_or(0, |smaps| smaps.rss);
+        match self {
+            SortOrder::Address => memory_regions.sort_by_key(|region| region.start),
+            SortOrder::Size => memory_regions.sort_by_key(|region| (std::cmp::Reverse(region.size), region.start)),
+            SortOrder::Rss => memory_regions.sort_by_key(|region| (std::cmp::Reverse(rss(region)), region.start)),
+            SortOrder::Path => memory_regions.sort_by(|a, b| (a.file_name.is_none(), &a.file_name, a.start).cmp(&(b.file_name.is_none(), &b.file_name, b.start))),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sharing {
+    Shared,
+    Private,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for Sharing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(Sharing::Shared),
+            "private" => Ok(Sharing::Private),
+            _ => Err(format!("Unknown sharing mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RenderOptions {
+    show_huge_pages: bool,
+    show_locked: bool,
+    show_vm_flags: bool,
+    // Runs of KSM merged pages, banded across their regions.
+    ksm: Vec<(usize, usize)>,
+    // Resident anonymous memory by the page size backing it.
+    thp: Vec<(usize, usize, thp::Backing)>,
+    // Bits per byte sampled from each readable region, by region start.
+    entropy: Vec<(usize, f64)>,
+    // First bytes of readable regions by start, for tooltips in SVG.
+    #[cfg(not(target_arch = "wasm32"))]
+    previews: Vec<(usize, Vec<u8>)>,
+    stacks: Vec<stacks::StackUsage>,
+    color_by: ColorBy,
+    size_metric: SizeMetric,
+    scale: Scale,
+    min_region_px: u32,
+    max_region_fraction: f64,
+    audit: bool,
+    annotations: Vec<(usize, String)>,
+    emphasis: Emphasis,
+    notice: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    fragmentation_panel: Option<fragmentation::Fragmentation>,
+    #[cfg(not(target_arch = "wasm32"))]
+    size_histogram_panel: Option<Vec<sizes::Bucket>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    numa_panel: Option<numa::NumaTotals>,
+    #[cfg(not(target_arch = "wasm32"))]
+    cgroup_panel: Option<cgroup::CgroupMemory>,
+    #[cfg(not(target_arch = "wasm32"))]
+    massif_panel: Option<massif::Panel>,
+    holes: Vec<(usize, usize)>,
+    // Regions per file and regions shared with each neighbour, for PNG.
+    #[cfg(not(target_arch = "wasm32"))]
+    tiles: Option<(usize, usize)>,
+    // Address ranges given to --highlight; single addresses are only marked.
+    highlights: Vec<(usize, usize)>,
+    collapse_gaps: Option<usize>,
+    // Ranges of regions removed by the region filters, drawn collapsed.
+    filtered: Vec<(usize, usize)>,
+    width: u32,
+    height: u32,
+    // Multiplies every pixel and font size, including width and height.
+    scale_factor: f64,
+    theme: Theme,
+    // Stripes for writable and executable regions, so permissions don't
+    // rest on color alone.
+    textures: bool,
+    legend: Option<LegendPosition>,
+    label_placement: LabelPlacement,
+    pattern_by: Option<PatternBy>,
+    pattern: Pattern,
+    dark: bool,
+    header: Option<header::Header>,
+    // A column right of the bars placing them in the whole address space.
+    context_bar: bool,
+    // The user/kernel split found for the process, when it was looked for;
+    // otherwise read off the mappings.
+    address_space: Option<context::AddressSpace>,
+    // Lines at mmap_min_addr, the end of user space and the kernel half.
+    boundaries: bool,
+    axis_labels: AxisLabels,
+    // Logical pixels between labeled ticks, and unlabeled ticks between those.
+    tick_spacing: u32,
+    minor_ticks: u32,
+    // Fraction of the cgroup limit below which usage counts as near it.
+    cgroup_margin: f64,
+}
+
+impl RenderOptions {
+    fn image_size(&self) -> (u32, u32) {
+        let column = if self.legend == Some(LegendPosition::Right) { LEGEND_COLUMN_WIDTH } else { 0 } + if self.context_bar { context::COLUMN_WIDTH } else { 0 };
+        let banner = self.header.as_ref().map_or(0, header_height);
+        (scaled((self.width + column) as i32, self.scale_factor) as u32, scaled((self.height + banner) as i32, self.scale_factor) as u32)
+    }
+
+    fn background(&self) -> RGBColor {
+        if self.dark { RGBColor(30, 30, 34) } else { WHITE }
+    }
+
+    fn foreground(&self) -> RGBColor {
+        if self.dark { RGBColor(220, 220, 220) } else { BLACK }
+    }
+
+    // Whether anything read from smaps is drawn; if not, a live process's
+    // RSS churn leaves the picture as it was.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draws_smaps(&self) -> bool {
+        self.show_huge_pages || self.show_locked || self.show_vm_flags || self.color_by.needs_smaps() || self.size_metric.needs_smaps()
+    }
+
+    // What of the regions reaches the picture, to tell whether an earlier
+    // render of them still holds.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn drawn_regions(&self, memory_regions: &[MemoryRegion]) -> Vec<MemoryRegion> {
+        let draws_smaps = self.draws_smaps();
+        memory_regions
+            .iter()
+            .map(|region| MemoryRegion { smaps: if draws_smaps { region.smaps.clone() } else { None }, ..region.clone() })
+            .collect()
+    }
+
+    fn is_collapsed(&self, region: &MemoryRegion) -> bool {
+        !region.attributes.allocated
+            && (self.collapse_gaps.is_some_and(|threshold| region.size > threshold)
+                || self.filtered.iter().any(|(start, end)| *start < region.end && region.start < *end))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MemoryAttributes {
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    pub shared: bool,
+    pub allocated: bool,
+}
+
+impl MemoryAttributes {
+    fn perms(&self) -> String {
+        let flag = |set: bool, c: char| if set { c } else { '-' };
+        [
+            flag(self.readable, 'r'),
+            flag(self.writable, 'w'),
+            flag(self.executable, 'x'),
+            if self.shared { 's' } else { 'p' },
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+    pub size: usize,
+    pub attributes: MemoryAttributes,
+    pub offset: usize,
+    pub device: (u32, u32),
+    pub inode: u64,
+    pub file_name: Option<String>,
+    pub thread_id: Option<u32>,
+    pub guard: bool,
+    pub smaps: Option<SmapsInfo>,
+    // How many maps lines this region stands for once grouped.
+    pub mappings: usize,
+}
+
+fn next_field(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+impl FromStr for MemoryRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, rest) = next_field(s);
+        let (attributes, rest) = next_field(rest);
+        if range.is_empty() || attributes.is_empty() {
+            return Err("Invalid input format".to_string());
+        }
+
+        let (start, end) = range.split_once('-').ok_or_else(|| "Invalid address range".to_string())?;
+        let start = usize::from_str_radix(start, 16).map_err(|_| "Invalid start address".to_string())?;
+        let end = usize::from_str_radix(end, 16).map_err(|_| "Invalid end address".to_string())?;
+
+        let flag = |byte: u8, set: u8| match byte {
+            b'-' => Ok(false),
+            _ if byte == set => Ok(true),
+            _ => Err("Invalid memory attributes".to_string()),
+        };
+        let &[read, write, execute, sharing] = attributes.as_bytes() else {
+            return Err("Invalid memory attributes".to_string());
+        };
+        let readable = flag(read, b'r')?;
+        let writable = flag(write, b'w')?;
+        let executable = flag(execute, b'x')?;
+        let shared = match sharing {
+            b's' => true,
+            b'p' => false,
+            _ => return Err("Invalid sharing flag".to_string()),
+        };
+
+        let (offset, rest) = next_field(rest);
+        let offset = if offset.is_empty() { 0 } else { usize::from_str_radix(offset, 16).map_err(|_| "Invalid offset".to_string())? };
+
+        let (device, rest) = next_field(rest);
+        let device = match device.split_once(':') {
+            Some((major, minor)) => (
+                u32::from_str_radix(major, 16).map_err(|_| "Invalid device".to_string())?,
+                u32::from_str_radix(minor, 16).map_err(|_| "Invalid device".to_string())?,
+            ),
+            None if device.is_empty() => (0, 0),
+            None => return Err("Invalid device".to_string()),
+        };
+
+        let (inode, rest) = next_field(rest);
+        let inode = if inode.is_empty() { 0 } else { inode.parse::<u64>().map_err(|_| "Invalid inode".to_string())? };
+
+        // The pathname is everything after the inode column, spaces included.
+        let path = rest.trim_start().trim_end_matches(['\n', '\r']);
+        let file_name = if path.is_empty() { None } else { Some(path.to_string()) };
+
+        let size = end.checked_sub(start).ok_or_else(|| "End address before start address".to_string())?;
+
+        Ok(MemoryRegion {
+            start,
+            end,
+            size,
+            attributes: MemoryAttributes {
+                readable,
+                writable,
+                executable,
+                shared,
+                allocated: true,
+            },
+            offset,
+            device,
+            inode,
+            file_name,
+            thread_id: None,
+            guard: false,
+            smaps: None,
+            mappings: 1,
+        })
+    }
+}
+
+impl fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}-{:08x} {} {:08x} {:02x}:{:02x} {}",
+            self.start, self.end, self.attributes.perms(), self.offset, self.device.0, self.device.1, self.inode
+        )?;
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{:width$}{}", "", file_name, width = 26usize.saturating_sub(self.inode.to_string().len()).max(1))?;
+        }
+        Ok(())
+    }
+}
+
+impl MemoryRegion {
+    fn anon_name(&self) -> Option<&str> {
+        let name = self.file_name.as_deref()?;
+        if let Some(ashmem) = name.strip_prefix("/dev/ashmem/") {
+            return Some(ashmem.strip_suffix(" (deleted)").unwrap_or(ashmem));
+        }
+        let inner = name.strip_prefix("[anon:").or_else(|| name.strip_prefix("[anon_shmem:"))?;
+        inner.strip_suffix(']')
+    }
+
+    // Memory shared through a file descriptor or a fork rather than a file
+    // on disk: memfd_create() files, MAP_SHARED | MAP_ANONYMOUS (which maps
+    // shmem's "/dev/zero (deleted)") and System V segments.
+    fn shared_memory_kind(&self) -> Option<&'static str> {
+        match self.file_name.as_deref() {
+            _ if !self.attributes.allocated => None,
+            Some(name) if name.starts_with("/memfd:") => Some("memfd"),
+            Some(name) if name.starts_with("/SYSV") => Some("sysv shm"),
+            Some("/dev/zero (deleted)") if self.attributes.shared => Some("shared anon"),
+            None if self.attributes.shared => Some("shared anon"),
+            _ => None,
+        }
+    }
+
+    // Per-thread names such as Android's "stack_and_tls:1234" share one group.
+    fn anon_group(&self) -> Option<&str> {
+        let name = self.anon_name()?;
+        match name.rsplit_once(':') {
+            Some((group, tid)) if !tid.is_empty() && tid.bytes().all(|b| b.is_ascii_digit()) => Some(group),
+            _ => Some(name),
+        }
+    }
+}
+
+fn region_category(region: &MemoryRegion) -> &'static str {
+    if let Some(kind) = region.shared_memory_kind() {
+        return kind;
+    }
+    match region.file_name.as_deref() {
+        _ if !region.attributes.allocated => "gap",
+        _ if region.thread_id.is_some() => "stack",
+        _ if region.guard => "guard",
+        _ if jit::is_jit(region) => "jit",
+        _ if region.anon_name().is_some() => "named anon",
+        _ if sanitizer::is_shadow(region) => "shadow",
+        Some("[heap]") => "heap",
+        Some(name) if name.starts_with("[stack") => "stack",
+        Some(name) if name.starts_with('/') => "file",
+        Some(_) => "special",
+        None => "anon",
+    }
+}
+
+// Regions one at a time, reusing one line buffer, so a maps file with a
+// million lines never exists in memory as a Vec<String>.
+fn stream_memory_regions<'a, R: BufRead + 'a>(mut reader: R, warnings: &'a mut Warnings) -> impl Iterator<Item = MemoryRegion> + 'a {
+    let mut line = String::new();
+    let mut number = 0;
+    std::iter::from_fn(move || loop {
+        line.clear();
+        number += 1;
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => match line.trim_end_matches('\n').parse::<MemoryRegion>() {
+                Ok(region) => return Some(region),
+                Err(_) if line.trim().is_empty() => {}
+                Err(e) => warnings.skip("maps", number, &e, line.trim_end()),
+            },
+        }
+    })
+}
+
+fn parse_memory_regions<R: BufRead>(reader: R) -> (Vec<MemoryRegion>, Warnings) {
+    let mut warnings = Warnings::default();
+    let memory_regions = stream_memory_regions(reader, &mut warnings).collect();
+    (memory_regions, warnings)
+}
+
+impl MemoryRegion {
+    fn gap(start: usize, end: usize) -> Self {
+        MemoryRegion {
+            start,
+            end,
+            size: end - start,
+            attributes: MemoryAttributes {
+                readable: false,
+                writable: false,
+                executable: false,
+                shared: false,
+                allocated: false,
+            },
+            offset: 0,
+            device: (0, 0),
+            inode: 0,
+            file_name: None,
+            thread_id: None,
+            guard: false,
+            smaps: None,
+            mappings: 1,
+        }
+    }
+}
+
+// The parts of `region` no overlay covers, each a copy of the region, for
+// layouts where specific areas are drawn over a larger one.
+#[cfg(not(target_arch = "wasm32"))]
+fn carve(region: &MemoryRegion, overlays: &[MemoryRegion]) -> Vec<MemoryRegion> {
+    let mut pieces = vec![(region.start, region.end)];
+    for overlay in overlays {
+        pieces = pieces
+            .into_iter()
+            .flat_map(|(start, end)| [(start, end.min(overlay.start)), (start.max(overlay.end), end)])
+            .filter(|(start, end)| start < end)
+            .collect();
+    }
+    pieces.into_iter().map(|(start, end)| MemoryRegion { start, end, size: end - start, ..region.clone() }).collect()
+}
+
+// Yields a gap region wherever the address-ordered input skips ahead,
+// moving the regions through rather than copying them.
+fn with_gaps<I: IntoIterator<Item = MemoryRegion>>(memory_regions: I) -> impl Iterator<Item = MemoryRegion> {
+    let mut regions = memory_regions.into_iter().peekable();
+    let mut prev_end: usize = 0;
+    std::iter::from_fn(move || {
+        let next_start = regions.peek()?.start;
+        if next_start > prev_end {
+            let gap = MemoryRegion::gap(prev_end, next_start);
+            prev_end = next_start;
+            return Some(gap);
+        }
+        let region = regions.next()?;
+        prev_end = region.end;
+        Some(region)
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn insert_gap_memory_regions(memory_regions: &[MemoryRegion]) -> Vec<MemoryRegion> {
+    with_gaps(memory_regions.iter().cloned()).collect()
+}
+
+
+// FNV-1a, so a given name keeps its color across runs and builds.
+fn name_hash(name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn name_color(name: &str) -> Rgb<u8> {
+    let hue = (name_hash(name) % 360) as f64;
+    let (s, v) = (0.55, 0.85);
+    let c = v * s;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let to_u8 = |f: f64| ((f + m) * 255.0).round() as u8;
+    Rgb([to_u8(r), to_u8(g), to_u8(b)])
+}
+
+const SPECIAL_REGIONS: [(&str, &str, [u8; 3]); 5] = [
+    ("[heap]", "Heap", [255, 140, 0]),
+    ("[stack]", "Stack", [0, 160, 160]),
+    ("[vdso]", "vDSO", [150, 80, 200]),
+    ("[vvar]", "vvar", [200, 170, 230]),
+    ("[vsyscall]", "vsyscall", [140, 90, 40]),
+];
+
+// Keyed by MemoryRegion::shared_memory_kind, and drawn like special regions.
+const SHARED_MEMORY: [(&str, &str, [u8; 3]); 3] = [
+    ("memfd", "memfd", [230, 90, 160]),
+    ("shared anon", "Shared anon", [120, 120, 230]),
+    ("sysv shm", "SysV shm", [90, 170, 230]),
+];
+
+fn special_region_color(region: &MemoryRegion) -> Option<Rgb<u8>> {
+    if let Some(kind) = region.shared_memory_kind() {
+        return SHARED_MEMORY.iter().find(|(known, _, _)| *known == kind).map(|(_, _, rgb)| Rgb(*rgb));
+    }
+    if jit::is_jit(region) {
+        return Some(Rgb(jit::JIT_COLOR));
+    }
+    if sanitizer::is_shadow(region) {
+        return Some(Rgb(sanitizer::SHADOW_COLOR));
+    }
+    let name = if region.thread_id.is_some() { "[stack]" } else { region.file_name.as_deref()? };
+    SPECIAL_REGIONS.iter().find(|(path, _, _)| *path == name).map(|(_, _, rgb)| Rgb(*rgb))
+}
+
+// Legend entries for each kind of shared memory present, with its total.
+fn shared_memory_entries(memory_regions: &[MemoryRegion]) -> Vec<LegendEntry> {
+    SHARED_MEMORY
+        .iter()
+        .filter_map(|(kind, label, rgb)| {
+            let bytes: usize = memory_regions.iter().filter(|r| r.shared_memory_kind() == Some(kind)).map(|r| r.size).sum();
+            (bytes > 0).then(|| LegendEntry::new(format!("{} ({})", label, format_size(bytes)), Rgb(*rgb)))
+        })
+        .collect()
+}
+
+fn jit_entry(memory_regions: &[MemoryRegion]) -> Option<LegendEntry> {
+    let bytes: usize = memory_regions.iter().filter(|r| jit::is_jit(r)).map(|r| r.size).sum();
+    (bytes > 0).then(|| LegendEntry::new(format!("JIT code ({})", format_size(bytes)), Rgb(jit::JIT_COLOR)))
+}
+
+fn shadow_entry(memory_regions: &[MemoryRegion]) -> Option<LegendEntry> {
+    let bytes: usize = memory_regions.iter().filter(|r| sanitizer::is_shadow(r)).map(|r| r.size).sum();
+    (bytes > 0).then(|| LegendEntry::new(format!("sanitizer shadow ({})", format_size(bytes)), Rgb(sanitizer::SHADOW_COLOR)))
+}
+
+fn swap_color(fraction: f64) -> Rgb<u8> {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let blend = |from: f64, to: f64| (from + (to - from) * fraction).round() as u8;
+    Rgb([blend(220.0, 200.0), blend(220.0, 30.0), blend(220.0, 30.0)])
+}
+
+const ANONYMOUS_COLOR: [u8; 3] = [215, 215, 215];
+const KSM_COLOR: RGBColor = RGBColor(0, 200, 120);
+const DELETED_COLOR: RGBColor = RGBColor(170, 0, 60);
+const FILE_LEGEND_LIMIT: usize = 16;
+
+// memfd and shmem paths name no file on disk, so they keep their own color.
+fn backing_file(region: &MemoryRegion) -> Option<&str> {
+    region.file_name.as_deref().filter(|path| path.starts_with('/') && region.shared_memory_kind().is_none())
+}
+
+fn region_color(region: &MemoryRegion, color_by: ColorBy, theme: &Theme) -> Rgb<u8> {
+    if color_by == ColorBy::Swap && region.attributes.allocated {
+        let swap = region.smaps.as_ref().map_or(0, |smaps| smaps.swap);
+        return swap_color(swap as f64 / region.size as f64);
+    }
+    // The hue comes from the whole path alone, so a library keeps it in
+    // every process and every run.
+    if color_by == ColorBy::File && region.attributes.allocated {
+        if let Some(path) = backing_file(region) {
+            return name_color(path);
+        }
+    }
+
+    let color = match (special_region_color(region), region.anon_group()) {
+        (Some(color), _) => color,
+        (None, Some(name)) if region.attributes.allocated => name_color(name),
+        _ if color_by == ColorBy::File && region.attributes.allocated => Rgb(ANONYMOUS_COLOR),
+        _ => theme.permission_color(&region.attributes),
+    };
+    match &region.smaps {
+        Some(smaps) if color_by == ColorBy::Rss && region.attributes.allocated => resident_shade(color, smaps.rss as f64 / region.size as f64),
+        _ => color,
+    }
+}
+
+// Keeps the hue and fades it toward a pale tint as less of the region is
+// resident, so a mostly untouched reservation reads as nearly empty.
+fn resident_shade(color: Rgb<u8>, fraction: f64) -> Rgb<u8> {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let Rgb(channels) = color;
+    Rgb(channels.map(|channel| (235.0 + (channel as f64 - 235.0) * (0.15 + 0.85 * fraction)).round() as u8))
+}
+
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+// Keeps the largest regions as-is and merges every run of regions between
+// them into one placeholder spanning the run, holes included. With k kept
+// there are at most k + 1 runs, so keeping (cap - 1) / 2 never exceeds a cap
+// of 3 or more.
+fn truncate_regions(memory_regions: Vec<MemoryRegion>, max_regions: usize) -> (Vec<MemoryRegion>, Option<String>) {
+    if memory_regions.len() <= max_regions {
+        return (memory_regions, None);
+    }
+
+    let total = memory_regions.len();
+    // Kept regions and the placeholders around them: k of them leave at
+    // most k + 1 runs, so 2k + 1 fits the cap. A cap of 1 or 2 keeps none
+    // and draws one placeholder for the lot.
+    let keep_count = max_regions.saturating_sub(1) / 2;
+    let mut by_size: Vec<usize> = (0..total).collect();
+    by_size.sort_by_key(|&i| std::cmp::Reverse(memory_regions[i].size));
+    let mut keep = vec![false; total];
+    for &i in &by_size[..keep_count] {
+        keep[i] = true;
+    }
+
+    let mut truncated = Vec::new();
+    let mut run: Vec<MemoryRegion> = Vec::new();
+    let flush = |run: &mut Vec<MemoryRegion>, truncated: &mut Vec<MemoryRegion>| {
+        if let (Some(first), Some(last)) = (run.first(), run.last()) {
+            truncated.push(MemoryRegion {
+                start: first.start,
+                end: last.end,
+                size: last.end - first.start,
+                attributes: MemoryAttributes {
+                    readable: false,
+                    writable: false,
+                    executable: false,
+                    shared: false,
+                    allocated: true,
+                },
+                offset: 0,
+                device: (0, 0),
+                inode: 0,
+                file_name: Some(format!("[coalesced: {} regions]", format_count(run.len()))),
+                thread_id: None,
+                guard: false,
+                smaps: None,
+                mappings: 1,
+            });
+        }
+        run.clear();
+    };
+    for (i, region) in memory_regions.into_iter().enumerate() {
+        if keep[i] {
+            flush(&mut run, &mut truncated);
+            truncated.push(region);
+        } else {
+            run.push(region);
+        }
+    }
+    flush(&mut run, &mut truncated);
+
+    let placeholders = truncated.len() - keep_count;
+    let notice = format!(
+        "truncated: showing {} of {} regions, the other {} coalesced into {}",
+        format_count(keep_count),
+        format_count(total),
+        format_count(total - keep_count),
+        format_count(placeholders)
+    );
+    (truncated, Some(notice))
+}
+
+// One line for --highlight: the range, size, permissions and path.
+#[cfg(not(target_arch = "wasm32"))]
+fn describe_region(region: &MemoryRegion) -> String {
+    let range = format!("{:#x}-{:#x} ({})", region.start, region.end, format_size(region.size));
+    if !region.attributes.allocated {
+        return format!("unmapped {}", range);
+    }
+    let path = match region.file_name.as_deref() {
+        Some(path) if path.starts_with('/') => path.to_string(),
+        _ => display_name(region).unwrap_or_default(),
+    };
+    format!("{} {} {}", region.attributes.perms(), range, path).trim_end().to_string()
+}
+
+// An address, or a start and exclusive end joined by "-".
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    match s.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (symbols::parse_address(start)?, symbols::parse_address(end)?);
+            if end <= start {
+                return Err(format!("Empty range: {}", s));
+            }
+            Ok((start, end))
+        }
+        None => symbols::parse_address(s).map(|address| (address, address.saturating_add(1))),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn print_top_regions(memory_regions: &[MemoryRegion], count: usize, metric: SizeMetric) {
+    let mut ranked: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| region.attributes.allocated).collect();
+    ranked.sort_by_key(|region| std::cmp::Reverse(metric.value(region)));
+
+    println!("{:>4}  {:<33} {:>10} {:>10} {:<5} path", "#", "range", "size", "rss", "perms");
+    for (rank, region) in ranked.into_iter().take(count).enumerate() {
+        let rss = region.smaps.as_ref().map_or("-".to_string(), |smaps| format_size(smaps.rss));
+        let path = match region.file_name.as_deref() {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => display_name(region).unwrap_or_default(),
+        };
+        println!(
+            "{:>4}  {:<33} {:>10} {:>10} {:<5} {}",
+            rank + 1,
+            format!("{:#x}-{:#x}", region.start, region.end),
+            format_size(region.size),
+            rss,
+            region.attributes.perms(),
+            path,
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn group_anon_names(memory_regions: &[MemoryRegion]) -> BTreeMap<&str, (usize, usize)> {
+    let mut groups: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for region in memory_regions {
+        if let Some(name) = region.anon_group() {
+            let entry = groups.entry(name).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += region.size;
+        }
+    }
+    groups
+}
+
+use plotters::style::{FontDesc, FontStyle, FontFamily};
+
+fn font_bold(size: f64) -> FontDesc<'static> {
+    FontDesc::new(FontFamily::SansSerif, size, FontStyle::Bold)
+}
+
+fn display_name(region: &MemoryRegion) -> Option<String> {
+    if !region.attributes.allocated {
+        return None;
+    }
+    if let Some(tid) = region.thread_id {
+        return Some(format!("stack (tid {})", tid));
+    }
+    if region.guard {
+        return Some("guard".to_string());
+    }
+    if let Some(name) = region.anon_name() {
+        return Some(name.to_string());
+    }
+    match region.file_name.as_deref() {
+        Some(path) if path.starts_with('/') && region.mappings > 1 => Some(grouping::group_label(region)),
+        Some(path) if path.starts_with('/') => Some(path.rsplit('/').next().unwrap_or(path).to_string()),
+        Some(name) => Some(name.to_string()),
+        None if jit::is_jit(region) && region.mappings > 1 => Some(grouping::group_label(region)),
+        None if jit::is_jit(region) => Some("JIT".to_string()),
+        None => Some("anon".to_string()),
+    }
+}
+
+fn luminance(color: Rgb<u8>) -> f64 {
+    0.299 * color[0] as f64 + 0.587 * color[1] as f64 + 0.114 * color[2] as f64
+}
+
+// Shortens text with an ellipsis until it fits, or gives up if not even one
+// character does.
+fn fit_text<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, text: &str, font: &FontDesc, max_width: i32) -> Result<Option<String>, Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let style = TextStyle::from(font.clone());
+    let width = |t: &str| root.estimate_text_size(t, &style).map(|(w, _)| w as i32);
+    if width(text)? <= max_width {
+        return Ok(Some(text.to_string()));
+    }
+    let chars: Vec<char> = text.chars().collect();
+    for keep in (1..chars.len()).rev() {
+        let candidate: String = chars[..keep].iter().chain(std::iter::once(&'…')).collect();
+        if width(&candidate)? <= max_width {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+// The label goes at `label_top`, normally just below the line; a placed
+// label further down gets a leader back to the line, and none is drawn
+// when placement left it out.
+fn draw_marker<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, y: i32, label_top: Option<i32>, label: &str, image_width: i32, scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, scale);
+    let x = px(LEGEND_WIDTH as i32);
+    root.draw(&Polygon::new(vec![(x - px(8), y - px(5)), (x, y), (x - px(8), y + px(5))], RED.filled()))?;
+    root.draw(&PathElement::new(vec![(x, y), (image_width, y)], RED.stroke_width(px(2) as u32)))?;
+
+    let top = match label_top {
+        Some(top) => top,
+        None => return Ok(()),
+    };
+    if top > y + px(2) {
+        root.draw(&PathElement::new(vec![(x + px(1), y), (x + px(1), top + px(6))], RED))?;
+    }
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal);
+    let label = fit_text(root, label, &font, image_width - x - px(6))?.unwrap_or_default();
+    let (w, h) = root.estimate_text_size(&label, &TextStyle::from(font.clone()))?;
+    root.draw(&Rectangle::new([(x + px(2), top), (x + px(4) + w as i32, top + px(1) + h as i32)], WHITE.mix(0.85).filled()))?;
+    root.draw(&Text::new(label, (x + px(3), top), font.color(&RED)))?;
+    Ok(())
+}
+
+fn scaled(logical: i32, scale: f64) -> i32 {
+    (logical as f64 * scale).round() as i32
+}
+
+// A padlock 8 by 11 logical pixels: a shackle over a filled body.
+fn draw_lock<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (x, y): (i32, i32), color: &RGBColor, scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |logical: i32| scaled(logical, scale);
+    root.draw(&Circle::new((x + px(4), y + px(4)), px(3) as u32, color.stroke_width(px(2).max(1) as u32)))?;
+    root.draw(&Rectangle::new([(x, y + px(4)), (x + px(8), y + px(11))], color.filled()))?;
+    Ok(())
+}
+
+fn draw_hatch<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (x0, y0): (i32, i32), (x1, y1): (i32, i32), color: &RGBColor, spacing: i32) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if x1 <= x0 || y1 <= y0 {
+        return Ok(());
+    }
+
+    // 45 degree lines x - y = c, clipped to the rectangle.
+    let mut c = x0 - y1 + spacing;
+    while c < x1 - y0 {
+        let (start_x, start_y) = if c + y0 >= x0 { (c + y0, y0) } else { (x0, x0 - c) };
+        let (end_x, end_y) = if c + y1 <= x1 { (c + y1, y1) } else { (x1, x1 - c) };
+        root.draw(&PathElement::new(vec![(start_x, start_y), (end_x, end_y)], color))?;
+        c += spacing;
+    }
+
+    Ok(())
+}
+
+fn draw_stripes<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (x0, y0): (i32, i32), (x1, y1): (i32, i32), color: &RGBColor, spacing: i32, vertical: bool) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    if vertical {
+        for x in (x0 + spacing / 2..x1).step_by(spacing.max(1) as usize) {
+            root.draw(&PathElement::new(vec![(x, y0), (x, y1)], color))?;
+        }
+    } else {
+        for y in (y0 + spacing / 2..y1).step_by(spacing.max(1) as usize) {
+            root.draw(&PathElement::new(vec![(x0, y), (x1, y)], color))?;
+        }
+    }
+    Ok(())
+}
+
+// Dashes 6 logical pixels long with gaps of 4, two pixels wide.
+fn draw_dashed_outline<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (x0, y0): (i32, i32), (x1, y1): (i32, i32), color: &RGBColor, scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |logical: i32| scaled(logical, scale);
+    let style = color.stroke_width(px(2).max(1) as u32);
+    for x in (x0..x1).step_by(px(10).max(1) as usize) {
+        for y in [y0, y1] {
+            root.draw(&PathElement::new(vec![(x, y), ((x + px(6)).min(x1), y)], style))?;
+        }
+    }
+    for y in (y0..y1).step_by(px(10).max(1) as usize) {
+        for x in [x0, x1] {
+            root.draw(&PathElement::new(vec![(x, y), (x, (y + px(6)).min(y1))], style))?;
+        }
+    }
+    Ok(())
+}
+
+// The address under each pixel row follows the layout, so ticks go at even
+// pixel intervals and each labeled one snaps to the roundest address nearby.
+fn draw_ruler<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, memory_regions: &[MemoryRegion], extents: &[(i32, i32)], options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let axis_x = px(LEGEND_WIDTH as i32) - px(1);
+    let spacing = px(options.tick_spacing as i32).max(1);
+    let last = match memory_regions.last() {
+        Some(last) => last.end - 1,
+        None => return Ok(()),
+    };
+    let digits = format!("{:x}", last).len();
+    let ink = options.foreground();
+    let label_style = TextStyle::from(FontDesc::new(FontFamily::Monospace, 9.0 * options.scale_factor, FontStyle::Normal)).pos(Pos::new(HPos::Right, VPos::Center)).color(&ink);
+    let bottom = extents.iter().map(|(y, height)| y + height).max().unwrap_or(0);
+    root.draw(&PathElement::new(vec![(axis_x, 0), (axis_x, bottom)], ink))?;
+
+    let region_at = |y: i32| {
+        (0..memory_regions.len()).find(|&i| {
+            let (top, height) = extents[i];
+            height > 0 && top <= y && y < top + height && !options.is_collapsed(&memory_regions[i])
+        })
+    };
+    // Offsets count from the first mapped address after the last break, so
+    // they stay readable across the distant clusters of a 64-bit map.
+    let offset_base = |index: usize| {
+        let after_break = memory_regions[..index].iter().rposition(|region| options.is_collapsed(region)).map_or(0, |i| i + 1);
+        memory_regions[after_break..].iter().find(|region| region.attributes.allocated).map_or(0, |region| region.start)
+    };
+    let y_of = |region: &MemoryRegion, (top, height): (i32, i32), address: usize| top + ((address - region.start) as f64 / region.size as f64 * height as f64) as i32;
+
+    let minor = spacing / (options.minor_ticks as i32 + 1);
+    let mut y = 0;
+    while y < bottom {
+        if let Some(index) = region_at(y) {
+            let (region, extent) = (&memory_regions[index], extents[index]);
+            let within = (y - extent.0) as f64 / extent.1 as f64;
+            let address = region.start + (within * region.size as f64) as usize;
+            // The coarsest power-of-two alignment that stays in the region
+            // and within a quarter spacing of the tick.
+            let snapped = (12..usize::BITS)
+                .rev()
+                .map(|shift| (address as u128 + (1u128 << shift >> 1)) >> shift << shift)
+                .find(|candidate| *candidate >= region.start as u128 && *candidate < region.end as u128 && (y_of(region, extent, *candidate as usize) - y).abs() <= spacing / 4)
+                .map_or(address, |candidate| candidate as usize);
+            let tick_y = y_of(region, extent, snapped);
+            root.draw(&PathElement::new(vec![(axis_x - px(6), tick_y), (axis_x, tick_y)], ink))?;
+            let label = match options.axis_labels {
+                AxisLabels::Offset => format!("+{}", format_size(snapped.saturating_sub(offset_base(index)))),
+                _ => format!("{:#0width$x}", snapped, width = digits + 2),
+            };
+            root.draw(&Text::new(label, (axis_x - px(8), tick_y), label_style.clone()))?;
+        }
+        if minor > 0 {
+            for i in 1..=options.minor_ticks as i32 {
+                let minor_y = y + i * minor;
+                if minor_y < bottom && region_at(minor_y).is_some() {
+                    root.draw(&PathElement::new(vec![(axis_x - px(3), minor_y), (axis_x, minor_y)], ink))?;
+                }
+            }
+        }
+        y += spacing;
+    }
+    Ok(())
+}
+
+fn draw_pattern<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (x0, y0): (i32, i32), (x1, y1): (i32, i32), color: &RGBColor, pattern: Pattern, scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, scale);
+    match pattern {
+        Pattern::Stripes => draw_hatch(root, (x0, y0), (x1, y1), color, px(8)),
+        Pattern::Dots => {
+            let spacing = px(6).max(2) as usize;
+            for (row, y) in (y0 + px(3)..y1).step_by(spacing).enumerate() {
+                // Alternate rows are offset so the dots don't read as stripes.
+                let offset = if row % 2 == 0 { px(3) } else { px(6) };
+                for x in (x0 + offset..x1).step_by(spacing) {
+                    root.draw(&Rectangle::new([(x, y), (x + px(1), y + px(1))], color.filled()))?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+// Two zig-zags across the bar with the size of the range left out between them.
+fn draw_break<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, y: i32, height: i32, size: usize, image_width: i32, scale: f64) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, scale);
+    let x = px(LEGEND_WIDTH as i32);
+    for edge in [y + px(2), y + height - px(3)] {
+        let points: Vec<(i32, i32)> = (0..=(image_width - x) / px(6)).map(|i| (x + i * px(6), edge + if i % 2 == 0 { -px(2) } else { px(2) })).collect();
+        root.draw(&PathElement::new(points, WHITE.stroke_width(px(2) as u32)))?;
+    }
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal);
+    let label = format!("{} unmapped", format_size(size));
+    let (w, h) = root.estimate_text_size(&label, &TextStyle::from(font.clone()))?;
+    root.draw(&Text::new(label, (x + (image_width - x - w as i32) / 2, y + (height - h as i32) / 2), font.color(&WHITE)))?;
+    Ok(())
+}
+
+// Room for the three standard lines, and one more each for --proc-status
+// and --meminfo.
+fn header_height(header: &header::Header) -> u32 {
+    HEADER_HEIGHT + 15 * (header.lines().len() as u32 - 3)
+}
+
+fn draw_header<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, header: &header::Header, image_width: i32, options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let scale = options.scale_factor;
+    let px = |v: i32| scaled(v, scale);
+    root.fill(&if options.dark { RGBColor(50, 50, 56) } else { RGBColor(235, 235, 235) })?;
+    let bottom = px(header_height(header) as i32) - 1;
+    root.draw(&PathElement::new(vec![(0, bottom), (image_width, bottom)], RGBColor(160, 160, 160)))?;
+
+    // The gauge takes the right end of the banner, the text what's left.
+    let gauge = header.cgroup.as_ref().and_then(|cgroup| Some((cgroup.fraction()?, cgroup.near_limit(options.cgroup_margin))));
+    let gauge_width = if gauge.is_some() { px(150) } else { 0 };
+    if let Some((fraction, near)) = gauge {
+        let (x0, x1, y0, y1) = (image_width - gauge_width - px(5), image_width - px(5), px(22), px(34));
+        let fill = if near { RGBColor(220, 40, 30) } else if fraction >= 0.5 { RGBColor(240, 170, 0) } else { RGBColor(60, 170, 80) };
+        root.draw(&Rectangle::new([(x0, y0), (x1, y1)], options.background().filled()))?;
+        let filled = x0 + ((x1 - x0) as f64 * fraction.min(1.0)).round() as i32;
+        root.draw(&Rectangle::new([(x0, y0), (filled, y1)], fill.filled()))?;
+        root.draw(&Rectangle::new([(x0, y0), (x1, y1)], RGBColor(120, 120, 120)))?;
+        let font = FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal);
+        let label = format!("cgroup {:.0}%{}", fraction * 100.0, if near { " - near limit" } else { "" });
+        root.draw(&Text::new(label, (x0, px(8)), font.color(&if near { RED } else { options.foreground() })))?;
+    }
+
+    for (i, line) in header.lines().iter().enumerate() {
+        let font = if i == 0 { font_bold(12.0 * scale) } else { FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal) };
+        if let Some(line) = fit_text(root, line, &font, image_width - px(15) - gauge_width)? {
+            root.draw(&Text::new(line, (px(5), px(4) + px(15) * i as i32), font.color(&options.foreground())))?;
+        }
+    }
+    Ok(())
+}
+
+// Draws onto any plotters backend, so the PNG and the SVG views of a map
+// are the same picture.
+fn draw_memory_map<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, memory_regions: &[MemoryRegion], image_width: u32, image_height: u32, options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&options.background())?;
+    let scale = options.scale_factor;
+    let px = |v: i32| scaled(v, scale);
+    let banner_height = px(options.header.as_ref().map_or(0, header_height) as i32);
+    let (banner, root) = root.split_vertically(banner_height);
+    let root = &root;
+    if let Some(header) = &options.header {
+        draw_header(&banner, header, image_width as i32, options)?;
+    }
+    let image_height = image_height - banner_height as u32;
+    let legend_width = px(LEGEND_WIDTH as i32);
+    let legend_column = if options.legend == Some(LegendPosition::Right) { px(LEGEND_COLUMN_WIDTH as i32) as u32 } else { 0 };
+    let context_column = if options.context_bar { px(context::COLUMN_WIDTH as i32) as u32 } else { 0 };
+    let image_width = image_width - legend_column - context_column;
+
+    let mut markers: Vec<(i32, &str)> = Vec::new();
+    let mut address_labels: Vec<(i32, String)> = Vec::new();
+    let space = options.address_space.unwrap_or_else(|| context::AddressSpace::infer(memory_regions));
+    let boundaries = if options.boundaries { space.boundaries() } else { Vec::new() };
+    let anomalies: Vec<usize> = if options.boundaries { space.anomalies(memory_regions).into_iter().map(|(region, _)| region.start).collect() } else { Vec::new() };
+    let mut boundary_lines: Vec<(i32, &str)> = Vec::new();
+    let extents = layout(memory_regions, image_height, px(BREAK_HEIGHT), options);
+    progress::phase("drawing");
+    for (region, &(current_y, region_height_in_pixels)) in memory_regions.iter().zip(&extents) {
+        if region_weight(region, options.size_metric, options.scale) == 0.0 {
+            continue;
+        }
+        let (region_color, outlined) = options.emphasis.apply(region, region_color(region, options.color_by, &options.theme));
+
+        let bar = Rectangle::new(
+            [(legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels)],
+            RGBColor(region_color[0], region_color[1], region_color[2]).filled().stroke_width(0),
+        );
+        root.draw(&bar)?;
+
+        if options.textures && region.attributes.allocated {
+            let ink = if luminance(region_color) > 128.0 { &BLACK } else { &WHITE };
+            let (from, to) = ((legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels));
+            if region.attributes.writable {
+                draw_stripes(root, from, to, ink, px(5), false)?;
+            }
+            if region.attributes.executable {
+                draw_stripes(root, from, to, ink, px(5), true)?;
+            }
+        }
+
+        if options.is_collapsed(region) {
+            draw_break(root, current_y, region_height_in_pixels, region.size, image_width as i32, scale)?;
+        }
+
+        if outlined {
+            root.draw(&Rectangle::new(
+                [(legend_width, current_y), (image_width as i32 - 1, current_y + region_height_in_pixels.max(px(1)))],
+                BLACK.stroke_width(px(2) as u32),
+            ))?;
+        }
+
+        if options.pattern_by.is_some_and(|attribute| attribute.matches(region)) {
+            let ink = if luminance(region_color) > 128.0 { &BLACK } else { &WHITE };
+            draw_pattern(root, (legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels), ink, options.pattern, scale)?;
+        }
+
+        if region.guard {
+            root.draw(&Rectangle::new(
+                [(legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels.max(px(2)))],
+                RGBColor(60, 60, 60).filled(),
+            ))?;
+            draw_hatch(root, (legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels.max(px(2))), &YELLOW, px(3))?;
+        }
+
+        if options.audit && audit::is_writable_executable(region) {
+            let warning = RGBColor(255, 40, 0);
+            root.draw(&Rectangle::new(
+                [(legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels.max(px(3)))],
+                warning.filled(),
+            ))?;
+            draw_hatch(root, (legend_width, current_y), (image_width as i32, current_y + region_height_in_pixels.max(px(3))), &YELLOW, px(6))?;
+            root.draw(&Text::new("W+X", (legend_width - px(22), current_y), font_bold(10.0 * scale).color(&warning)))?;
+        }
+
+        if audit::is_deleted_mapping(region) {
+            let bottom = current_y + region_height_in_pixels.max(px(2));
+            draw_dashed_outline(root, (legend_width, current_y), (image_width as i32 - 1, bottom), &DELETED_COLOR, scale)?;
+            if region_height_in_pixels >= px(11) {
+                root.draw(&Text::new("DEL", (legend_width - px(48), current_y), font_bold(10.0 * scale).color(&DELETED_COLOR)))?;
+            }
+        }
+
+        if options.show_huge_pages {
+            if let Some(smaps) = &region.smaps {
+                let fraction = (smaps.huge_page_bytes() as f64 / region.size as f64).min(1.0);
+                let hatch_width = ((image_width as i32 - legend_width) as f64 * fraction) as i32;
+                draw_hatch(root, (legend_width, current_y), (legend_width + hatch_width, current_y + region_height_in_pixels), &WHITE, px(4))?;
+            }
+        }
+
+        if options.show_locked && region.smaps.as_ref().is_some_and(SmapsInfo::is_locked) {
+            let lock = RGBColor(230, 160, 0);
+            let bottom = current_y + region_height_in_pixels.max(px(2));
+            root.draw(&Rectangle::new([(legend_width, current_y), (image_width as i32 - 1, bottom)], lock.stroke_width(px(2) as u32)))?;
+            if region_height_in_pixels >= px(14) {
+                draw_lock(root, (image_width as i32 - px(13), current_y + px(2)), &lock, scale)?;
+            }
+        }
+
+        let y = |address: usize| current_y + ((address - region.start) as f64 / region.size as f64 * region_height_in_pixels as f64) as i32;
+        for (hole_start, hole_end) in &options.holes {
+            let (from, to) = ((*hole_start).max(region.start), (*hole_end).min(region.end));
+            if !region.attributes.allocated && from < to {
+                let (y0, y1) = (y(from), y(to).max(y(from) + px(2)));
+                root.draw(&Rectangle::new([(legend_width, y0), (image_width as i32, y1)], GREEN.mix(0.6).filled()))?;
+                root.draw(&Rectangle::new([(legend_width, y0), (image_width as i32 - 1, y1)], GREEN.stroke_width(px(2) as u32)))?;
+            }
+        }
+
+        for (backed_start, backed_end, backing) in &options.thp {
+            let (from, to) = ((*backed_start).max(region.start), (*backed_end).min(region.end));
+            if from < to {
+                let [r, g, b] = thp::color(*backing);
+                root.draw(&Rectangle::new([(legend_width, y(from)), (image_width as i32, y(to).max(y(from) + 1))], RGBColor(r, g, b).mix(0.8).filled()))?;
+            }
+        }
+
+        for (merged_start, merged_end) in &options.ksm {
+            let (from, to) = ((*merged_start).max(region.start), (*merged_end).min(region.end));
+            if from < to {
+                root.draw(&Rectangle::new([(legend_width, y(from)), (image_width as i32, y(to).max(y(from) + 1))], KSM_COLOR.mix(0.7).filled()))?;
+            }
+        }
+
+        if let Some((_, bits)) = options.entropy.iter().find(|(start, _)| *start == region.start) {
+            let [r, g, b] = entropy::color(*bits);
+            let strip = [(image_width as i32 - px(12), current_y), (image_width as i32, current_y + region_height_in_pixels.max(px(1)))];
+            root.draw(&Rectangle::new(strip, RGBColor(r, g, b).filled()))?;
+        }
+
+        for stack in &options.stacks {
+            let used = if stack.over_threshold { stacks::OVER_COLOR } else { stacks::USED_COLOR };
+            for (from, to, [r, g, b], opacity) in [(stack.start, stack.used_from, stacks::HEADROOM_COLOR, 0.3), (stack.used_from, stack.end, used, 0.75)] {
+                let (from, to) = (from.max(region.start), to.min(region.end));
+                if from < to {
+                    let (y0, y1) = (y(from), y(to).max(y(from) + px(2)));
+                    root.draw(&Rectangle::new([(legend_width, y0), (image_width as i32, y1)], RGBColor(r, g, b).mix(opacity).filled()))?;
+                }
+            }
+        }
+
+        for (highlight_start, highlight_end) in &options.highlights {
+            let (from, to) = ((*highlight_start).max(region.start), (*highlight_end).min(region.end));
+            if from < to {
+                let highlight = RGBColor(0, 190, 255);
+                let (y0, y1) = (y(from), y(to).max(y(from) + px(2)));
+                root.draw(&Rectangle::new([(legend_width, y0), (image_width as i32, y1)], highlight.mix(0.45).filled()))?;
+                root.draw(&Rectangle::new([(legend_width, y0), (image_width as i32 - 1, y1)], highlight.stroke_width(px(2) as u32)))?;
+            }
+        }
+
+        let font = FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal);
+        // With a ruler the sizes move into the bar.
+        let name = match display_name(region) {
+            name if options.axis_labels == AxisLabels::Regions => {
+                address_labels.push((current_y, format!("{:#x} ({:#x})", region.start, region.size)));
+                name
+            }
+            Some(name) => Some(format!("{} ({})", name, format_size(region.size))),
+            None if !options.is_collapsed(region) => Some(format_size(region.size)),
+            None => None,
+        };
+        let name = match (name, region.smaps.as_ref().map(SmapsInfo::interesting_flags)) {
+            (Some(name), Some(flags)) if options.show_vm_flags && !flags.is_empty() => Some(format!("{} [{}]", name, flags.join(" "))),
+            (name, _) => name,
+        };
+        if let Some(name) = name {
+            if region_height_in_pixels >= px(11) {
+                let bar_width = image_width as i32 - legend_width;
+                if let Some(name) = fit_text(root, &name, &font, bar_width - px(6))? {
+                    let text_color = if luminance(region_color) > 128.0 { &BLACK } else { &WHITE };
+                    root.draw(&Text::new(name, (legend_width + px(3), current_y + px(1)), font.color(text_color)))?;
+                }
+            }
+        }
+
+        if anomalies.contains(&region.start) && region.attributes.allocated {
+            let bottom = current_y + region_height_in_pixels.max(px(3));
+            root.draw(&Rectangle::new([(legend_width, current_y), (image_width as i32 - 1, bottom)], context::BOUNDARY_COLOR.stroke_width(px(3) as u32)))?;
+        }
+
+        for (address, label) in &boundaries {
+            if region.start as u128 <= *address && *address < region.end as u128 {
+                let within = (*address - region.start as u128) as f64 / region.size as f64;
+                boundary_lines.push((current_y + (within * region_height_in_pixels as f64) as i32, label.as_str()));
+            }
+        }
+
+        for (address, label) in &options.annotations {
+            if region.start <= *address && *address < region.end {
+                let within = (*address - region.start) as f64 / region.size as f64;
+                markers.push((current_y + (within * region_height_in_pixels as f64) as i32, label.as_str()));
+            }
+        }
+    }
+
+    if options.axis_labels != AxisLabels::Regions {
+        draw_ruler(root, memory_regions, &extents, options)?;
+    }
+
+    let label_height = px(12);
+    let wanted: Vec<i32> = address_labels.iter().map(|(y, _)| *y).collect();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * scale, FontStyle::Normal);
+    for ((y, label), top) in address_labels.into_iter().zip(place_labels(&wanted, label_height, image_height as i32, options.label_placement)) {
+        if let Some(top) = top {
+            if top != y {
+                root.draw(&PathElement::new(vec![(px(2), y), (px(22), top + label_height / 2)], options.foreground()))?;
+            }
+            root.draw(&Text::new(label, (px(25), top), font.color(&options.foreground())))?;
+        }
+    }
+
+    for (y, label) in boundary_lines {
+        context::draw_boundary(root, y, label, (legend_width, image_width as i32), scale)?;
+    }
+
+    markers.sort_by_key(|(y, _)| *y);
+    let wanted: Vec<i32> = markers.iter().map(|(y, _)| y + px(2)).collect();
+    for ((y, label), top) in markers.into_iter().zip(place_labels(&wanted, px(14), image_height as i32, options.label_placement)) {
+        draw_marker(root, y, top, label, image_width as i32, scale)?;
+    }
+
+    if let Some(notice) = &options.notice {
+        root.draw(&Rectangle::new([(0, 0), (image_width as i32, px(14))], options.background().filled()))?;
+        root.draw(&Text::new(notice.as_str(), (px(5), px(1)), font_bold(11.0 * scale).color(&RED)))?;
+    }
+
+    if options.context_bar {
+        context::draw_context_bar(root, memory_regions, image_width as i32 + px(12), image_height as i32, options)?;
+    }
+
+    if let Some(position) = options.legend {
+        let entries = legend_entries(memory_regions, options);
+        let rows = px(20) * entries.len() as i32;
+        let origin = match position {
+            LegendPosition::BottomLeft => (px(5), image_height as i32 - rows),
+            LegendPosition::TopLeft => (px(5), px(20)),
+            LegendPosition::Right => ((image_width + context_column) as i32 + px(5), px(20)),
+        };
+        if position != LegendPosition::Right {
+            // The corners overlay the address column.
+            root.draw(&Rectangle::new([(0, origin.1 - px(5)), (legend_width - px(1), origin.1 + rows - px(5))], options.background().filled()))?;
+        }
+        draw_legend(root, origin, &entries, options)?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+// Where draw_memory_map puts each region's bar in the whole image, as
+// top left and bottom right corners, for output that points back at the
+// regions it drew.
+#[cfg(not(target_arch = "wasm32"))]
+fn bar_boxes(memory_regions: &[MemoryRegion], (image_width, image_height): (u32, u32), options: &RenderOptions) -> Vec<((i32, i32), (i32, i32))> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let banner_height = px(options.header.as_ref().map_or(0, header_height) as i32);
+    let legend_column = if options.legend == Some(LegendPosition::Right) { px(LEGEND_COLUMN_WIDTH as i32) } else { 0 };
+    let context_column = if options.context_bar { px(context::COLUMN_WIDTH as i32) } else { 0 };
+    let right = image_width as i32 - legend_column - context_column;
+    layout(memory_regions, image_height - banner_height as u32, px(BREAK_HEIGHT), options)
+        .into_iter()
+        .map(|(top, height)| ((px(LEGEND_WIDTH as i32), banner_height + top), (right, banner_height + top + height)))
+        .collect()
+}
+
+// A swatch in the legend. Permission entries keep their attributes so the
+// swatch can carry the same stripes as the bars.
+struct LegendEntry {
+    label: String,
+    color: Rgb<u8>,
+    attributes: Option<MemoryAttributes>,
+    patterned: bool,
+}
+
+impl LegendEntry {
+    fn new(label: impl Into<String>, color: Rgb<u8>) -> Self {
+        LegendEntry { label: label.into(), color, attributes: None, patterned: false }
+    }
+}
+
+// Built from the same decisions region_color makes, listing only the
+// colors that appear in this map.
+fn legend_entries(memory_regions: &[MemoryRegion], options: &RenderOptions) -> Vec<LegendEntry> {
+    match options.color_by {
+        ColorBy::Permissions | ColorBy::Rss => {
+            let mut permissions = [false; 8];
+            let mut gaps = false;
+            let mut groups: Vec<&str> = Vec::new();
+            for region in memory_regions {
+                if special_region_color(region).is_some() {
+                    continue;
+                }
+                match region.anon_group() {
+                    Some(name) if region.attributes.allocated => groups.push(name),
+                    _ if !region.attributes.allocated => gaps = true,
+                    _ => permissions[theme::permission_index(&region.attributes)] = true,
+                }
+            }
+            groups.sort_unstable();
+            groups.dedup();
+
+            let mut entries = Vec::new();
+            for (index, label) in theme::PERMISSIONS.iter().enumerate().rev() {
+                if permissions[index] {
+                    let attributes = MemoryAttributes {
+                        readable: index & 4 != 0,
+                        writable: index & 2 != 0,
+                        executable: index & 1 != 0,
+                        shared: false,
+                        allocated: true,
+                    };
+                    entries.push(LegendEntry { attributes: Some(attributes), ..LegendEntry::new(*label, options.theme.permissions[index]) });
+                }
+            }
+            if gaps {
+                entries.push(LegendEntry::new("unmapped", options.theme.gap));
+            }
+            for (_, label, rgb) in SPECIAL_REGIONS {
+                if memory_regions.iter().any(|r| special_region_color(r) == Some(Rgb(rgb))) {
+                    entries.push(LegendEntry::new(label, Rgb(rgb)));
+                }
+            }
+            entries.extend(jit_entry(memory_regions));
+            entries.extend(shadow_entry(memory_regions));
+            entries.extend(shared_memory_entries(memory_regions));
+            entries.extend(groups.into_iter().map(|name| LegendEntry::new(name, name_color(name))));
+            if memory_regions.iter().any(|r| r.guard) {
+                entries.push(LegendEntry::new("guard page", Rgb([60, 60, 60])));
+            }
+            for (backing, label, rgb) in thp::BACKINGS {
+                let bytes: usize = options.thp.iter().filter(|range| range.2 == backing).map(|(start, end, _)| end - start).sum();
+                if bytes > 0 {
+                    entries.push(LegendEntry::new(format!("{} ({})", label, format_size(bytes)), Rgb(rgb)));
+                }
+            }
+            if !options.entropy.is_empty() {
+                for (bits, label) in [(0.0, "entropy 0 (zeros)"), (4.5, "entropy 4.5 (code, text)"), (8.0, "entropy 8 (random)")] {
+                    entries.push(LegendEntry::new(label, Rgb(entropy::color(bits))));
+                }
+            }
+            if !options.stacks.is_empty() {
+                entries.push(LegendEntry::new("stack used", Rgb(stacks::USED_COLOR)));
+                entries.push(LegendEntry::new("stack headroom", Rgb(stacks::HEADROOM_COLOR)));
+                if options.stacks.iter().any(|stack| stack.over_threshold) {
+                    entries.push(LegendEntry::new("stack over threshold", Rgb(stacks::OVER_COLOR)));
+                }
+            }
+            if !options.ksm.is_empty() {
+                let RGBColor(r, g, b) = KSM_COLOR;
+                let merged: usize = options.ksm.iter().map(|(start, end)| end - start).sum();
+                entries.push(LegendEntry::new(format!("KSM merged ({})", format_size(merged)), Rgb([r, g, b])));
+            }
+            let deleted = memory_regions.iter().filter(|r| audit::is_deleted_mapping(r)).count();
+            if deleted > 0 {
+                let RGBColor(r, g, b) = DELETED_COLOR;
+                entries.push(LegendEntry::new(format!("deleted file ({})", format_count(deleted)), Rgb([r, g, b])));
+            }
+            if options.audit && memory_regions.iter().any(audit::is_writable_executable) {
+                entries.push(LegendEntry::new("W+X", Rgb([255, 40, 0])));
+            }
+            if let Some(attribute) = options.pattern_by.filter(|attribute| memory_regions.iter().any(|r| attribute.matches(r))) {
+                entries.push(LegendEntry { patterned: true, ..LegendEntry::new(attribute.label(), Rgb([200, 200, 200])) });
+            }
+            if options.color_by == ColorBy::Rss {
+                let rss: usize = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).sum();
+                entries.push(LegendEntry::new("Fully resident", resident_shade(Rgb([60, 60, 60]), 1.0)));
+                entries.push(LegendEntry::new("50% resident", resident_shade(Rgb([60, 60, 60]), 0.5)));
+                entries.push(LegendEntry::new("Not resident", resident_shade(Rgb([60, 60, 60]), 0.0)));
+                entries.push(LegendEntry::new(format!("RSS {}", format_size(rss)), Rgb([255, 255, 255])));
+            }
+            entries
+        }
+        ColorBy::File => {
+            let mut files: Vec<&str> = Vec::new();
+            for path in memory_regions.iter().filter(|r| r.attributes.allocated && !r.guard).filter_map(backing_file) {
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+            let mut entries: Vec<LegendEntry> = files
+                .iter()
+                .take(FILE_LEGEND_LIMIT)
+                .map(|path| LegendEntry::new(path.rsplit('/').next().unwrap_or(path), name_color(path)))
+                .collect();
+            if files.len() > FILE_LEGEND_LIMIT {
+                let RGBColor(r, g, b) = options.background();
+                entries.push(LegendEntry::new(format!("{} more files", files.len() - FILE_LEGEND_LIMIT), Rgb([r, g, b])));
+            }
+            if memory_regions.iter().any(|r| r.attributes.allocated && backing_file(r).is_none() && special_region_color(r).is_none() && r.anon_group().is_none()) {
+                entries.push(LegendEntry::new("anonymous", Rgb(ANONYMOUS_COLOR)));
+            }
+            entries.push(LegendEntry::new("unmapped", options.theme.gap));
+            for (_, label, rgb) in SPECIAL_REGIONS {
+                if memory_regions.iter().any(|r| special_region_color(r) == Some(Rgb(rgb))) {
+                    entries.push(LegendEntry::new(label, Rgb(rgb)));
+                }
+            }
+            entries.extend(jit_entry(memory_regions));
+            entries.extend(shadow_entry(memory_regions));
+            entries.extend(shared_memory_entries(memory_regions));
+            entries
+        }
+        ColorBy::Swap => {
+            let swap: usize = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.swap).sum();
+            let swap_pss: usize = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.swap_pss).sum();
+            vec![
+                LegendEntry::new("Not swapped", swap_color(0.0)),
+                LegendEntry::new("50% swapped", swap_color(0.5)),
+                LegendEntry::new("Fully swapped", swap_color(1.0)),
+                LegendEntry::new("unmapped", options.theme.gap),
+                LegendEntry::new(format!("Swap {}", format_size(swap)), Rgb([255, 255, 255])),
+                LegendEntry::new(format!("SwapPss {}", format_size(swap_pss)), Rgb([255, 255, 255])),
+            ]
+        }
+    }
+}
+
+fn draw_legend<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, (legend_x, legend_y): (i32, i32), entries: &[LegendEntry], options: &RenderOptions) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let y = legend_y + px(20) * i as i32;
+        let (from, to) = ((legend_x, y), (legend_x + px(10), y + px(10)));
+        let Rgb([r, g, b]) = entry.color;
+        root.draw(&Rectangle::new([from, to], RGBColor(r, g, b).filled()))?;
+        root.draw(&Rectangle::new([from, to], RGBColor(160, 160, 160)))?;
+        if let Some(attributes) = entry.attributes.as_ref().filter(|_| options.textures) {
+            let ink = if luminance(entry.color) > 128.0 { &BLACK } else { &WHITE };
+            if attributes.writable {
+                draw_stripes(root, from, to, ink, px(3), false)?;
+            }
+            if attributes.executable {
+                draw_stripes(root, from, to, ink, px(3), true)?;
+            }
+        }
+        if entry.patterned {
+            draw_pattern(root, from, to, &BLACK, options.pattern, options.scale_factor / 2.0)?;
+        }
+        root.draw(&Text::new(entry.label.as_str(), (legend_x + px(15), y - px(2)), font.color(&options.foreground())))?;
+    }
+
+    Ok(())
+}
+
+// A capture of a local process that reports failure instead of exiting,
+// for the long-running modes.
+#[cfg(not(target_arch = "wasm32"))]
+fn capture_local(pid: u32, needs_smaps: bool, cache: Option<std::time::Duration>) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+    let (mut memory_regions, warnings) = source::load(&source::Procfs { pid, smaps: needs_smaps, cache })?;
+    threads::label_thread_stacks(&mut memory_regions, Some(pid));
+    guards::mark_guard_pages(&mut memory_regions);
+    sanitizer::label_shadow(&mut memory_regions);
+    Ok((memory_regions, warnings))
+}
+
+// With --strict, a capture with any line that didn't parse ends the run.
+#[cfg(not(target_arch = "wasm32"))]
+fn enforce_strict(strict: bool, warnings: &Warnings) {
+    if let (true, Err(e)) = (strict, warnings.strict()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_regions(args: &ExportArgs, pid: Option<u32>, memory_regions: &[MemoryRegion], warnings: &Warnings) {
+    if let Some(format) = args.export.as_deref() {
+        let order: SortOrder = args.sort.parse().unwrap();
+        let mut sorted: Vec<&MemoryRegion> = memory_regions.iter().collect();
+        order.sort(&mut sorted);
+        let sorted: Vec<MemoryRegion> = sorted.into_iter().cloned().collect();
+        let memory_regions = &sorted;
+        let document = match format {
+            "csv" => report::export_csv(memory_regions),
+            "dot" => {
+                let name = pid.and_then(|pid| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()).map_or_else(|| "process".to_string(), |comm| comm.trim().to_string());
+                let rss = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).sum();
+                graph::dot(&[overview::ProcessSample { pid: pid.unwrap_or(0), name, rss, memory_regions: memory_regions.clone() }])
+            }
+            _ => serde_json::to_string_pretty(&report::export(pid, memory_regions, warnings)).expect("Unable to serialize the region list") + "\n",
+        };
+        write_export(args, format, document);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_export(args: &ExportArgs, format: &str, document: String) {
+    let default_path = format!("memory_regions.{}", format);
+    match args.export_path.as_deref().unwrap_or(&default_path) {
+        "-" => print!("{}", document),
+        path => std::fs::write(path, document).expect("Unable to write the region list"),
+    }
+}
+
+// --export dot over several processes, as one graph.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_graph(args: &ExportArgs, processes: &[overview::ProcessSample]) {
+    if args.export.as_deref() == Some("dot") {
+        write_export(args, "dot", graph::dot(processes));
+    }
+}
+
+// Truncation, gaps and the console summaries every renderer starts from.
+// With --stdout the encoded map owns stdout, so the summaries printed while
+// preparing a render go to stderr instead.
+#[cfg(not(target_arch = "wasm32"))]
+static SUMMARIES_TO_STDERR: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn print_summary(line: &str) {
+    match SUMMARIES_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+        true => eprintln!("{}", line),
+        false => println!("{}", line),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn prepare_regions(memory_regions: Vec<MemoryRegion>, max_regions: usize, options: &mut RenderOptions) -> Vec<MemoryRegion> {
+    let (memory_regions, notice) = truncate_regions(memory_regions, max_regions);
+    if let Some(notice) = &notice {
+        eprintln!("{}", notice);
+    }
+    if notice.is_some() {
+        options.notice = notice;
+    }
+    // Gaps can at most double the count.
+    let mut with_gap_regions = Vec::with_capacity(memory_regions.len() * 2 + 1);
+    with_gap_regions.extend(with_gaps(memory_regions));
+    let memory_regions = with_gap_regions;
+
+    if options.show_huge_pages {
+        let huge: usize = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(SmapsInfo::huge_page_bytes).sum();
+        let mapped: usize = memory_regions.iter().filter(|r| r.attributes.allocated).map(|r| r.size).sum();
+        print_summary(&format!("Huge pages: {:#x} of {:#x} mapped bytes", huge, mapped));
+    }
+    progress::phase(&format!("laying out {} regions", format_count(memory_regions.len())));
+    memory_regions
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn render_image(memory_regions: Vec<MemoryRegion>, max_regions: usize, options: &mut RenderOptions) -> image::RgbImage {
+    let memory_regions = prepare_regions(memory_regions, max_regions, options);
+    compose_image(&memory_regions, options)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn compose_image(memory_regions: &[MemoryRegion], options: &RenderOptions) -> image::RgbImage {
+    let (width, height) = options.image_size();
+    let mut img = renderer::render(renderer::Bitmap::new((width, height)), memory_regions, (width, height), options).expect("Unable to create memory map image");
+    let panel_width = scaled(PANEL_WIDTH as i32, options.scale_factor) as u32;
+    let mut panels = Vec::new();
+    if let Some(fragmentation) = &options.fragmentation_panel {
+        panels.push(fragmentation::draw_histogram_panel(fragmentation, panel_width, height).expect("Unable to draw the fragmentation panel"));
+    }
+    if let Some(buckets) = &options.size_histogram_panel {
+        panels.push(sizes::draw_panel(buckets, panel_width, height).expect("Unable to draw the region size panel"));
+    }
+    if let Some(totals) = &options.numa_panel {
+        panels.push(numa::draw_panel(totals, panel_width, height).expect("Unable to draw the NUMA panel"));
+    }
+    if let Some(memory) = &options.cgroup_panel {
+        panels.push(cgroup::draw_panel(memory, panel_width, height).expect("Unable to draw the cgroup panel"));
+    }
+    // A chart over time needs more room than the others.
+    if let Some(panel) = &options.massif_panel {
+        panels.push(massif::draw_panel(panel, panel_width * 2, height).expect("Unable to draw the massif panel"));
+    }
+    for panel in panels {
+        let map_width = img.width();
+        let mut composed = image::RgbImage::new(map_width + panel.width(), height);
+        image::imageops::replace(&mut composed, &img, 0, 0);
+        image::imageops::replace(&mut composed, &panel, map_width, 0);
+        img = composed;
+    }
+    img
+}
+
+// Consecutive runs of regions in numbered files. Each tile also draws
+// `overlap` regions of its neighbours, with a marker where its own run
+// starts and where the next tile takes over.
+#[cfg(not(target_arch = "wasm32"))]
+fn render_tiles(memory_regions: Vec<MemoryRegion>, per_tile: usize, overlap: usize, max_regions: usize, options: &RenderOptions) {
+    let count = memory_regions.len().div_ceil(per_tile);
+    let tiles = progress::batch(count, "tiles");
+    for tile in 0..count {
+        let (own_start, own_end) = (tile * per_tile, ((tile + 1) * per_tile).min(memory_regions.len()));
+        let (from, to) = (own_start.saturating_sub(overlap), (own_end + overlap).min(memory_regions.len()));
+        let mut tile_options = options.clone();
+        tile_options.notice = Some(format!("tile {}/{}: {:#x}-{:#x}", tile + 1, count, memory_regions[own_start].start, memory_regions[own_end - 1].end));
+        if from < own_start {
+            tile_options.annotations.push((memory_regions[own_start].start, format!("tile {} starts", tile + 1)));
+        }
+        if own_end < to {
+            tile_options.annotations.push((memory_regions[own_end].start, format!("tile {} continues", tile + 2)));
+        }
+
+        let mut tile_regions = prepare_regions(memory_regions[from..to].to_vec(), max_regions, &mut tile_options);
+        // Only the first tile starts from address zero.
+        if from > 0 && tile_regions.first().is_some_and(|region| !region.attributes.allocated) {
+            tile_regions.remove(0);
+        }
+        let path = format!("memory_map_{:03}.png", tile + 1);
+        compose_image(&tile_regions, &tile_options).save(&path).expect("Unable to save image");
+        tiles.println(&format!("Wrote {} ({} regions)", path, own_end - own_start));
+        tiles.inc();
+    }
+}
+
+// What becomes of an encoded PNG or PDF: --stdout streams it in place of
+// the file, --open hands the written file to the default viewer and
+// --clipboard copies a PNG map.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Default)]
+struct Delivery {
+    stdout: bool,
+    open: bool,
+    clipboard: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Delivery {
+    fn write(self, path: &str, bytes: &[u8]) {
+        use std::io::Write;
+        match self.stdout {
+            true => std::io::stdout().lock().write_all(bytes).expect("Unable to write to stdout"),
+            false => std::fs::write(path, bytes).unwrap_or_else(|e| panic!("Unable to save {}: {}", path, e)),
+        }
+        self.opened(path);
+    }
+
+    // For outputs written elsewhere, such as the tiles and the charts.
+    fn opened(self, path: &str) {
+        if self.open && !self.stdout {
+            viewer::open(path);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn render(memory_regions: Vec<MemoryRegion>, max_regions: usize, options: &mut RenderOptions, format: OutputFormat, delivery: Delivery) {
+    match format {
+        OutputFormat::Text { unicode } => {
+            let memory_regions = prepare_regions(memory_regions, max_regions, options);
+            progress::pause();
+            print!("{}", text::render(&memory_regions, options, TEXT_LINES, unicode));
+        }
+        OutputFormat::Pdf => {
+            let memory_regions = prepare_regions(memory_regions, max_regions, options);
+            if options.fragmentation_panel.is_some() || options.size_histogram_panel.is_some() || options.numa_panel.is_some() || options.cgroup_panel.is_some() || options.massif_panel.is_some() {
+                eprintln!("Side panels are only drawn on bitmap output");
+            }
+            let (width, height) = options.image_size();
+            let pdf = renderer::render(renderer::Pdf::default(), &memory_regions, (width, height), options).expect("Unable to create memory map PDF");
+            progress::pause();
+            delivery.write("memory_map.pdf", &pdf);
+        }
+        OutputFormat::Svg | OutputFormat::Html => {
+            let memory_regions = prepare_regions(memory_regions, max_regions, options);
+            if options.fragmentation_panel.is_some() || options.size_histogram_panel.is_some() || options.numa_panel.is_some() || options.cgroup_panel.is_some() || options.massif_panel.is_some() {
+                eprintln!("Side panels are only drawn on bitmap output");
+            }
+            let size = options.image_size();
+            let svg = renderer::render(renderer::Svg::default(), &memory_regions, size, options).expect("Unable to create memory map SVG");
+            progress::pause();
+            if format == OutputFormat::Svg {
+                delivery.write("memory_map.svg", hyperlinks::anchor_svg(svg, &memory_regions, size, options, false).as_bytes());
+            } else {
+                let title = options.header.as_ref().and_then(|header| header.lines().into_iter().next()).unwrap_or_else(|| "Memory map".to_string());
+                delivery.write("memory_map.html", hyperlinks::html(svg, &memory_regions, size, options, &title).as_bytes());
+            }
+        }
+        OutputFormat::Png => match options.tiles {
+            Some((per_tile, overlap)) => {
+                render_tiles(memory_regions, per_tile, overlap, max_regions, options);
+                delivery.opened("memory_map_001.png");
+            }
+            None => {
+                let image = render_image(memory_regions, max_regions, options);
+                if delivery.clipboard {
+                    if let Err(e) = clipboard::copy_image(&image) {
+                        eprintln!("Unable to copy the map to the clipboard: {}", e);
+                    }
+                }
+                progress::phase("encoding memory_map.png");
+                let mut png = Vec::new();
+                image::DynamicImage::ImageRgb8(image).write_to(&mut png, image::ImageOutputFormat::Png).expect("Unable to encode image");
+                progress::pause();
+                delivery.write("memory_map.png", &png);
+            }
+        },
+        OutputFormat::Terminal(protocol) => {
+            let image = render_image(memory_regions, max_regions, options);
+            progress::phase("encoding");
+            let encoded = terminal::encode(&image, protocol);
+            progress::pause();
+            print!("{}", encoded);
+        }
+    }
+    progress::pause();
+}
+
+fn render_options(args: &DrawArgs) -> RenderOptions {
+    RenderOptions {
+        show_huge_pages: args.hugepages,
+        show_locked: args.locked,
+        show_vm_flags: args.vm_flags,
+        ksm: Vec::new(),
+        entropy: Vec::new(),
+        #[cfg(not(target_arch = "wasm32"))]
+        previews: Vec::new(),
+        stacks: Vec::new(),
+        thp: Vec::new(),
+        color_by: args.color_by.parse().unwrap(),
+        size_metric: args.size_metric.parse().unwrap(),
+        scale: args.scale.parse().unwrap(),
+        min_region_px: args.min_region_px,
+        max_region_fraction: args.max_region_fraction,
+        audit: args.audit,
+        annotations: Vec::new(),
+        emphasis: Emphasis {
+            dim: args.dim.clone(),
+            emphasize: args.emphasize.clone(),
+            dim_opacity: args.dim_opacity.clamp(0.0, 1.0),
+        },
+        notice: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        fragmentation_panel: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        size_histogram_panel: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        numa_panel: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        cgroup_panel: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        massif_panel: None,
+        holes: Vec::new(),
+        #[cfg(not(target_arch = "wasm32"))]
+        tiles: args.tile_regions.map(|per_tile| (per_tile.get(), args.tile_overlap)),
+        highlights: Vec::new(),
+        filtered: Vec::new(),
+        width: args.width,
+        height: args.height,
+        scale_factor: args.scale_factor,
+        theme: args.theme.clone().unwrap_or_else(|| Theme::builtin("classic").unwrap()),
+        textures: args.palette == "colorblind",
+        dark: args.dark,
+        label_placement: args.labels.parse().unwrap(),
+        pattern_by: match args.pattern_by.as_str() {
+            "none" => None,
+            attribute => Some(attribute.parse().unwrap()),
+        },
+        pattern: if args.pattern == "dots" { Pattern::Dots } else { Pattern::Stripes },
+        header: None,
+        context_bar: args.context_bar,
+        address_space: None,
+        boundaries: args.boundaries,
+        axis_labels: args.axis_labels.parse().unwrap(),
+        tick_spacing: args.tick_spacing,
+        minor_ticks: args.minor_ticks,
+        cgroup_margin: args.cgroup_margin / 100.0,
+        legend: match args.legend.as_str() {
+            "none" => None,
+            position => Some(position.parse().unwrap()),
+        },
+        collapse_gaps: args.collapse_gaps,
+    }
+}
+
+// Regions made ready for drawing with the command line's defaults at the
+// given size, for callers without a command line: the C ABI and the
+// browser build. Unlike prepare_regions it prints nothing.
+fn embedded_input(memory_regions: Vec<MemoryRegion>, width: u32, height: u32) -> Result<(Vec<MemoryRegion>, RenderOptions), String> {
+    let args = DrawArgs { width, height, ..cli::defaults() };
+    let options = render_options(&args);
+    let (memory_regions, notice) = truncate_regions(memory_regions, args.max_regions);
+    Ok((with_gaps(memory_regions).collect(), RenderOptions { notice, ..options }))
+}
+
+// The maps text drawn as SVG. Nothing here reads /proc or the filesystem,
+// so it is what the wasm build exports.
+pub fn render_svg(maps: &str, width: u32, height: u32) -> Result<String, String> {
+    let (memory_regions, _) = validate::validate(parse_memory_regions(maps.as_bytes()).0);
+    let (memory_regions, options) = embedded_input(memory_regions, width, height)?;
+    let (width, height) = options.image_size();
+    renderer::render(renderer::Svg::default(), &memory_regions, (width, height), &options).map_err(|e| e.to_string())
+}
+
+// The calling program's own address space as SVG, for embedding as a
+// self-diagnostic.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_self_svg(width: u32, height: u32) -> Result<String, String> {
+    let maps = std::fs::read_to_string("/proc/self/maps").map_err(|e| format!("Unable to read /proc/self/maps: {}", e))?;
+    render_svg(&maps, width, height)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(capture::CAPTURE_STAGE_ARG) {
+        capture::run_capture_stage(&args[2..]);
+    }
+
+    let args = config::apply(&cli::Cli::command(), args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let cli = cli::Cli::parse_args(args);
+    logging::init(&cli.log);
+    let strict = cli.strict;
+    let quiet = cli.log.quiet;
+    let cli::Invocation { process, command, args, mode } = cli.invocation();
+    // The long-running modes log instead.
+    let long_running = matches!(mode, Mode::Watch | Mode::Serve { .. } | Mode::Record(_)) || args.agent || args.serve_metrics.is_some();
+    if !quiet && !long_running {
+        progress::enable();
+    }
+    if let Mode::Completions(shell) = &mode {
+        print!("{}", completions::generate(shell));
+        return;
+    }
+
+    #[cfg(feature = "self-update")]
+    {
+        if let Mode::SelfUpdate = mode {
+            match update::self_update() {
+                Ok(message) => println!("{}", message),
+                Err(e) => {
+                    eprintln!("self-update failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        if args.check_update {
+            match update::check_update() {
+                Ok(Some(release)) => println!("memlayout {} is available (running {})", release.version, env!("CARGO_PKG_VERSION")),
+                Ok(None) => println!("memlayout {} is up to date", env!("CARGO_PKG_VERSION")),
+                Err(e) => eprintln!("Unable to check for updates: {}", e),
+            }
+            if process.is_none() && args.target.pid.is_none() && args.target.package.is_none() {
+                return;
+            }
+        }
+    }
+
+
+    let mut options = render_options(&args.draw);
+    if options.textures && args.draw.theme.is_none() {
+        options.theme = Theme::builtin("colorblind").unwrap();
+    }
+    // A black gap would disappear into the dark background.
+    if options.dark && luminance(options.theme.gap) < 60.0 {
+        options.theme.gap = Rgb([60, 66, 92]);
+    }
+    let format: OutputFormat = args.format.parse().unwrap();
+    // report is the render pipeline with every region listed and no image.
+    let report = matches!(mode, Mode::Report);
+    let no_image = args.export.no_image || report;
+    if options.tiles.is_some() && !matches!(format, OutputFormat::Png) {
+        eprintln!("--tile-regions only splits PNG output");
+    }
+    let delivery = Delivery { stdout: args.stdout, open: args.open, clipboard: args.clipboard };
+    if args.clipboard && !matches!(format, OutputFormat::Png) {
+        eprintln!("--clipboard only copies PNG output");
+    }
+    if args.stdout {
+        if !matches!(format, OutputFormat::Png | OutputFormat::Pdf | OutputFormat::Svg | OutputFormat::Html) {
+            eprintln!("--stdout only applies to PNG, PDF, SVG and HTML output, the other formats print to stdout already");
+        }
+        SUMMARIES_TO_STDERR.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    let group_by: Vec<&str> = args.filter.group_by.iter().map(String::as_str).collect();
+    let group_by_file = group_by.contains(&"file");
+    let top = if report { Some(args.top.unwrap_or(usize::MAX)) } else { args.top };
+    let top_by: SizeMetric = args.top_by.parse().unwrap();
+    let sort: SortOrder = args.export.sort.parse().unwrap();
+    let filters = filter::Filters {
+        include: args.filter.include.clone(),
+        exclude: args.filter.exclude.clone(),
+        perms: args.filter.perm.clone(),
+        vm_flags: args.filter.vm_flag.clone(),
+        min_size: args.filter.min_size,
+        max_size: args.filter.max_size,
+        exclude_shadow: args.filter.exclude_shadow,
+    };
+    let assertions = &args.fail_if;
+    if let Mode::Animate(animate) = &mode {
+        let snapshots = animate::load_series(&animate.dir).expect("Unable to read the snapshots");
+        if let Some(count) = animate.timeline {
+            if snapshots.is_empty() {
+                eprintln!("No snapshots in {}", animate.dir);
+                std::process::exit(1);
+            }
+            let snapshots = timeline::sample(snapshots, count);
+            options.size_metric = SizeMetric::Virtual;
+            let path = if animate.output == "memory_map.gif" { "memory_timeline.png" } else { animate.output.as_str() };
+            timeline::draw_timeline(&snapshots, &options, path).expect("Unable to draw the timeline");
+            println!("Wrote {} snapshots to {}", snapshots.len(), path);
+            return;
+        }
+        let first = snapshots.first().map_or(0, |snapshot| snapshot.timestamp);
+        let frames: Vec<Vec<MemoryRegion>> = snapshots.iter().map(|snapshot| snapshot.regions.clone()).collect();
+        // Only virtual sizes are the same for an interval in every frame.
+        options.size_metric = SizeMetric::Virtual;
+        let rendered = progress::batch(frames.len(), "frames");
+        let images = animate::align_frames(&frames).into_iter().zip(&snapshots).map(|(frame, snapshot)| {
+            options.notice = Some(format!("pid {} {} (+{}s)", snapshot.pid, header::format_timestamp(snapshot.timestamp), snapshot.timestamp - first));
+            rendered.inc();
+            render_image(frame, usize::MAX, &mut options)
+        });
+        let delay = animate.frame_delay;
+        let format: AnimationFormat = animate.format.parse().unwrap();
+        let path = format.output(&animate.output, "memory_map.gif");
+        animate::write(images, delay, &path, format).expect("Unable to write the animation");
+        println!("Wrote {} frames to {}", snapshots.len(), path);
+        return;
+    }
+
+    if let Mode::Diff(compare) = &mode {
+        let (before, after) = (compare.before.as_str(), compare.after.as_str());
+        let load = |path: &str| {
+            let memory_regions = diff::load(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            filters.apply(memory_regions).0
+        };
+        let (before_regions, after_regions) = (load(before), load(after));
+        if compare.by_object || diff::different_processes(before, after) {
+            if compare.json.is_some() {
+                eprintln!("--json lists changes by address, which two processes don't share; leaving it out");
+            }
+            diff::print_objects(&before_regions, &after_regions, (before, after), compare.top_objects);
+            options.size_metric = SizeMetric::Virtual;
+            let path = compare.output.as_str();
+            diff::draw_by_object(&before_regions, &after_regions, (before, after), &options, path).expect("Unable to draw the comparison");
+            println!("Wrote {}", path);
+            return;
+        }
+        let pairs = diff::pair(before_regions.clone(), after_regions.clone());
+        if let Some(json_path) = compare.json.as_deref() {
+            let report = diff::report(&before_regions, &after_regions, &pairs, (before, after));
+            let json = serde_json::to_string_pretty(&report).expect("Unable to serialize the diff") + "\n";
+            match json_path {
+                "-" => print!("{}", json),
+                path => std::fs::write(path, json).expect("Unable to write the diff"),
+            }
+        }
+        // JSON on stdout stays the only thing there.
+        let listing = compare.json.as_deref() != Some("-");
+        for run in diff::changed_runs(&pairs).into_iter().filter(|_| listing) {
+            println!("{:#x}-{:#x} {}", pairs[run.0].1.start, pairs[run.1].1.end, diff::describe_run(&pairs, run));
+        }
+        // Pieces of a region have no RSS of their own.
+        options.size_metric = SizeMetric::Virtual;
+        let path = compare.output.as_str();
+        diff::draw_diff(&pairs, (before, after), &options, path).expect("Unable to draw the diff");
+        if listing {
+            println!("Wrote {}", path);
+        }
+        return;
+    }
+
+    let snapshot_file = match &mode {
+        Mode::SnapshotRender(path) => Some(path.as_str()),
+        _ => None,
+    };
+    // Snapshots always carry smaps so any metric can be chosen at render time.
+    let needs_smaps = options.show_huge_pages
+        || options.show_locked
+        || args.stack_headroom
+        || args.by_library.is_some()
+        || options.show_vm_flags
+        || args.ksm
+        || args.thp
+        || !filters.vm_flags.is_empty()
+        || options.color_by.needs_smaps()
+        || options.size_metric.needs_smaps()
+        || args.layout == "icicle"
+        || args.summary_chart
+        || !group_by.is_empty()
+        || (top.is_some() && top_by.needs_smaps())
+        || sort == SortOrder::Rss
+        || matches!(mode, Mode::SnapshotSave(_) | Mode::Record(_))
+        || args.serve_metrics.is_some()
+        || args.csv.is_some()
+        || assertions.iter().any(assertions::Assertion::needs_smaps);
+
+    if let Some(runs) = args.aslr {
+        if command.is_empty() {
+            eprintln!("--aslr needs the COMMAND to launch after --");
+            std::process::exit(1);
+        }
+        let samples = aslr::sample_launches(&command, runs, args.aslr_delay).expect("Unable to sample launches");
+        let entropies = aslr::entropy(&samples);
+        println!("{:<32} {:>7} {:>8} {:>12}", "object", "samples", "distinct", "varying bits");
+        for e in &entropies {
+            println!("{:<32} {:>7} {:>8} {:>12}", e.name, e.samples, e.distinct, e.varying_bits);
+        }
+        aslr::draw_entropy_chart(&entropies, "aslr_entropy.png").expect("Unable to draw the entropy chart");
+        delivery.opened("aslr_entropy.png");
+        return;
+    }
+
+    let max_regions = args.draw.max_regions;
+    let cache = args.cache.then_some(args.cache_max_age);
+
+    if let Mode::Replay(replay) = &mode {
+        let events = replay::load(&replay.log, replay.pid).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let animate = replay.animate;
+        let every = replay.every.get();
+        let mut space = replay::AddressSpace::default();
+        let (mut frames, mut labels, mut changes) = (Vec::new(), Vec::new(), 0);
+        for (index, event) in events.iter().enumerate() {
+            if space.apply(&events, index) {
+                changes += 1;
+                if animate && (changes % every == 0 || index == events.len() - 1) {
+                    frames.push(space.regions());
+                    labels.push(event.label.clone());
+                }
+            }
+        }
+
+        println!("{:<33} {:<5} {:>6}  created by", "range", "perms", "line");
+        for (region, event) in space.origins(&events) {
+            let range = format!("{:#x}-{:#x}", region.start, region.end);
+            let mut text = event.text.clone();
+            if text.len() > 80 {
+                text.truncate(text.floor_char_boundary(77));
+                text.push_str("...");
+            }
+            println!("{:<33} {:<5} {:>6}  {}", range, region.attributes.perms(), event.line, text);
+        }
+
+        if animate {
+            options.size_metric = SizeMetric::Virtual;
+            let rendered = progress::batch(frames.len(), "frames");
+            let images = animate::align_frames(&frames).into_iter().zip(&labels).map(|(frame, label)| {
+                options.notice = Some(label.clone());
+                rendered.inc();
+                render_image(frame, usize::MAX, &mut options)
+            });
+            let delay = replay.frame_delay;
+            let format: AnimationFormat = replay.format.parse().unwrap();
+            let output = format.output(&replay.output, "memory_replay.gif");
+            animate::write(images, delay, &output, format).expect("Unable to write the animation");
+            println!("Wrote {} frames to {}", frames.len(), output);
+        } else {
+            let mut memory_regions = space.regions();
+            guards::mark_guard_pages(&mut memory_regions);
+            sanitizer::label_shadow(&mut memory_regions);
+            export_regions(&args.export, None, &memory_regions, &Warnings::default());
+            if !no_image {
+                render(memory_regions, max_regions, &mut options, format, delivery);
+            }
+        }
+        return;
+    }
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new().num_threads(jobs.get()).build_global().expect("Unable to start the worker threads");
+    }
+
+    if args.all && args.summary {
+        rollup::print_header();
+        for (pid, name, rollup) in rollup::all_processes() {
+            rollup::print_row(pid, &name, &rollup);
+        }
+        return;
+    }
+
+    if args.all {
+        let samples = overview::sample_processes(needs_smaps);
+        println!("{:>7} {:<16} {:>10} {:>10} {:>7}", "pid", "name", "rss", "mapped", "regions");
+        for sample in &samples {
+            println!("{:>7} {:<16} {:>10} {:>10} {:>7}", sample.pid, sample.name, format_size(sample.rss), format_size(sample.mapped()), sample.memory_regions.len());
+        }
+        overview::draw_overview(&samples, &options, "memory_overview.png").expect("Unable to draw the overview");
+        delivery.opened("memory_overview.png");
+        export_graph(&args.export, &samples);
+        if let Some(shown) = args.by_library {
+            let libraries = libraries::analyze(&samples);
+            libraries::print(&libraries, shown);
+            if !libraries.is_empty() {
+                libraries::draw_libraries(&libraries, shown, samples.len(), &options, "library_pss.png").expect("Unable to draw the library PSS chart");
+            }
+        }
+        if args.per_process {
+            let with_header = !args.draw.no_header;
+            let rendered = progress::batch(samples.len(), "rendering processes");
+            samples.into_par_iter().for_each(|sample| {
+                let mut options = options.clone();
+                if with_header {
+                    options.header = Some(header::Header::capture(sample.pid, None, &sample.memory_regions));
+                }
+                let path = format!("memory_map_{}.png", sample.pid);
+                if let Err(e) = render_image(sample.memory_regions, max_regions, &mut options).save(&path) {
+                    eprintln!("Unable to save {}: {}", path, e);
+                }
+                rendered.inc();
+            });
+        }
+        return;
+    }
+
+    if let Some(path) = args.binary.as_deref() {
+        let (segments, memory_regions) = binary::read_binary_layout(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e));
+        println!("{:<14} {:>18} {:>12} flags", "segment", "vaddr", "memsz");
+        for segment in &segments {
+            println!("{:<14} {:#18x} {:#12x} {}", segment.kind, segment.address, segment.size, segment.perms());
+        }
+        if let Some(spec) = args.mcu.as_deref() {
+            let fail = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let device = firmware::device(spec).unwrap_or_else(|e| fail(e));
+            let usage = firmware::usage(path).unwrap_or_else(|e| fail(e));
+            firmware::print_usage(&device, &usage);
+            if !no_image {
+                firmware::draw_usage(&device, &usage, &options, "firmware_usage.png").expect("Unable to draw the firmware usage");
+                delivery.opened("firmware_usage.png");
+            }
+        }
+        export_regions(&args.export, None, &memory_regions, &Warnings::default());
+        let (memory_regions, removed) = filters.apply(memory_regions);
+        if args.filter.collapse_filtered {
+            options.filtered = removed;
+        }
+        if !no_image {
+            render(memory_regions, max_regions, &mut options, format, delivery);
+        }
+        return;
+    }
+
+    if let Some(path) = args.uboot.as_deref() {
+        let layout = uboot::read(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        println!("{:<24} {:>18} {:>12}", "region", "start", "size");
+        for region in &layout.memory_regions {
+            println!("{:<24} {:#18x} {:>12}", region.file_name.as_deref().unwrap_or("?"), region.start, format_size(region.size));
+        }
+        for (address, name) in &layout.images {
+            println!("{:<24} {:#18x}", name, address);
+            options.annotations.push((*address, name.clone()));
+        }
+        for collision in uboot::collisions(&layout) {
+            println!("collision: {}", collision);
+        }
+        export_regions(&args.export, None, &layout.memory_regions, &Warnings::default());
+        let (memory_regions, removed) = filters.apply(layout.memory_regions);
+        if args.filter.collapse_filtered {
+            options.filtered = removed;
+        }
+        if !no_image {
+            render(memory_regions, max_regions, &mut options, format, delivery);
+        }
+        return;
+    }
+
+    if let Some(path) = args.kernel_modules.as_deref() {
+        let modules = kmodules::read_modules(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        println!("{:<24} {:>18} {:>10} state", "module", "address", "size");
+        for module in &modules {
+            println!("{:<24} {:#18x} {:>10} {}", module.name, module.address, format_size(module.size), module.state);
+        }
+        let sections_root = args.module_sections.then(|| std::path::Path::new("/sys/module"));
+        let memory_regions = kmodules::module_regions(&modules, sections_root);
+        export_regions(&args.export, None, &memory_regions, &Warnings::default());
+        let (memory_regions, removed) = filters.apply(memory_regions);
+        if args.filter.collapse_filtered {
+            options.filtered = removed;
+        }
+        if !no_image {
+            render(memory_regions, max_regions, &mut options, format, delivery);
+        }
+        return;
+    }
+
+    if let Some(dir) = args.physical.as_deref() {
+        let frames = physical::read_frames(std::path::Path::new(dir)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let page_size = physical::page_size();
+        for ((_, label, _), count) in physical::STATES.iter().zip(physical::totals(&frames)) {
+            println!("{:<14} {:>12} {:>10}", label, format_count(count), format_size(count * page_size));
+        }
+        physical::draw_physical(&frames, page_size, &options, "physical_memory.png").expect("Unable to draw physical memory");
+        delivery.opened("physical_memory.png");
+        return;
+    }
+
+    if let Some(path) = args.page_owner.as_deref() {
+        let page_owners = pageowner::read(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let page_size = physical::page_size();
+        pageowner::print(&page_owners, page_size, args.top.unwrap_or(20));
+        if !no_image {
+            pageowner::draw(&page_owners, page_size, &options, "page_owner.png").expect("Unable to draw the page owners");
+            delivery.opened("page_owner.png");
+        }
+        return;
+    }
+
+    if let Some(path) = args.slabinfo.as_deref() {
+        let caches = slab::read(path, physical::page_size()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        slab::print(&caches, args.top.unwrap_or(20));
+        if !no_image && !caches.is_empty() {
+            slab::draw_caches(&caches, &options, "slab_caches.png").expect("Unable to draw the slab caches");
+            delivery.opened("slab_caches.png");
+        }
+        return;
+    }
+
+    if let Some(path) = args.zoneinfo.as_deref() {
+        let page_size = physical::page_size();
+        let read = || {
+            zoneinfo::read(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        };
+        let zones = read();
+        zoneinfo::print(&zones, page_size);
+        if !no_image {
+            zoneinfo::draw(&zones, page_size, &[], &options, "zoneinfo.png").expect("Unable to draw the zones");
+            delivery.opened("zoneinfo.png");
+        }
+        if let Mode::Watch = mode {
+            let interval = args.interval;
+            let started = std::time::Instant::now();
+            let mut trend = vec![(0.0, zones.iter().map(zoneinfo::headroom).collect::<Vec<f64>>())];
+            loop {
+                std::thread::sleep(interval);
+                let zones = read();
+                let values: Vec<String> = zones.iter().map(|zone| format!("{} {} ({})", zone.name, format_size(zone.free * page_size), zone.state())).collect();
+                println!("{:>8.0}s  {}", started.elapsed().as_secs_f64(), values.join("  "));
+                trend.push((started.elapsed().as_secs_f64(), zones.iter().map(zoneinfo::headroom).collect()));
+                if !no_image {
+                    zoneinfo::draw(&zones, page_size, &trend, &options, "zoneinfo.png").expect("Unable to draw the zones");
+                }
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = args.buddyinfo.as_deref() {
+        let page_size = physical::page_size();
+        let order = args.buddy_order.unwrap_or_else(|| buddy::hugepage_order(page_size));
+        let read = || {
+            buddy::read(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        };
+        let zones = read();
+        buddy::print(&zones, page_size, order);
+        if !no_image {
+            buddy::draw(&zones, page_size, order, &[], &options, "buddyinfo.png").expect("Unable to draw the buddy allocator");
+            delivery.opened("buddyinfo.png");
+        }
+        if let Mode::Watch = mode {
+            // A row of indices per sample, in the order of the zones.
+            let interval = args.interval;
+            let started = std::time::Instant::now();
+            let mut trend = vec![(0.0, zones.iter().map(|zone| zone.unusable_index(order)).collect::<Vec<f64>>())];
+            loop {
+                std::thread::sleep(interval);
+                let zones = read();
+                let indices: Vec<f64> = zones.iter().map(|zone| zone.unusable_index(order)).collect();
+                let values: Vec<String> = zones.iter().zip(&indices).map(|(zone, index)| format!("{} {:.3}", zone.name, index)).collect();
+                println!("{:>8.0}s  {}", started.elapsed().as_secs_f64(), values.join("  "));
+                trend.push((started.elapsed().as_secs_f64(), indices));
+                if !no_image {
+                    buddy::draw(&zones, page_size, order, &trend, &options, "buddyinfo.png").expect("Unable to draw the buddy allocator");
+                }
+            }
+        }
+        return;
+    }
+
+    if args.shm {
+        let segments = shm::segments(&overview::sample_processes(false));
+        println!("{:<5} {:<32} {:>10} {:>10}  mapped by", "kind", "segment", "size", "resident");
+        for segment in &segments {
+            let kind = match segment.kind {
+                shm::Kind::Posix => "posix",
+                shm::Kind::SysV => "sysv",
+            };
+            let name = if segment.unlinked { format!("{} (unlinked)", segment.name) } else { segment.name.clone() };
+            let resident = segment.resident.map_or("-".to_string(), format_size);
+            let mappers: Vec<String> = segment.mappers.iter().map(|(pid, comm, mapped)| format!("{} {} ({})", pid, comm, format_size(*mapped))).collect();
+            let mappers = if segment.orphaned() { "none".to_string() } else { mappers.join(", ") };
+            println!("{:<5} {:<32} {:>10} {:>10}  {}", kind, name, format_size(segment.size), resident, mappers);
+        }
+        let orphaned: usize = segments.iter().filter(|segment| segment.orphaned()).map(|segment| segment.size).sum();
+        if orphaned > 0 {
+            println!("{} in segments no process maps", format_size(orphaned));
+        }
+        if !no_image && !segments.is_empty() {
+            shm::draw_segments(&segments, &options, "shared_memory.png").expect("Unable to draw the shared memory segments");
+            delivery.opened("shared_memory.png");
+        }
+        return;
+    }
+
+    if !args.library_sharing.is_empty() {
+        let processes: Vec<overview::ProcessSample> = args
+            .library_sharing
+            .iter()
+            .filter_map(|&pid| {
+                let process = overview::sample_process(pid, true);
+                if process.is_none() {
+                    eprintln!("Unable to read smaps of pid {}, leaving it out", pid);
+                }
+                process
+            })
+            .collect();
+        let libraries = libraries::analyze(&processes);
+        libraries::print(&libraries, libraries.len());
+        export_graph(&args.export, &processes);
+        if !no_image && !libraries.is_empty() {
+            libraries::draw_libraries(&libraries, libraries.len(), processes.len(), &options, "library_sharing.png").expect("Unable to draw the library sharing chart");
+            delivery.opened("library_sharing.png");
+        }
+        return;
+    }
+
+    if let Some(path) = args.vmallocinfo.as_deref() {
+        let memory_regions = vmalloc::read_vmallocinfo(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let (first, last) = (memory_regions[0].start, memory_regions[memory_regions.len() - 1].end);
+        let used: usize = memory_regions.iter().map(|r| r.size).sum();
+        println!("{} allocations in {:#x}-{:#x}, {} of {} in use", format_count(memory_regions.len()), first, last, format_size(used), format_size(last - first));
+        println!("{:<40} {:>8} {:>10}", "caller", "count", "size");
+        for total in vmalloc::caller_totals(&memory_regions) {
+            println!("{:<40} {:>8} {:>10}", total.caller, total.allocations, format_size(total.bytes));
+        }
+        export_regions(&args.export, None, &memory_regions, &Warnings::default());
+        let (memory_regions, removed) = filters.apply(memory_regions);
+        if args.filter.collapse_filtered {
+            options.filtered = removed;
+        }
+        if !no_image {
+            render(memory_regions, max_regions, &mut options, format, delivery);
+        }
+        return;
+    }
+
+    let (mut malloc_xml, mut malloc_arenas) = (None, Vec::new());
+    if args.malloc_info.as_deref() == Some("gdb") && !args.gdb && args.gdb_mi.is_none() {
+        eprintln!("--malloc-info without a FILE runs malloc_info() through --gdb or --gdb-mi");
+        std::process::exit(1);
+    }
+    // Under watch the program may still be running under massif, which
+    // writes the file as it exits, so a missing file isn't fatal there.
+    if let Some(path) = args.massif.as_deref() {
+        match massif::read(path) {
+            Ok(massif) => {
+                massif::print(&massif);
+                options.massif_panel = Some(massif::Panel { massif, cursor: None });
+            }
+            Err(e) if matches!(mode, Mode::Watch) => eprintln!("{}", e),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let (pid, memory_regions, mut header, warnings) = match (snapshot_file, args.source.as_deref()) {
+        (Some(path), _) => {
+            progress::phase(&format!("reading {}", path));
+            let snapshot = snapshot::Snapshot::load(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e));
+            progress::pause();
+            eprintln!("snapshot of pid {} on {} ({}), taken at {}", snapshot.pid, snapshot.hostname, snapshot.kernel, snapshot.timestamp);
+            let mut header = header::Header::from_snapshot(&snapshot);
+            if args.meminfo {
+                header.system = snapshot.system.clone();
+                if header.system.is_none() {
+                    eprintln!("{} was saved without /proc/meminfo", path);
+                }
+            }
+            if args.cgroup {
+                eprintln!("--cgroup only applies to live processes, not snapshots");
+            }
+            if args.proc_status {
+                eprintln!("--proc-status only applies to live processes, not snapshots");
+            }
+            options.previews = snapshot.previews;
+            let (memory_regions, region_warnings) = validate::validate(snapshot.regions);
+            (snapshot.pid, memory_regions, header, Warnings { region_warnings, ..Warnings::default() })
+        }
+        (None, Some(spec)) => {
+            let source = source::open(spec, needs_smaps).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let capabilities = source.capabilities();
+            progress::phase(&format!("reading {}", source.name()));
+            let (mut memory_regions, warnings) = source::load(source.as_ref()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            progress::pause();
+            enforce_strict(strict, &warnings);
+            if needs_smaps && !capabilities.smaps {
+                eprintln!("{} has no smaps detail; RSS and swap are left out", source.name());
+            }
+            let pid = source.pid().unwrap_or(0);
+            threads::label_thread_stacks(&mut memory_regions, (capabilities.local && pid != 0).then_some(pid));
+            guards::mark_guard_pages(&mut memory_regions);
+            sanitizer::label_shadow(&mut memory_regions);
+            let header = if capabilities.local && pid != 0 {
+                header::Header::capture(pid, None, &memory_regions)
+            } else {
+                header::Header::from_source(&source.name(), pid, &memory_regions)
+            };
+            (pid, memory_regions, header, warnings)
+        }
+        (None, None) => {
+            let adb = args.target.adb.as_ref().map(|serial| AdbTarget {
+                serial: serial.clone(),
+                package: args.target.package.clone(),
+            });
+
+            let pid = match process.as_deref().or(args.target.pid.as_deref()) {
+                _ if args.target.own => std::process::id(),
+                // 0 would otherwise mean the process asking, but only to
+                // the kernel, and /proc/0 doesn't exist.
+                Some("self" | "0") if adb.is_none() => std::process::id(),
+                Some(pid) => pid.parse::<u32>().expect("Invalid PID"),
+                None => match (&adb, args.target.container.as_deref()) {
+                    (Some(adb), _) => adb.resolve_pid().expect("Unable to resolve the package PID"),
+                    (None, Some(container)) => {
+                        let pid = container::resolve_pid(container).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        });
+                        eprintln!("container {} is pid {}", container, pid);
+                        pid
+                    }
+                    (None, None) => {
+                        eprintln!("No process given: pass a PID, --pid, --container or --adb with --package");
+                        std::process::exit(1);
+                    }
+                },
+            };
+
+            if args.summary {
+                let rollup = rollup::read(pid, adb.as_ref()).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let name = match &adb {
+                    Some(adb) => adb.read_proc_file(pid, "comm"),
+                    None => capture::read_proc_file(pid, "comm"),
+                };
+                rollup::print_header();
+                rollup::print_row(pid, name.unwrap_or_default().trim(), &rollup);
+                return;
+            }
+
+            let sharing = args.filter.sharing.as_deref().map(|s| s.parse::<Sharing>().unwrap());
+            let source: Box<dyn MemorySource> = match &adb {
+                Some(adb) => Box::new(source::Adb { target: adb.clone(), pid, smaps: needs_smaps }),
+                None => Box::new(source::Procfs { pid, smaps: needs_smaps, cache }),
+            };
+            let try_capture = || {
+                let started = std::time::Instant::now();
+                let (memory_regions, warnings) = source::load(source.as_ref())?;
+                let mut memory_regions: Vec<MemoryRegion> = match sharing {
+                    Some(sharing) => memory_regions
+                        .into_iter()
+                        .filter(|region| region.attributes.shared == (sharing == Sharing::Shared))
+                        .collect(),
+                    None => memory_regions,
+                };
+                threads::label_thread_stacks(&mut memory_regions, source.capabilities().local.then_some(pid));
+                guards::mark_guard_pages(&mut memory_regions);
+                sanitizer::label_shadow(&mut memory_regions);
+                tracing::debug!(pid, regions = memory_regions.len(), elapsed_ms = started.elapsed().as_millis() as u64, "captured");
+                Ok::<_, String>((memory_regions, warnings))
+            };
+            let capture = || try_capture().unwrap_or_else(|e| panic!("{}", e));
+            let previews = |memory_regions: &[MemoryRegion]| match args.hex_preview {
+                true => preview::capture(pid, memory_regions).unwrap_or_else(|e| {
+                    eprintln!("{}; no hex previews", e);
+                    Vec::new()
+                }),
+                false => Vec::new(),
+            };
+
+            if args.agent || args.history.is_some() {
+                let store = agent::HistoryStore::open(&args.store).expect("Unable to open the history store");
+                if let Some(window) = args.history {
+                    let samples = store.query(pid, window).expect("Unable to read the history store");
+                    agent::draw_timeline_chart(&samples, "memory_history.png", 800, 500).expect("Unable to draw the timeline chart");
+                    delivery.opened("memory_history.png");
+                } else {
+                    agent::run_agent(&store, pid, args.interval, args.retention, args.csv.as_deref(), || try_capture().map(|(memory_regions, _)| memory_regions)).expect("Agent failed");
+                }
+                return;
+            }
+
+            if let Some(addr) = args.serve_metrics.as_deref() {
+                metrics::serve_metrics(addr, pid, source.capabilities().local, args.interval, || try_capture().map(|(memory_regions, _)| memory_regions)).expect("Metrics exporter failed");
+                return;
+            }
+
+            if let Mode::Serve { listen, refresh, token } = &mode {
+                let refresh = *refresh;
+                let render_with = |memory_regions: Vec<MemoryRegion>, previews: Vec<(usize, Vec<u8>)>| {
+                    let (memory_regions, notice) = truncate_regions(memory_regions, max_regions);
+                    let options = RenderOptions { notice, previews, ..options.clone() };
+                    let (width, height) = options.image_size();
+                    let memory_regions = insert_gap_memory_regions(&memory_regions);
+                    let svg = renderer::render(renderer::Svg::default(), &memory_regions, (width, height), &options).map_err(|e| e.to_string())?;
+                    Ok(preview::annotate_svg(svg, &memory_regions, (width, height), &options))
+                };
+                // Previews are of the served process only, not of the ones
+                // the API renders on request.
+                let render_svg = |memory_regions: Vec<MemoryRegion>| render_with(memory_regions, Vec::new());
+                // Most polls find the map drawn as it was, so the last render
+                // is kept and redone whole only when something it draws
+                // changed; smaps figures count only when they are drawn.
+                // There's no redraw of just the changed bands: a region
+                // that grows moves every band below it.
+                let last_render: std::cell::RefCell<Option<(Vec<MemoryRegion>, String)>> = std::cell::RefCell::new(None);
+                let live_map = || {
+                    let (memory_regions, _) = try_capture()?;
+                    let drawn = options.drawn_regions(&memory_regions);
+                    if let Some((previous, svg)) = &*last_render.borrow() {
+                        if *previous == drawn {
+                            return Ok(svg.clone());
+                        }
+                    }
+                    let svg = render_with(memory_regions.clone(), previews(&memory_regions))?;
+                    *last_render.borrow_mut() = Some((drawn, svg.clone()));
+                    Ok(svg)
+                };
+                let capture_local = |requested: u32| capture_local(requested, needs_smaps, cache);
+                let handlers = serve::Handlers { live_map: &live_map, capture: &capture_local, render_svg: &render_svg };
+                let token = token.clone().or_else(|| std::env::var("MEMLAYOUT_API_TOKEN").ok());
+                if let Err(e) = serve::serve(listen, pid, refresh, token.as_deref(), args.open, handlers) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+
+            if let Mode::Record(record) = &mode {
+                let retention = recorder::Retention { keep_for: record.keep_for, max_bytes: record.max_size, dump_window: record.dump_window };
+                // Unlike the other modes, a failed read ends the recording
+                // with a dump instead of a panic.
+                let snapshot = || {
+                    let (mut memory_regions, _) = source::load(source.as_ref())?;
+                    threads::label_thread_stacks(&mut memory_regions, source.capabilities().local.then_some(pid));
+                    guards::mark_guard_pages(&mut memory_regions);
+                    sanitizer::label_shadow(&mut memory_regions);
+                    let mut snapshot = snapshot::Snapshot::capture(pid, memory_regions, adb.as_ref());
+                    snapshot.previews = previews(&snapshot.regions);
+                    Ok(snapshot)
+                };
+                let dir = std::path::Path::new(&record.dir);
+                tracing::info!("recording pid {} into {}; kill -USR1 {} dumps the last {:?}", pid, dir.display(), std::process::id(), record.dump_window);
+                recorder::record(dir, pid, record.interval, &retention, snapshot).expect("Recording failed");
+                return;
+            }
+
+            if let Mode::SnapshotSave(path) = &mode {
+                let (memory_regions, warnings) = capture();
+                enforce_strict(strict, &warnings);
+                let mut snapshot = snapshot::Snapshot::capture(pid, memory_regions, adb.as_ref());
+                snapshot.previews = previews(&snapshot.regions);
+                snapshot.save(path).expect("Unable to write the snapshot");
+                println!("Saved {} regions of pid {} to {}", snapshot.regions.len(), pid, path);
+                return;
+            }
+
+            if let Mode::Watch = mode {
+                let interval = args.interval;
+                tracing::info!("watching pid {} every {:?}", pid, args.interval);
+                let mut previous: Option<Vec<MemoryRegion>> = None;
+                let mut delivery = delivery;
+                let watch_started = std::time::Instant::now();
+                let mut massif_panel = options.massif_panel.clone();
+                loop {
+                    // A zombie's maps are empty, and an exited process has
+                    // none to read.
+                    let memory_regions = match try_capture() {
+                        Ok((memory_regions, warnings)) if !memory_regions.is_empty() => {
+                            enforce_strict(strict, &warnings);
+                            memory_regions
+                        }
+                        _ => {
+                            tracing::info!(pid, "process exited");
+                            return;
+                        }
+                    };
+                    let (memory_regions, removed) = filters.apply(memory_regions);
+                    if let Some(before) = previous.replace(memory_regions.clone()) {
+                        let pairs = diff::pair(before, memory_regions.clone());
+                        for run in diff::changed_runs(&pairs) {
+                            println!("{:#x}-{:#x} {}", pairs[run.0].1.start, pairs[run.1].1.end, diff::describe_run(&pairs, run));
+                        }
+                    }
+                    let mut options = options.clone();
+                    if args.filter.collapse_filtered {
+                        options.filtered = removed;
+                    }
+                    // Massif's time starts with the program, which a watch
+                    // started with it shares, in milliseconds only.
+                    if let Some(path) = args.massif.as_deref() {
+                        if let Ok(massif) = massif::read(path) {
+                            massif_panel = Some(massif::Panel { massif, cursor: None });
+                        }
+                        if let Some(panel) = massif_panel.as_mut() {
+                            panel.cursor = (panel.massif.time_unit == "ms").then(|| watch_started.elapsed().as_secs_f64() * 1000.0);
+                        }
+                        options.massif_panel = massif_panel.clone();
+                    }
+                    if !args.draw.no_header {
+                        options.header = Some(header::Header::capture(pid, adb.as_ref(), &memory_regions));
+                    }
+                    // Every frame is drawn whole, unlike serve's map: the
+                    // header's capture time and totals change each time.
+                    if !no_image {
+                        let started = std::time::Instant::now();
+                        render(memory_regions, max_regions, &mut options, format, delivery);
+                        tracing::debug!(elapsed_ms = started.elapsed().as_millis() as u64, "rendered");
+                        // Opened once; viewers pick up the rewritten file
+                        // themselves.
+                        delivery.open = false;
+                    }
+                    std::thread::sleep(interval);
+                }
+            }
+
+            progress::phase(&format!("reading pid {}", pid));
+            let (memory_regions, warnings) = capture();
+            progress::pause();
+            enforce_strict(strict, &warnings);
+            let mut header = header::Header::capture(pid, adb.as_ref(), &memory_regions);
+            if args.meminfo {
+                header.system = meminfo::read(adb.as_ref());
+            }
+            if args.proc_status {
+                match status::read(pid, adb.as_ref()) {
+                    Ok(status) => header.status = Some(status),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            if args.cgroup || args.cgroup_panel {
+                match cgroup::CgroupMemory::read(pid, adb.as_ref()) {
+                    Ok(memory) => {
+                        if args.cgroup_panel {
+                            options.cgroup_panel = Some(memory.clone());
+                        }
+                        if memory.near_limit(options.cgroup_margin) {
+                            eprintln!("warning: cgroup {} uses {} of its {} limit", memory.path, format_size(memory.current), format_size(memory.limit.unwrap()));
+                        }
+                        header.cgroup = Some(memory);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            if args.ksm {
+                let page_size = physical::page_size();
+                match ksm::merged_ranges(pid, &memory_regions, page_size) {
+                    Ok(ranges) => {
+                        let merged: usize = ranges.iter().map(|(start, end)| end - start).sum();
+                        println!("KSM merged: {} in {} runs", format_size(merged), format_count(ranges.len()));
+                        for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+                            let in_region: usize = ranges.iter().map(|(start, end)| (*end).min(region.end).saturating_sub((*start).max(region.start))).sum();
+                            if in_region > 0 {
+                                println!("  {:#x}-{:#x} {:>10} of {:>10}  {}", region.start, region.end, format_size(in_region), format_size(region.size), display_name(region).unwrap_or_default());
+                            }
+                        }
+                        options.ksm = ranges;
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+                if let Some((merging, profit)) = ksm::process_stats(pid) {
+                    let profit = profit.map_or(String::new(), |profit| format!(", profit {} bytes", profit));
+                    println!("ksm_stat: {} merging{}", format_size(merging as usize * page_size), profit);
+                }
+                match ksm::system_stats() {
+                    Some(stats) => {
+                        let state = if stats.running { "running" } else { "not running" };
+                        let profit = stats.general_profit.map_or(String::new(), |profit| format!(", general profit {} bytes", profit));
+                        println!(
+                            "KSM {}: {} shared pages back {} more, saving {}{}",
+                            state,
+                            format_count(stats.pages_shared as usize),
+                            format_count(stats.pages_sharing as usize),
+                            format_size(stats.pages_sharing as usize * page_size),
+                            profit
+                        );
+                    }
+                    None => eprintln!("No KSM in this kernel (/sys/kernel/mm/ksm)"),
+                }
+            }
+            if args.thp {
+                match thp::backing_ranges(pid, &memory_regions, physical::page_size()) {
+                    Ok(ranges) => {
+                        println!("{:<33} {:>10} {:>10} {:>10} {:>10} {:>5}  path", "range", "AnonHuge", "PMD THP", "PTE THP", "base", "THP");
+                        for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+                            let within = (region.start, region.end);
+                            let (pmd, pte, small) = (thp::bytes(&ranges, thp::Backing::Pmd, within), thp::bytes(&ranges, thp::Backing::Pte, within), thp::bytes(&ranges, thp::Backing::Small, within));
+                            if pmd + pte + small == 0 {
+                                continue;
+                            }
+                            let anon_huge = region.smaps.as_ref().map_or("-".to_string(), |smaps| format_size(smaps.anon_huge_pages));
+                            let share = (pmd + pte) as f64 / (pmd + pte + small) as f64 * 100.0;
+                            println!(
+                                "{:<33} {:>10} {:>10} {:>10} {:>10} {:>4.0}%  {}",
+                                format!("{:#x}-{:#x}", region.start, region.end),
+                                anon_huge,
+                                format_size(pmd),
+                                format_size(pte),
+                                format_size(small),
+                                share,
+                                display_name(region).unwrap_or_default()
+                            );
+                        }
+                        options.thp = ranges;
+                    }
+                    Err(e) => {
+                        eprintln!("{}; hatching the AnonHugePages share of each region instead", e);
+                        options.show_huge_pages = true;
+                    }
+                }
+            }
+            if args.entropy {
+                match entropy::sample(pid, &memory_regions) {
+                    Ok(entropies) => {
+                        for (start, bits) in &entropies {
+                            let class = entropy::class(*bits);
+                            if let Some(region) = memory_regions.iter().find(|region| region.start == *start).filter(|_| !class.is_empty()) {
+                                println!("{:.2} bits/byte, {}: {}", bits, class, describe_region(region));
+                            }
+                        }
+                        options.entropy = entropies;
+                    }
+                    Err(e) => eprintln!("{}; drawing without entropy", e),
+                }
+            }
+            options.previews = previews(&memory_regions);
+            if args.numa_panel {
+                let numa_maps = match adb.as_ref() {
+                    Some(adb) => adb.read_proc_file(pid, "numa_maps"),
+                    None => capture::read_proc_file(pid, "numa_maps"),
+                };
+                match numa_maps {
+                    Ok(numa_maps) => {
+                        let totals = numa::parse(&numa_maps);
+                        let totals = if adb.is_none() { totals.with_machine(pid) } else { totals };
+                        totals.print();
+                        options.numa_panel = Some(totals);
+                    }
+                    Err(e) => eprintln!("{} (numa_maps needs a kernel with CONFIG_NUMA)", e),
+                }
+            }
+            if args.stack_headroom {
+                let threshold = Some(args.stack_threshold).filter(|p| (0.0..=100.0).contains(p)).expect("The stack threshold must be a percentage") / 100.0;
+                let limits = match adb.as_ref() {
+                    Some(adb) => adb.read_proc_file(pid, "limits"),
+                    None => capture::read_proc_file(pid, "limits"),
+                };
+                let rlimit = limits.and_then(|limits| stacks::stack_limit(&limits)).unwrap_or_else(|e| {
+                    eprintln!("{}; the main stack's reserve is unknown", e);
+                    None
+                });
+                let pointers = if adb.is_none() { threads::thread_stack_pointers(pid) } else { Vec::new() };
+                options.stacks = stacks::usage(&memory_regions, pid, rlimit, &pointers, threshold);
+                stacks::print(&options.stacks, threshold);
+            }
+            if options.show_locked {
+                let limits = match adb.as_ref() {
+                    Some(adb) => adb.read_proc_file(pid, "limits"),
+                    None => capture::read_proc_file(pid, "limits"),
+                };
+                let locked = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.locked).sum();
+                match limits.and_then(|limits| smaps::memlock_limit(&limits)) {
+                    Ok(Some(limit)) => {
+                        println!("Locked: {} of {} RLIMIT_MEMLOCK", format_size(locked), format_size(limit));
+                        header.locked = Some((locked, Some(limit)));
+                    }
+                    Ok(None) => println!("Locked: {} (RLIMIT_MEMLOCK unlimited)", format_size(locked)),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            if args.gdb || args.gdb_mi.is_some() {
+                let session = match args.gdb_mi.as_deref() {
+                    Some(address) => gdb::Session::connect(address),
+                    None => gdb::Session::attach(pid),
+                };
+                match session.and_then(|mut session| Ok((gdb::annotations(&mut session)?, session))) {
+                    Ok((annotations, mut session)) => {
+                        for (address, label) in &annotations {
+                            println!("{:#x}: {}", address, label);
+                        }
+                        options.annotations.extend(annotations);
+                        if args.malloc_info.as_deref() == Some("gdb") {
+                            malloc_arenas = gdb::arenas(&mut session);
+                            match gdb::malloc_info(&mut session, pid) {
+                                Ok(xml) => malloc_xml = Some(xml),
+                                Err(e) => eprintln!("{}", e),
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            (pid, memory_regions, header, warnings)
+        }
+    };
+    let file_root = header.namespace.as_ref().and_then(|namespace| namespace.file_root());
+    if options.show_locked && header.locked.is_none() {
+        header.locked = Some((memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.locked).sum(), None));
+    }
+    if !args.draw.no_header {
+        options.header = Some(header);
+    }
+
+    let findings = if options.audit { audit::audit(&memory_regions) } else { Vec::new() };
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    if options.audit {
+        let report = report::audit_report(pid, &memory_regions, &findings);
+        let json = serde_json::to_string_pretty(&report).expect("Unable to serialize the audit report");
+        std::fs::write(&args.report_json, json).expect("Unable to write the audit report");
+    }
+
+    export_regions(&args.export, Some(pid), &memory_regions, &warnings);
+
+    for address in &args.annotate {
+        let label = match symbols::resolve(&memory_regions, *address, file_root.as_deref()) {
+            Some(resolution) => resolution.to_string(),
+            None => format!("{:#x}", address),
+        };
+        println!("{:#x}: {}", address, label);
+        options.annotations.push((*address, label));
+    }
+
+    if let Some(path) = args.malloc_info.as_deref().filter(|path| *path != "gdb") {
+        malloc_xml = Some(std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e)));
+    }
+    if let Some(xml) = malloc_xml {
+        match mallocinfo::parse(&xml) {
+            Ok(info) => {
+                println!("{:<8} {:>10} {:>10} {:>10} {:>12}  region", "heap", "system", "in use", "free", "free chunks");
+                for (heap, start) in mallocinfo::locate(&info, &memory_regions, &malloc_arenas) {
+                    let region = start.map_or("not located".to_string(), |start| format!("{:#x}", start));
+                    println!("{:<8} {:>10} {:>10} {:>10} {:>12}  {}", heap.nr, format_size(heap.system), format_size(heap.in_use()), format_size(heap.free), heap.free_chunks, region);
+                    if let Some(start) = start {
+                        options.annotations.push((start, heap.label()));
+                    }
+                }
+                println!("mmapped directly: {} in {} chunks", format_size(info.mmapped), info.mmapped_chunks);
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(path) = args.jemalloc_stats.as_deref() {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e));
+        match jemalloc::parse(&text) {
+            Ok(stats) => {
+                if let Some(version) = &stats.version {
+                    println!("jemalloc {}", version);
+                }
+                println!(
+                    "allocated {}, active {}, metadata {}, resident {}, mapped {}, retained {}",
+                    format_size(stats.allocated as usize),
+                    format_size(stats.active as usize),
+                    format_size(stats.metadata as usize),
+                    format_size(stats.resident as usize),
+                    format_size(stats.mapped as usize),
+                    format_size(stats.retained as usize)
+                );
+                if !stats.bins.is_empty() {
+                    println!("{:>10} {:>8} {:>12} {:>12} {:>6}", "size", "slabs", "regions", "capacity", "util");
+                }
+                for bin in &stats.bins {
+                    println!("{:>10} {:>8} {:>12} {:>12} {:>5.0}%", format_size(bin.size as usize), bin.slabs, bin.regions, bin.capacity, bin.utilization() * 100.0);
+                }
+                for (size, extents) in &stats.large {
+                    println!("{:>10} {:>8} large extents", format_size(*size as usize), extents);
+                }
+                if let Some(start) = jemalloc::anchor(&memory_regions) {
+                    options.annotations.push((start, stats.label()));
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(path) = args.v8_heap.as_deref() {
+        match v8heap::read(path) {
+            Ok(heap) => {
+                v8heap::print(&heap);
+                // RSS where smaps was read, otherwise what is mapped.
+                let anonymous: usize = memory_regions
+                    .iter()
+                    .filter(|region| region.attributes.allocated && region.file_name.is_none() && !region.attributes.shared)
+                    .map(|region| region.smaps.as_ref().map_or(region.size, |smaps| smaps.rss))
+                    .sum();
+                let measure = if memory_regions.iter().any(|region| region.smaps.is_some()) { "resident" } else { "mapped" };
+                println!("V8 heap commits {} of {} anonymous memory {} ({:.0}%)", format_size(heap.committed() as usize), format_size(anonymous), measure, heap.committed() as f64 * 100.0 / anonymous.max(1) as f64);
+                if let Some(start) = v8heap::anchor(&memory_regions) {
+                    options.annotations.push((start, heap.label()));
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(path) = args.alloc_profile.as_deref() {
+        match allocsites::read(path) {
+            Ok(profile) => {
+                allocsites::print(&profile, args.top.unwrap_or(5));
+                options.annotations.extend(allocsites::annotations(&profile, &memory_regions, args.top.unwrap_or(5)));
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    let memory_regions = match args.jvm_nmt.as_deref().map(|source| nmt::read(source, pid)) {
+        Some(Ok(report)) => {
+            nmt::print(&report);
+            if report.reservations.is_empty() {
+                eprintln!("No virtual memory map to place the categories; it needs -XX:NativeMemoryTracking=detail and VM.native_memory detail");
+            }
+            let anonymous: usize = memory_regions.iter().filter(|region| region.attributes.allocated && region.file_name.is_none() && !region.attributes.shared).map(|region| region.size).sum();
+            let (memory_regions, attributed) = nmt::attribute(memory_regions, &report.reservations);
+            println!("NMT names {} of {} anonymous memory ({:.0}%)", format_size(attributed), format_size(anonymous), attributed as f64 * 100.0 / anonymous.max(1) as f64);
+            memory_regions
+        }
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            memory_regions
+        }
+        None => memory_regions,
+    };
+
+    let memory_regions = match args.go_memstats.as_deref().map(gomem::read) {
+        Some(Ok(stats)) => {
+            gomem::print(&stats);
+            let live = (snapshot_file.is_none() && args.source.is_none()).then_some(pid);
+            let (memory_regions, arenas) = gomem::attribute(memory_regions, live, physical::page_size());
+            if arenas.mapped == 0 {
+                eprintln!("No Go heap arenas in the map");
+            } else {
+                gomem::explain(&stats, &arenas, live.is_some());
+            }
+            if let Some(region) = memory_regions.iter().find(|region| region.file_name.as_deref().is_some_and(|name| name.starts_with("[anon:Go heap"))) {
+                options.annotations.push((region.start, stats.label()));
+            }
+            memory_regions
+        }
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            memory_regions
+        }
+        None => memory_regions,
+    };
+
+    // A sanitized process is killed once its RSS passes hard_rss_limit_mb,
+    // and warned at soft_rss_limit_mb; shadow pages count against both.
+    if snapshot_file.is_none() && args.source.is_none() {
+        let rss: usize = memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).sum();
+        let shadow: usize = memory_regions.iter().filter(|r| sanitizer::is_shadow(r)).filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).sum();
+        for (variable, key, limit) in sanitizer::rss_limits(pid) {
+            if needs_smaps {
+                println!("{} {}: RSS {} of {} ({:.0}%), {} of it shadow", variable, key, format_size(rss), format_size(limit), rss as f64 * 100.0 / limit as f64, format_size(shadow));
+            } else {
+                println!("{} {}: {} (RSS against it needs smaps, e.g. --color-by rss)", variable, key, format_size(limit));
+            }
+        }
+        if wsl::is_wsl() {
+            wsl::report(pid, &memory_regions);
+        }
+    }
+
+    if !args.highlight.is_empty() {
+        let with_gaps = insert_gap_memory_regions(&memory_regions);
+        for &(start, end) in &args.highlight {
+            let overlapping: Vec<&MemoryRegion> = with_gaps.iter().filter(|region| region.start < end && start < region.end).collect();
+            let label = if end - start > 1 { format!("{:#x}-{:#x}", start, end) } else { format!("{:#x}", start) };
+            match overlapping.as_slice() {
+                [] => println!("{}: past the last mapping", label),
+                [region] if end - start == 1 => println!("{}: {}, offset {:#x}", label, describe_region(region), start - region.start),
+                regions => {
+                    println!("{}:", label);
+                    for region in regions {
+                        println!("  {}", describe_region(region));
+                    }
+                }
+            }
+            if end - start > 1 {
+                options.highlights.push((start, end));
+            }
+            options.annotations.push((start, label));
+        }
+    }
+
+    let local = snapshot_file.is_none() && args.source.is_none() && args.target.adb.is_none();
+    let space = context::AddressSpace::detect(&memory_regions, local);
+    if options.boundaries || options.context_bar {
+        if options.boundaries {
+            let min = space.mmap_min_addr.map_or("unknown".to_string(), |min| format!("{:#x}", min));
+            println!("{}-bit user space up to {:#x}, kernel from {:#x}, mmap_min_addr {}", space.user_bits, space.user_end(), space.kernel_start(), min);
+            for (region, reason) in space.anomalies(&memory_regions) {
+                eprintln!("warning: {} is {}", describe_region(region), reason);
+            }
+        }
+        options.address_space = Some(space);
+    }
+
+    let (memory_regions, removed) = filters.apply(memory_regions);
+    if args.filter.collapse_filtered {
+        options.filtered = removed;
+    }
+
+    let memory_regions = if group_by_file {
+        let expand: Vec<&str> = args.filter.expand.iter().map(String::as_str).collect();
+        let memory_regions = grouping::group_by_file(memory_regions, &expand);
+        for region in memory_regions.iter().filter(|region| region.mappings > 1) {
+            println!("{}\t{}", region.file_name.as_deref().unwrap_or("-"), grouping::group_label(region));
+        }
+        memory_regions
+    } else {
+        memory_regions
+    };
+    let memory_regions = if group_by.contains(&"jit") {
+        let memory_regions = grouping::group_jit(memory_regions);
+        for region in memory_regions.iter().filter(|region| region.mappings > 1 && jit::is_jit(region)) {
+            println!("{:#x}-{:#x}\t{}", region.start, region.end, grouping::group_label(region));
+        }
+        memory_regions
+    } else {
+        memory_regions
+    };
+    if let Some(summary) = jit::summary(&memory_regions) {
+        eprintln!("{}", summary);
+    }
+
+    if let Some(count) = top {
+        print_top_regions(&memory_regions, count, top_by);
+    }
+    if report {
+        for (name, (count, size)) in group_anon_names(&memory_regions) {
+            println!("[anon:{}] {} regions, {:#x} bytes", name, count, size);
+        }
+        utilization::utilization(&memory_regions).print();
+        warnings.print();
+    }
+
+    if args.ranked_chart {
+        let mut ranked: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| region.attributes.allocated).collect();
+        let (order, metric, by) = match sort {
+            SortOrder::Rss => (sort, SizeMetric::Rss, "RSS"),
+            SortOrder::Path => (sort, SizeMetric::Virtual, "path"),
+            _ => (SortOrder::Size, SizeMetric::Virtual, "size"),
+        };
+        order.sort(&mut ranked);
+        ranked.truncate(top.unwrap_or(50));
+        let bars: Vec<(&MemoryRegion, usize)> = ranked.into_iter().map(|region| (region, metric.value(region))).collect();
+        let title = format!("pid {}: {} regions by {}", pid, bars.len(), by);
+        ranked::draw_ranked_chart(&bars, &title, &options, "memory_ranked.png").expect("Unable to draw the ranked chart");
+        delivery.opened("memory_ranked.png");
+    }
+
+    if args.summary_chart {
+        let title = format!("pid {}: memory by category", pid);
+        summary::draw_summary_chart(&memory_regions, &title, &options, "memory_summary.png").expect("Unable to draw the summary chart");
+        delivery.opened("memory_summary.png");
+    }
+
+    #[cfg(feature = "gui")]
+    if args.gui {
+        // Recapturing needs to outlive this function, so only local
+        // processes, which need nothing but the pid, get it.
+        let refresh: Option<gui::Refresh> = match (args.target.adb.is_some(), snapshot_file) {
+            (false, None) => Some(Box::new(move || capture_local(pid, true, cache).map(|(memory_regions, _)| memory_regions))),
+            _ => None,
+        };
+        gui::run(gui::MemoryMapApp::new(format!("memlayout: pid {}", pid), memory_regions, options.color_by, options.theme.clone(), options.size_metric, options.scale, refresh).with_previews(options.previews.clone()));
+    }
+
+    if args.fragmentation || args.fragmentation_panel {
+        let fragmentation = fragmentation::fragmentation(&memory_regions, &space);
+        if args.fragmentation {
+            fragmentation.print();
+        }
+        if args.fragmentation_panel {
+            options.fragmentation_panel = Some(fragmentation);
+        }
+    }
+
+    if args.size_histogram || args.size_histogram_panel {
+        let buckets = sizes::histogram(&memory_regions);
+        if args.size_histogram {
+            sizes::print(&buckets);
+        }
+        if args.size_histogram_panel {
+            options.size_histogram_panel = Some(buckets);
+        }
+    }
+
+    if let Some(size) = args.find_hole {
+        let align = args.align;
+        let holes = fragmentation::find_holes(&memory_regions, &space, size, align);
+        if holes.is_empty() {
+            println!("No free range fits {} aligned to {:#x}", format_size(size as usize), align);
+        }
+        for hole in &holes {
+            println!(
+                "{:#x}-{:#x} ({})\tlowest fit {:#x}\thighest fit {:#x}",
+                hole.start,
+                hole.end,
+                format_size((hole.end - hole.start) as usize),
+                hole.lowest_fit,
+                hole.highest_fit
+            );
+            options.holes.push((hole.start as usize, hole.end as usize));
+        }
+    }
+
+    let status = assertions::check(assertions, &memory_regions);
+    if !no_image && args.layout == "icicle" {
+        // Virtual sizes would have every reservation drown out what the
+        // process actually uses.
+        let (metric, by) = match options.size_metric {
+            SizeMetric::Pss => (SizeMetric::Pss, "PSS"),
+            SizeMetric::Swap => (SizeMetric::Swap, "swap"),
+            SizeMetric::Dirty => (SizeMetric::Dirty, "dirty"),
+            _ => (SizeMetric::Rss, "RSS"),
+        };
+        let tree = icicle::tree(&memory_regions, |region| metric.value(region), &format!("pid {}", pid), &options);
+        let title = format!("pid {}: {} of {} by path", pid, format_size(tree.bytes), by);
+        icicle::draw_icicle(&tree, &title, &options, "memory_icicle.png").expect("Unable to draw the icicle chart");
+        delivery.opened("memory_icicle.png");
+    } else if !no_image {
+        render(memory_regions, max_regions, &mut options, format, delivery);
+    }
+    if let Some(status) = status {
+        std::process::exit(status);
+    }
+
+    if !findings.is_empty() {
+        eprintln!("audit: {} finding(s)", findings.len());
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_file_backed_line() {
+        let region: MemoryRegion = "7f3a1c000000-7f3a1c021000 r-xp 00002000 fd:01 1835015                    /usr/lib/x86_64-linux-gnu/libc.so.6".parse().unwrap();
+        assert_eq!((region.start, region.end, region.size), (0x7f3a1c000000, 0x7f3a1c021000, 0x21000));
+        assert_eq!(region.attributes.perms(), "r-xp");
+        assert_eq!((region.offset, region.device, region.inode), (0x2000, (0xfd, 0x01), 1835015));
+        assert_eq!(region.file_name.as_deref(), Some("/usr/lib/x86_64-linux-gnu/libc.so.6"));
+    }
+
+    #[test]
+    fn keeps_spaces_in_the_path() {
+        let region: MemoryRegion = "00400000-00401000 r--p 00000000 08:02 42   /home/user/My Programs/a b.so\n".parse().unwrap();
+        assert_eq!(region.file_name.as_deref(), Some("/home/user/My Programs/a b.so"));
+    }
+
+    #[test]
+    fn keeps_the_deleted_suffix() {
+        let region: MemoryRegion = "7f0000000000-7f0000100000 rw-s 00000000 00:01 1024   /dev/zero (deleted)".parse().unwrap();
+        assert_eq!(region.file_name.as_deref(), Some("/dev/zero (deleted)"));
+        assert_eq!(region.shared_memory_kind(), Some("shared anon"));
+    }
+
+    #[test]
+    fn parses_an_anonymous_line() {
+        let region: MemoryRegion = "7ffd5c8e0000-7ffd5c901000 rw-p 00000000 00:00 0                          [stack]".parse().unwrap();
+        assert_eq!(region.file_name.as_deref(), Some("[stack]"));
+        let region: MemoryRegion = "55d0c0a00000-55d0c0a21000 rw-p 00000000 00:00 0".parse().unwrap();
+        assert_eq!((region.inode, region.file_name), (0, None));
+    }
+
+    #[test]
+    fn defaults_missing_columns() {
+        let region: MemoryRegion = "1000-2000 rw-p".parse().unwrap();
+        assert_eq!((region.offset, region.device, region.inode, region.file_name), (0, (0, 0), 0, None));
+    }
+
+    #[test]
+    fn rejects_bad_perms() {
+        for line in ["1000-2000 rw-", "1000-2000 rw-pp", "1000-2000 rw-q", "1000-2000 zw-p", "1000-2000 éab", "1000-2000 ééép"] {
+            assert!(line.parse::<MemoryRegion>().is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        for line in ["", "1000 rw-p", "x000-2000 rw-p", "1000-2000 rw-p zz 00:00 0", "1000-2000 rw-p 0 00 0", "1000-2000 rw-p 0 00:00 -1", "2000-1000 rw-p"] {
+            assert!(line.parse::<MemoryRegion>().is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn capping_lowers_the_largest_to_the_fraction() {
+        let mut weights = [8.0, 1.0, 1.0, 0.0];
+        cap_weights(&mut weights, 0.5);
+        assert_eq!(weights, [2.0, 1.0, 1.0, 0.0]);
+
+        let mut weights = [10.0, 1.0, 10.0, 1.0];
+        cap_weights(&mut weights, 0.3);
+        assert!((weights[0] - 1.5).abs() < 1e-9 && weights[0] == weights[2]);
+        assert_eq!((weights[1], weights[3]), (1.0, 1.0));
+        let total: f64 = weights.iter().sum();
+        assert!(weights.iter().all(|weight| weight / total <= 0.3 + 1e-9));
+    }
+
+    #[test]
+    fn capping_leaves_weights_under_the_fraction_alone() {
+        let mut weights = [3.0, 2.0, 2.0, 2.0];
+        cap_weights(&mut weights, 0.4);
+        assert_eq!(weights, [3.0, 2.0, 2.0, 2.0]);
+        cap_weights(&mut weights, 1.0);
+        assert_eq!(weights, [3.0, 2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn capping_below_an_even_share_evens_them_out() {
+        let mut weights = [4.0, 2.0, 1.0];
+        cap_weights(&mut weights, 0.1);
+        assert_eq!(weights, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn heights_are_proportional() {
+        assert_eq!(allot_heights(&[1.0, 1.0, 2.0, 0.0], 100, 0), [25, 25, 50, 0]);
+    }
+
+    #[test]
+    fn heights_raise_small_regions_to_the_floor() {
+        let heights = allot_heights(&[1000.0, 1.0, 0.0, 1.0], 100, 10);
+        assert_eq!(heights, [80, 10, 0, 10]);
+    }
+
+    #[test]
+    fn heights_floor_is_at_most_an_even_share() {
+        let heights = allot_heights(&[1.0, 1.0, 1.0, 100.0], 10, 5);
+        assert_eq!(heights[..3], [2, 2, 2]);
+        assert!(heights.iter().sum::<i32>() <= 10);
+    }
+
+    // Regions of 1 to 20 pages, one after another with a page between each.
+    fn spaced(count: usize) -> Vec<MemoryRegion> {
+        let mut start = 0x10000;
+        (0..count)
+            .map(|i| {
+                let end = start + (i % 20 + 1) * 0x1000;
+                let region = format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end).parse().unwrap();
+                start = end + 0x1000;
+                region
+            })
+            .collect()
+    }
+
+    #[test]
+    fn truncation_stays_within_the_cap() {
+        for (count, cap) in [(100, 10), (100, 11), (1000, 3), (57, 56), (1000, 2), (2, 1)] {
+            let (truncated, notice) = truncate_regions(spaced(count), cap);
+            assert!(truncated.len() <= cap, "{} regions capped at {} gave {}", count, cap, truncated.len());
+            assert!(notice.is_some());
+        }
+        let (untouched, notice) = truncate_regions(spaced(10), 10);
+        assert_eq!((untouched.len(), notice), (10, None));
+    }
+
+    #[test]
+    fn placeholders_span_their_run() {
+        let memory_regions = spaced(100);
+        let (first, last) = (memory_regions[0].start, memory_regions[99].end);
+        let (truncated, _) = truncate_regions(memory_regions, 10);
+        assert!(truncated.iter().all(|region| region.size == region.end - region.start));
+        assert!(truncated.windows(2).all(|pair| pair[0].end <= pair[1].start));
+        assert_eq!((truncated[0].start, truncated.last().unwrap().end), (first, last));
+    }
+
+    #[test]
+    fn truncation_notice_counts_kept_and_coalesced() {
+        // The four 20 page regions are kept, leaving five runs around them.
+        let (truncated, notice) = truncate_regions(spaced(100), 10);
+        assert_eq!(truncated.len(), 9);
+        assert_eq!(truncated[0].file_name.as_deref(), Some("[coalesced: 19 regions]"));
+        assert_eq!(notice.unwrap(), "truncated: showing 4 of 100 regions, the other 96 coalesced into 5");
+    }
+
+    // The budget for huge JVM and Chrome maps, with a few times the
+    // measured figures as margin: cargo test --release -- --ignored
+    #[test]
+    #[ignore = "times a million regions; run on a release build"]
+    fn a_million_regions_stay_within_budget() {
+        let mut maps = String::new();
+        for i in 0..1_000_000usize {
+            // Every third region leaves a hole before the next.
+            let start = 0x10000000 + i * 0x3000;
+            let end = start + if i % 3 == 0 { 0x1000 } else { 0x3000 };
+            match i % 2 {
+                0 => maps.push_str(&format!("{:x}-{:x} rw-p 00000000 00:00 0\n", start, end)),
+                _ => maps.push_str(&format!("{:x}-{:x} r-xp {:08x} fd:01 1835015 /usr/lib/libjvm.so\n", start, end, i * 0x1000)),
+            }
+        }
+        let options = render_options(&cli::defaults());
+        let timed = |budget: std::time::Duration, stage: &str, started: std::time::Instant| {
+            let elapsed = started.elapsed();
+            assert!(elapsed < budget, "{} took {:?}, over its {:?}", stage, elapsed, budget);
+        };
+
+        let started = std::time::Instant::now();
+        let (memory_regions, warnings) = parse_memory_regions(maps.as_bytes());
+        timed(std::time::Duration::from_secs(2), "parsing", started);
+        assert_eq!((memory_regions.len(), warnings.skipped_lines), (1_000_000, 0));
+
+        let started = std::time::Instant::now();
+        let memory_regions = insert_gap_memory_regions(&memory_regions);
+        timed(std::time::Duration::from_secs(1), "gap insertion", started);
+        assert_eq!(memory_regions.len(), 1_000_000 + 333_334);
+
+        let started = std::time::Instant::now();
+        let bands = layout(&memory_regions, 1_000_000, 0, &options);
+        timed(std::time::Duration::from_secs(1), "layout", started);
+        assert_eq!(bands.len(), memory_regions.len());
+    }
+
+    #[test]
+    fn smaps_only_count_as_drawn_when_shown() {
+        let line = "00400000-00452000 rw-p 00000000 00:00 0";
+        let with_rss = |rss| MemoryRegion { smaps: Some(SmapsInfo { rss, ..SmapsInfo::default() }), ..line.parse().unwrap() };
+        let (before, after) = (vec![with_rss(4096)], vec![with_rss(8192)]);
+        let plain = render_options(&cli::defaults());
+        assert_eq!(plain.drawn_regions(&before), plain.drawn_regions(&after));
+        let shaded = render_options(&DrawArgs { color_by: "rss".to_string(), ..cli::defaults() });
+        assert_ne!(shaded.drawn_regions(&before), shaded.drawn_regions(&after));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let line = "00400000-00452000 r-xp 00000000 08:02 173521                    /usr/bin/dbus-daemon";
+        let region: MemoryRegion = line.parse().unwrap();
+        assert_eq!(region.to_string(), line);
+        assert_eq!(region.to_string().parse::<MemoryRegion>().unwrap(), region);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::overview::ProcessSample;
+use crate::{backing_file, fit_text, format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+
+const ROW_HEIGHT: i32 = 18;
+const LABEL_WIDTH: i32 = 420;
+const WIDTH: i32 = 1100;
+const SHARED_COLOR: RGBColor = RGBColor(90, 170, 230);
+const PRIVATE_COLOR: RGBColor = RGBColor(240, 170, 0);
+const DUPLICATE_COLOR: RGBColor = RGBColor(220, 60, 40);
+
+// One mapped file across the processes: every distinct copy of it on disk,
+// which an upgrade leaves behind as the deleted old inode, and what those
+// copies cost.
+pub struct Library {
+    pub path: String,
+    pub processes: usize,
+    // The copy most PSS sits in first.
+    pub copies: Vec<FileCopy>,
+    pub rss: usize,
+    pub pss: usize,
+    pub private_dirty: usize,
+}
+
+pub struct FileCopy {
+    pub device: (u32, u32),
+    pub inode: u64,
+    pub deleted: bool,
+    pub processes: usize,
+    pub pss: usize,
+}
+
+impl Library {
+    // What sharing saves: each process counts the whole page in RSS but
+    // only its share in PSS.
+    pub fn saved(&self) -> usize {
+        self.rss.saturating_sub(self.pss)
+    }
+
+    // PSS charged to every copy but the main one, which a single copy
+    // would not need.
+    pub fn duplicated(&self) -> usize {
+        self.copies.iter().skip(1).map(|copy| copy.pss).sum()
+    }
+
+    // PSS over RSS is 1/N when N processes share every page and 1 when
+    // none are shared; this turns it into 0 to 100% of the ideal.
+    pub fn sharing(&self) -> f64 {
+        if self.processes <= 1 || self.rss == 0 {
+            return 0.0;
+        }
+        let ratio = self.pss as f64 / self.rss as f64;
+        let ideal = 1.0 / self.processes as f64;
+        ((1.0 - ratio) / (1.0 - ideal)).clamp(0.0, 1.0)
+    }
+}
+
+// Every file mapped by the processes, largest PSS first. Paths are compared
+// without " (deleted)", so an old and a new copy of a library are one
+// entry with two copies.
+pub fn analyze(processes: &[ProcessSample]) -> Vec<Library> {
+    let mut libraries: Vec<Library> = Vec::new();
+    for process in processes {
+        let mut seen: Vec<(String, (u32, u32), u64)> = Vec::new();
+        for region in process.memory_regions.iter().filter(|region| region.attributes.allocated) {
+            let Some(mapped) = backing_file(region) else { continue };
+            let deleted = mapped.ends_with(" (deleted)");
+            let path = mapped.strip_suffix(" (deleted)").unwrap_or(mapped);
+            let index = match libraries.iter().position(|library| library.path == path) {
+                Some(index) => index,
+                None => {
+                    libraries.push(Library { path: path.to_string(), processes: 0, copies: Vec::new(), rss: 0, pss: 0, private_dirty: 0 });
+                    libraries.len() - 1
+                }
+            };
+            let library = &mut libraries[index];
+            let pss = region.smaps.as_ref().map_or(0, |smaps| smaps.pss);
+            if let Some(smaps) = &region.smaps {
+                library.rss += smaps.rss;
+                library.pss += smaps.pss;
+                library.private_dirty += smaps.private_dirty;
+            }
+            let first_in_process = !seen.iter().any(|(other, _, _)| other == path);
+            let first_of_copy = !seen.iter().any(|(other, device, inode)| other == path && *device == region.device && *inode == region.inode);
+            match library.copies.iter_mut().find(|copy| copy.device == region.device && copy.inode == region.inode) {
+                Some(copy) => {
+                    copy.processes += first_of_copy as usize;
+                    copy.pss += pss;
+                }
+                None => library.copies.push(FileCopy { device: region.device, inode: region.inode, deleted, processes: 1, pss }),
+            }
+            library.processes += first_in_process as usize;
+            if first_of_copy {
+                seen.push((path.to_string(), region.device, region.inode));
+            }
+        }
+    }
+    for library in &mut libraries {
+        library.copies.sort_by_key(|copy| std::cmp::Reverse(copy.pss));
+    }
+    libraries.sort_by(|a, b| b.pss.cmp(&a.pss).then_with(|| a.path.cmp(&b.path)));
+    libraries
+}
+
+// Only the first `shown` get a row; the totals are over all of them.
+pub fn print(libraries: &[Library], shown: usize) {
+    println!("{:>5} {:>11} {:>10} {:>10} {:>10} {:>10} {:>10} {:>7}  path", "procs", "copies", "RSS", "PSS", "saved", "duplicate", "priv dirty", "shared");
+    for library in libraries.iter().take(shown) {
+        let deleted = library.copies.iter().filter(|copy| copy.deleted).count();
+        let copies = if deleted > 0 { format!("{} ({} del)", library.copies.len(), deleted) } else { library.copies.len().to_string() };
+        println!(
+            "{:>5} {:>11} {:>10} {:>10} {:>10} {:>10} {:>10} {:>6.0}%  {}",
+            library.processes,
+            copies,
+            format_size(library.rss),
+            format_size(library.pss),
+            format_size(library.saved()),
+            format_size(library.duplicated()),
+            format_size(library.private_dirty),
+            library.sharing() * 100.0,
+            library.path
+        );
+    }
+    let duplicated: usize = libraries.iter().map(Library::duplicated).sum();
+    let saved: usize = libraries.iter().map(Library::saved).sum();
+    if shown < libraries.len() {
+        println!("... and {} more files", libraries.len() - shown);
+    }
+    println!("Sharing saves {}; {} is spent on duplicate copies", format_size(saved), format_size(duplicated));
+}
+
+// One row per file on a common scale: the bar is its RSS summed over the
+// processes, the part of it PSS accounts for is filled, private dirty
+// (pages written after relocation, never shareable) and then the duplicate
+// copies' share painted over it from the left.
+pub fn draw_libraries(libraries: &[Library], shown: usize, process_count: usize, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let width = px(WIDTH) as u32;
+    let rows = &libraries[..shown.min(libraries.len())];
+    let height = px(rows.len() as i32 * ROW_HEIGHT + 60) as u32;
+    let root = BitMapBackend::new(path, (width, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let pss: usize = libraries.iter().map(|library| library.pss).sum();
+    let duplicated: usize = libraries.iter().map(Library::duplicated).sum();
+    let files = if rows.len() < libraries.len() { format!("Top {} of {} files", rows.len(), libraries.len()) } else { format!("{} files", libraries.len()) };
+    let title = format!("{} mapped by {} processes: PSS {}, {} in duplicate copies", files, process_count, format_size(pss), format_size(duplicated));
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+    let mut x = px(10);
+    for (label, color) in [("PSS", SHARED_COLOR), ("private dirty", PRIVATE_COLOR), ("duplicate copies", DUPLICATE_COLOR)] {
+        root.draw(&Rectangle::new([(x, px(30)), (x + px(12), px(42))], color.filled()))?;
+        root.draw(&Text::new(label, (x + px(16), px(31)), font.color(&ink)))?;
+        x += px(130);
+    }
+
+    let max = rows.iter().map(|library| library.rss).max().unwrap_or(1).max(1) as f64;
+    let bar_space = (px(WIDTH - LABEL_WIDTH - 10)) as f64;
+    for (i, library) in rows.iter().enumerate() {
+        let y = px(52 + i as i32 * ROW_HEIGHT);
+        let name = library.path.rsplit('/').next().unwrap_or(&library.path);
+        let copies = if library.copies.len() > 1 { format!(", {} copies", library.copies.len()) } else { String::new() };
+        let label = format!("{} x{}{}: {:.0}% shared", name, library.processes, copies, library.sharing() * 100.0);
+        if let Some(label) = fit_text(&root, &label, &font, px(LABEL_WIDTH - 15))? {
+            root.draw(&Text::new(label, (px(10), y + px(3)), font.color(&ink)))?;
+        }
+        let x0 = px(LABEL_WIDTH);
+        let to_x = |bytes: usize| x0 + (bytes as f64 / max * bar_space).round() as i32;
+        let bottom = y + px(ROW_HEIGHT - 4);
+        root.draw(&Rectangle::new([(x0, y), (to_x(library.pss), bottom)], SHARED_COLOR.filled()))?;
+        root.draw(&Rectangle::new([(x0, y), (to_x(library.private_dirty.min(library.pss)), bottom)], PRIVATE_COLOR.filled()))?;
+        root.draw(&Rectangle::new([(x0, y), (to_x(library.duplicated()), bottom)], DUPLICATE_COLOR.filled()))?;
+        root.draw(&Rectangle::new([(x0, y), (to_x(library.rss).max(x0 + px(1)), bottom)], ink.stroke_width(px(1) as u32)))?;
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use crate::cli::LogArgs;
+use std::io::IsTerminal;
+use tracing::Level;
+
+// What the long-running modes report as they go, on stderr so a report or
+// an inline map on stdout stays clean. The one-shot output of a normal run
+// is printed as before.
+pub fn init(args: &LogArgs) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt().with_max_level(level).with_writer(std::io::stderr).with_target(false).with_ansi(std::io::stderr().is_terminal());
+    match args.log_format.as_str() {
+        "json" => subscriber.json().init(),
+        _ => subscriber.init(),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{format_size, MemoryRegion};
+
+// One arena from glibc's malloc_info(); heap 0 is main_arena, on [heap].
+#[derive(Debug, Default)]
+pub struct Heap {
+    pub nr: usize,
+    // Bytes the arena got from the system, and how much of it sits in
+    // free chunks (fastbins and the rest).
+    pub system: usize,
+    pub free: usize,
+    pub free_chunks: usize,
+}
+
+impl Heap {
+    pub fn in_use(&self) -> usize {
+        self.system.saturating_sub(self.free)
+    }
+
+    pub fn label(&self) -> String {
+        let name = if self.nr == 0 { "main_arena".to_string() } else { format!("arena {}", self.nr) };
+        format!("{}: {} in use, {} free", name, format_size(self.in_use()), format_size(self.free))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MallocInfo {
+    pub heaps: Vec<Heap>,
+    // Large allocations served by mmap directly, outside every arena.
+    pub mmapped: usize,
+    pub mmapped_chunks: usize,
+}
+
+pub fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    tag[start..].split('"').next()
+}
+
+// The XML is flat enough to read tag by tag: <heap nr="N"> holds <total>
+// and <system> elements, and the totals after the last heap cover them all.
+pub fn parse(xml: &str) -> Result<MallocInfo, String> {
+    if !xml.contains("<malloc") {
+        return Err("Not malloc_info output".to_string());
+    }
+    let mut info = MallocInfo::default();
+    let mut current: Option<Heap> = None;
+    for tag in xml.split('<').skip(1).map(|tag| tag.split('>').next().unwrap_or("")) {
+        let size = || attribute(tag, "size").and_then(|size| size.parse::<usize>().ok()).unwrap_or(0);
+        let count = || attribute(tag, "count").and_then(|count| count.parse::<usize>().ok()).unwrap_or(0);
+        let kind = attribute(tag, "type");
+        if tag.starts_with("heap ") {
+            let nr = attribute(tag, "nr").and_then(|nr| nr.parse().ok()).ok_or_else(|| format!("Invalid heap: <{}>", tag))?;
+            current = Some(Heap { nr, ..Heap::default() });
+        } else if tag == "/heap" {
+            info.heaps.extend(current.take());
+        } else if let Some(heap) = current.as_mut() {
+            match (tag.split_whitespace().next(), kind) {
+                (Some("total"), Some("fast" | "rest")) => {
+                    heap.free += size();
+                    heap.free_chunks += count();
+                }
+                (Some("system"), Some("current")) => heap.system = size(),
+                _ => {}
+            }
+        } else if tag.starts_with("total ") && kind == Some("mmap") {
+            info.mmapped = size();
+            info.mmapped_chunks = count();
+        }
+    }
+    if info.heaps.is_empty() {
+        return Err("malloc_info output lists no heaps".to_string());
+    }
+    Ok(info)
+}
+
+// Where each heap is: main_arena on [heap], the others in the mapping
+// holding their arena address, when gdb found those.
+pub fn locate<'a>(info: &'a MallocInfo, memory_regions: &[MemoryRegion], arenas: &[usize]) -> Vec<(&'a Heap, Option<usize>)> {
+    info.heaps
+        .iter()
+        .map(|heap| {
+            let start = match (heap.nr, arenas.get(heap.nr)) {
+                (0, _) => memory_regions.iter().find(|region| region.file_name.as_deref() == Some("[heap]")).map(|region| region.start),
+                (_, Some(arena)) => memory_regions.iter().find(|region| region.start <= *arena && *arena < region.end).map(|region| region.start),
+                _ => None,
+            };
+            (heap, start)
+        })
+        .collect()
+}
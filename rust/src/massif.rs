@@ -0,0 +1,271 @@
+use crate::{fit_text, format_size};
+use plotters::prelude::*;
+
+// Allocation sites drawn as layers of their own; the rest are one layer.
+const SITES: usize = 8;
+const CHART_HEIGHT: u32 = 380;
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub time: f64,
+    pub heap: u64,
+    // Allocator overhead: headers and alignment padding.
+    pub extra: u64,
+    pub stacks: u64,
+    // The heap tree's top-level entries, for detailed and peak snapshots.
+    pub sites: Option<Vec<(String, u64)>>,
+}
+
+#[derive(Clone)]
+pub struct Massif {
+    pub command: String,
+    // "i" (instructions), "ms" or "B" (bytes allocated), from --time-unit.
+    pub time_unit: String,
+    pub snapshots: Vec<Snapshot>,
+}
+
+// The side panel, with the watched frame's place on the timeline.
+#[derive(Clone)]
+pub struct Panel {
+    pub massif: Massif,
+    pub cursor: Option<f64>,
+}
+
+// "0x4E2A6B3: std::vector<int>::push_back (vector.h:12)" without the
+// address, and massif's line for the sites under its threshold shortened.
+fn site_name(description: &str) -> String {
+    if description.contains("below massif's threshold") {
+        return "sites below threshold".to_string();
+    }
+    match description.split_once(": ") {
+        Some((address, name)) if address.starts_with("0x") => name.to_string(),
+        _ => description.to_string(),
+    }
+}
+
+// massif.out.PID: a header of "desc:", "cmd:" and "time_unit:", then per
+// snapshot "time=", "mem_heap_B=", "mem_heap_extra_B=", "mem_stacks_B="
+// and "heap_tree=", which for detailed and peak snapshots is followed by
+// the tree, one "nCHILDREN: BYTES DESCRIPTION" line per node indented by
+// its depth.
+pub fn parse(text: &str) -> Result<Massif, String> {
+    let mut massif = Massif { command: String::new(), time_unit: "i".to_string(), snapshots: Vec::new() };
+    for line in text.lines() {
+        if let Some(command) = line.strip_prefix("cmd: ") {
+            massif.command = command.to_string();
+        } else if let Some(unit) = line.strip_prefix("time_unit: ") {
+            massif.time_unit = unit.trim().to_string();
+        } else if line.starts_with("snapshot=") {
+            massif.snapshots.push(Snapshot { time: 0.0, heap: 0, extra: 0, stacks: 0, sites: None });
+        } else if let Some(snapshot) = massif.snapshots.last_mut() {
+            let number = |value: &str| value.trim().parse::<u64>().map_err(|_| format!("Invalid massif line: {}", line));
+            if let Some(time) = line.strip_prefix("time=") {
+                snapshot.time = time.trim().parse().map_err(|_| format!("Invalid massif line: {}", line))?;
+            } else if let Some(heap) = line.strip_prefix("mem_heap_B=") {
+                snapshot.heap = number(heap)?;
+            } else if let Some(extra) = line.strip_prefix("mem_heap_extra_B=") {
+                snapshot.extra = number(extra)?;
+            } else if let Some(stacks) = line.strip_prefix("mem_stacks_B=") {
+                snapshot.stacks = number(stacks)?;
+            } else if let Some(tree) = line.strip_prefix("heap_tree=") {
+                if tree != "empty" {
+                    snapshot.sites = Some(Vec::new());
+                }
+            } else if let (Some(sites), Some(node)) = (snapshot.sites.as_mut(), line.strip_prefix(' ')) {
+                // Only the root's children, indented by one.
+                if node.starts_with(' ') {
+                    continue;
+                }
+                let Some((_, rest)) = node.split_once(": ") else { continue };
+                let (bytes, description) = rest.split_once(' ').unwrap_or((rest, ""));
+                sites.push((site_name(description), number(bytes)?));
+            }
+        }
+    }
+    if massif.snapshots.is_empty() {
+        return Err("No massif snapshots".to_string());
+    }
+    Ok(massif)
+}
+
+pub fn read(path: &str) -> Result<Massif, String> {
+    parse(&std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?)
+}
+
+fn total(snapshot: &Snapshot) -> u64 {
+    snapshot.heap + snapshot.extra + snapshot.stacks
+}
+
+pub fn print(massif: &Massif) {
+    let Some(peak) = massif.snapshots.iter().max_by_key(|snapshot| total(snapshot)) else { return };
+    println!(
+        "{}: {} snapshots, peak {} at {}{} (heap {}, extra {}, stacks {})",
+        massif.command,
+        massif.snapshots.len(),
+        format_size(total(peak) as usize),
+        peak.time,
+        massif.time_unit,
+        format_size(peak.heap as usize),
+        format_size(peak.extra as usize),
+        format_size(peak.stacks as usize)
+    );
+    for (site, bytes) in peak.sites.iter().flatten() {
+        println!("{:>10}  {}", format_size(*bytes as usize), site);
+    }
+}
+
+// The layers to stack, each with its bytes per point: the largest sites,
+// the other sites, allocator overhead and stacks, over the detailed
+// snapshots. With fewer than two of those the heap is one layer over every
+// snapshot.
+fn layers(massif: &Massif) -> (Vec<f64>, Vec<(String, Vec<u64>)>) {
+    let detailed: Vec<&Snapshot> = massif.snapshots.iter().filter(|snapshot| snapshot.sites.is_some()).collect();
+    let points: Vec<&Snapshot> = if detailed.len() >= 2 { detailed } else { massif.snapshots.iter().collect() };
+    let bytes = |snapshot: &Snapshot, site: &str| snapshot.sites.iter().flatten().filter(|(name, _)| name == site).map(|(_, bytes)| bytes).sum::<u64>();
+    let mut names: Vec<&String> = points.iter().flat_map(|snapshot| snapshot.sites.iter().flatten().map(|(name, _)| name)).collect();
+    names.sort();
+    names.dedup();
+    names.sort_by_key(|name| std::cmp::Reverse(points.iter().map(|snapshot| bytes(snapshot, name)).max().unwrap_or(0)));
+    names.truncate(SITES);
+    let mut layers: Vec<(String, Vec<u64>)> = names.iter().map(|name| (name.to_string(), points.iter().map(|snapshot| bytes(snapshot, name)).collect())).collect();
+    let rest: Vec<u64> = points.iter().map(|snapshot| snapshot.heap - names.iter().map(|name| bytes(snapshot, name)).sum::<u64>().min(snapshot.heap)).collect();
+    let label = if names.is_empty() { "heap" } else { "other sites" };
+    layers.push((label.to_string(), rest));
+    layers.push(("allocator overhead".to_string(), points.iter().map(|snapshot| snapshot.extra).collect()));
+    if points.iter().any(|snapshot| snapshot.stacks > 0) {
+        layers.push(("stacks".to_string(), points.iter().map(|snapshot| snapshot.stacks).collect()));
+    }
+    layers.retain(|(_, values)| values.iter().any(|bytes| *bytes > 0));
+    (points.iter().map(|snapshot| snapshot.time).collect(), layers)
+}
+
+fn layer_color(index: usize, name: &str) -> RGBColor {
+    match name {
+        "allocator overhead" => RGBColor(170, 170, 170),
+        "stacks" => RGBColor(150, 110, 70),
+        "other sites" | "heap" => RGBColor(110, 140, 190),
+        _ => {
+            let (r, g, b) = Palette99::pick(index).rgb();
+            RGBColor(r, g, b)
+        }
+    }
+}
+
+// The heap's composition stacked over time, the total of every snapshot
+// as a line over it and, under watch, a line at the frame's time.
+pub fn draw_panel(panel: &Panel, width: u32, height: u32) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let massif = &panel.massif;
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    {
+        let root = BitMapBackend::with_buffer(&mut imgbuf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let (chart_area, legend_area) = root.split_vertically(CHART_HEIGHT.min(height));
+        let (times, layers) = layers(massif);
+        let last = massif.snapshots.iter().map(|snapshot| snapshot.time).fold(panel.cursor.unwrap_or(0.0), f64::max).max(1.0);
+        let most = massif.snapshots.iter().map(total).max().unwrap_or(0).max(1) as f64;
+        let mut chart = ChartBuilder::on(&chart_area)
+            .caption(format!("massif heap over time ({})", massif.time_unit), ("sans-serif", 13))
+            .margin(8)
+            .x_label_area_size(25)
+            .y_label_area_size(60)
+            .build_cartesian_2d(0.0..last, 0.0..most * 1.05)?;
+        chart.configure_mesh().x_labels(4).y_labels(6).y_label_formatter(&|bytes| format_size(*bytes as usize)).draw()?;
+        // Drawn top down, each layer's area from zero to its running total
+        // covers the ones above it everywhere but their own band.
+        let mut running = vec![0u64; times.len()];
+        let mut tops: Vec<Vec<u64>> = Vec::new();
+        for (_, values) in &layers {
+            running.iter_mut().zip(values).for_each(|(sum, bytes)| *sum += bytes);
+            tops.push(running.clone());
+        }
+        for (index, ((name, _), top)) in layers.iter().zip(&tops).enumerate().rev() {
+            let points = times.iter().zip(top).map(|(time, bytes)| (*time, *bytes as f64));
+            chart.draw_series(AreaSeries::new(points, 0.0, layer_color(index, name).filled()))?;
+        }
+        chart.draw_series(LineSeries::new(massif.snapshots.iter().map(|snapshot| (snapshot.time, total(snapshot) as f64)), BLACK.stroke_width(1)))?;
+        if let Some(cursor) = panel.cursor {
+            chart.draw_series(LineSeries::new([(cursor, 0.0), (cursor, most * 1.05)], RED.stroke_width(2)))?;
+        }
+
+        let font = ("sans-serif", 11).into_font();
+        let mut y = 4;
+        for (index, (name, values)) in layers.iter().enumerate() {
+            let peak = values.iter().max().copied().unwrap_or(0);
+            legend_area.draw(&Rectangle::new([(10, y), (20, y + 10)], layer_color(index, name).filled()))?;
+            let label = fit_text(&legend_area, &format!("{} {}", format_size(peak as usize), name), &font, width as i32 - 34)?.unwrap_or_default();
+            legend_area.draw(&Text::new(label, (24, y), font.clone()))?;
+            y += 15;
+        }
+    }
+    Ok(imgbuf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASSIF: &str = "\
+desc: --time-unit=ms
+cmd: ./server --port 8080
+time_unit: ms
+#-----------
+snapshot=0
+#-----------
+time=0
+mem_heap_B=0
+mem_heap_extra_B=0
+mem_stacks_B=0
+heap_tree=empty
+#-----------
+snapshot=1
+#-----------
+time=52
+mem_heap_B=30000
+mem_heap_extra_B=120
+mem_stacks_B=0
+heap_tree=detailed
+n3: 30000 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.
+ n1: 20000 0x4E2A6B3: std::vector<int>::push_back(int const&) (stl_vector.h:1198)
+  n0: 20000 0x109C8E: main (server.cpp:40)
+ n0: 9000 0x109D12: load_config (config.cpp:17)
+ n0: 1000 in 4 places, all below massif's threshold (1.00%)
+#-----------
+snapshot=2
+#-----------
+time=97.5
+mem_heap_B=50000
+mem_heap_extra_B=200
+mem_stacks_B=0
+heap_tree=peak
+n1: 50000 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.
+ n0: 50000 0x4E2A6B3: std::vector<int>::push_back(int const&) (stl_vector.h:1198)
+";
+
+    #[test]
+    fn parses_snapshots_and_their_sites() {
+        let massif = parse(MASSIF).unwrap();
+        assert_eq!((massif.command.as_str(), massif.time_unit.as_str(), massif.snapshots.len()), ("./server --port 8080", "ms", 3));
+        assert!(massif.snapshots[0].sites.is_none());
+        let detailed = &massif.snapshots[1];
+        assert_eq!((detailed.time, detailed.heap, detailed.extra), (52.0, 30000, 120));
+        // Only the root's children, without their addresses.
+        let sites: Vec<(&str, u64)> = detailed.sites.iter().flatten().map(|(name, bytes)| (name.as_str(), *bytes)).collect();
+        assert_eq!(sites, [("std::vector<int>::push_back(int const&) (stl_vector.h:1198)", 20000), ("load_config (config.cpp:17)", 9000), ("sites below threshold", 1000)]);
+        assert_eq!(massif.snapshots[2].time, 97.5);
+    }
+
+    #[test]
+    fn stacks_the_detailed_snapshots() {
+        let (times, layers) = layers(&parse(MASSIF).unwrap());
+        assert_eq!(times, [52.0, 97.5]);
+        let names: Vec<&str> = layers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["std::vector<int>::push_back(int const&) (stl_vector.h:1198)", "load_config (config.cpp:17)", "sites below threshold", "allocator overhead"]);
+        assert_eq!(layers[0].1, [20000, 50000]);
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        assert!(parse("snapshot=0\nmem_heap_B=lots\n").is_err());
+        assert!(parse("desc: (none)\n").is_err());
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adb::AdbTarget;
+use crate::format_size;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+// The machine's overall memory pressure when the map was taken, from
+// /proc/meminfo. Bytes; the file counts in kB.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemMemory {
+    pub total: usize,
+    pub available: usize,
+    pub swap_total: usize,
+    pub swap_free: usize,
+    pub committed: usize,
+    pub commit_limit: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse(text: &str) -> Option<SystemMemory> {
+    let value = |name: &str| -> Option<usize> {
+        let line = text.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?;
+        Some(line.split_whitespace().next()?.parse::<usize>().ok()? * 1024)
+    };
+    Some(SystemMemory {
+        total: value("MemTotal")?,
+        // Kernels before 3.14 don't estimate it.
+        available: value("MemAvailable").unwrap_or(value("MemFree")?),
+        swap_total: value("SwapTotal").unwrap_or(0),
+        swap_free: value("SwapFree").unwrap_or(0),
+        committed: value("Committed_AS").unwrap_or(0),
+        commit_limit: value("CommitLimit").unwrap_or(0),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(adb: Option<&AdbTarget>) -> Option<SystemMemory> {
+    let text = match adb {
+        Some(adb) => adb.shell(&["cat", "/proc/meminfo"]).ok()?,
+        None => fs::read_to_string("/proc/meminfo").ok()?,
+    };
+    parse(&text)
+}
+
+impl SystemMemory {
+    pub fn label(&self) -> String {
+        let mut label = format!("system: {} available of {}", format_size(self.available), format_size(self.total));
+        if self.swap_total > 0 {
+            label.push_str(&format!(", swap {} free of {}", format_size(self.swap_free), format_size(self.swap_total)));
+        } else {
+            label.push_str(", no swap");
+        }
+        label.push_str(&format!(", {} committed", format_size(self.committed)));
+        if self.commit_limit > 0 {
+            label.push_str(&format!(" ({:.0}% of CommitLimit)", self.committed as f64 / self.commit_limit as f64 * 100.0));
+        }
+        label
+    }
+}
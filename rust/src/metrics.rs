@@ -0,0 +1,114 @@
+use crate::context::AddressSpace;
+use crate::{audit, fragmentation, region_category, MemoryRegion};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const UP_HELP: &str = "# HELP memlayout_up Whether the last sample of the process could be read.\n# TYPE memlayout_up gauge\n";
+
+// `local` when the process runs on this machine, whose address space
+// bounds can then be read instead of guessed from the mappings.
+pub fn render_metrics(pid: u32, memory_regions: &[MemoryRegion], local: bool) -> String {
+    let mapped: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| region.attributes.allocated).collect();
+    let mut rss_by_category: BTreeMap<&str, usize> = BTreeMap::new();
+    for region in &mapped {
+        *rss_by_category.entry(region_category(region)).or_insert(0) += region.smaps.as_ref().map_or(0, |smaps| smaps.rss);
+    }
+
+    let mut text = format!("{}memlayout_up{{pid=\"{}\"}} 1\n", UP_HELP, pid);
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, usize)>| {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+        for (labels, value) in samples {
+            text.push_str(&format!("{}{{pid=\"{}\"{}}} {}\n", name, pid, labels, value));
+        }
+    };
+    gauge("memlayout_mapped_bytes", "Virtual bytes mapped by the process.", vec![(String::new(), mapped.iter().map(|region| region.size).sum())]);
+    gauge(
+        "memlayout_rss_bytes",
+        "Resident bytes by region category.",
+        rss_by_category.into_iter().map(|(category, rss)| (format!(",category=\"{}\"", category), rss)).collect(),
+    );
+    gauge("memlayout_regions", "Number of mappings.", vec![(String::new(), mapped.len())]);
+    gauge(
+        "memlayout_wx_regions",
+        "Mappings that are both writable and executable.",
+        vec![(String::new(), mapped.iter().filter(|region| audit::is_writable_executable(region)).count())],
+    );
+    gauge(
+        "memlayout_largest_free_hole_bytes",
+        "Largest free range in the user address space.",
+        vec![(String::new(), fragmentation::fragmentation(memory_regions, &AddressSpace::detect(memory_regions, local)).largest_free as usize)],
+    );
+    text
+}
+
+// What a scrape gets while the process can't be read, e.g. after it exited:
+// no stale figures, only the sample having failed.
+fn render_down(pid: u32) -> String {
+    format!("{}memlayout_up{{pid=\"{}\"}} 0\n", UP_HELP, pid)
+}
+
+fn sample<F: Fn() -> Result<Vec<MemoryRegion>, String>>(pid: u32, local: bool, capture: &F) -> String {
+    match capture() {
+        Ok(memory_regions) => render_metrics(pid, &memory_regions, local),
+        Err(e) => {
+            tracing::warn!(pid, "metrics: sample failed: {}", e);
+            render_down(pid)
+        }
+    }
+}
+
+// Long enough for any scraper; a client that stalls past it is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn respond(mut stream: TcpStream, latest: &Mutex<String>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let response = if path == "/metrics" {
+        let body = latest.lock().map(|text| text.clone()).unwrap_or_default();
+        format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())
+}
+
+// Scrapes are answered from the last sample, so a slow smaps read never
+// holds up Prometheus; sampling stays on the calling thread. Each
+// connection gets a thread of its own so an idle one can't stall the rest.
+pub fn serve_metrics<F: Fn() -> Result<Vec<MemoryRegion>, String>>(addr: &str, pid: u32, local: bool, interval: Duration, capture: F) -> Result<(), Box<dyn std::error::Error>> {
+    let latest = Arc::new(Mutex::new(sample(pid, local, &capture)));
+    let listener = TcpListener::bind(addr)?;
+    tracing::info!("serving metrics on http://{}/metrics", listener.local_addr()?);
+
+    let shared = Arc::clone(&latest);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::warn!("metrics: accept failed: {}", e);
+                    continue;
+                }
+            };
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                if let Err(e) = respond(stream, &shared) {
+                    tracing::debug!("metrics: {}", e);
+                }
+            });
+        }
+    });
+
+    loop {
+        thread::sleep(interval);
+        let text = sample(pid, local, &capture);
+        *latest.lock().map_err(|_| "metrics lock poisoned")? = text;
+    }
+}
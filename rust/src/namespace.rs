@@ -0,0 +1,63 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+// How a target in a container differs from us. Shared namespaces are left
+// out, so a process on the host has nothing to report.
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub pid: u32,
+    // The target's PID as the processes in its own namespace see it.
+    pub inner_pid: Option<u32>,
+    pub pid_namespace: Option<String>,
+    pub mount_namespace: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn foreign(pid: u32, kind: &str) -> Option<String> {
+    let theirs = fs::read_link(format!("/proc/{}/ns/{}", pid, kind)).ok()?;
+    let ours = fs::read_link(format!("/proc/self/ns/{}", kind)).ok()?;
+    (theirs != ours).then(|| theirs.to_string_lossy().into_owned())
+}
+
+// The last NSpid field is the PID in the innermost namespace.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn innermost_pid(pid: u32) -> Option<u32> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("NSpid:"))?;
+    line.split_whitespace().last()?.parse().ok()
+}
+
+impl Namespace {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn detect(pid: u32) -> Option<Self> {
+        let pid_namespace = foreign(pid, "pid");
+        let mount_namespace = foreign(pid, "mnt");
+        if pid_namespace.is_none() && mount_namespace.is_none() {
+            return None;
+        }
+        let inner_pid = pid_namespace.as_ref().and_then(|_| innermost_pid(pid));
+        Some(Namespace { pid, inner_pid, pid_namespace, mount_namespace })
+    }
+
+    // Mapped paths name files in the target's mount namespace, which the
+    // kernel exposes under /proc/<pid>/root.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn file_root(&self) -> Option<PathBuf> {
+        self.mount_namespace.as_ref().map(|_| PathBuf::from(format!("/proc/{}/root", self.pid)))
+    }
+
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(namespace) = &self.pid_namespace {
+            match self.inner_pid {
+                Some(inner) => parts.push(format!("pid {} in {}", inner, namespace)),
+                None => parts.push(namespace.clone()),
+            }
+        }
+        parts.extend(self.mount_namespace.clone());
+        parts.join(", ")
+    }
+}
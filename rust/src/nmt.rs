@@ -0,0 +1,204 @@
+use crate::{carve, format_size, MemoryRegion};
+use std::process::Command;
+
+// A summary line: "-   Java Heap (reserved=262144KB, committed=96256KB)".
+pub struct Category {
+    pub name: String,
+    pub reserved: usize,
+    pub committed: usize,
+}
+
+// A range from the virtual memory map of `detail` mode, reserved for one
+// category: "[0x00000000f0000000 - 0x0000000100000000] reserved 262144KB
+// for Java Heap from".
+pub struct Reservation {
+    pub start: usize,
+    pub end: usize,
+    pub category: String,
+}
+
+pub struct Report {
+    pub reserved: usize,
+    pub committed: usize,
+    pub categories: Vec<Category>,
+    pub reservations: Vec<Reservation>,
+}
+
+// jcmd prints in the scale it was asked for, KB unless told otherwise;
+// -XX:+PrintNMTStatistics at exit prints bytes without a unit.
+fn amount(text: &str) -> Option<usize> {
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number: usize = text[..digits].parse().ok()?;
+    let shift = match &text[digits..] {
+        "" => 0,
+        "KB" => 10,
+        "MB" => 20,
+        "GB" => 30,
+        _ => return None,
+    };
+    Some(number << shift)
+}
+
+// "reserved=N, committed=M" anywhere in a line.
+fn reserved_committed(line: &str) -> Option<(usize, usize)> {
+    let value = |key: &str| amount(line.split(key).nth(1)?.split([',', ')']).next()?.trim());
+    Some((value("reserved=")?, value("committed=")?))
+}
+
+fn range(text: &str) -> Option<(usize, usize)> {
+    let (start, end) = text.strip_prefix('[')?.split_once(']')?.0.split_once('-')?;
+    let address = |text: &str| usize::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok();
+    Some((address(start)?, address(end)?))
+}
+
+// jcmd PID VM.native_memory summary or detail output, or the same printed
+// at exit by -XX:+PrintNMTStatistics. Only detail has the virtual memory
+// map; the call stacks under each range and the malloc sites are skipped.
+pub fn parse(text: &str) -> Result<Report, String> {
+    let mut report = Report { reserved: 0, committed: 0, categories: Vec::new(), reservations: Vec::new() };
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(total) = trimmed.strip_prefix("Total:") {
+            (report.reserved, report.committed) = reserved_committed(total).ok_or_else(|| format!("Invalid NMT total: {}", line))?;
+        } else if let Some(category) = trimmed.strip_prefix('-') {
+            let Some((name, amounts)) = category.split_once('(') else { continue };
+            if let Some((reserved, committed)) = reserved_committed(amounts) {
+                report.categories.push(Category { name: name.trim().to_string(), reserved, committed });
+            }
+        } else if line.starts_with('[') && line.contains(" reserved ") {
+            let (Some((start, end)), Some((_, category))) = (range(line), line.split_once(" for ")) else { continue };
+            let category = category.strip_suffix(" from").unwrap_or(category).trim();
+            report.reservations.push(Reservation { start, end, category: category.to_string() });
+        }
+    }
+    if report.categories.is_empty() {
+        return Err("No Native Memory Tracking summary; was the JVM started with -XX:NativeMemoryTracking=summary or detail?".to_string());
+    }
+    report.categories.sort_by(|a, b| b.committed.cmp(&a.committed).then(a.name.cmp(&b.name)));
+    report.reservations.sort_by_key(|reservation| reservation.start);
+    Ok(report)
+}
+
+// "jcmd" asks the JVM itself; anything else is a file of saved output.
+pub fn read(source: &str, pid: u32) -> Result<Report, String> {
+    let text = if source == "jcmd" {
+        let output = Command::new("jcmd")
+            .args([pid.to_string().as_str(), "VM.native_memory", "detail"])
+            .output()
+            .map_err(|e| format!("Unable to run jcmd: {}", e))?;
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() || text.contains("Native memory tracking is not enabled") {
+            return Err(format!("jcmd {} VM.native_memory detail failed: {}", pid, text.lines().last().unwrap_or("").trim()));
+        }
+        text
+    } else {
+        std::fs::read_to_string(source).map_err(|e| format!("Unable to read {}: {}", source, e))?
+    };
+    parse(&text)
+}
+
+pub fn print(report: &Report) {
+    println!("{:<28} {:>10} {:>10}", "category", "reserved", "committed");
+    for category in &report.categories {
+        println!("{:<28} {:>10} {:>10}", category.name, format_size(category.reserved), format_size(category.committed));
+    }
+    println!("{:<28} {:>10} {:>10}", "total", format_size(report.reserved), format_size(report.committed));
+}
+
+// Anonymous mappings named "[anon:Java Heap]" and so on for the range of
+// each reservation they overlap, split where one mapping holds several.
+// Thread stacks the process view already found keep their names, and
+// split mappings lose their smaps. Also returns how many bytes were named.
+pub fn attribute(memory_regions: Vec<MemoryRegion>, reservations: &[Reservation]) -> (Vec<MemoryRegion>, usize) {
+    let mut attributed = 0;
+    let mut result = Vec::with_capacity(memory_regions.len());
+    for region in memory_regions {
+        let anonymous = region.attributes.allocated && region.file_name.is_none() && region.thread_id.is_none() && !region.attributes.shared;
+        let overlays: Vec<MemoryRegion> = reservations
+            .iter()
+            .filter(|reservation| anonymous && reservation.start < region.end && region.start < reservation.end)
+            .map(|reservation| {
+                let (start, end) = (reservation.start.max(region.start), reservation.end.min(region.end));
+                MemoryRegion { start, end, size: end - start, file_name: Some(format!("[anon:{}]", reservation.category)), ..region.clone() }
+            })
+            .collect();
+        if overlays.is_empty() {
+            result.push(region);
+            continue;
+        }
+        attributed += overlays.iter().map(|overlay| overlay.size).sum::<usize>();
+        let mut pieces = carve(&region, &overlays);
+        pieces.extend(overlays);
+        pieces.sort_by_key(|piece| piece.start);
+        // The mapping's smaps totals can't be divided between its pieces.
+        if pieces.len() > 1 {
+            pieces.iter_mut().for_each(|piece| piece.smaps = None);
+        }
+        result.extend(pieces);
+    }
+    (result, attributed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // jcmd PID VM.native_memory detail, cut down.
+    const DETAIL: &str = "\
+12345:
+
+Native Memory Tracking:
+
+(Omitting categories weighting less than 1KB)
+
+Total: reserved=1671581KB, committed=113461KB
+       malloc: 19429KB #45020
+       mmap:   reserved=1652152KB, committed=94032KB
+
+-                 Java Heap (reserved=262144KB, committed=16384KB)
+                            (mmap: reserved=262144KB, committed=16384KB)
+
+-                     Class (reserved=1048713KB, committed=265KB)
+                            (classes #508)
+                            (  instance classes #426, array classes #82)
+                            (malloc=137KB #652)
+
+-                    Thread (reserved=18488KB, committed=1060KB)
+                            (thread #18)
+                            (stack: reserved=18432KB, committed=1004KB)
+
+Virtual memory map:
+
+[0x00000000f0000000 - 0x0000000100000000] reserved 262144KB for Java Heap from
+    [0x00007f4ad3c3f6a5] ReservedHeapSpace::try_reserve_heap(unsigned long, unsigned long, unsigned long, char*)+0x1a5
+\t[0x00000000f0000000 - 0x00000000f1000000] committed 16384KB from
+            [0x00007f4ad3c3e6b4] G1PageBasedVirtualSpace::commit(unsigned long, unsigned long)+0x174
+
+[0x00007f4a80000000 - 0x00007f4ac0000000] reserved 1048576KB for Class from
+    [0x00007f4ad3d1c7d8] Metaspace::reserve_address_space_for_compressed_classes(unsigned long)+0x68
+";
+
+    #[test]
+    fn parses_a_detail_report() {
+        let report = parse(DETAIL).unwrap();
+        assert_eq!((report.reserved, report.committed), (1671581 << 10, 113461 << 10));
+        let categories: Vec<(&str, usize)> = report.categories.iter().map(|category| (category.name.as_str(), category.committed)).collect();
+        // Largest committed first.
+        assert_eq!(categories, [("Java Heap", 16384 << 10), ("Thread", 1060 << 10), ("Class", 265 << 10)]);
+        let reservations: Vec<(usize, usize, &str)> = report.reservations.iter().map(|r| (r.start, r.end, r.category.as_str())).collect();
+        assert_eq!(reservations, [(0xf0000000, 0x100000000, "Java Heap"), (0x7f4a80000000, 0x7f4ac0000000, "Class")]);
+    }
+
+    #[test]
+    fn reads_amounts_in_any_scale() {
+        assert_eq!(amount("262144KB"), Some(256 << 20));
+        assert_eq!(amount("3MB"), Some(3 << 20));
+        assert_eq!(amount("4096"), Some(4096));
+        assert_eq!(amount("12TB"), None);
+    }
+
+    #[test]
+    fn rejects_a_report_without_tracking() {
+        assert!(parse("12345:\nNative memory tracking is not enabled\n").is_err());
+    }
+}
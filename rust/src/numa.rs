@@ -0,0 +1,138 @@
+use crate::format_size;
+use plotters::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+
+// Bytes the process has on each node, from numa_maps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumaTotals {
+    pub nodes: BTreeMap<u32, usize>,
+    // The node of the CPU the process last ran on, when it runs here.
+    pub local: Option<u32>,
+    // Per memory policy ("default", "bind:1", "interleave:0-1", ...).
+    pub policies: BTreeMap<String, usize>,
+}
+
+// "7f12... default file=/x anon=3 N0=2 N1=1 kernelpagesize_kB=4": pages per
+// node in the N fields, of the page size at the end.
+pub fn parse(numa_maps: &str) -> NumaTotals {
+    let mut nodes = BTreeMap::new();
+    let mut policies = BTreeMap::new();
+    for line in numa_maps.lines() {
+        let mut fields = line.split_whitespace();
+        let policy = fields.nth(1).unwrap_or("default");
+        let fields: Vec<&str> = fields.collect();
+        let page_size = fields.iter().find_map(|field| field.strip_prefix("kernelpagesize_kB=")?.parse::<usize>().ok()).unwrap_or(4) * 1024;
+        let mut bytes = 0;
+        for (node, pages) in fields.iter().filter_map(|field| {
+            let (node, pages) = field.strip_prefix('N')?.split_once('=')?;
+            Some((node.parse::<u32>().ok()?, pages.parse::<usize>().ok()?))
+        }) {
+            *nodes.entry(node).or_insert(0) += pages * page_size;
+            bytes += pages * page_size;
+        }
+        if bytes > 0 {
+            *policies.entry(policy.to_string()).or_insert(0) += bytes;
+        }
+    }
+    NumaTotals { nodes, local: None, policies }
+}
+
+// Nodes with no pages of the process still count toward the imbalance.
+fn online_nodes() -> Vec<u32> {
+    let online = fs::read_to_string("/sys/devices/system/node/online").unwrap_or_default();
+    online
+        .trim()
+        .split(',')
+        .filter_map(|range| match range.split_once('-') {
+            Some((first, last)) => Some(first.parse::<u32>().ok()?..=last.parse().ok()?),
+            None => range.parse::<u32>().ok().map(|node| node..=node),
+        })
+        .flatten()
+        .collect()
+}
+
+// The processor field of stat, then the nodeN link under that CPU.
+fn local_node(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let cpu: u32 = stat.rsplit_once(')')?.1.split_whitespace().nth(36)?.parse().ok()?;
+    fs::read_dir(format!("/sys/devices/system/cpu/cpu{}", cpu)).ok()?.flatten().find_map(|entry| entry.file_name().to_str()?.strip_prefix("node")?.parse().ok())
+}
+
+impl NumaTotals {
+    // For a process on this machine: every online node, and where it runs.
+    pub fn with_machine(mut self, pid: u32) -> Self {
+        for node in online_nodes() {
+            self.nodes.entry(node).or_insert(0);
+        }
+        self.local = local_node(pid);
+        self
+    }
+
+    pub fn total(&self) -> usize {
+        self.nodes.values().sum()
+    }
+
+    // How far the fullest node is above an even split, as a share of the
+    // total: 0 when balanced, 100% minus 1/N when all of it is on one node.
+    pub fn imbalance(&self) -> f64 {
+        let total = self.total();
+        if total == 0 || self.nodes.len() < 2 {
+            return 0.0;
+        }
+        let largest = self.nodes.values().copied().max().unwrap_or(0);
+        (largest as f64 - total as f64 / self.nodes.len() as f64) / total as f64
+    }
+
+    pub fn remote(&self) -> Option<usize> {
+        let local = self.local?;
+        Some(self.total() - self.nodes.get(&local).copied().unwrap_or(0))
+    }
+
+    pub fn print(&self) {
+        for (node, bytes) in &self.nodes {
+            let local = if self.local == Some(*node) { " (local)" } else { "" };
+            println!("N{}: {}{}", node, format_size(*bytes), local);
+        }
+        if let Some(remote) = self.remote() {
+            println!("Remote: {} ({:.0}%)", format_size(remote), remote as f64 / self.total().max(1) as f64 * 100.0);
+        }
+        println!("NUMA imbalance: {:.0}%", self.imbalance() * 100.0);
+        for (policy, bytes) in &self.policies {
+            println!("  {:<16} {}", policy, format_size(*bytes));
+        }
+    }
+}
+
+// A bar per node on a common scale, the local node in a color of its
+// own, then the totals under them.
+pub fn draw_panel(totals: &NumaTotals, width: u32, height: u32) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    {
+        let root = BitMapBackend::with_buffer(&mut imgbuf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let font = ("sans-serif", 11).into_font();
+        root.draw(&Text::new("Bytes per NUMA node", (10, 8), ("sans-serif", 13).into_font()))?;
+        let largest = totals.nodes.values().copied().max().unwrap_or(0).max(1) as f64;
+        let bar_space = width as f64 - 60.0;
+        let mut y = 30;
+        for (node, bytes) in &totals.nodes {
+            let color = if totals.local == Some(*node) { RGBColor(40, 160, 80) } else { RGBColor(90, 120, 220) };
+            root.draw(&Text::new(format!("N{}", node), (10, y + 3), font.clone()))?;
+            let x1 = 40 + (*bytes as f64 / largest * bar_space) as i32;
+            root.draw(&Rectangle::new([(40, y), (x1.max(41), y + 16)], color.filled()))?;
+            root.draw(&Text::new(format_size(*bytes), (44, y + 20), font.clone()))?;
+            y += 40;
+        }
+        let mut lines = vec![format!("imbalance: {:.0}%", totals.imbalance() * 100.0)];
+        if let (Some(local), Some(remote)) = (totals.local, totals.remote()) {
+            lines.push(format!("running on N{}", local));
+            lines.push(format!("remote: {} ({:.0}%)", format_size(remote), remote as f64 / totals.total().max(1) as f64 * 100.0));
+        }
+        for line in lines {
+            root.draw(&Text::new(line, (10, y), font.clone()))?;
+            y += 15;
+        }
+    }
+    Ok(imgbuf)
+}
@@ -0,0 +1,93 @@
+use crate::{fit_text, format_size, parse_memory_regions, progress, region_color, region_weight, rollup, scaled, smaps, validate, MemoryRegion, RenderOptions};
+use plotters::prelude::*;
+use rayon::prelude::*;
+use std::fs;
+
+const ROW_HEIGHT: i32 = 16;
+const LABEL_WIDTH: i32 = 260;
+const OVERVIEW_WIDTH: i32 = 1000;
+
+pub struct ProcessSample {
+    pub pid: u32,
+    pub name: String,
+    pub rss: usize,
+    pub memory_regions: Vec<MemoryRegion>,
+}
+
+impl ProcessSample {
+    pub fn mapped(&self) -> usize {
+        self.memory_regions.iter().map(|r| r.size).sum()
+    }
+}
+
+pub fn sample_process(pid: u32, needs_smaps: bool) -> Option<ProcessSample> {
+    let text = fs::read_to_string(format!("/proc/{}/{}", pid, if needs_smaps { "smaps" } else { "maps" })).ok()?;
+    let (memory_regions, _) = if needs_smaps { smaps::parse_smaps(text.as_bytes()) } else { parse_memory_regions(text.as_bytes()) };
+    let (memory_regions, _) = validate::validate(memory_regions);
+    if memory_regions.is_empty() {
+        return None;
+    }
+    // smaps_rollup is a single cheap read, so RSS doesn't need the full
+    // smaps unless something else asked for it.
+    let rss = if needs_smaps { memory_regions.iter().filter_map(|r| r.smaps.as_ref()).map(|s| s.rss).sum() } else { rollup::read(pid, None).map_or(0, |rollup| rollup.rss) };
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default().trim().to_string();
+    Some(ProcessSample { pid, name, rss, memory_regions })
+}
+
+// Every process whose maps we can read without elevating; kernel threads
+// have empty maps and are left out. Processes are read on the rayon pool,
+// since smaps of a large process takes the kernel a while to produce.
+pub fn sample_processes(needs_smaps: bool) -> Vec<ProcessSample> {
+    let Ok(entries) = fs::read_dir("/proc") else { return Vec::new() };
+    let pids: Vec<u32> = entries.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok()).collect();
+    let read = progress::batch(pids.len(), "reading processes");
+    let mut samples: Vec<ProcessSample> = pids
+        .into_par_iter()
+        .filter_map(|pid| {
+            let sample = sample_process(pid, needs_smaps);
+            read.inc();
+            sample
+        })
+        .collect();
+    samples.sort_by(|a, b| b.rss.cmp(&a.rss).then(a.pid.cmp(&b.pid)));
+    samples
+}
+
+// One strip per process with gaps left out. Every strip uses the same
+// pixels per unit of weight, so lengths compare across processes.
+pub fn draw_overview(samples: &[ProcessSample], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let width = px(OVERVIEW_WIDTH) as u32;
+    let height = px(samples.len() as i32 * ROW_HEIGHT + 40) as u32;
+    let root = BitMapBackend::new(path, (width, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let title = format!("{} processes by RSS", samples.len());
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let weight = |sample: &ProcessSample| -> f64 { sample.memory_regions.iter().map(|r| region_weight(r, options.size_metric, options.scale)).sum() };
+    let max = samples.iter().map(weight).fold(0.0, f64::max).max(f64::MIN_POSITIVE);
+    let strip_space = (px(OVERVIEW_WIDTH - LABEL_WIDTH - 10)) as f64;
+    for (i, sample) in samples.iter().enumerate() {
+        let y = px(32 + i as i32 * ROW_HEIGHT);
+        let label = format!("{} {} ({})", sample.pid, sample.name, format_size(sample.rss));
+        if let Some(label) = fit_text(&root, &label, &font, px(LABEL_WIDTH - 15))? {
+            root.draw(&Text::new(label, (px(10), y + px(2)), font.color(&ink)))?;
+        }
+
+        let mut offset = 0.0;
+        for region in &sample.memory_regions {
+            let x0 = px(LABEL_WIDTH) + (offset / max * strip_space).round() as i32;
+            offset += region_weight(region, options.size_metric, options.scale);
+            let x1 = px(LABEL_WIDTH) + (offset / max * strip_space).round() as i32;
+            if x1 <= x0 {
+                continue;
+            }
+            let color = region_color(region, options.color_by, &options.theme);
+            root.draw(&Rectangle::new([(x0, y), (x1, y + px(ROW_HEIGHT - 3))], RGBColor(color[0], color[1], color[2]).filled()))?;
+        }
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::MemoryRegion;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+
+// pagemap's entry layout, from Documentation/admin-guide/mm/pagemap.rst.
+const PAGE_PRESENT: u64 = 1 << 63;
+const PAGE_SWAPPED: u64 = 1 << 62;
+const PFN_MASK: u64 = (1 << 55) - 1;
+
+// Pages read from pagemap at a time, so a large region isn't one buffer.
+const BATCH: usize = 4096;
+
+// Calls visit with the address and kpageflags of every present page in
+// the regions, in address order. PFNs in pagemap and kpageflags itself
+// both need CAP_SYS_ADMIN.
+pub fn walk<'a, F: FnMut(usize, u64)>(pid: u32, memory_regions: impl Iterator<Item = &'a MemoryRegion>, page_size: usize, mut visit: F) -> Result<(), String> {
+    let pagemap = File::open(format!("/proc/{}/pagemap", pid)).map_err(|e| format!("Unable to read the pagemap of {}: {}", pid, e))?;
+    let kpageflags = File::open("/proc/kpageflags").map_err(|e| format!("Unable to read /proc/kpageflags: {} (it needs root)", e))?;
+    let (mut entries, mut flags) = (vec![0u8; BATCH * 8], [0u8; 8]);
+    let (mut present, mut pfn_seen) = (false, false);
+    for region in memory_regions {
+        let pages = region.size / page_size;
+        for batch_start in (0..pages).step_by(BATCH) {
+            let count = BATCH.min(pages - batch_start);
+            let first_page = region.start / page_size + batch_start;
+            let buffer = &mut entries[..count * 8];
+            if pagemap.read_exact_at(buffer, first_page as u64 * 8).is_err() {
+                break;
+            }
+            for (index, entry) in buffer.chunks_exact(8).enumerate() {
+                let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                let pfn = entry & PFN_MASK;
+                present |= entry & PAGE_PRESENT != 0;
+                if entry & PAGE_PRESENT == 0 || pfn == 0 {
+                    continue;
+                }
+                pfn_seen = true;
+                if kpageflags.read_exact_at(&mut flags, pfn * 8).is_ok() {
+                    visit((first_page + index) * page_size, u64::from_ne_bytes(flags));
+                }
+            }
+        }
+    }
+    if present && !pfn_seen {
+        return Err("pagemap shows no page frame numbers; reading them needs CAP_SYS_ADMIN".to_string());
+    }
+    Ok(())
+}
+
+// The runs of pages in a region with nothing behind them, neither in
+// memory nor in swap: never touched, or dropped with MADV_DONTNEED. These
+// bits need no privilege for one's own processes.
+pub fn absent(pid: u32, region: &MemoryRegion, page_size: usize) -> Result<Vec<(usize, usize)>, String> {
+    let pagemap = File::open(format!("/proc/{}/pagemap", pid)).map_err(|e| format!("Unable to read the pagemap of {}: {}", pid, e))?;
+    let mut entries = vec![0u8; BATCH * 8];
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let pages = region.size / page_size;
+    for batch_start in (0..pages).step_by(BATCH) {
+        let count = BATCH.min(pages - batch_start);
+        let first_page = region.start / page_size + batch_start;
+        let buffer = &mut entries[..count * 8];
+        pagemap.read_exact_at(buffer, first_page as u64 * 8).map_err(|e| format!("Unable to read the pagemap of {}: {}", pid, e))?;
+        for (index, entry) in buffer.chunks_exact(8).enumerate() {
+            if u64::from_ne_bytes(entry.try_into().unwrap()) & (PAGE_PRESENT | PAGE_SWAPPED) != 0 {
+                continue;
+            }
+            let address = (first_page + index) * page_size;
+            match runs.last_mut() {
+                Some((_, end)) if *end == address => *end += page_size,
+                _ => runs.push((address, address + page_size)),
+            }
+        }
+    }
+    Ok(runs)
+}
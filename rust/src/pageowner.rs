@@ -0,0 +1,276 @@
+use crate::{format_count, format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+
+const COLUMNS: usize = 256;
+const MAX_ROWS: usize = 256;
+const CELL: i32 = 3;
+const LABEL_WIDTH: i32 = 140;
+// Owners drawn in their own color by rank, hashed name colors being too
+// alike for neighbours to tell apart; the rest share gray.
+const COLORS: [u32; 16] = [
+    0x1f77b4, 0xff7f0e, 0x2ca02c, 0xd62728, 0x9467bd, 0x8c564b, 0xe377c2, 0xbcbd22,
+    0x17becf, 0xaec7e8, 0xffbb78, 0x98df8a, 0xff9896, 0xc5b0d5, 0xc49c94, 0xf7b6d2,
+];
+const COLORED: usize = COLORS.len();
+
+// Frames of the allocator itself, which every stack starts with. The
+// owner is the first frame past them.
+const ALLOCATOR: [&str; 24] = [
+    "post_alloc_hook",
+    "prep_new_page",
+    "get_page_from_freelist",
+    "__alloc_pages",
+    "__alloc_pages_slowpath",
+    "alloc_pages",
+    "alloc_pages_mpol",
+    "__alloc_pages_node",
+    "alloc_pages_node",
+    "alloc_pages_exact",
+    "__folio_alloc",
+    "folio_alloc",
+    "vma_alloc_folio",
+    "__get_free_pages",
+    "get_zeroed_page",
+    "alloc_slab_page",
+    "allocate_slab",
+    "new_slab",
+    "___slab_alloc",
+    "__slab_alloc",
+    "kmem_cache_alloc",
+    "__kmalloc",
+    "kmalloc_large",
+    "kmalloc_order",
+];
+
+pub struct Owner {
+    pub name: String,
+    pub pages: usize,
+    pub allocations: usize,
+}
+
+// Allocations in PFN order by index into the owners, which are ranked by
+// pages held.
+pub struct PageOwners {
+    pub owners: Vec<Owner>,
+    pub allocations: Vec<(usize, usize, usize)>,
+}
+
+// "__alloc_pages_noprof+0x1a2/0x3c0 [nvidia]" to the function and module;
+// newer kernels add _noprof to the allocator's own names.
+fn frame(line: &str) -> (&str, Option<&str>) {
+    let (symbol, module) = match line.rsplit_once(" [") {
+        Some((symbol, module)) => (symbol, module.strip_suffix(']')),
+        None => (line, None),
+    };
+    let function = symbol.split('+').next().unwrap_or(symbol).trim();
+    (function.strip_suffix("_noprof").unwrap_or(function), module)
+}
+
+// A frame in a module names it, since that is what a leak gets reported
+// against; otherwise the first frame outside the allocator does.
+fn owner(stack: &[&str]) -> String {
+    if let Some((function, Some(module))) = stack.iter().map(|line| frame(line)).find(|(_, module)| module.is_some()) {
+        return format!("{} [{}]", function, module);
+    }
+    let mut functions = stack.iter().map(|line| frame(line).0);
+    functions.find(|function| !ALLOCATOR.iter().any(|allocator| function.starts_with(allocator))).unwrap_or("unknown").to_string()
+}
+
+fn number(value: &str) -> Option<usize> {
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+// Records of /sys/kernel/debug/page_owner, separated by blank lines:
+//
+//   Page allocated via order 0, mask 0x100cca(GFP_HIGHUSER_MOVABLE), pid 1, ...
+//   PFN 0x10a3c type Movable Block 133 type Movable Flags 0x...
+//    post_alloc_hook+0x20/0x30
+//    ...
+//
+// Older kernels print the PFN in decimal.
+pub fn parse(text: &str) -> Result<PageOwners, String> {
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut owners: Vec<Owner> = Vec::new();
+    let mut allocations = Vec::new();
+    for record in text.split("\n\n") {
+        let mut lines = record.lines().map(str::trim).skip_while(|line| !line.starts_with("Page allocated via order"));
+        let Some(header) = lines.next() else { continue };
+        let order = header.trim_start_matches("Page allocated via order").split(',').next().and_then(|order| order.trim().parse::<u32>().ok());
+        let pfn = lines.next().and_then(|line| line.strip_prefix("PFN ")).and_then(|rest| number(rest.split_whitespace().next()?));
+        let (Some(order), Some(pfn)) = (order, pfn) else { continue };
+        let stack: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+        let name = owner(&stack);
+        let id = *ids.entry(name.clone()).or_insert_with(|| {
+            owners.push(Owner { name, pages: 0, allocations: 0 });
+            owners.len() - 1
+        });
+        owners[id].pages += 1 << order;
+        owners[id].allocations += 1;
+        allocations.push((pfn, 1 << order, id));
+    }
+    if allocations.is_empty() {
+        return Err("No page_owner records; is page_owner=on on the kernel command line?".to_string());
+    }
+    // Rank the owners and renumber the allocations to match.
+    let mut ranking: Vec<usize> = (0..owners.len()).collect();
+    ranking.sort_by(|a, b| owners[*b].pages.cmp(&owners[*a].pages).then(owners[*a].name.cmp(&owners[*b].name)));
+    let mut rank = vec![0; owners.len()];
+    for (position, id) in ranking.iter().enumerate() {
+        rank[*id] = position;
+    }
+    let mut owners: Vec<Option<Owner>> = owners.into_iter().map(Some).collect();
+    let owners: Vec<Owner> = ranking.iter().filter_map(|id| owners[*id].take()).collect();
+    for allocation in &mut allocations {
+        allocation.2 = rank[allocation.2];
+    }
+    allocations.sort();
+    Ok(PageOwners { owners, allocations })
+}
+
+pub fn read(path: &str) -> Result<PageOwners, String> {
+    parse(&fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {} (it needs root and debugfs)", path, e))?)
+}
+
+pub fn print(page_owners: &PageOwners, page_size: usize, limit: usize) {
+    let total: usize = page_owners.owners.iter().map(|owner| owner.pages).sum();
+    println!("{:>10} {:>6} {:>11}  owner", "held", "share", "allocations");
+    for owner in page_owners.owners.iter().take(limit) {
+        println!("{:>10} {:>5.1}% {:>11}  {}", format_size(owner.pages * page_size), owner.pages as f64 * 100.0 / total as f64, format_count(owner.allocations), owner.name);
+    }
+    if page_owners.owners.len() > limit {
+        let rest: usize = page_owners.owners[limit..].iter().map(|owner| owner.pages).sum();
+        println!("{:>10} {:>5.1}% {:>11}  {} other owners", format_size(rest * page_size), rest as f64 * 100.0 / total as f64, "", page_owners.owners.len() - limit);
+    }
+}
+
+fn owner_color(rank: usize) -> RGBColor {
+    match COLORS.get(rank) {
+        Some(color) => RGBColor((color >> 16) as u8, (color >> 8) as u8, *color as u8),
+        None => RGBColor(120, 120, 120),
+    }
+}
+
+// The span of PFNs the dump covers as physical.rs draws all of it, left
+// to right and top to bottom. Each cell takes the owner of most of its
+// frames; frames no record holds are free or were allocated before
+// page_owner was on.
+pub fn draw(page_owners: &PageOwners, page_size: usize, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let first = page_owners.allocations[0].0;
+    let last = page_owners.allocations.iter().map(|(pfn, pages, _)| pfn + pages).max().unwrap_or(first + 1);
+    // Owner rank plus one per frame, 0 for none; ranks past the colored
+    // ones all count as other.
+    let mut frames = vec![0u8; last - first];
+    for (pfn, pages, rank) in &page_owners.allocations {
+        frames[pfn - first..pfn - first + pages].fill(((*rank).min(COLORED) + 1) as u8);
+    }
+    let per_cell = frames.len().div_ceil(COLUMNS * MAX_ROWS).max(1);
+    let cells: Vec<&[u8]> = frames.chunks(per_cell).collect();
+    let rows = cells.len().div_ceil(COLUMNS);
+    let shown = page_owners.owners.len().min(COLORED + 1);
+    let legend_top = 40 + rows as i32 * CELL + 15;
+    let (width, height) = (px(LABEL_WIDTH + COLUMNS as i32 * CELL + 10), px(legend_top + shown as i32 * 18 + 10));
+    let root = BitMapBackend::new(path, (width as u32, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let title = format!("page owners: {} allocated of {} frames, {} per cell", format_size(frames.iter().filter(|frame| **frame > 0).count() * page_size), frames.len(), format_size(per_cell * page_size));
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let gap = options.theme.gap.0;
+    for (i, cell) in cells.iter().enumerate() {
+        let (row, column) = ((i / COLUMNS) as i32, (i % COLUMNS) as i32);
+        let mut counts = [0usize; COLORED + 2];
+        for frame in *cell {
+            counts[*frame as usize] += 1;
+        }
+        let dominant = (0..counts.len()).max_by_key(|index| counts[*index]).unwrap();
+        let color = match dominant {
+            0 => RGBColor(gap[0], gap[1], gap[2]),
+            rank => owner_color(rank - 1),
+        };
+        let (x, y) = (px(LABEL_WIDTH + column * CELL), px(40 + row * CELL));
+        root.draw(&Rectangle::new([(x, y), (x + px(CELL), y + px(CELL))], color.filled()))?;
+        if column == 0 && row % 16 == 0 {
+            let address = (first + i * per_cell) * page_size;
+            root.draw(&Text::new(format!("{:#x}", address), (px(10), y - px(2)), font.color(&ink)))?;
+        }
+    }
+
+    for (rank, owner) in page_owners.owners.iter().take(shown).enumerate() {
+        let y = px(legend_top + rank as i32 * 18);
+        let (from, to) = ((px(LABEL_WIDTH), y), (px(LABEL_WIDTH + 10), y + px(10)));
+        root.draw(&Rectangle::new([from, to], owner_color(rank).filled()))?;
+        let text = if rank < COLORED {
+            format!("{} {}", owner.name, format_size(owner.pages * page_size))
+        } else {
+            let rest: usize = page_owners.owners[COLORED..].iter().map(|owner| owner.pages).sum();
+            format!("{} other owners {}", page_owners.owners.len() - COLORED, format_size(rest * page_size))
+        };
+        root.draw(&Text::new(text, (px(LABEL_WIDTH + 15), y - px(1)), font.color(&ink)))?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_OWNER: &str = "\
+Page allocated via order 0, mask 0x100cca(GFP_HIGHUSER_MOVABLE), pid 1, tgid 1 (systemd), ts 1920307025 ns
+PFN 0x10a3c type Movable Block 133 type Movable Flags 0x17ffffc0020018(uptodate|dirty|lru|node=0|zone=2|lastcpupid=0x1fffff)
+ post_alloc_hook+0x20/0x30
+ get_page_from_freelist+0x6f4/0x1050
+ __alloc_pages_noprof+0x1a2/0x3c0
+ alloc_pages_mpol_noprof+0x90/0x1f0
+ vma_alloc_folio_noprof+0x64/0xd0
+ do_anonymous_page+0x12c/0x7c0
+ __handle_mm_fault+0x3c4/0x6e0
+
+Page allocated via order 2, mask 0x152dc0(GFP_KERNEL|__GFP_NOWARN|__GFP_COMP|__GFP_ZERO), pid 412, tgid 412 (modprobe), ts 2911003340 ns
+PFN 0x20000 type Unmovable Block 256 type Unmovable Flags 0x17ffffc0000000(node=0|zone=2|lastcpupid=0x1fffff)
+ post_alloc_hook+0x20/0x30
+ get_page_from_freelist+0x6f4/0x1050
+ __alloc_pages_noprof+0x1a2/0x3c0
+ ___kmalloc_large_node+0x8a/0x130
+ nv_alloc_kernel_mapping+0x3e/0x90 [nvidia]
+ do_init_module+0x60/0x240
+
+Page allocated via order 0, mask 0x100cca(GFP_HIGHUSER_MOVABLE), pid 1, tgid 1 (systemd), ts 1920307100 ns
+PFN 4000 type Movable Block 7 type Movable Flags 0x17ffffc0020018(uptodate|dirty|lru|node=0|zone=2|lastcpupid=0x1fffff)
+ post_alloc_hook+0x20/0x30
+ get_page_from_freelist+0x6f4/0x1050
+ __alloc_pages_noprof+0x1a2/0x3c0
+ alloc_pages_mpol_noprof+0x90/0x1f0
+ vma_alloc_folio_noprof+0x64/0xd0
+ do_anonymous_page+0x12c/0x7c0
+";
+
+    #[test]
+    fn parses_page_owner_records() {
+        let page_owners = parse(PAGE_OWNER).unwrap();
+        let owners: Vec<(&str, usize, usize)> = page_owners.owners.iter().map(|owner| (owner.name.as_str(), owner.pages, owner.allocations)).collect();
+        // The order 2 allocation outweighs the two single pages.
+        assert_eq!(owners, [("nv_alloc_kernel_mapping [nvidia]", 4, 1), ("do_anonymous_page", 2, 2)]);
+        // In PFN order, the decimal one first, by rank.
+        assert_eq!(page_owners.allocations, [(4000, 1, 1), (0x10a3c, 1, 1), (0x20000, 4, 0)]);
+    }
+
+    #[test]
+    fn owners_skip_the_allocator() {
+        assert_eq!(frame("__alloc_pages_noprof+0x1a2/0x3c0"), ("__alloc_pages", None));
+        assert_eq!(frame("nv_alloc_kernel_mapping+0x3e/0x90 [nvidia]"), ("nv_alloc_kernel_mapping", Some("nvidia")));
+        assert_eq!(owner(&["post_alloc_hook+0x20/0x30", "kmem_cache_alloc_noprof+0x10/0x20"]), "unknown");
+    }
+
+    #[test]
+    fn rejects_a_dump_without_records() {
+        assert!(parse("").is_err());
+    }
+}
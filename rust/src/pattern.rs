@@ -0,0 +1,223 @@
+// A backtracking matcher for the regular expressions people write against
+// paths: literals, ., classes, \d \w \s, anchors, groups with |, and the
+// *, +, ? and {n,m} quantifiers. Only whether a pattern matches is needed,
+// so greedy and lazy quantifiers behave the same.
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    alternatives: Vec<Vec<Node>>,
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    source: &'a str,
+}
+
+fn class_escape(c: char) -> Option<(Vec<(char, char)>, bool)> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')],
+        _ => return None,
+    };
+    Some((ranges, c.is_ascii_uppercase()))
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("Invalid pattern {}: {}", self.source, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![Vec::new()];
+        while let Some(c) = self.peek() {
+            match c {
+                ')' => break,
+                '|' => {
+                    self.pos += 1;
+                    alternatives.push(Vec::new());
+                }
+                _ => {
+                    let atom = self.atom()?;
+                    let node = self.quantifier(atom)?;
+                    alternatives.last_mut().unwrap().push(node);
+                }
+            }
+        }
+        Ok(alternatives)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().unwrap();
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let group = self.alternatives()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                self.pos += 1;
+                Node::Group(group)
+            }
+            '[' => self.class()?,
+            '\\' => {
+                let escaped = self.peek().ok_or_else(|| self.error("trailing backslash"))?;
+                self.pos += 1;
+                match class_escape(escaped) {
+                    Some((ranges, negated)) => Node::Class(ranges, negated),
+                    None => Node::Char(escaped),
+                }
+            }
+            '*' | '+' | '?' => return Err(self.error("nothing to repeat")),
+            c => Node::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unclosed class"))?;
+            self.pos += 1;
+            match c {
+                ']' if !first => break,
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error("unclosed class"))?;
+                    self.pos += 1;
+                    match class_escape(escaped) {
+                        Some((class, false)) => ranges.extend(class),
+                        Some((_, true)) => return Err(self.error("negated escapes are not supported in classes")),
+                        None => ranges.push((escaped, escaped)),
+                    }
+                }
+                c if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|end| *end != ']') => {
+                    let end = self.chars[self.pos + 1];
+                    self.pos += 2;
+                    ranges.push((c, end));
+                }
+                c => ranges.push((c, c)),
+            }
+            first = false;
+        }
+        Ok(Node::Class(ranges, negated))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    fn quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let open = self.pos;
+                self.pos += 1;
+                let min = self.number();
+                let max = if self.peek() == Some(',') {
+                    self.pos += 1;
+                    self.number()
+                } else {
+                    min
+                };
+                match (min, self.peek()) {
+                    (Some(min), Some('}')) if max.is_none_or(|max| max >= min) => (min, max),
+                    // Not a repetition, so the brace is a literal.
+                    _ => {
+                        self.pos = open;
+                        return Ok(atom);
+                    }
+                }
+            }
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        if self.peek() == Some('?') {
+            self.pos += 1;
+        }
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+}
+
+impl std::str::FromStr for Regex {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { chars: source.chars().collect(), pos: 0, source };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched )"));
+        }
+        Ok(Regex { alternatives })
+    }
+}
+
+type Next<'a> = &'a mut dyn FnMut(usize) -> bool;
+
+fn match_node(node: &Node, text: &[char], pos: usize, next: Next) -> bool {
+    match node {
+        Node::Char(c) => text.get(pos) == Some(c) && next(pos + 1),
+        Node::Any => pos < text.len() && next(pos + 1),
+        Node::Class(ranges, negated) => {
+            text.get(pos).is_some_and(|c| ranges.iter().any(|(low, high)| low <= c && c <= high) != *negated) && next(pos + 1)
+        }
+        Node::Start => pos == 0 && next(pos),
+        Node::End => pos == text.len() && next(pos),
+        Node::Group(alternatives) => alternatives.iter().any(|sequence| match_sequence(sequence, text, pos, next)),
+        Node::Repeat(inner, min, max) => match_repeat(inner, *min, *max, text, pos, 0, next),
+    }
+}
+
+fn match_repeat(node: &Node, min: usize, max: Option<usize>, text: &[char], pos: usize, count: usize, next: Next) -> bool {
+    // An iteration has to consume something, or patterns like (a*)* never end.
+    if max.is_none_or(|max| count < max) && match_node(node, text, pos, &mut |after| after != pos && match_repeat(node, min, max, text, after, count + 1, next)) {
+        return true;
+    }
+    count >= min && next(pos)
+}
+
+fn match_sequence(sequence: &[Node], text: &[char], pos: usize, next: Next) -> bool {
+    match sequence.split_first() {
+        None => next(pos),
+        Some((first, rest)) => match_node(first, text, pos, &mut |after| match_sequence(rest, text, after, next)),
+    }
+}
+
+impl Regex {
+    // Like grep, the pattern may match anywhere in the text.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        (0..=text.len()).any(|start| self.alternatives.iter().any(|sequence| match_sequence(sequence, &text, start, &mut |_| true)))
+    }
+}
@@ -0,0 +1,197 @@
+use plotters_backend::{BackendColor, BackendCoord, BackendStyle, BackendTextStyle, DrawingBackend, DrawingErrorKind, FontStyle};
+use plotters_backend::text_anchor::{HPos, VPos};
+use std::fmt::{self, Write};
+
+// Maps taller than a letter page are split across pages; each page places
+// the one drawing shifted up, so the content is only stored once.
+const PAGE_HEIGHT: u32 = 792;
+
+// Content stream operators in PDF space, plus the opacities they refer to.
+#[derive(Default)]
+pub struct Drawing {
+    ops: String,
+    opacities: Vec<f64>,
+}
+
+pub struct PdfBackend<'a> {
+    drawing: &'a mut Drawing,
+    size: (u32, u32),
+}
+
+impl<'a> PdfBackend<'a> {
+    pub fn new(drawing: &'a mut Drawing, size: (u32, u32)) -> Self {
+        PdfBackend { drawing, size }
+    }
+
+    // PDF puts the origin at the bottom left.
+    fn y(&self, y: i32) -> i32 {
+        self.size.1 as i32 - y
+    }
+
+    fn paint(&mut self, color: BackendColor, stroke: bool) -> fmt::Result {
+        let opacity = match self.drawing.opacities.iter().position(|alpha| *alpha == color.alpha) {
+            Some(index) => index,
+            None => {
+                self.drawing.opacities.push(color.alpha);
+                self.drawing.opacities.len() - 1
+            }
+        };
+        let (r, g, b) = color.rgb;
+        let op = if stroke { "RG" } else { "rg" };
+        writeln!(self.drawing.ops, "/GS{} gs {:.3} {:.3} {:.3} {}", opacity, r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, op)
+    }
+}
+
+fn pdf_string(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '…' => escaped.push_str("\\205"),
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+type Result<T> = std::result::Result<T, DrawingErrorKind<fmt::Error>>;
+
+impl DrawingBackend for PdfBackend<'_> {
+    type ErrorType = fmt::Error;
+
+    fn get_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn ensure_prepared(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn draw_pixel(&mut self, (x, y): BackendCoord, color: BackendColor) -> Result<()> {
+        self.paint(color, false).map_err(DrawingErrorKind::DrawingError)?;
+        writeln!(self.drawing.ops, "{} {} 1 1 re f", x, self.y(y) - 1).map_err(DrawingErrorKind::DrawingError)
+    }
+
+    fn draw_line<S: BackendStyle>(&mut self, from: BackendCoord, to: BackendCoord, style: &S) -> Result<()> {
+        self.draw_path([from, to], style)
+    }
+
+    fn draw_rect<S: BackendStyle>(&mut self, (x0, y0): BackendCoord, (x1, y1): BackendCoord, style: &S, fill: bool) -> Result<()> {
+        self.paint(style.color(), !fill).map_err(DrawingErrorKind::DrawingError)?;
+        // Bitmap rectangles include both corners; strokes run through pixel centers.
+        let ops = if fill {
+            format!("{} {} {} {} re f", x0, self.y(y1) - 1, x1 - x0 + 1, y1 - y0 + 1)
+        } else {
+            format!("{} w {} {} {} {} re S", style.stroke_width(), x0 as f64 + 0.5, self.y(y1) as f64 - 0.5, x1 - x0, y1 - y0)
+        };
+        writeln!(self.drawing.ops, "{}", ops).map_err(DrawingErrorKind::DrawingError)
+    }
+
+    fn draw_path<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(&mut self, path: I, style: &S) -> Result<()> {
+        let points: Vec<BackendCoord> = path.into_iter().collect();
+        if points.len() < 2 || style.color().alpha == 0.0 {
+            return Ok(());
+        }
+        self.paint(style.color(), true).map_err(DrawingErrorKind::DrawingError)?;
+        let mut ops = format!("{} w", style.stroke_width());
+        for (i, (x, y)) in points.iter().enumerate() {
+            write!(ops, " {} {} {}", *x as f64 + 0.5, self.y(*y) as f64 - 0.5, if i == 0 { "m" } else { "l" }).unwrap();
+        }
+        writeln!(self.drawing.ops, "{} S", ops).map_err(DrawingErrorKind::DrawingError)
+    }
+
+    fn fill_polygon<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(&mut self, vert: I, style: &S) -> Result<()> {
+        let points: Vec<BackendCoord> = vert.into_iter().collect();
+        if points.is_empty() {
+            return Ok(());
+        }
+        self.paint(style.color(), false).map_err(DrawingErrorKind::DrawingError)?;
+        let mut ops = String::new();
+        for (i, (x, y)) in points.iter().enumerate() {
+            write!(ops, "{} {} {} ", x, self.y(*y), if i == 0 { "m" } else { "l" }).unwrap();
+        }
+        writeln!(self.drawing.ops, "{}h f", ops).map_err(DrawingErrorKind::DrawingError)
+    }
+
+    // Text stays text, set in the standard Helvetica faces so nothing has
+    // to be embedded; their metrics are close to the sans-serif used to fit it.
+    fn draw_text<TStyle: BackendTextStyle>(&mut self, text: &str, style: &TStyle, (x, y): BackendCoord) -> Result<()> {
+        let ((min_x, min_y), (max_x, max_y)) = style.layout_box(text).map_err(|e| DrawingErrorKind::FontError(Box::new(e)))?;
+        let (width, height) = (max_x - min_x, max_y - min_y);
+        let x = match style.anchor().h_pos {
+            HPos::Left => x,
+            HPos::Right => x - width,
+            HPos::Center => x - width / 2,
+        };
+        let top = match style.anchor().v_pos {
+            VPos::Top => y,
+            VPos::Center => y - height / 2,
+            VPos::Bottom => y - height,
+        };
+        let font = if matches!(style.style(), FontStyle::Bold) { "F2" } else { "F1" };
+        let baseline = self.y(top) as f64 - style.size() * 0.78;
+        self.paint(style.color(), false).map_err(DrawingErrorKind::DrawingError)?;
+        writeln!(self.drawing.ops, "BT /{} {:.1} Tf {} {:.1} Td ({}) Tj ET", font, style.size(), x, baseline, pdf_string(text)).map_err(DrawingErrorKind::DrawingError)
+    }
+}
+
+impl Drawing {
+    pub fn to_pdf(&self, (width, height): (u32, u32)) -> Vec<u8> {
+        let opacities: String = self.opacities.iter().enumerate().map(|(i, alpha)| format!("/GS{} << /ca {} /CA {} >> ", i, alpha, alpha)).collect();
+        let map_resources = format!("<< /Font << /F1 3 0 R /F2 4 0 R >> /ExtGState << {}>> >>", opacities);
+
+        let pages = height.div_ceil(PAGE_HEIGHT).max(1);
+        let first_page = 6;
+        let kids: Vec<String> = (0..pages).map(|page| format!("{} 0 R", first_page + 2 * page)).collect();
+
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+            format!(
+                "<< /Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Resources {} /Length {} >>\nstream\n{}endstream",
+                width,
+                height,
+                map_resources,
+                self.ops.len(),
+                self.ops
+            ),
+        ];
+        for page in 0..pages {
+            let page_height = PAGE_HEIGHT.min(height - page * PAGE_HEIGHT);
+            let shift = height as i64 - (page * PAGE_HEIGHT + page_height) as i64;
+            let content = format!("q 1 0 0 1 0 {} cm /Map Do Q\n", -shift);
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Map 5 0 R >> >> /Contents {} 0 R >>",
+                width,
+                page_height,
+                first_page + 2 * page + 1
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            writeln!(trailer, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(trailer, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).unwrap();
+        pdf.extend_from_slice(trailer.as_bytes());
+        pdf
+    }
+}
@@ -0,0 +1,167 @@
+use crate::replay::{self, Change, Event};
+use crate::symbols::parse_address;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+// perf.data files start with this magic; anything else is taken to be
+// perf script output already.
+const MAGIC: &[u8] = b"PERFILE2";
+
+pub fn is_perf_data(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn is_script(text: &str) -> bool {
+    text.contains("PERF_RECORD_MMAP")
+}
+
+// The mmap records of a perf.data file, through the perf tool itself.
+pub fn script(path: &str) -> Result<String, String> {
+    let output = Command::new("perf")
+        .args(["script", "--show-mmap-events", "-i", path])
+        .output()
+        .map_err(|e| format!("Unable to run perf script: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("perf script -i {} failed: {}", path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+struct Record {
+    pid: u32,
+    tid: u32,
+    comm: String,
+    time: Option<f64>,
+    start: usize,
+    length: usize,
+    offset: usize,
+    prot: String,
+    path: String,
+}
+
+// Lines look like "bash 4166 [002] 1829.423231: PERF_RECORD_MMAP2 4166/4170:
+// [0x7f3c2a3ee000(0x25000) @ 0 08:01 1062283 0]: r--p /usr/lib/libc.so.6".
+// The older PERF_RECORD_MMAP only says whether a mapping is executable.
+fn parse_record(line: &str) -> Option<Record> {
+    let (before, after) = line.split_once("PERF_RECORD_MMAP")?;
+    let after = after.strip_prefix('2').unwrap_or(after);
+    let comm = before.split_whitespace().next().unwrap_or("").to_string();
+    let time = before.split_whitespace().last().and_then(|time| time.trim_end_matches(':').parse().ok());
+    let (ids, rest) = after.trim_start().split_once(": [")?;
+    let (pid, tid) = ids.split_once('/')?;
+    // Kernel mappings are reported for pid -1.
+    let (pid, tid) = (pid.parse().ok()?, tid.parse().ok()?);
+    let (inside, rest) = rest.split_once("]: ")?;
+    let (start, inside) = inside.split_once('(')?;
+    let (length, inside) = inside.split_once(')')?;
+    let offset = inside.trim_start().strip_prefix('@')?.split_whitespace().next()?;
+    let (prot, path) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some(Record {
+        pid,
+        tid,
+        comm,
+        time,
+        start: parse_address(start).ok()?,
+        length: parse_address(length).ok()?,
+        offset: parse_address(offset).ok()?,
+        prot: prot.to_string(),
+        path: path.trim().to_string(),
+    })
+}
+
+fn permissions(prot: &str) -> ((bool, bool, bool), bool) {
+    match prot.as_bytes() {
+        [r, w, x, s] => ((*r == b'r', *w == b'w', *x == b'x'), *s == b's'),
+        _ => ((true, false, prot.contains('x')), false),
+    }
+}
+
+// Machine-wide recordings mix processes, and one map only makes sense for
+// one of them: the given pid, or the one with the most mappings.
+pub fn parse_script(text: &str, only_pid: Option<u32>) -> Vec<Event> {
+    let records: Vec<(usize, Record)> = text.lines().enumerate().filter_map(|(index, line)| Some((index + 1, parse_record(line)?))).collect();
+    let pid = only_pid.or_else(|| {
+        let mut counts: BTreeMap<u32, usize> = BTreeMap::new();
+        for (_, record) in &records {
+            *counts.entry(record.pid).or_default() += 1;
+        }
+        let (pid, _) = counts.iter().max_by_key(|(_, count)| **count)?;
+        if counts.len() > 1 {
+            eprintln!("{} processes recorded; replaying pid {} (pass --pid for another)", counts.len(), pid);
+        }
+        Some(*pid)
+    });
+    let first = records.iter().find_map(|(_, record)| record.time).unwrap_or(0.0);
+    records
+        .into_iter()
+        .filter(|(_, record)| Some(record.pid) == pid)
+        .map(|(line, record)| {
+            let (perms, shared) = permissions(&record.prot);
+            let file_name = match record.path.as_str() {
+                "" | "//anon" => None,
+                path => Some(path.to_string()),
+            };
+            let time = record.time.map_or(String::new(), |time| format!("+{:.3}s ", time - first));
+            let region = replay::region(record.start, record.start + record.length, perms, shared, record.offset, file_name);
+            Event {
+                line,
+                label: format!("{}{} tid {}", time, record.comm, record.tid),
+                text: format!("{}{} tid {}: {} {}", time, record.comm, record.tid, record.prot, record.path),
+                change: Change::Map(region),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // perf script --show-mmap-events of a machine-wide recording, with a
+    // kernel MMAP record for pid -1.
+    const SCRIPT: &str = "\
+         swapper     0 [000]     0.000000: PERF_RECORD_MMAP -1/0: [0xffffffff81000000(0x1000000) @ 0xffffffff81000000]: x [kernel.kallsyms]_text
+            bash  4166 [002]  1829.423231: PERF_RECORD_MMAP2 4166/4166: [0x55c0d4a00000(0x2f000) @ 0 08:01 1048631 0]: r--p /usr/bin/bash
+            bash  4166 [002]  1829.423240: PERF_RECORD_MMAP2 4166/4170: [0x7f3c2a3ee000(0x25000) @ 0x28000 08:01 1062283 0]: r-xp /usr/lib/x86_64-linux-gnu/libc.so.6
+            bash  4166 [002]  1829.423250: PERF_RECORD_MMAP2 4166/4166: [0x7f3c2a600000(0x21000) @ 0 00:00 0 0]: rw-s //anon
+           sleep  4200 [001]  1829.500000: PERF_RECORD_MMAP 4200/4200: [0x400000(0x1000) @ 0]: x /usr/bin/sleep
+            bash  4166 [002]  1829.423231:     250000 cpu-clock:  7f3c2a3f0123 __libc_start_main+0x80 (/usr/lib/x86_64-linux-gnu/libc.so.6)
+";
+
+    #[test]
+    fn parses_mmap2_records() {
+        let record = parse_record(SCRIPT.lines().nth(2).unwrap()).unwrap();
+        assert_eq!((record.pid, record.tid, record.comm.as_str()), (4166, 4170, "bash"));
+        assert_eq!((record.start, record.length, record.offset), (0x7f3c2a3ee000, 0x25000, 0x28000));
+        assert_eq!((record.prot.as_str(), record.path.as_str()), ("r-xp", "/usr/lib/x86_64-linux-gnu/libc.so.6"));
+        assert_eq!(record.time, Some(1829.42324));
+    }
+
+    #[test]
+    fn parses_old_mmap_records() {
+        let record = parse_record(SCRIPT.lines().nth(4).unwrap()).unwrap();
+        assert_eq!((record.pid, record.start, record.length, record.prot.as_str()), (4200, 0x400000, 0x1000, "x"));
+        assert_eq!(permissions(&record.prot), ((true, false, true), false));
+        // The kernel's pid -1 and samples aren't process mappings.
+        assert!(parse_record(SCRIPT.lines().next().unwrap()).is_none());
+        assert!(parse_record(SCRIPT.lines().nth(5).unwrap()).is_none());
+    }
+
+    #[test]
+    fn replays_the_process_with_the_most_mappings() {
+        assert!(is_script(SCRIPT));
+        let events = parse_script(SCRIPT, None);
+        assert_eq!(events.iter().map(|event| event.line).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(events[1].label, "+0.000s bash tid 4170");
+        let Change::Map(anonymous) = &events[2].change else { panic!("not a map") };
+        assert!(anonymous.file_name.is_none() && anonymous.attributes.shared && anonymous.attributes.writable);
+    }
+
+    #[test]
+    fn replays_the_given_pid() {
+        let events = parse_script(SCRIPT, Some(4200));
+        assert_eq!(events.len(), 1);
+        let Change::Map(region) = &events[0].change else { panic!("not a map") };
+        assert_eq!((region.start, region.end, region.file_name.as_deref()), (0x400000, 0x401000, Some("/usr/bin/sleep")));
+    }
+}
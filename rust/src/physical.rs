@@ -0,0 +1,147 @@
+use crate::{format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+// Bit numbers from include/uapi/linux/kernel-page-flags.h.
+const KPF_LRU: u64 = 1 << 5;
+const KPF_SLAB: u64 = 1 << 7;
+const KPF_BUDDY: u64 = 1 << 10;
+const KPF_MMAP: u64 = 1 << 11;
+const KPF_ANON: u64 = 1 << 12;
+const KPF_HUGE: u64 = 1 << 17;
+const KPF_NOPAGE: u64 = 1 << 20;
+const KPF_THP: u64 = 1 << 22;
+const KPF_OFFLINE: u64 = 1 << 23;
+
+const COLUMNS: usize = 256;
+const MAX_ROWS: usize = 256;
+const CELL: i32 = 3;
+const LABEL_WIDTH: i32 = 140;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameState {
+    NoPage,
+    Free,
+    Slab,
+    Anon,
+    File,
+    Huge,
+    Kernel,
+}
+
+pub const STATES: [(FrameState, &str, [u8; 3]); 7] = [
+    (FrameState::Free, "free", [235, 235, 235]),
+    (FrameState::Slab, "slab", [200, 60, 60]),
+    (FrameState::Anon, "anon", [240, 170, 0]),
+    (FrameState::File, "file", [60, 130, 200]),
+    (FrameState::Huge, "hugepage", [140, 80, 190]),
+    (FrameState::Kernel, "kernel/other", [90, 90, 90]),
+    (FrameState::NoPage, "no page", [0, 0, 0]),
+];
+
+fn state_index(state: FrameState) -> usize {
+    STATES.iter().position(|(s, _, _)| *s == state).unwrap()
+}
+
+// Only the head of a free buddy block has KPF_BUDDY; the rest of it is
+// unflagged with no references, which is what a free frame looks like.
+fn classify(flags: u64, count: u64) -> FrameState {
+    if flags & (KPF_NOPAGE | KPF_OFFLINE) != 0 {
+        FrameState::NoPage
+    } else if flags & KPF_BUDDY != 0 || (flags == 0 && count == 0) {
+        FrameState::Free
+    } else if flags & KPF_SLAB != 0 {
+        FrameState::Slab
+    } else if flags & (KPF_HUGE | KPF_THP) != 0 {
+        FrameState::Huge
+    } else if flags & KPF_ANON != 0 {
+        FrameState::Anon
+    } else if flags & (KPF_LRU | KPF_MMAP) != 0 {
+        FrameState::File
+    } else {
+        FrameState::Kernel
+    }
+}
+
+// The frame files are indexed by the kernel's page size, which smaps
+// reports; almost everything uses 4 KiB.
+pub fn page_size() -> usize {
+    let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap_or_default();
+    smaps
+        .lines()
+        .find_map(|line| line.strip_prefix("KernelPageSize:"))
+        .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<usize>().ok())
+        .map_or(4096, |kb| kb * 1024)
+}
+
+// One state per physical frame, from dir/kpageflags and dir/kpagecount.
+pub fn read_frames(dir: &Path) -> Result<Vec<FrameState>, String> {
+    let open = |name: &str| {
+        let path = dir.join(name);
+        File::open(&path).map(BufReader::new).map_err(|e| format!("Unable to read {}: {} (it needs root)", path.display(), e))
+    };
+    let (mut flags, mut counts) = (open("kpageflags")?, open("kpagecount")?);
+    let mut frames = Vec::new();
+    let (mut flag, mut count) = ([0u8; 8], [0u8; 8]);
+    while flags.read_exact(&mut flag).is_ok() {
+        // A short kpagecount just means no counts, not no frames.
+        let references = if counts.read_exact(&mut count).is_ok() { u64::from_ne_bytes(count) } else { 0 };
+        frames.push(classify(u64::from_ne_bytes(flag), references));
+    }
+    if frames.is_empty() {
+        return Err(format!("{} lists no frames", dir.join("kpageflags").display()));
+    }
+    Ok(frames)
+}
+
+pub fn totals(frames: &[FrameState]) -> [usize; 7] {
+    let mut totals = [0; 7];
+    for frame in frames {
+        totals[state_index(*frame)] += 1;
+    }
+    totals
+}
+
+// A grid read left to right, top to bottom, in physical address order.
+// Each cell takes the most common state among the frames it covers.
+pub fn draw_physical(frames: &[FrameState], page_size: usize, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let per_cell = frames.len().div_ceil(COLUMNS * MAX_ROWS).max(1);
+    let cells: Vec<&[FrameState]> = frames.chunks(per_cell).collect();
+    let rows = cells.len().div_ceil(COLUMNS);
+    let legend_top = 40 + rows as i32 * CELL + 15;
+    let (width, height) = (px(LABEL_WIDTH + COLUMNS as i32 * CELL + 10), px(legend_top + STATES.len() as i32 * 18 + 10));
+    let root = BitMapBackend::new(path, (width as u32, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let title = format!("physical memory: {} frames of {}, {} per cell", frames.len(), format_size(page_size), format_size(per_cell * page_size));
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    for (i, cell) in cells.iter().enumerate() {
+        let (row, column) = ((i / COLUMNS) as i32, (i % COLUMNS) as i32);
+        let counts = totals(cell);
+        let dominant = (0..STATES.len()).max_by_key(|index| counts[*index]).unwrap();
+        let [r, g, b] = STATES[dominant].2;
+        let (x, y) = (px(LABEL_WIDTH + column * CELL), px(40 + row * CELL));
+        root.draw(&Rectangle::new([(x, y), (x + px(CELL), y + px(CELL))], RGBColor(r, g, b).filled()))?;
+        if column == 0 && row % 16 == 0 {
+            let address = i * per_cell * page_size;
+            root.draw(&Text::new(format!("{:#x}", address), (px(10), y - px(2)), font.color(&ink)))?;
+        }
+    }
+
+    let all = totals(frames);
+    for (index, (_, label, [r, g, b])) in STATES.iter().enumerate() {
+        let y = px(legend_top + index as i32 * 18);
+        let (from, to) = ((px(LABEL_WIDTH), y), (px(LABEL_WIDTH + 10), y + px(10)));
+        root.draw(&Rectangle::new([from, to], RGBColor(*r, *g, *b).filled()))?;
+        root.draw(&Rectangle::new([from, to], RGBColor(160, 160, 160)))?;
+        let text = format!("{} {}", label, format_size(all[index] * page_size));
+        root.draw(&Text::new(text, (px(LABEL_WIDTH + 15), y - px(1)), font.color(&ink)))?;
+    }
+    root.present()?;
+    Ok(())
+}
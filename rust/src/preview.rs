@@ -0,0 +1,71 @@
+use crate::{bar_boxes, describe_region, entropy, MemoryRegion, RenderOptions};
+
+// Enough for an ELF header's identification and type, an arena or chunk
+// header, or the first words of a struct.
+const PREVIEW_BYTES: usize = 64;
+const ROW: usize = 16;
+
+// The first bytes of every readable region, by region start. Like the
+// entropy samples, a process that can't be read at all is an error and a
+// region that can't be read is left out.
+pub fn capture(pid: u32, memory_regions: &[MemoryRegion]) -> Result<Vec<(usize, Vec<u8>)>, String> {
+    let mut previews = Vec::new();
+    let mut denied = None;
+    for region in memory_regions.iter().filter(|region| entropy::candidate(region)) {
+        let mut buffer = vec![0u8; PREVIEW_BYTES.min(region.size)];
+        match entropy::read_remote(pid, region.start, &mut buffer) {
+            Ok(read) if read > 0 => {
+                buffer.truncate(read);
+                previews.push((region.start, buffer));
+            }
+            Ok(_) => {}
+            Err(e) if matches!(e.raw_os_error(), Some(1) | Some(3)) => denied = Some(e),
+            Err(_) => {}
+        }
+    }
+    match denied {
+        Some(e) if previews.is_empty() => Err(entropy::denied_error(pid, &e)),
+        _ => Ok(previews),
+    }
+}
+
+// hexdump -C style: offset, sixteen bytes, then the printable ones.
+pub fn hexdump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(ROW)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let text: String = chunk.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' }).collect();
+            format!("{:04x}  {:<47}  |{}|", row * ROW, hex.join(" "), text)
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Browsers show an SVG <title> as the tooltip of its parent, so every
+// region with a preview gets a transparent box over its bar carrying one.
+// They go last, on top of everything plotters drew.
+pub fn annotate_svg(svg: String, memory_regions: &[MemoryRegion], size: (u32, u32), options: &RenderOptions) -> String {
+    if options.previews.is_empty() {
+        return svg;
+    }
+    let Some(end) = svg.rfind("</svg>") else { return svg };
+    let mut boxes = String::new();
+    for (region, ((left, top), (right, bottom))) in memory_regions.iter().zip(bar_boxes(memory_regions, size, options)) {
+        let Some((_, bytes)) = options.previews.iter().find(|(start, _)| *start == region.start && region.attributes.allocated) else { continue };
+        let title = std::iter::once(describe_region(region)).chain(hexdump(bytes)).collect::<Vec<_>>().join("\n");
+        boxes.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"transparent\"><title>{}</title></rect>\n",
+            left,
+            top,
+            right - left,
+            (bottom - top).max(1),
+            escape(&title)
+        ));
+    }
+    format!("{}{}{}", &svg[..end], boxes, &svg[end..])
+}
@@ -0,0 +1,88 @@
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// A spinner naming what a single run is busy with, or a bar over the items
+// of a batch. Only one-shot runs on a terminal enable it; logs, pipes and
+// the long-running modes never see it.
+struct State {
+    enabled: bool,
+    spinner: Option<ProgressBar>,
+    batch: bool,
+}
+
+static STATE: Mutex<State> = Mutex::new(State { enabled: false, spinner: None, batch: false });
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn enable() {
+    state().enabled = std::io::stderr().is_terminal();
+}
+
+// Shows the spinner with this message until the next phase or pause. Inside
+// a batch the bar already says what is going on.
+pub fn phase(message: &str) {
+    let mut state = state();
+    if !state.enabled || state.batch {
+        return;
+    }
+    let spinner = state.spinner.get_or_insert_with(|| {
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_style(ProgressStyle::with_template("{spinner} {msg} {elapsed}").unwrap());
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        spinner
+    });
+    spinner.set_message(message.to_string());
+}
+
+// Clears the spinner before anything is printed to the terminal, which it
+// would otherwise draw over.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pause() {
+    if let Some(spinner) = state().spinner.take() {
+        spinner.finish_and_clear();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Batch(ProgressBar);
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn batch(len: usize, message: &str) -> Batch {
+    let mut state = state();
+    if !state.enabled {
+        return Batch(ProgressBar::hidden());
+    }
+    if let Some(spinner) = state.spinner.take() {
+        spinner.finish_and_clear();
+    }
+    state.batch = true;
+    let bar = ProgressBar::new(len as u64).with_message(message.to_string());
+    bar.set_style(ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} {eta}").unwrap().progress_chars("=> "));
+    Batch(bar)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Batch {
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+
+    // A line on stdout above the bar.
+    pub fn println(&self, line: &str) {
+        self.0.suspend(|| println!("{}", line));
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+        state().batch = false;
+    }
+}
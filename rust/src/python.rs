@@ -0,0 +1,135 @@
+// The Python module, built with maturin (see pyproject.toml):
+//
+//     import memlayout
+//     regions = memlayout.parse_maps(open("/proc/self/maps").read())
+//     png = memlayout.render(regions, width=800, height=1000)
+use crate::{compose_image, embedded_input, parse_memory_regions, validate, MemoryRegion};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+#[pyclass(frozen, module = "memlayout")]
+pub struct Region {
+    region: MemoryRegion,
+}
+
+#[pymethods]
+impl Region {
+    #[getter]
+    fn start(&self) -> usize {
+        self.region.start
+    }
+
+    #[getter]
+    fn end(&self) -> usize {
+        self.region.end
+    }
+
+    #[getter]
+    fn size(&self) -> usize {
+        self.region.size
+    }
+
+    #[getter]
+    fn offset(&self) -> usize {
+        self.region.offset
+    }
+
+    #[getter]
+    fn inode(&self) -> u64 {
+        self.region.inode
+    }
+
+    #[getter]
+    fn readable(&self) -> bool {
+        self.region.attributes.readable
+    }
+
+    #[getter]
+    fn writable(&self) -> bool {
+        self.region.attributes.writable
+    }
+
+    #[getter]
+    fn executable(&self) -> bool {
+        self.region.attributes.executable
+    }
+
+    #[getter]
+    fn shared(&self) -> bool {
+        self.region.attributes.shared
+    }
+
+    #[getter]
+    fn perms(&self) -> String {
+        self.region.attributes.perms()
+    }
+
+    // The path or pseudo-path ("[heap]"), None for an anonymous region.
+    #[getter]
+    fn path(&self) -> Option<&str> {
+        self.region.file_name.as_deref()
+    }
+
+    // One row for pandas.DataFrame([r.as_dict() for r in regions]).
+    fn as_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let row = PyDict::new(py);
+        row.set_item("start", self.start())?;
+        row.set_item("end", self.end())?;
+        row.set_item("size", self.size())?;
+        row.set_item("perms", self.perms())?;
+        row.set_item("offset", self.offset())?;
+        row.set_item("inode", self.inode())?;
+        row.set_item("path", self.path())?;
+        Ok(row)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Region({})", self.region)
+    }
+}
+
+// The regions of a /proc/PID/maps or smaps text, in address order. Lines
+// that don't parse are skipped, as on the command line.
+#[pyfunction]
+fn parse_maps(text: &str) -> Vec<Region> {
+    let (memory_regions, _) = validate::validate(parse_memory_regions(text.as_bytes()).0);
+    memory_regions.into_iter().map(|region| Region { region }).collect()
+}
+
+#[derive(FromPyObject)]
+enum Regions<'py> {
+    Text(String),
+    Parsed(Vec<Bound<'py, Region>>),
+}
+
+// A PNG of the regions, given as maps text or as parse_maps() results,
+// drawn with the command line's default options.
+#[pyfunction]
+#[pyo3(signature = (regions, width = 800, height = 1000))]
+fn render<'py>(py: Python<'py>, regions: Regions<'py>, width: u32, height: u32) -> PyResult<Bound<'py, PyBytes>> {
+    if width == 0 || height == 0 {
+        return Err(PyValueError::new_err("width and height must be positive"));
+    }
+    let memory_regions = match regions {
+        Regions::Text(text) => parse_memory_regions(text.as_bytes()).0,
+        Regions::Parsed(regions) => regions.iter().map(|region| region.get().region.clone()).collect(),
+    };
+    let (memory_regions, _) = validate::validate(memory_regions);
+    let (memory_regions, options) = embedded_input(memory_regions, width, height).map_err(PyRuntimeError::new_err)?;
+    let png = py.allow_threads(|| {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(compose_image(&memory_regions, &options)).write_to(&mut png, image::ImageOutputFormat::Png).map(|_| png)
+    });
+    let png = png.map_err(|e| PyRuntimeError::new_err(format!("Unable to encode the PNG: {}", e)))?;
+    Ok(PyBytes::new(py, &png))
+}
+
+#[pymodule]
+fn memlayout(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add_class::<Region>()?;
+    module.add_function(wrap_pyfunction!(parse_maps, module)?)?;
+    module.add_function(wrap_pyfunction!(render, module)?)?;
+    Ok(())
+}
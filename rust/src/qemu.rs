@@ -0,0 +1,179 @@
+use crate::symbols::parse_address;
+use crate::{MemoryAttributes, MemoryRegion};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[derive(Clone)]
+struct Node {
+    depth: usize,
+    start: usize,
+    end: usize,
+    priority: i64,
+    kind: String,
+    name: String,
+    disabled: bool,
+}
+
+// Lines look like "  00000000000c0000-00000000000dffff (prio 1, rom): pc.rom",
+// indented by depth in the tree view. Aliases read "alias ram-below-4g
+// @pc.ram 0000000000000000-000000007fffffff", flat view entries may carry
+// an "@offset" and "KVM" after the name. Ends are inclusive.
+fn parse_line(line: &str) -> Option<Node> {
+    let body = line.trim_start();
+    let depth = line.len() - body.len();
+    let (range, rest) = body.split_once(" (")?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (parse_address(start).ok()?, parse_address(end).ok()?);
+    let (attributes, label) = rest.split_once("): ")?;
+    let (priority, kind) = attributes.strip_prefix("prio ")?.split_once(", ")?;
+    let mut words = label.split_whitespace();
+    let name = match words.next()? {
+        "alias" => words.next()?,
+        name => name,
+    };
+    Some(Node {
+        depth,
+        start,
+        end: end.saturating_add(1),
+        priority: priority.parse().ok()?,
+        kind: kind.to_string(),
+        name: name.to_string(),
+        disabled: label.contains("[disabled]"),
+    })
+}
+
+fn region(node: &Node) -> MemoryRegion {
+    // MMIO windows belong to a device, which the shared color says.
+    let attributes = match node.kind.as_str() {
+        "ram" => MemoryAttributes { readable: true, writable: true, executable: false, shared: false, allocated: true },
+        "rom" | "romd" => MemoryAttributes { readable: true, writable: false, executable: false, shared: false, allocated: true },
+        _ => MemoryAttributes { readable: true, writable: true, executable: false, shared: true, allocated: true },
+    };
+    MemoryRegion {
+        start: node.start,
+        end: node.end,
+        size: node.end - node.start,
+        attributes,
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        // Names become anonymous names, so each block and device gets its
+        // own color.
+        file_name: Some(format!("[anon:{}]", node.name)),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// The flat view of the "memory" address space, as `info mtree -f` prints
+// it, is already resolved into what the guest sees.
+fn flat_view(text: &str) -> Option<Vec<Node>> {
+    let mut nodes = Vec::new();
+    let mut in_memory = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if line.starts_with("FlatView") {
+            if in_memory {
+                break;
+            }
+        } else if trimmed.starts_with("AS ") {
+            in_memory |= trimmed.starts_with("AS \"memory\"");
+        } else if in_memory {
+            nodes.extend(parse_line(line));
+        }
+    }
+    Some(nodes).filter(|nodes| !nodes.is_empty())
+}
+
+// Without -f only the tree is there: the leaves of "address-space: memory",
+// with higher priorities covering lower ones. QEMU compares priorities
+// between siblings only, so this is an approximation of the flat view.
+fn tree_leaves(text: &str) -> Vec<Node> {
+    let nodes: Vec<Node> = text
+        .lines()
+        .skip_while(|line| line.trim() != "address-space: memory")
+        .skip(1)
+        .take_while(|line| !line.starts_with("address-space:") && !line.starts_with("memory-region:") && !line.trim().is_empty())
+        .filter_map(parse_line)
+        .collect();
+    let mut leaves: Vec<&Node> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(index, node)| !node.disabled && nodes.get(index + 1).is_none_or(|next| next.depth <= node.depth))
+        .map(|(_, node)| node)
+        .collect();
+    leaves.sort_by_key(|leaf| std::cmp::Reverse(leaf.priority));
+    let mut visible: Vec<Node> = Vec::new();
+    for leaf in leaves {
+        // What the stronger leaves already cover is cut out.
+        let mut pieces = vec![(leaf.start, leaf.end)];
+        for other in &visible {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|(start, end)| [(start, end.min(other.start)), (start.max(other.end), end)])
+                .filter(|(start, end)| start < end)
+                .collect();
+        }
+        visible.extend(pieces.into_iter().map(|(start, end)| Node { start, end, ..leaf.clone() }));
+    }
+    visible
+}
+
+pub fn parse_mtree(text: &str) -> Result<Vec<MemoryRegion>, String> {
+    let nodes = flat_view(text).unwrap_or_else(|| tree_leaves(text));
+    let mut memory_regions: Vec<MemoryRegion> = nodes.iter().filter(|node| node.end > node.start).map(region).collect();
+    if memory_regions.is_empty() {
+        return Err("No regions of the memory address space in the info mtree output".to_string());
+    }
+    memory_regions.sort_by_key(|region| region.start);
+    Ok(memory_regions)
+}
+
+// One QMP exchange: skip events, return the "return" value or the error.
+fn execute<S: Read + Write>(session: &mut BufReader<S>, command: &serde_json::Value) -> Result<serde_json::Value, String> {
+    session.get_mut().write_all(format!("{}\n", command).as_bytes()).map_err(|e| format!("QMP write failed: {}", e))?;
+    loop {
+        let mut line = String::new();
+        if session.read_line(&mut line).map_err(|e| format!("QMP read failed: {}", e))? == 0 {
+            return Err("QMP connection closed".to_string());
+        }
+        let reply: serde_json::Value = serde_json::from_str(&line).map_err(|e| format!("Invalid QMP reply: {}", e))?;
+        if let Some(value) = reply.get("return") {
+            return Ok(value.clone());
+        }
+        if let Some(error) = reply.get("error") {
+            return Err(format!("QMP error: {}", error["desc"].as_str().unwrap_or("unknown")));
+        }
+    }
+}
+
+fn mtree_over<S: Read + Write>(stream: S) -> Result<String, String> {
+    let mut session = BufReader::new(stream);
+    // The server greets first.
+    let mut greeting = String::new();
+    session.read_line(&mut greeting).map_err(|e| format!("QMP read failed: {}", e))?;
+    execute(&mut session, &serde_json::json!({ "execute": "qmp_capabilities" }))?;
+    let reply = execute(&mut session, &serde_json::json!({ "execute": "human-monitor-command", "arguments": { "command-line": "info mtree -f" } }))?;
+    reply.as_str().map(str::to_string).ok_or_else(|| "Unexpected reply to info mtree".to_string())
+}
+
+// A QMP socket given to QEMU with -qmp unix:PATH,server or tcp:HOST:PORT.
+pub fn read_mtree(endpoint: &str) -> Result<String, String> {
+    let timeout = Some(Duration::from_secs(5));
+    if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+        let stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5)).map_err(|e| format!("Unable to connect to QMP at {}: {}", endpoint, e))?;
+        stream.set_read_timeout(timeout).map_err(|e| e.to_string())?;
+        return mtree_over(stream);
+    }
+    #[cfg(unix)]
+    {
+        let stream = std::os::unix::net::UnixStream::connect(endpoint).map_err(|e| format!("Unable to connect to QMP at {}: {}", endpoint, e))?;
+        stream.set_read_timeout(timeout).map_err(|e| e.to_string())?;
+        mtree_over(stream)
+    }
+    #[cfg(not(unix))]
+    Err(format!("QMP sockets other than HOST:PORT need a Unix system: {}", endpoint))
+}
@@ -0,0 +1,38 @@
+use crate::{display_name, fit_text, format_size, luminance, region_color, MemoryRegion, RenderOptions};
+use plotters::prelude::*;
+
+const ROW_HEIGHT: i32 = 18;
+const LABEL_WIDTH: i32 = 240;
+const CHART_WIDTH: u32 = 800;
+
+// One bar per region in the order given, its length the region's value
+// relative to the largest; address order is what the map is for.
+pub fn draw_ranked_chart(ranked: &[(&MemoryRegion, usize)], title: &str, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let height = (ranked.len() as i32 * ROW_HEIGHT + 40) as u32;
+    let root = BitMapBackend::new(path, (CHART_WIDTH, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 11.0, FontStyle::Normal);
+    root.draw(&Text::new(title.to_string(), (10, 8), FontDesc::new(FontFamily::SansSerif, 14.0, FontStyle::Bold).color(&ink)))?;
+
+    let max = ranked.iter().map(|(_, value)| *value).max().unwrap_or(0).max(1);
+    let bar_space = CHART_WIDTH as i32 - LABEL_WIDTH - 90;
+    for (i, (region, value)) in ranked.iter().enumerate() {
+        let y = 32 + i as i32 * ROW_HEIGHT;
+        let name = display_name(region).unwrap_or_else(|| "anon".to_string());
+        let label = format!("{:#x} {}", region.start, name);
+        if let Some(label) = fit_text(&root, &label, &font, LABEL_WIDTH - 15)? {
+            root.draw(&Text::new(label, (10, y + 2), font.color(&ink)))?;
+        }
+
+        let color = options.emphasis.apply(region, region_color(region, options.color_by, &options.theme)).0;
+        let length = ((*value as f64 / max as f64) * bar_space as f64).round().max(1.0) as i32;
+        root.draw(&Rectangle::new([(LABEL_WIDTH, y), (LABEL_WIDTH + length, y + ROW_HEIGHT - 3)], RGBColor(color[0], color[1], color[2]).filled()))?;
+        if luminance(color) > 235.0 && !options.dark {
+            root.draw(&Rectangle::new([(LABEL_WIDTH, y), (LABEL_WIDTH + length, y + ROW_HEIGHT - 3)], RGBColor(160, 160, 160)))?;
+        }
+        root.draw(&Text::new(format_size(*value), (LABEL_WIDTH + length + 5, y + 2), font.color(&ink)))?;
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::snapshot::Snapshot;
+use signal_hook::consts::SIGUSR1;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct Retention {
+    // Snapshots older than this are deleted.
+    pub keep_for: Duration,
+    // And the oldest go first while the ring is larger than this.
+    pub max_bytes: u64,
+    // How far back a dump reaches.
+    pub dump_window: Duration,
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+}
+
+// Ring entries are named by capture time in milliseconds, zero padded so
+// name order is time order.
+fn ring(dir: &Path) -> std::io::Result<Vec<(u128, PathBuf, u64)>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(taken) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()) else { continue };
+        if path.extension().is_some_and(|extension| extension == "mmsnap") {
+            entries.push((taken, path, entry.metadata()?.len()));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn rotate(dir: &Path, retention: &Retention) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = ring(dir)?;
+    let oldest_kept = now_ms().saturating_sub(retention.keep_for.as_millis());
+    let mut total: u64 = entries.iter().map(|(_, _, size)| size).sum();
+    // The newest snapshot always stays.
+    for (taken, path, size) in &entries[..entries.len().saturating_sub(1)] {
+        if *taken >= oldest_kept && total <= retention.max_bytes {
+            break;
+        }
+        fs::remove_file(path)?;
+        total -= size;
+    }
+    Ok(())
+}
+
+// Copies the snapshots of the last window into a directory of their own,
+// which the animate subcommand replays.
+pub fn dump(dir: &Path, window: Duration) -> Result<(PathBuf, usize), Box<dyn std::error::Error>> {
+    let now = now_ms();
+    let target = dir.join(format!("dump-{}", now / 1000));
+    fs::create_dir_all(&target)?;
+    let since = now.saturating_sub(window.as_millis());
+    let mut copied = 0;
+    for (_, path, _) in ring(dir)?.into_iter().filter(|(taken, _, _)| *taken >= since) {
+        fs::copy(&path, target.join(path.file_name().unwrap()))?;
+        copied += 1;
+    }
+    Ok((target, copied))
+}
+
+// Snapshots the process every interval until it exits. SIGUSR1 dumps the
+// last window, and so does the process going away, which is the crash the
+// recording was for.
+pub fn record<F: Fn() -> Result<Snapshot, String>>(dir: &Path, pid: u32, interval: Duration, retention: &Retention, capture: F) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    // Only a flag is set by the signal; the sampling loop does the dump.
+    let dump_requested = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGUSR1, Arc::clone(&dump_requested))?;
+    loop {
+        match capture() {
+            Ok(snapshot) => {
+                snapshot.save(&dir.join(format!("{:016}.mmsnap", now_ms())).to_string_lossy())?;
+                rotate(dir, retention)?;
+            }
+            Err(e) => {
+                tracing::warn!("pid {} is gone ({}), dumping the last {}s", pid, e, retention.dump_window.as_secs());
+                let (target, copied) = dump(dir, retention.dump_window)?;
+                println!("Dumped {} snapshots to {}", copied, target.display());
+                return Ok(());
+            }
+        }
+        if dump_requested.swap(false, Ordering::SeqCst) {
+            let (target, copied) = dump(dir, retention.dump_window)?;
+            println!("Dumped {} snapshots to {}", copied, target.display());
+        }
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pdf;
+use crate::{draw_memory_map, MemoryRegion, RenderOptions};
+use plotters::prelude::*;
+
+// Where a drawing ends up. A format only says how to make its backend and
+// what to hand back once drawn; the layout and drawing are draw_memory_map
+// for all of them.
+pub trait Sink {
+    type Backend<'a>: DrawingBackend
+    where
+        Self: 'a;
+    type Output;
+
+    fn backend(&mut self, size: (u32, u32)) -> Self::Backend<'_>;
+    fn finish(self, size: (u32, u32)) -> Self::Output;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Bitmap(image::RgbImage);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Bitmap {
+    pub fn new((width, height): (u32, u32)) -> Self {
+        Bitmap(image::RgbImage::new(width, height))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sink for Bitmap {
+    type Backend<'a> = BitMapBackend<'a>;
+    type Output = image::RgbImage;
+
+    fn backend(&mut self, size: (u32, u32)) -> BitMapBackend<'_> {
+        BitMapBackend::with_buffer(&mut self.0, size)
+    }
+
+    fn finish(self, _: (u32, u32)) -> image::RgbImage {
+        self.0
+    }
+}
+
+#[derive(Default)]
+pub struct Svg(String);
+
+impl Sink for Svg {
+    type Backend<'a> = SVGBackend<'a>;
+    type Output = String;
+
+    fn backend(&mut self, size: (u32, u32)) -> SVGBackend<'_> {
+        SVGBackend::with_string(&mut self.0, size)
+    }
+
+    fn finish(self, _: (u32, u32)) -> String {
+        self.0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct Pdf(pdf::Drawing);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sink for Pdf {
+    type Backend<'a> = pdf::PdfBackend<'a>;
+    type Output = Vec<u8>;
+
+    fn backend(&mut self, size: (u32, u32)) -> pdf::PdfBackend<'_> {
+        pdf::PdfBackend::new(&mut self.0, size)
+    }
+
+    fn finish(self, size: (u32, u32)) -> Vec<u8> {
+        self.0.to_pdf(size)
+    }
+}
+
+// The backend is dropped before finish, which flushes the bitmap and SVG
+// ones into their buffers.
+pub fn render<S: Sink>(mut sink: S, memory_regions: &[MemoryRegion], (width, height): (u32, u32), options: &RenderOptions) -> Result<S::Output, Box<dyn std::error::Error>>
+where
+    for<'a> <S::Backend<'a> as DrawingBackend>::ErrorType: 'static,
+{
+    {
+        let root = sink.backend((width, height)).into_drawing_area();
+        draw_memory_map(&root, memory_regions, width, height, options)?;
+    }
+    Ok(sink.finish((width, height)))
+}
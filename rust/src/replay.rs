@@ -0,0 +1,459 @@
+use crate::perf;
+use crate::symbols::parse_address;
+use crate::{MemoryAttributes, MemoryRegion};
+use std::collections::HashMap;
+use std::fs;
+
+const PAGE_SIZE: usize = 4096;
+
+// A memory syscall that succeeded, as strace printed it.
+struct Call {
+    line: usize,
+    name: String,
+    args: Vec<String>,
+    result: String,
+    text: String,
+}
+
+pub enum Change {
+    Map(MemoryRegion),
+    Unmap(usize, usize),
+    Protect(usize, usize, (bool, bool, bool)),
+    Remap { old: usize, old_size: usize, new: usize, new_size: usize },
+    // The break as brk returned it; the first call only reports where it starts.
+    Brk(usize),
+}
+
+// One change to the address space, from whichever log it was read.
+pub struct Event {
+    pub line: usize,
+    // Shown on the animation frame it produced.
+    pub label: String,
+    // Shown in the table as what created a region.
+    pub text: String,
+    pub change: Change,
+}
+
+// Strips what -f, -o and -t/-tt/-ttt put before the call: "[pid  42] ",
+// a bare "42  " and timestamps.
+fn split_prefix(line: &str) -> (Option<u32>, &str) {
+    let mut rest = line.trim_start();
+    let mut pid = None;
+    if let Some((number, tail)) = rest.strip_prefix("[pid").and_then(|tail| tail.split_once(']')) {
+        pid = number.trim().parse().ok();
+        rest = tail.trim_start();
+    }
+    loop {
+        let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if token.is_empty() || !token.chars().all(|c| c.is_ascii_digit() || c == ':' || c == '.') {
+            return (pid, rest);
+        }
+        if pid.is_none() && token.chars().all(|c| c.is_ascii_digit()) {
+            pid = token.parse().ok();
+        }
+        rest = tail.trim_start();
+    }
+}
+
+// Arguments split at top-level commas; flags never contain one, but
+// strings, structures and -y paths can.
+fn split_args(args: &str) -> Vec<String> {
+    let (mut parts, mut current, mut depth, mut quoted) = (Vec::new(), String::new(), 0, false);
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                current.push(c);
+                current.extend(chars.next());
+                continue;
+            }
+            '"' => quoted = !quoted,
+            '(' | '[' | '{' | '<' if !quoted => depth += 1,
+            ')' | ']' | '}' | '>' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+fn parse_call(line: usize, body: &str) -> Option<Call> {
+    let open = body.find('(')?;
+    let name = &body[..open];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    // strace pads the result out to a column.
+    let equals = body.rfind(" = ")?;
+    let call = body[..equals].trim_end().strip_suffix(')')?;
+    let result = body[equals + 3..].split_whitespace().next()?;
+    // Failures return -1 with an errno, and "?" means the process exited first.
+    if result.starts_with('-') || result == "?" {
+        return None;
+    }
+    Some(Call { line, name: name.to_string(), args: split_args(&call[open + 1..]), result: result.to_string(), text: format!("{})", call) })
+}
+
+// Calls interrupted by another thread are printed in two halves, which
+// are joined back up per pid.
+pub fn parse_log(text: &str, only_pid: Option<u32>) -> Vec<Event> {
+    let mut pending: HashMap<Option<u32>, String> = HashMap::new();
+    // Descriptors from traced open calls, for logs taken without -y.
+    let mut fds: HashMap<String, String> = HashMap::new();
+    let mut events = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let (pid, body) = split_prefix(line);
+        if only_pid.is_some_and(|only| pid != Some(only)) {
+            continue;
+        }
+        let body = if let Some(start) = body.strip_suffix("<unfinished ...>") {
+            pending.insert(pid, start.trim_end().to_string());
+            continue;
+        } else if body.starts_with("<...") {
+            match (pending.remove(&pid), body.split_once("resumed>")) {
+                (Some(start), Some((_, rest))) => format!("{}{}", start, rest),
+                _ => continue,
+            }
+        } else {
+            body.to_string()
+        };
+        if let Some(call) = parse_call(index + 1, &body) {
+            events.extend(strace_change(&call, &mut fds).map(|change| Event { line: call.line, label: format!("line {}: {}", call.line, call.name), text: call.text, change }));
+        }
+    }
+    events
+}
+
+// An strace log, perf script output or a perf.data file, told apart by
+// their contents.
+pub fn load(path: &str, only_pid: Option<u32>) -> Result<Vec<Event>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let text = if perf::is_perf_data(&bytes) { perf::script(path)? } else { String::from_utf8_lossy(&bytes).into_owned() };
+    let events = if perf::is_script(&text) { perf::parse_script(&text, only_pid) } else { parse_log(&text, only_pid) };
+    if events.is_empty() {
+        return Err(format!("{} has no memory mappings to replay", path));
+    }
+    Ok(events)
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    let s = s.trim();
+    match s {
+        "NULL" => Some(0),
+        _ if s.starts_with("0x") => parse_address(s).ok(),
+        _ => s.parse().ok(),
+    }
+}
+
+fn page_align(size: usize) -> usize {
+    size.div_ceil(PAGE_SIZE) * PAGE_SIZE
+}
+
+fn protection(prot: &str) -> (bool, bool, bool) {
+    (prot.contains("PROT_READ"), prot.contains("PROT_WRITE"), prot.contains("PROT_EXEC"))
+}
+
+// The path strace -y prints after a descriptor, as in "3</usr/lib/libc.so.6>".
+fn decorated_path(fd: &str) -> Option<&str> {
+    let (_, path) = fd.split_once('<')?;
+    path.strip_suffix('>')
+}
+
+pub fn region(start: usize, end: usize, (readable, writable, executable): (bool, bool, bool), shared: bool, offset: usize, file_name: Option<String>) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end,
+        size: end - start,
+        attributes: MemoryAttributes { readable, writable, executable, shared, allocated: true },
+        offset,
+        device: (0, 0),
+        inode: 0,
+        file_name,
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// What a call did to the map; open and close only track descriptors.
+fn strace_change(call: &Call, fds: &mut HashMap<String, String>) -> Option<Change> {
+    let arg = |i: usize| call.args.get(i).map(String::as_str).unwrap_or("");
+    let range = |start: &str, length: &str| Some((parse_number(start)?, page_align(parse_number(length)?)));
+    match call.name.as_str() {
+        "mmap" | "mmap2" => {
+            let (start, length) = range(&call.result, arg(1))?;
+            let fd = arg(4);
+            let file_name = if fd.starts_with("-1") || arg(3).contains("MAP_ANONYMOUS") {
+                None
+            } else {
+                decorated_path(fd).map(str::to_string).or_else(|| fds.get(fd).cloned())
+            };
+            // strace prints mmap2's offset in bytes too, though the call
+            // takes it in pages.
+            let offset = parse_number(arg(5)).unwrap_or(0);
+            let shared = arg(3).contains("MAP_SHARED");
+            Some(Change::Map(region(start, start + length, protection(arg(2)), shared, offset, file_name)))
+        }
+        "munmap" => {
+            let (start, length) = range(arg(0), arg(1))?;
+            Some(Change::Unmap(start, start + length))
+        }
+        "mprotect" | "pkey_mprotect" => {
+            let (start, length) = range(arg(0), arg(1))?;
+            Some(Change::Protect(start, start + length, protection(arg(2))))
+        }
+        "mremap" => {
+            let (old, old_size) = range(arg(0), arg(1))?;
+            let (new, new_size) = range(&call.result, arg(2))?;
+            Some(Change::Remap { old, old_size, new, new_size })
+        }
+        "brk" => Some(Change::Brk(parse_number(&call.result)?)),
+        "open" | "openat" => {
+            let path = call.args.iter().find(|arg| arg.starts_with('"'))?;
+            fds.insert(call.result.split('<').next().unwrap_or("").to_string(), path.trim_matches('"').to_string());
+            None
+        }
+        "close" => {
+            fds.remove(arg(0).split('<').next().unwrap_or(""));
+            None
+        }
+        _ => None,
+    }
+}
+
+fn piece(region: &MemoryRegion, start: usize, end: usize) -> MemoryRegion {
+    let mut piece = region.clone();
+    if piece.file_name.is_some() {
+        piece.offset += start - region.start;
+    }
+    piece.start = start;
+    piece.end = end;
+    piece.size = end - start;
+    piece
+}
+
+// The address space as the calls so far left it.
+#[derive(Default)]
+pub struct AddressSpace {
+    // Sorted and disjoint, each with the index of the event that made it.
+    regions: Vec<(MemoryRegion, usize)>,
+    // Where brk(NULL) first put the break, and where it is now.
+    heap: Option<(usize, usize)>,
+}
+
+impl AddressSpace {
+    fn unmap(&mut self, start: usize, end: usize) {
+        let mut kept = Vec::with_capacity(self.regions.len() + 1);
+        for (region, origin) in self.regions.drain(..) {
+            if region.end <= start || end <= region.start {
+                kept.push((region, origin));
+                continue;
+            }
+            if region.start < start {
+                kept.push((piece(&region, region.start, start), origin));
+            }
+            if end < region.end {
+                kept.push((piece(&region, end, region.end), origin));
+            }
+        }
+        self.regions = kept;
+    }
+
+    fn map(&mut self, region: MemoryRegion, origin: usize) {
+        self.unmap(region.start, region.end);
+        let at = self.regions.partition_point(|(existing, _)| existing.start < region.start);
+        self.regions.insert(at, (region, origin));
+    }
+
+    fn split_at(&mut self, address: usize) {
+        if let Some(at) = self.regions.iter().position(|(region, _)| region.start < address && address < region.end) {
+            let (region, origin) = self.regions[at].clone();
+            self.regions[at] = (piece(&region, region.start, address), origin);
+            self.regions.insert(at + 1, (piece(&region, address, region.end), origin));
+        }
+    }
+
+    fn protect(&mut self, start: usize, end: usize, (readable, writable, executable): (bool, bool, bool)) {
+        self.split_at(start);
+        self.split_at(end);
+        for (region, _) in self.regions.iter_mut().filter(|(region, _)| start <= region.start && region.end <= end) {
+            region.attributes.readable = readable;
+            region.attributes.writable = writable;
+            region.attributes.executable = executable;
+        }
+    }
+
+    // Applies events[index]; returns whether it changed the map.
+    pub fn apply(&mut self, events: &[Event], index: usize) -> bool {
+        match &events[index].change {
+            Change::Map(region) => self.map(region.clone(), index),
+            Change::Unmap(start, end) => self.unmap(*start, *end),
+            Change::Protect(start, end, perms) => self.protect(*start, *end, *perms),
+            &Change::Remap { old, old_size, new, new_size } => {
+                let Some((moved, _)) = self.regions.iter().find(|(region, _)| region.start <= old && old < region.end) else { return false };
+                let moved = piece(moved, old, old + 1);
+                self.unmap(old, old + old_size);
+                self.map(MemoryRegion { start: new, end: new + new_size, size: new_size, ..moved }, index);
+            }
+            &Change::Brk(end) => match self.heap {
+                None => {
+                    self.heap = Some((end, end));
+                    return false;
+                }
+                Some((start, previous)) => {
+                    self.unmap(start, previous.max(end));
+                    if end > start {
+                        self.map(region(start, end, (true, true, false), false, 0, Some("[heap]".to_string())), index);
+                    }
+                    self.heap = Some((start, end));
+                }
+            },
+        }
+        true
+    }
+
+    pub fn replay(events: &[Event]) -> Self {
+        let mut space = AddressSpace::default();
+        for index in 0..events.len() {
+            space.apply(events, index);
+        }
+        space
+    }
+
+    pub fn regions(&self) -> Vec<MemoryRegion> {
+        self.regions.iter().map(|(region, _)| region.clone()).collect()
+    }
+
+    // Each region with the event that created it.
+    pub fn origins<'a>(&'a self, events: &'a [Event]) -> impl Iterator<Item = (&'a MemoryRegion, &'a Event)> {
+        self.regions.iter().map(move |(region, origin)| (region, &events[*origin]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+execve(\"/usr/bin/true\", [\"true\"], 0x7ffd8a0a0e10 /* 20 vars */) = 0
+brk(NULL)                               = 0x55d4c4a1b000
+openat(AT_FDCWD, \"/lib/x86_64-linux-gnu/libc.so.6\", O_RDONLY|O_CLOEXEC) = 3
+mmap(NULL, 2037344, PROT_READ, MAP_PRIVATE|MAP_DENYWRITE, 3, 0) = 0x7f2a1c000000
+mmap(0x7f2a1c022000, 1540096, PROT_READ|PROT_EXEC, MAP_PRIVATE|MAP_FIXED|MAP_DENYWRITE, 3, 0x22000) = 0x7f2a1c022000
+close(3)                                = 0
+mmap(NULL, 8192, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x7f2a1c400000
+mprotect(0x7f2a1c1f0000, 16384, PROT_READ) = 0
+munmap(0x7f2a1c500000, 4096)            = -1 EINVAL (Invalid argument)
+brk(0x55d4c4a3c000)                     = 0x55d4c4a3c000
+";
+
+    fn changes(log: &str) -> Vec<Change> {
+        parse_log(log, None).into_iter().map(|event| event.change).collect()
+    }
+
+    #[test]
+    fn reads_the_memory_calls_of_a_log() {
+        let events = parse_log(LOG, None);
+        // execve, openat and close change nothing, and the failed munmap
+        // is left out.
+        assert_eq!(events.iter().map(|event| event.line).collect::<Vec<_>>(), vec![2, 4, 5, 7, 8, 10]);
+        assert_eq!(events[1].label, "line 4: mmap");
+        let Change::Map(libc) = &events[2].change else { panic!("not a map") };
+        assert_eq!((libc.start, libc.end, libc.offset), (0x7f2a1c022000, 0x7f2a1c022000 + 1540096, 0x22000));
+        assert_eq!(libc.file_name.as_deref(), Some("/lib/x86_64-linux-gnu/libc.so.6"));
+        assert!(libc.attributes.executable && !libc.attributes.writable);
+        let Change::Map(anonymous) = &events[3].change else { panic!("not a map") };
+        assert_eq!((anonymous.file_name.as_deref(), anonymous.size), (None, 8192));
+        assert!(matches!(events[4].change, Change::Protect(0x7f2a1c1f0000, 0x7f2a1c1f4000, (true, false, false))));
+        assert!(matches!(events[5].change, Change::Brk(0x55d4c4a3c000)));
+    }
+
+    #[test]
+    fn takes_mmap2_offsets_as_printed() {
+        let log = "mmap2(NULL, 4096, PROT_READ, MAP_PRIVATE, 3</lib/libc.so.6>, 0x1a000) = 0xb7700000\n";
+        let changes = changes(log);
+        let [Change::Map(region)] = changes.as_slice() else { panic!("not one map") };
+        assert_eq!((region.offset, region.file_name.as_deref()), (0x1a000, Some("/lib/libc.so.6")));
+    }
+
+    #[test]
+    fn reads_munmap_and_mremap() {
+        let log = "\
+munmap(0x7f2a1c400000, 5000)            = 0
+mremap(0x7f2a1c600000, 4096, 16384, MREMAP_MAYMOVE) = 0x7f2a1c800000
+";
+        let changes = changes(log);
+        assert!(matches!(changes[0], Change::Unmap(0x7f2a1c400000, 0x7f2a1c402000)));
+        assert!(matches!(changes[1], Change::Remap { old: 0x7f2a1c600000, old_size: 4096, new: 0x7f2a1c800000, new_size: 16384 }));
+    }
+
+    #[test]
+    fn joins_unfinished_calls_per_pid() {
+        let log = "\
+[pid  4242] mmap(NULL, 4096, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0 <unfinished ...>
+[pid  4243] munmap(0x7f0000000000, 4096) = 0
+[pid  4242] <... mmap resumed>)         = 0x7f0000100000
+";
+        let events = parse_log(log, None);
+        assert_eq!(events.iter().map(|event| event.line).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(matches!(&events[1].change, Change::Map(region) if region.start == 0x7f0000100000 && region.size == 4096));
+        let only = parse_log(log, Some(4242));
+        assert_eq!(only.len(), 1);
+        assert!(matches!(only[0].change, Change::Map(_)));
+    }
+
+    #[test]
+    fn strips_pids_and_timestamps() {
+        assert_eq!(split_prefix("4242  12:00:01.123456 brk(NULL) = 0x1000"), (Some(4242), "brk(NULL) = 0x1000"));
+        assert_eq!(split_prefix("[pid  17] 1697364000.5 brk(NULL) = 0x1000"), (Some(17), "brk(NULL) = 0x1000"));
+        assert_eq!(split_prefix("brk(NULL) = 0x1000"), (None, "brk(NULL) = 0x1000"));
+    }
+
+    #[test]
+    fn a_partial_munmap_splits_the_region() {
+        let log = "\
+mmap(NULL, 40960, PROT_READ, MAP_PRIVATE, 3</usr/lib/libfoo.so>, 0x1000) = 0x10000
+munmap(0x14000, 8192)                   = 0
+";
+        let space = AddressSpace::replay(&parse_log(log, None));
+        let regions = space.regions();
+        assert_eq!(regions.iter().map(|r| (r.start, r.end, r.offset)).collect::<Vec<_>>(), vec![(0x10000, 0x14000, 0x1000), (0x16000, 0x1a000, 0x7000)]);
+        // Both halves still come from the mmap.
+        assert!(space.origins(&parse_log(log, None)).all(|(_, event)| event.line == 1));
+    }
+
+    #[test]
+    fn mprotect_splits_out_what_it_covers() {
+        let log = "\
+mmap(NULL, 12288, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x20000
+mprotect(0x21000, 4096, PROT_NONE)      = 0
+";
+        let regions = AddressSpace::replay(&parse_log(log, None)).regions();
+        let perms: Vec<String> = regions.iter().map(|r| r.attributes.perms()).collect();
+        assert_eq!(regions.iter().map(|r| r.start).collect::<Vec<_>>(), vec![0x20000, 0x21000, 0x22000]);
+        assert_eq!(perms, vec!["rw-p", "---p", "rw-p"]);
+    }
+
+    #[test]
+    fn tracks_the_heap_and_moved_regions() {
+        let log = "\
+brk(NULL)                               = 0x55d4c4a1b000
+brk(0x55d4c4a3c000)                     = 0x55d4c4a3c000
+mmap(NULL, 8192, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x7f0000000000
+mremap(0x7f0000000000, 8192, 16384, MREMAP_MAYMOVE) = 0x7f0000100000
+brk(0x55d4c4a2c000)                     = 0x55d4c4a2c000
+";
+        let regions = AddressSpace::replay(&parse_log(log, None)).regions();
+        let spans: Vec<(usize, usize, Option<&str>)> = regions.iter().map(|r| (r.start, r.end, r.file_name.as_deref())).collect();
+        assert_eq!(spans, vec![(0x55d4c4a1b000, 0x55d4c4a2c000, Some("[heap]")), (0x7f0000100000, 0x7f0000104000, None)]);
+    }
+}
@@ -0,0 +1,189 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::audit::{self, Finding};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::smaps::SmapsInfo;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utilization::{self, Utilization};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validate::Warnings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{region_category, MemoryRegion};
+#[cfg(not(target_arch = "wasm32"))]
+use serde::Serialize;
+use serde::Serializer;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+
+// Addresses are emitted as hex strings: 64-bit values don't survive a trip
+// through JSON consumers that parse numbers as doubles.
+pub fn hex<S: Serializer>(value: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:#x}", value))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize)]
+pub struct RegionRecord {
+    #[serde(serialize_with = "hex")]
+    pub start: usize,
+    #[serde(serialize_with = "hex")]
+    pub end: usize,
+    pub size: usize,
+    pub perms: String,
+    #[serde(serialize_with = "hex")]
+    pub offset: usize,
+    pub device: String,
+    pub inode: u64,
+    pub category: &'static str,
+    pub path: Option<String>,
+    pub thread_id: Option<u32>,
+    pub guard: bool,
+    pub deleted: bool,
+    pub smaps: Option<SmapsInfo>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<&MemoryRegion> for RegionRecord {
+    fn from(region: &MemoryRegion) -> Self {
+        RegionRecord {
+            start: region.start,
+            end: region.end,
+            size: region.size,
+            perms: region.attributes.perms(),
+            offset: region.offset,
+            device: format!("{:02x}:{:02x}", region.device.0, region.device.1),
+            inode: region.inode,
+            category: region_category(region),
+            path: region.file_name.clone(),
+            thread_id: region.thread_id,
+            guard: region.guard,
+            deleted: audit::is_deleted_mapping(region),
+            smaps: region.smaps.clone(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize)]
+pub struct Totals {
+    pub regions: usize,
+    pub mapped_bytes: usize,
+    pub rss_bytes: Option<usize>,
+    pub findings: usize,
+    pub bytes_by_category: BTreeMap<&'static str, usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize)]
+pub struct AuditReport<'a> {
+    pub pid: u32,
+    pub findings: &'a [Finding],
+    pub regions: Vec<RegionRecord>,
+    pub totals: Totals,
+}
+
+// Bump on any change that isn't a pure addition, so consumers can refuse
+// documents they don't understand.
+#[cfg(not(target_arch = "wasm32"))]
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize)]
+pub struct Export {
+    pub schema_version: u32,
+    pub pid: Option<u32>,
+    pub regions: Vec<RegionRecord>,
+    pub utilization: Utilization,
+    // skipped_lines, parse_warnings and region_warnings, of this capture
+    // only.
+    #[serde(flatten)]
+    pub warnings: Warnings,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export(pid: Option<u32>, memory_regions: &[MemoryRegion], warnings: &Warnings) -> Export {
+    Export {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        pid,
+        regions: memory_regions.iter().filter(|region| region.attributes.allocated).map(RegionRecord::from).collect(),
+        utilization: utilization::utilization(memory_regions),
+        warnings: warnings.clone(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Every numeric column appears twice, decimal for arithmetic in a
+// spreadsheet and hex to match against maps output.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_csv(memory_regions: &[MemoryRegion]) -> String {
+    let numeric = |name: &str| format!("{0},{0}_hex", name);
+    let smaps_columns: Vec<String> = SmapsInfo::default().fields().iter().map(|(name, _)| numeric(name)).collect();
+    let mut csv = format!(
+        "{},{},{},perms,{},device,inode,category,path,thread_id,guard,{}\n",
+        numeric("start"),
+        numeric("end"),
+        numeric("size"),
+        numeric("offset"),
+        smaps_columns.join(",")
+    );
+
+    for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+        let number = |value: usize| format!("{},{:#x}", value, value);
+        let smaps: Vec<String> = match &region.smaps {
+            Some(smaps) => smaps.fields().iter().map(|(_, value)| number(*value)).collect(),
+            None => SmapsInfo::default().fields().iter().map(|_| ",".to_string()).collect(),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{:02x}:{:02x},{},{},{},{},{},{}\n",
+            number(region.start),
+            number(region.end),
+            number(region.size),
+            region.attributes.perms(),
+            number(region.offset),
+            region.device.0,
+            region.device.1,
+            region.inode,
+            region_category(region),
+            csv_field(region.file_name.as_deref().unwrap_or("")),
+            region.thread_id.map(|tid| tid.to_string()).unwrap_or_default(),
+            region.guard,
+            smaps.join(",")
+        ));
+    }
+    csv
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn audit_report<'a>(pid: u32, memory_regions: &[MemoryRegion], findings: &'a [Finding]) -> AuditReport<'a> {
+    let mapped: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| region.attributes.allocated).collect();
+
+    let mut bytes_by_category = BTreeMap::new();
+    for region in &mapped {
+        *bytes_by_category.entry(region_category(region)).or_insert(0) += region.size;
+    }
+    let rss_bytes = if mapped.iter().all(|region| region.smaps.is_some()) && !mapped.is_empty() {
+        Some(mapped.iter().filter_map(|region| region.smaps.as_ref()).map(|smaps| smaps.rss).sum())
+    } else {
+        None
+    };
+
+    AuditReport {
+        pid,
+        findings,
+        regions: mapped.iter().map(|region| RegionRecord::from(*region)).collect(),
+        totals: Totals {
+            regions: mapped.len(),
+            mapped_bytes: mapped.iter().map(|region| region.size).sum(),
+            rss_bytes,
+            findings: findings.len(),
+            bytes_by_category,
+        },
+    }
+}
@@ -0,0 +1,59 @@
+use crate::adb::AdbTarget;
+use crate::capture;
+use std::fs;
+
+// Totals from /proc/PID/smaps_rollup, which the kernel sums while walking
+// the mappings once instead of formatting a block per region. Bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rollup {
+    pub rss: usize,
+    pub pss: usize,
+    pub swap: usize,
+    pub locked: usize,
+}
+
+pub fn parse(text: &str) -> Rollup {
+    let value = |name: &str| -> usize {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<usize>().ok())
+            .map_or(0, |kb| kb * 1024)
+    };
+    Rollup { rss: value("Rss"), pss: value("Pss"), swap: value("Swap"), locked: value("Locked") }
+}
+
+// smaps_rollup arrived in 4.14; older kernels would need the full smaps,
+// which is what --summary is avoiding, so that's an error.
+pub fn read(pid: u32, adb: Option<&AdbTarget>) -> Result<Rollup, String> {
+    let text = match adb {
+        Some(adb) => adb.read_proc_file(pid, "smaps_rollup"),
+        None => capture::read_proc_file(pid, "smaps_rollup"),
+    };
+    text.map(|text| parse(&text)).map_err(|e| format!("{} (smaps_rollup needs Linux 4.14 or later)", e))
+}
+
+// Tab separated with raw byte counts, so scripts can cut and sort it.
+pub fn print_header() {
+    println!("pid\trss\tpss\tswap\tlocked\tname");
+}
+
+pub fn print_row(pid: u32, name: &str, rollup: &Rollup) {
+    println!("{}\t{}\t{}\t{}\t{}\t{}", pid, rollup.rss, rollup.pss, rollup.swap, rollup.locked, name);
+}
+
+// Every process whose rollup is readable, by PSS. Kernel threads have
+// nothing resident and processes that exit between the listing and the
+// read are gone, so both are left out.
+pub fn all_processes() -> Vec<(u32, String, Rollup)> {
+    let Ok(entries) = fs::read_dir("/proc") else { return Vec::new() };
+    let mut rollups: Vec<(u32, String, Rollup)> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let rollup = read(pid, None).ok().filter(|rollup| rollup.rss > 0)?;
+            let name = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default().trim().to_string();
+            Some((pid, name, rollup))
+        })
+        .collect();
+    rollups.sort_by(|a, b| b.2.pss.cmp(&a.2.pss).then(a.0.cmp(&b.0)));
+    rollups
+}
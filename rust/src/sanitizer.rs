@@ -0,0 +1,88 @@
+use crate::MemoryRegion;
+
+pub const SHADOW_COLOR: [u8; 3] = [120, 100, 70];
+
+// Where the sanitizer runtimes put their shadow on x86_64 Linux, from
+// compiler-rt: ASan's default mapping with shadow offset 0x7fff8000 and
+// TSan's 48-bit mapping of shadow cells and metainfo.
+#[cfg(not(target_arch = "wasm32"))]
+const SHADOWS: [(&str, &str, u64, u64); 5] = [
+    ("asan", "low shadow", 0x7fff8000, 0x8fff7000),
+    ("asan", "shadow gap", 0x8fff7000, 0x2008fff7000),
+    ("asan", "high shadow", 0x2008fff7000, 0x10007fff8000),
+    ("tsan", "shadow", 0x10000000000, 0x100000000000),
+    ("tsan", "meta", 0x300000000000, 0x340000000000),
+];
+
+// A mapping that alone spans this much is the runtime's reservation, not
+// anything a program maps for itself.
+#[cfg(not(target_arch = "wasm32"))]
+const RESERVATION: u64 = 1 << 40;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn anonymous(region: &MemoryRegion) -> bool {
+    region.attributes.allocated && region.file_name.is_none() && region.thread_id.is_none() && !region.attributes.shared
+}
+
+// The sanitizers whose runtime the process carries: its shared library
+// mapped, or for the static runtime clang links by default, the shadow's
+// own fingerprint. ASan maps its low shadow exactly at the shadow offset;
+// TSan reserves terabytes at once within its shadow range.
+#[cfg(not(target_arch = "wasm32"))]
+fn detect(memory_regions: &[MemoryRegion]) -> Vec<&'static str> {
+    let loaded = |library: &str| memory_regions.iter().any(|region| region.file_name.as_deref().is_some_and(|path| path.rsplit('/').next().is_some_and(|name| name.starts_with(library))));
+    let mut sanitizers = Vec::new();
+    if loaded("libasan.so") || loaded("libclang_rt.asan") || memory_regions.iter().any(|region| anonymous(region) && region.start as u64 == SHADOWS[0].2) {
+        sanitizers.push("asan");
+    }
+    let (_, _, start, end) = SHADOWS[3];
+    if loaded("libtsan.so") || loaded("libclang_rt.tsan") || memory_regions.iter().any(|region| anonymous(region) && region.start as u64 >= start && region.end as u64 <= end && region.size as u64 >= RESERVATION) {
+        sanitizers.push("tsan");
+    }
+    sanitizers
+}
+
+// Names the anonymous mappings in a detected sanitizer's shadow ranges
+// "[asan: high shadow]" and so on.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn label_shadow(memory_regions: &mut [MemoryRegion]) {
+    let sanitizers = detect(memory_regions);
+    if sanitizers.is_empty() {
+        return;
+    }
+    for region in memory_regions.iter_mut().filter(|region| anonymous(region)) {
+        let shadow = SHADOWS.iter().find(|(sanitizer, _, start, end)| sanitizers.contains(sanitizer) && *start <= region.start as u64 && (region.start as u64) < *end);
+        if let Some((sanitizer, part, _, _)) = shadow {
+            region.file_name = Some(format!("[{}: {}]", sanitizer, part));
+        }
+    }
+}
+
+pub fn is_shadow(region: &MemoryRegion) -> bool {
+    region.file_name.as_deref().is_some_and(|name| name.starts_with("[asan: ") || name.starts_with("[tsan: "))
+}
+
+// hard_rss_limit_mb and soft_rss_limit_mb from ASAN_OPTIONS or
+// TSAN_OPTIONS in the process's environment, as (variable, option, bytes).
+// Options are separated by colons, or by spaces and commas.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn rss_limits(pid: u32) -> Vec<(String, String, usize)> {
+    let Ok(environ) = std::fs::read(format!("/proc/{}/environ", pid)) else { return Vec::new() };
+    let mut limits = Vec::new();
+    for variable in environ.split(|byte| *byte == 0).map(String::from_utf8_lossy) {
+        let Some((name, value)) = variable.split_once('=') else { continue };
+        if name != "ASAN_OPTIONS" && name != "TSAN_OPTIONS" {
+            continue;
+        }
+        for option in value.split([':', ' ', ',']) {
+            let Some((key, megabytes)) = option.split_once('=') else { continue };
+            if let (true, Ok(megabytes)) = (key.ends_with("_rss_limit_mb"), megabytes.parse::<usize>()) {
+                // 0 is the default and means no limit.
+                if megabytes > 0 {
+                    limits.push((name.to_string(), key.to_string(), megabytes << 20));
+                }
+            }
+        }
+    }
+    limits
+}
@@ -0,0 +1,42 @@
+use std::str::FromStr;
+
+// How a region's byte count becomes its share of the strip height. Log is the
+// cube of log2: it keeps a 4K guard page visible next to a 128T gap while
+// still ordering them by size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Linear,
+    Log,
+    Sqrt,
+    Equal,
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Scale::Linear),
+            "log" => Ok(Scale::Log),
+            "sqrt" => Ok(Scale::Sqrt),
+            "equal" => Ok(Scale::Equal),
+            _ => Err(format!("Unknown scale: {}", s)),
+        }
+    }
+}
+
+impl Scale {
+    pub fn weight(self, bytes: usize) -> f64 {
+        if bytes == 0 {
+            return 0.0;
+        }
+        let bytes = bytes as f64;
+        match self {
+            Scale::Linear => bytes,
+            Scale::Log if bytes > 1.0 => bytes.log2().powi(3),
+            Scale::Log => 0.0,
+            Scale::Sqrt => bytes.sqrt(),
+            Scale::Equal => 1.0,
+        }
+    }
+}
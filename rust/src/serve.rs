@@ -0,0 +1,325 @@
+use crate::{report, viewer, MemoryRegion, Warnings};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const TOKEN_COOKIE: &str = "memlayout_token";
+
+pub struct Handlers<'a> {
+    // The live page's map, captured the same way as a normal run.
+    pub live_map: &'a dyn Fn() -> Result<String, String>,
+    // Any local process, for the API routes.
+    pub capture: &'a dyn Fn(u32) -> Result<(Vec<MemoryRegion>, Warnings), String>,
+    pub render_svg: &'a dyn Fn(Vec<MemoryRegion>) -> Result<String, String>,
+}
+
+struct Request {
+    path: String,
+    query: String,
+    host: Option<String>,
+    authorization: Option<String>,
+    cookie: Option<String>,
+    if_none_match: Option<String>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Response { status: "200 OK", content_type, body, headers: Vec::new() }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Response { status, content_type: "application/json", body: serde_json::json!({ "error": message }).to_string(), headers: Vec::new() }
+    }
+
+    // Back to the page without the secret in the address bar, holding the
+    // token as a cookie the page's own requests carry.
+    fn log_in(token: &str) -> Self {
+        let cookie = format!("{}={}; HttpOnly; SameSite=Strict; Path=/", TOKEN_COOKIE, token);
+        Response { status: "303 See Other", content_type: "text/plain", body: String::new(), headers: vec![("Location", "/".to_string()), ("Set-Cookie", cookie)] }
+    }
+
+    // The live page sends back the ETag of the map it shows, so an unchanged
+    // map costs neither the transfer nor the page rebuilding its DOM.
+    fn tagged(self, if_none_match: Option<&str>) -> Self {
+        if self.status != "200 OK" {
+            return self;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.body.hash(&mut hasher);
+        let etag = format!("\"{:016x}\"", hasher.finish());
+        if if_none_match == Some(etag.as_str()) {
+            return Response { status: "304 Not Modified", content_type: self.content_type, body: String::new(), headers: vec![("ETag", etag)] };
+        }
+        Response { headers: vec![("ETag", etag)], ..self }
+    }
+}
+
+#[derive(Serialize)]
+struct ProcessEntry {
+    pid: u32,
+    name: String,
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (target, String::new()),
+    };
+
+    let mut host = None;
+    let mut authorization = None;
+    let mut cookie = None;
+    let mut if_none_match = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("cookie") {
+                cookie = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("if-none-match") {
+                if_none_match = Some(value.trim().to_string());
+            }
+        }
+    }
+    Ok(Request { path, query, host, authorization, cookie, if_none_match })
+}
+
+// Who may use the server, settled once when it starts listening.
+struct Access {
+    token: Option<String>,
+    // The Host headers the server answers to, so a page whose name was
+    // rebound to this address can't read from it. None when listening on
+    // every address, where the token keeps others out instead.
+    hosts: Option<Vec<String>>,
+    // A one-time code in the URL --open hands the browser, swapped for the
+    // token cookie on first use so the token itself is never in a URL.
+    login: Option<String>,
+}
+
+impl Access {
+    fn new(addr: SocketAddr, token: Option<&str>) -> Result<Self, String> {
+        if token.is_none() && !addr.ip().is_loopback() {
+            return Err(format!("Listening on {} needs a token (--token or $MEMLAYOUT_API_TOKEN)", addr));
+        }
+        let hosts = match addr.ip() {
+            ip if ip.is_unspecified() => None,
+            ip if ip.is_loopback() => Some(vec![addr.to_string(), format!("localhost:{}", addr.port())]),
+            _ => Some(vec![addr.to_string()]),
+        };
+        Ok(Access { token: token.map(str::to_string), hosts, login: None })
+    }
+
+    fn known_host(&self, request: &Request) -> bool {
+        match (&self.hosts, request.host.as_deref()) {
+            (None, _) => true,
+            (Some(hosts), Some(host)) => hosts.iter().any(|known| known.eq_ignore_ascii_case(host)),
+            (Some(_), None) => false,
+        }
+    }
+
+    // Scripts and API clients send the token as a bearer token; the page
+    // has it as a cookie.
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = self.token.as_deref() else { return true };
+        let bearer = request.authorization.as_deref().and_then(|value| value.strip_prefix("Bearer "));
+        let cookie = request.cookie.as_deref().and_then(|cookies| cookies.split(';').find_map(|pair| pair.trim().strip_prefix(TOKEN_COOKIE)?.strip_prefix('=')));
+        bearer.is_some_and(|bearer| same_secret(bearer, token)) || cookie.is_some_and(|cookie| same_secret(cookie, token))
+    }
+
+    // The page logs in at / with ?login= from --open, or with ?token=
+    // typed by someone given the token.
+    fn log_in(&mut self, request: &Request) -> Option<Response> {
+        let token = self.token.as_deref()?;
+        if request.path != "/" {
+            return None;
+        }
+        let param = |name: &str| request.query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='));
+        let by_code = matches!((param("login"), self.login.as_deref()), (Some(given), Some(code)) if same_secret(given, code));
+        if by_code {
+            self.login = None;
+        }
+        (by_code || param("token").is_some_and(|given| same_secret(given, token))).then(|| Response::log_in(token))
+    }
+}
+
+// Takes as long whichever byte differs, so the response time doesn't
+// reveal how much of a guess was right.
+fn same_secret(given: &str, secret: &str) -> bool {
+    given.len() == secret.len() && given.bytes().zip(secret.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn login_code() -> std::io::Result<String> {
+    let mut bytes = [0u8; 16];
+    fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn list_pids() -> Vec<ProcessEntry> {
+    let mut entries: Vec<ProcessEntry> = fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .map(|pid| ProcessEntry {
+            pid,
+            name: fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default().trim().to_string(),
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.pid);
+    entries
+}
+
+fn page(pid: u32, refresh: Duration) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>memlayout: pid {pid}</title>
+<style>body {{ font-family: sans-serif; margin: 1em; }} #updated {{ color: #666; }}</style>
+</head>
+<body>
+<h3>Memory map of pid {pid} <span id="updated"></span></h3>
+<div id="map"></div>
+<script>
+let etag = null;
+async function refresh() {{
+  const headers = etag ? {{ "If-None-Match": etag }} : {{}};
+  const response = await fetch("/map.svg", {{ cache: "no-store", headers }});
+  if (response.status === 304) {{
+    document.getElementById("updated").textContent = "unchanged at " + new Date().toLocaleTimeString();
+  }} else if (response.ok) {{
+    document.getElementById("map").innerHTML = await response.text();
+    etag = response.headers.get("ETag");
+    document.getElementById("updated").textContent = "updated " + new Date().toLocaleTimeString();
+  }} else {{
+    document.getElementById("updated").textContent = "capture failed: " + response.status;
+  }}
+}}
+refresh();
+setInterval(refresh, {interval});
+</script>
+</body>
+</html>
+"#,
+        pid = pid,
+        interval = refresh.as_millis()
+    )
+}
+
+fn route(request: &Request, pid: u32, refresh: Duration, handlers: &Handlers) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let rendered = |result: Result<String, String>, content_type: &'static str| match result {
+        Ok(body) => Response::ok(content_type, body),
+        Err(e) => Response::error("500 Internal Server Error", &e),
+    };
+    match segments.as_slice() {
+        [""] => Response::ok("text/html; charset=utf-8", page(pid, refresh)),
+        ["map.svg"] => rendered((handlers.live_map)(), "image/svg+xml").tagged(request.if_none_match.as_deref()),
+        ["pids"] => rendered(serde_json::to_string(&list_pids()).map_err(|e| e.to_string()), "application/json"),
+        ["pid", id, resource] => {
+            let Ok(id) = id.parse::<u32>() else {
+                return Response::error("400 Bad Request", "Invalid pid");
+            };
+            let (memory_regions, warnings) = match (handlers.capture)(id) {
+                Ok(captured) => captured,
+                Err(e) => return Response::error("404 Not Found", &e),
+            };
+            match *resource {
+                "regions" => rendered(serde_json::to_string(&report::export(Some(id), &memory_regions, &warnings)).map_err(|e| e.to_string()), "application/json"),
+                "render.svg" => rendered((handlers.render_svg)(memory_regions), "image/svg+xml"),
+                _ => Response::error("404 Not Found", "Unknown resource"),
+            }
+        }
+        _ => Response::error("404 Not Found", "Unknown route"),
+    }
+}
+
+fn respond(mut stream: TcpStream, pid: u32, refresh: Duration, access: &mut Access, handlers: &Handlers) -> std::io::Result<()> {
+    let started = Instant::now();
+    // A client that connects and then says nothing would otherwise hold up
+    // every request after it.
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = read_request(&stream)?;
+    let response = if !access.known_host(&request) {
+        Response::error("403 Forbidden", "Unexpected Host header")
+    } else if let Some(response) = access.log_in(&request) {
+        response
+    } else if access.authorized(&request) {
+        route(&request, pid, refresh, handlers)
+    } else {
+        Response::error("401 Unauthorized", "Missing or wrong bearer token")
+    };
+    let headers: String = response.headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        headers
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(response.body.as_bytes())?;
+    tracing::debug!(path = request.path, status = response.status, bytes = response.body.len(), elapsed_ms = started.elapsed().as_millis() as u64, "served");
+    Ok(())
+}
+
+// Every response is produced on request, one connection at a time on this
+// thread: the live page polls /map.svg every `refresh`, and the API routes
+// capture the requested process afresh. A connection gets REQUEST_TIMEOUT
+// to send its request and take the response.
+//
+// Off loopback a token is required; on it, requests must name the address
+// listened on so a web page can't reach the server by DNS rebinding.
+pub fn serve(addr: &str, pid: u32, refresh: Duration, token: Option<&str>, open: bool, handlers: Handlers) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    let mut access = Access::new(local, token)?;
+    let url = format!("http://{}/", local);
+    tracing::info!("serving pid {} on {}", pid, url);
+    if open {
+        match token {
+            Some(_) => {
+                let code = login_code()?;
+                let login = format!("{}?login={}", url, code);
+                access.login = Some(code);
+                viewer::open(&login);
+            }
+            None => viewer::open(&url),
+        }
+    }
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("serve: accept failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = respond(stream, pid, refresh, &mut access, &handlers) {
+            tracing::warn!("serve: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use crate::overview::ProcessSample;
+use crate::{fit_text, format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+const ROW_HEIGHT: i32 = 18;
+const LABEL_WIDTH: i32 = 380;
+const WIDTH: i32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Posix,
+    SysV,
+}
+
+pub struct Segment {
+    pub kind: Kind,
+    pub name: String,
+    // tmpfs files by path and inode; System V segments by shmid, which
+    // their mappings show as the inode of "/SYSV<key> (deleted)".
+    pub inode: u64,
+    pub size: usize,
+    // Pages actually backing the segment, when the kernel says.
+    pub resident: Option<usize>,
+    // Unlinked POSIX objects are only found through their mappings.
+    pub unlinked: bool,
+    pub mappers: Vec<(u32, String, usize)>,
+}
+
+impl Segment {
+    // Nobody maps it, yet it holds memory until removed: the shape of a
+    // leak that outlives the process which made it.
+    pub fn orphaned(&self) -> bool {
+        self.mappers.is_empty()
+    }
+}
+
+fn posix_segments(dir: &Path) -> Vec<Segment> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut segments = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else { continue };
+        if metadata.is_dir() {
+            segments.extend(posix_segments(&entry.path()));
+        } else if metadata.is_file() {
+            segments.push(Segment {
+                kind: Kind::Posix,
+                name: entry.path().to_string_lossy().into_owned(),
+                inode: metadata.ino(),
+                size: metadata.len() as usize,
+                resident: Some(metadata.blocks() as usize * 512),
+                unlinked: false,
+                mappers: Vec::new(),
+            });
+        }
+    }
+    segments
+}
+
+// Columns are found by the header names, since rss and swap were only added
+// in later kernels.
+fn sysv_segments(text: &str) -> Vec<Segment> {
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+    let column = |name: &str| header.iter().position(|column| *column == name);
+    let (Some(key), Some(shmid), Some(size)) = (column("key"), column("shmid"), column("size")) else { return Vec::new() };
+    let rss = column("rss");
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let key: i64 = fields.get(key)?.parse().ok()?;
+            Some(Segment {
+                kind: Kind::SysV,
+                name: format!("SYSV{:08x}", key as u32),
+                inode: fields.get(shmid)?.parse().ok()?,
+                size: fields.get(size)?.parse().ok()?,
+                resident: rss.and_then(|rss| fields.get(rss)?.parse().ok()),
+                unlinked: false,
+                mappers: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+// Every object in /dev/shm and every System V segment, with the processes
+// that map each. Processes only show up if their maps are readable.
+pub fn segments(processes: &[ProcessSample]) -> Vec<Segment> {
+    let mut segments = posix_segments(Path::new("/dev/shm"));
+    segments.extend(sysv_segments(&fs::read_to_string("/proc/sysvipc/shm").unwrap_or_default()));
+    for process in processes {
+        for region in &process.memory_regions {
+            let Some(path) = region.file_name.as_deref() else { continue };
+            let kind = if path.starts_with("/SYSV") {
+                Kind::SysV
+            } else if path.starts_with("/dev/shm/") {
+                Kind::Posix
+            } else {
+                continue;
+            };
+            let index = match segments.iter().position(|segment| segment.kind == kind && segment.inode == region.inode) {
+                Some(index) => index,
+                None if kind == Kind::Posix => {
+                    let name = path.strip_suffix(" (deleted)").unwrap_or(path).to_string();
+                    segments.push(Segment { kind, name, inode: region.inode, size: 0, resident: None, unlinked: true, mappers: Vec::new() });
+                    segments.len() - 1
+                }
+                // Removed System V segments stay listed until detached.
+                None => continue,
+            };
+            let segment = &mut segments[index];
+            if segment.unlinked {
+                segment.size = segment.size.max(region.offset + region.size);
+            }
+            match segment.mappers.iter_mut().find(|(pid, _, _)| *pid == process.pid) {
+                Some((_, _, mapped)) => *mapped += region.size,
+                None => segment.mappers.push((process.pid, process.name.clone(), region.size)),
+            }
+        }
+    }
+    segments.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    segments
+}
+
+// One row per segment, labeled with the processes mapping it: the bar is
+// its size on a common scale, filled as far as it is resident.
+pub fn draw_segments(segments: &[Segment], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let width = px(WIDTH) as u32;
+    let height = px(segments.len() as i32 * ROW_HEIGHT + 40) as u32;
+    let root = BitMapBackend::new(path, (width, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let total: usize = segments.iter().map(|segment| segment.size).sum();
+    let title = format!("{} shared memory segments, {}", segments.len(), format_size(total));
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let max = segments.iter().map(|segment| segment.size).max().unwrap_or(1).max(1) as f64;
+    let bar_space = (px(WIDTH - LABEL_WIDTH - 10)) as f64;
+    for (i, segment) in segments.iter().enumerate() {
+        let y = px(32 + i as i32 * ROW_HEIGHT);
+        let mappers: Vec<String> = segment.mappers.iter().map(|(pid, name, _)| format!("{} {}", pid, name)).collect();
+        let mappers = if segment.orphaned() { "mapped by no process".to_string() } else { mappers.join(", ") };
+        let label = format!("{} ({}): {}", segment.name, format_size(segment.size), mappers);
+        if let Some(label) = fit_text(&root, &label, &font, px(LABEL_WIDTH - 15))? {
+            root.draw(&Text::new(label, (px(10), y + px(3)), font.color(&ink)))?;
+        }
+
+        let color = match (segment.kind, segment.orphaned()) {
+            (_, true) => RGBColor(220, 60, 40),
+            (Kind::Posix, false) => RGBColor(230, 90, 160),
+            (Kind::SysV, false) => RGBColor(90, 170, 230),
+        };
+        let x0 = px(LABEL_WIDTH);
+        let to_x = |bytes: usize| x0 + (bytes as f64 / max * bar_space).round() as i32;
+        let (x1, bottom) = (to_x(segment.size).max(x0 + px(1)), y + px(ROW_HEIGHT - 4));
+        if let Some(resident) = segment.resident {
+            root.draw(&Rectangle::new([(x0, y), (to_x(resident.min(segment.size)), bottom)], color.filled()))?;
+        }
+        root.draw(&Rectangle::new([(x0, y), (x1, bottom)], color.stroke_width(px(1) as u32)))?;
+    }
+    root.present()?;
+    Ok(())
+}
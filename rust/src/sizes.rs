@@ -0,0 +1,100 @@
+use crate::{format_size, MemoryRegion};
+use plotters::prelude::*;
+
+// Regions from `low` up to twice that.
+#[derive(Clone)]
+pub struct Bucket {
+    pub low: usize,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+// "64K" for a power of two, short enough for an axis.
+fn short(bytes: usize) -> String {
+    match bytes.trailing_zeros() {
+        40.. => format!("{}T", bytes >> 40),
+        30..=39 => format!("{}G", bytes >> 30),
+        20..=29 => format!("{}M", bytes >> 20),
+        10..=19 => format!("{}K", bytes >> 10),
+        _ => bytes.to_string(),
+    }
+}
+
+impl Bucket {
+    pub fn label(&self) -> String {
+        short(self.low)
+    }
+}
+
+// Mapped regions by size in power-of-two buckets, from the smallest
+// region's to the largest's, with none left out between them so the
+// shape of the distribution shows.
+pub fn histogram(memory_regions: &[MemoryRegion]) -> Vec<Bucket> {
+    let sizes: Vec<usize> = memory_regions.iter().filter(|region| region.attributes.allocated && region.size > 0).map(|region| region.size).collect();
+    let (Some(smallest), Some(largest)) = (sizes.iter().min(), sizes.iter().max()) else { return Vec::new() };
+    let (first, last) = (smallest.ilog2(), largest.ilog2());
+    let mut buckets: Vec<Bucket> = (first..=last).map(|shift| Bucket { low: 1 << shift, count: 0, bytes: 0 }).collect();
+    for size in sizes {
+        let bucket = &mut buckets[(size.ilog2() - first) as usize];
+        bucket.count += 1;
+        bucket.bytes += size;
+    }
+    buckets
+}
+
+pub fn print(buckets: &[Bucket]) {
+    let total: usize = buckets.iter().map(|bucket| bucket.bytes).sum();
+    let mut cumulative = 0;
+    println!("Region sizes:");
+    for bucket in buckets {
+        cumulative += bucket.bytes;
+        println!("  >= {:<5} {:>7} {:>12} {:>12} {:>5.1}%", bucket.label(), bucket.count, format_size(bucket.bytes), format_size(cumulative), 100.0 * cumulative as f64 / total.max(1) as f64);
+    }
+}
+
+// Counts as bars, and the bytes in regions up to each bucket as a line
+// across them, on a scale of its own.
+pub fn draw_panel(buckets: &[Bucket], width: u32, height: u32) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    {
+        let root = BitMapBackend::with_buffer(&mut imgbuf, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let (chart_area, _) = root.split_vertically(320.min(height));
+
+        let max_count = buckets.iter().map(|bucket| bucket.count).max().unwrap_or(0).max(1);
+        let mut chart = ChartBuilder::on(&chart_area)
+            .caption("Region sizes", ("sans-serif", 13))
+            .margin(8)
+            .x_label_area_size(25)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0..max_count + 1, (0..buckets.len()).into_segmented())?;
+
+        chart
+            .configure_mesh()
+            .disable_y_mesh()
+            .y_label_formatter(&|value| match value {
+                SegmentValue::CenterOf(i) => buckets.get(*i).map(Bucket::label).unwrap_or_default(),
+                _ => String::new(),
+            })
+            .draw()?;
+
+        chart.draw_series(buckets.iter().enumerate().map(|(i, bucket)| Rectangle::new([(0, SegmentValue::Exact(i)), (bucket.count, SegmentValue::Exact(i + 1))], BLUE.mix(0.7).filled())))?;
+
+        let total: usize = buckets.iter().map(|bucket| bucket.bytes).sum();
+        let mut cumulative = 0;
+        let line: Vec<(usize, SegmentValue<usize>)> = buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                cumulative += bucket.bytes;
+                ((max_count as f64 * cumulative as f64 / total.max(1) as f64).round() as usize, SegmentValue::CenterOf(i))
+            })
+            .collect();
+        chart.draw_series(LineSeries::new(line, RED.stroke_width(2)))?;
+
+        let font = ("sans-serif", 11).into_font();
+        root.draw(&Text::new(format!("regions: {}", buckets.iter().map(|bucket| bucket.count).sum::<usize>()), (10, 325), font.clone()))?;
+        root.draw(&Text::new("red: cumulative bytes".to_string(), (10, 340), font.color(&RED)))?;
+    }
+    Ok(imgbuf)
+}
@@ -0,0 +1,233 @@
+use crate::{fit_text, format_count, format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const CHART_WIDTH: i32 = 1000;
+const CHART_HEIGHT: i32 = 700;
+
+pub struct Cache {
+    pub name: String,
+    pub active_objects: usize,
+    pub objects: usize,
+    pub object_size: usize,
+    // Bytes of the slabs backing the cache.
+    pub size: usize,
+}
+
+impl Cache {
+    // How much of the slabs live objects fill; the rest is free objects
+    // and per-slab waste.
+    pub fn utilization(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        (self.active_objects * self.object_size) as f64 / self.size as f64
+    }
+}
+
+// slabinfo 2.x: name, active objects, objects, object size, objects and
+// pages per slab, then the tunables and slabdata groups after colons.
+pub fn parse_slabinfo(text: &str, page_size: usize) -> Result<Vec<Cache>, String> {
+    if !text.starts_with("slabinfo - version: 2.") {
+        return Err("Not slabinfo version 2".to_string());
+    }
+    let mut caches = Vec::new();
+    for line in text.lines().skip(1).filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let number = |index: usize| fields.get(index).and_then(|field| field.parse::<usize>().ok());
+        let Some(data) = fields.iter().position(|field| *field == "slabdata") else { continue };
+        let (Some(active_objects), Some(objects), Some(object_size), Some(pages_per_slab), Some(slabs)) = (number(1), number(2), number(3), number(5), number(data + 2)) else { continue };
+        caches.push(Cache { name: fields[0].to_string(), active_objects, objects, object_size, size: slabs * pages_per_slab * page_size });
+    }
+    Ok(caches)
+}
+
+// SLUB's /sys/kernel/slab, readable where slabinfo isn't. Merged caches
+// are symlinks to one named like ":a-0000104", which takes the name of
+// the first of them as slabinfo does. Without "objects" the cache reads
+// as full.
+pub fn read_sysfs(dir: &Path, page_size: usize) -> Result<Vec<Cache>, String> {
+    let entries: Vec<fs::DirEntry> = fs::read_dir(dir).map_err(|e| format!("Unable to read {}: {}", dir.display(), e))?.flatten().collect();
+    let mut aliases: Vec<(String, String)> = entries
+        .iter()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_symlink()))
+        .filter_map(|entry| Some((fs::read_link(entry.path()).ok()?.file_name()?.to_string_lossy().into_owned(), entry.file_name().to_string_lossy().into_owned())))
+        .collect();
+    aliases.sort();
+    let mut caches = Vec::new();
+    for entry in &entries {
+        if entry.file_type().map_or(true, |kind| kind.is_symlink()) {
+            continue;
+        }
+        let path = entry.path();
+        let value = |file: &str| fs::read_to_string(path.join(file)).ok().and_then(|text| text.split_whitespace().next()?.parse::<usize>().ok());
+        let (Some(object_size), Some(order), Some(slabs)) = (value("object_size"), value("order"), value("slabs")) else { continue };
+        let objects = value("total_objects").unwrap_or(0);
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        if let Some((_, alias)) = aliases.iter().find(|(target, _)| *target == name) {
+            name = alias.clone();
+        }
+        caches.push(Cache {
+            name,
+            active_objects: value("objects").unwrap_or(objects),
+            objects,
+            object_size,
+            size: slabs * (page_size << order),
+        });
+    }
+    if caches.is_empty() {
+        return Err(format!("{} lists no slab caches", dir.display()));
+    }
+    Ok(caches)
+}
+
+// A slabinfo file, or a /sys/kernel/slab directory; /proc/slabinfo falls
+// back to /sys/kernel/slab when it needs root and isn't root.
+pub fn read(path: &str, page_size: usize) -> Result<Vec<Cache>, String> {
+    let mut caches = if Path::new(path).is_dir() {
+        read_sysfs(Path::new(path), page_size)?
+    } else {
+        match fs::read_to_string(path) {
+            Ok(text) => parse_slabinfo(&text, page_size)?,
+            Err(e) if path == "/proc/slabinfo" => read_sysfs(Path::new("/sys/kernel/slab"), page_size).map_err(|_| format!("Unable to read {}: {}", path, e))?,
+            Err(e) => return Err(format!("Unable to read {}: {}", path, e)),
+        }
+    };
+    caches.retain(|cache| cache.size > 0);
+    caches.sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+    Ok(caches)
+}
+
+pub fn print(caches: &[Cache], limit: usize) {
+    println!("{:<28} {:>10} {:>12} {:>12} {:>8} {:>6}", "cache", "size", "active", "objects", "objsize", "used");
+    for cache in caches.iter().take(limit) {
+        println!("{:<28} {:>10} {:>12} {:>12} {:>8} {:>5.1}%", cache.name, format_size(cache.size), format_count(cache.active_objects), format_count(cache.objects), cache.object_size, cache.utilization() * 100.0);
+    }
+    let (total, live): (usize, f64) = caches.iter().fold((0, 0.0), |(total, live), cache| (total + cache.size, live + cache.utilization() * cache.size as f64));
+    println!("{} caches, {} in slabs, {:.1}% used by live objects", caches.len(), format_size(total), live * 100.0 / total.max(1) as f64);
+}
+
+// Squarified treemap (Bruls, Huizing and van Wijk): rows laid along the
+// short side, adding items while that makes the row's worst aspect ratio
+// better. Sizes are sorted largest first.
+fn squarify(sizes: &[f64], (x, y, width, height): (f64, f64, f64, f64)) -> Vec<(f64, f64, f64, f64)> {
+    let mut rectangles = Vec::new();
+    let (mut x, mut y, mut width, mut height) = (x, y, width, height);
+    let total: f64 = sizes.iter().sum();
+    let scale = width * height / total.max(f64::MIN_POSITIVE);
+    let areas: Vec<f64> = sizes.iter().map(|size| size * scale).collect();
+    let worst = |row: &[f64], side: f64| {
+        let sum: f64 = row.iter().sum();
+        let (largest, smallest) = (row.iter().cloned().fold(0.0, f64::max), row.iter().cloned().fold(f64::MAX, f64::min));
+        f64::max(side * side * largest / (sum * sum), sum * sum / (side * side * smallest))
+    };
+    let mut start = 0;
+    while start < areas.len() {
+        let side = width.min(height);
+        let mut end = start + 1;
+        while end < areas.len() && worst(&areas[start..end + 1], side) <= worst(&areas[start..end], side) {
+            end += 1;
+        }
+        let row = &areas[start..end];
+        let thickness = row.iter().sum::<f64>() / side;
+        let mut offset = 0.0;
+        for area in row {
+            let length = area / thickness;
+            if width >= height {
+                rectangles.push((x, y + offset, thickness, length));
+            } else {
+                rectangles.push((x + offset, y, length, thickness));
+            }
+            offset += length;
+        }
+        if width >= height {
+            x += thickness;
+            width -= thickness;
+        } else {
+            y += thickness;
+            height -= thickness;
+        }
+        start = end;
+    }
+    rectangles
+}
+
+// Red for caches mostly free, through yellow, to green for full ones.
+fn utilization_color(utilization: f64) -> RGBColor {
+    let u = utilization.clamp(0.0, 1.0);
+    if u < 0.5 {
+        RGBColor(220, (60.0 + u * 2.0 * 160.0) as u8, 60)
+    } else {
+        RGBColor((220.0 - (u - 0.5) * 2.0 * 160.0) as u8, 220, 60)
+    }
+}
+
+// Each cache a rectangle by the size of its slabs, colored by how full
+// its objects keep them; labels where they fit.
+pub fn draw_caches(caches: &[Cache], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let (width, height) = (px(CHART_WIDTH), px(CHART_HEIGHT + 60));
+    let root = BitMapBackend::new(path, (width as u32, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let total: usize = caches.iter().map(|cache| cache.size).sum();
+    let title = format!("slab caches: {} in {} caches, area by size, colored by use", format_size(total), caches.len());
+    root.draw(&Text::new(title, (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let sizes: Vec<f64> = caches.iter().map(|cache| cache.size as f64).collect();
+    let bounds = (px(10) as f64, px(32) as f64, (width - px(20)) as f64, px(CHART_HEIGHT) as f64);
+    for (cache, (x, y, w, h)) in caches.iter().zip(squarify(&sizes, bounds)) {
+        let (x0, y0, x1, y1) = (x.round() as i32, y.round() as i32, (x + w).round() as i32, (y + h).round() as i32);
+        root.draw(&Rectangle::new([(x0, y0), (x1, y1)], utilization_color(cache.utilization()).filled()))?;
+        root.draw(&Rectangle::new([(x0, y0), (x1, y1)], BLACK.mix(0.5)))?;
+        if y1 - y0 >= px(26) {
+            let lines = [cache.name.clone(), format!("{} {:.0}%", format_size(cache.size), cache.utilization() * 100.0)];
+            for (index, line) in lines.into_iter().enumerate() {
+                if let Some(line) = fit_text(&root, &line, &font, x1 - x0 - px(6))? {
+                    root.draw(&Text::new(line, (x0 + px(3), y0 + px(3 + index as i32 * 12)), font.color(&BLACK)))?;
+                }
+            }
+        }
+    }
+
+    let legend_y = px(CHART_HEIGHT + 40);
+    for (index, label) in ["0%", "25%", "50%", "75%", "100% of slabs in use"].iter().enumerate() {
+        let x = px(10 + index as i32 * 80);
+        root.draw(&Rectangle::new([(x, legend_y), (x + px(10), legend_y + px(10))], utilization_color(index as f64 / 4.0).filled()))?;
+        root.draw(&Text::new(*label, (x + px(14), legend_y - px(1)), font.color(&ink)))?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLABINFO: &str = "\
+slabinfo - version: 2.1
+# name            <active_objs> <num_objs> <objsize> <objperslab> <pagesperslab> : tunables <limit> <batchcount> <sharedfactor> : slabdata <active_slabs> <num_slabs> <sharedavail>
+ext4_inode_cache   41580  42120   1080   30    8 : tunables    0    0    0 : slabdata   1404   1404      0
+dentry            120204 131040    192   21    1 : tunables    0    0    0 : slabdata   6240   6240      0
+kmalloc-8k           120    124   8192    4    8 : tunables    0    0    0 : slabdata     31     31      0
+";
+
+    #[test]
+    fn parses_slabinfo() {
+        let caches = parse_slabinfo(SLABINFO, 4096).unwrap();
+        assert_eq!(caches.iter().map(|cache| cache.name.as_str()).collect::<Vec<_>>(), ["ext4_inode_cache", "dentry", "kmalloc-8k"]);
+        let dentry = &caches[1];
+        assert_eq!((dentry.active_objects, dentry.objects, dentry.object_size), (120204, 131040, 192));
+        assert_eq!(dentry.size, 6240 * 4096);
+        // Eight page slabs.
+        assert_eq!(caches[0].size, 1404 * 8 * 4096);
+        assert!((dentry.utilization() - 120204.0 * 192.0 / (6240.0 * 4096.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(parse_slabinfo("slabinfo - version: 1.1\n", 4096).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validate::Warnings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::MemoryRegion;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufRead;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SmapsInfo {
+    pub rss: usize,
+    pub pss: usize,
+    pub shared_dirty: usize,
+    pub private_dirty: usize,
+    pub swap: usize,
+    pub swap_pss: usize,
+    pub anon_huge_pages: usize,
+    pub shmem_pmd_mapped: usize,
+    pub file_pmd_mapped: usize,
+    pub shared_hugetlb: usize,
+    pub private_hugetlb: usize,
+    // Snapshots taken before these were read have neither.
+    #[serde(default)]
+    pub locked: usize,
+    // The two letter VmFlags codes, e.g. "rd", "wr", "dd".
+    #[serde(default)]
+    pub vm_flags: Vec<String>,
+}
+
+// Every VmFlags code the kernel prints, with what it means.
+#[cfg(not(target_arch = "wasm32"))]
+pub const VM_FLAGS: [(&str, &str); 35] = [
+    ("rd", "readable"),
+    ("wr", "writeable"),
+    ("ex", "executable"),
+    ("sh", "shared"),
+    ("mr", "may read"),
+    ("mw", "may write"),
+    ("me", "may execute"),
+    ("ms", "may share"),
+    ("gd", "stack segment grows down"),
+    ("pf", "pure PFN range"),
+    ("dw", "disabled write to the mapped file"),
+    ("lo", "pages are locked in memory"),
+    ("io", "memory mapped I/O area"),
+    ("sr", "sequential read advise provided"),
+    ("rr", "random read advise provided"),
+    ("dc", "do not copy area on fork"),
+    ("de", "do not expand area on remapping"),
+    ("ac", "area is accountable"),
+    ("nr", "swap space is not reserved for the area"),
+    ("ht", "area uses huge tlb pages"),
+    ("sf", "perform synchronous page faults"),
+    ("ar", "architecture specific flag"),
+    ("wf", "wipe on fork"),
+    ("dd", "do not include area into core dump"),
+    ("sd", "soft-dirty flag"),
+    ("mm", "mixed map area"),
+    ("hg", "huge page advise flag"),
+    ("nh", "no-huge page advise flag"),
+    ("mg", "mergeable advise flag"),
+    ("bt", "arm64 BTI guarded page"),
+    ("mt", "arm64 MTE allocation tags are enabled"),
+    ("um", "userfaultfd missing pages tracking"),
+    ("uw", "userfaultfd wprotect pages tracking"),
+    ("ss", "shadow stack page"),
+    ("sl", "sealed"),
+];
+
+// Permissions, the may-* mirrors of them and accounting are on nearly
+// every region and say nothing the perms column doesn't.
+const ROUTINE_FLAGS: [&str; 9] = ["rd", "wr", "ex", "sh", "mr", "mw", "me", "ms", "ac"];
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn describe_flag(code: &str) -> Option<&'static str> {
+    VM_FLAGS.iter().find(|(known, _)| *known == code).map(|(_, description)| *description)
+}
+
+impl SmapsInfo {
+    pub fn huge_page_bytes(&self) -> usize {
+        self.anon_huge_pages + self.shmem_pmd_mapped + self.file_pmd_mapped + self.shared_hugetlb + self.private_hugetlb
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.has_flag("lo") || self.locked > 0
+    }
+
+    pub fn has_flag(&self, code: &str) -> bool {
+        self.vm_flags.iter().any(|flag| flag == code)
+    }
+
+    pub fn interesting_flags(&self) -> Vec<&str> {
+        self.vm_flags.iter().map(String::as_str).filter(|flag| !ROUTINE_FLAGS.contains(flag)).collect()
+    }
+
+    pub fn fields(&self) -> [(&'static str, usize); 12] {
+        [
+            ("rss", self.rss),
+            ("pss", self.pss),
+            ("shared_dirty", self.shared_dirty),
+            ("private_dirty", self.private_dirty),
+            ("swap", self.swap),
+            ("swap_pss", self.swap_pss),
+            ("anon_huge_pages", self.anon_huge_pages),
+            ("shmem_pmd_mapped", self.shmem_pmd_mapped),
+            ("file_pmd_mapped", self.file_pmd_mapped),
+            ("shared_hugetlb", self.shared_hugetlb),
+            ("private_hugetlb", self.private_hugetlb),
+            ("locked", self.locked),
+        ]
+    }
+
+    pub fn add(&mut self, other: &SmapsInfo) {
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared_dirty += other.shared_dirty;
+        self.private_dirty += other.private_dirty;
+        self.swap += other.swap;
+        self.swap_pss += other.swap_pss;
+        self.anon_huge_pages += other.anon_huge_pages;
+        self.shmem_pmd_mapped += other.shmem_pmd_mapped;
+        self.file_pmd_mapped += other.file_pmd_mapped;
+        self.shared_hugetlb += other.shared_hugetlb;
+        self.private_hugetlb += other.private_hugetlb;
+        self.locked += other.locked;
+        for flag in &other.vm_flags {
+            if !self.has_flag(flag) {
+                self.vm_flags.push(flag.clone());
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_field(&mut self, key: &str, value: &str) {
+        if key == "VmFlags" {
+            self.vm_flags = value.split_whitespace().map(str::to_string).collect();
+            return;
+        }
+        let bytes = parse_kb(value);
+        match key {
+            "Rss" => self.rss = bytes,
+            "Pss" => self.pss = bytes,
+            "Shared_Dirty" => self.shared_dirty = bytes,
+            "Private_Dirty" => self.private_dirty = bytes,
+            "Swap" => self.swap = bytes,
+            "SwapPss" => self.swap_pss = bytes,
+            "AnonHugePages" => self.anon_huge_pages = bytes,
+            "ShmemPmdMapped" => self.shmem_pmd_mapped = bytes,
+            "FilePmdMapped" => self.file_pmd_mapped = bytes,
+            "Shared_Hugetlb" => self.shared_hugetlb = bytes,
+            "Private_Hugetlb" => self.private_hugetlb = bytes,
+            "Locked" => self.locked = bytes,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_kb(value: &str) -> usize {
+    let number = value.split_whitespace().next().unwrap_or("0");
+    number.parse::<usize>().unwrap_or(0) * 1024
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_smaps<R: BufRead>(mut reader: R) -> (Vec<MemoryRegion>, Warnings) {
+    let mut memory_regions: Vec<MemoryRegion> = Vec::new();
+    let mut warnings = Warnings::default();
+
+    let mut line = String::new();
+    let mut number = 0;
+    while matches!(reader.read_line(&mut line), Ok(read) if read > 0) {
+        number += 1;
+        let l = line.trim_end_matches('\n');
+        let first = l.split_whitespace().next().unwrap_or("");
+        if let Some(key) = first.strip_suffix(':') {
+            if let Some(region) = memory_regions.last_mut() {
+                let value = l[l.find(':').unwrap() + 1..].trim();
+                region.smaps.get_or_insert_with(SmapsInfo::default).set_field(key, value);
+            }
+        } else {
+            match l.parse::<MemoryRegion>() {
+                Ok(mut region) => {
+                    region.smaps = Some(SmapsInfo::default());
+                    memory_regions.push(region);
+                }
+                Err(_) if l.trim().is_empty() => {}
+                Err(e) => warnings.skip("smaps", number, &e, l.trim_end()),
+            }
+        }
+        line.clear();
+    }
+
+    (memory_regions, warnings)
+}
+
+// RLIMIT_MEMLOCK from /proc/PID/limits, whose line reads
+// "Max locked memory         8388608              8388608              bytes".
+// None when the soft limit is unlimited.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn memlock_limit(limits: &str) -> Result<Option<usize>, String> {
+    let line = limits.lines().find(|line| line.starts_with("Max locked memory")).ok_or("No locked memory limit in limits")?;
+    match line["Max locked memory".len()..].split_whitespace().next() {
+        Some("unlimited") => Ok(None),
+        Some(soft) => soft.parse().map(Some).map_err(|_| format!("Invalid locked memory limit: {}", soft)),
+        None => Err("No locked memory limit in limits".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMAPS: &str = "\
+55d0c0a00000-55d0c0a21000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+Rss:                  40 kB
+Pss:                  36 kB
+Shared_Dirty:          8 kB
+Private_Dirty:        32 kB
+Swap:                 12 kB
+SwapPss:              12 kB
+AnonHugePages:         0 kB
+Locked:                0 kB
+VmFlags: rd wr mr mw me ac
+7f3a1c000000-7f3a1c021000 r-xp 00002000 fd:01 1835015                    /usr/lib/libc.so.6
+Rss:                 100 kB
+Pss:                  20 kB
+Locked:                4 kB
+VmFlags: rd ex mr mw me lo
+";
+
+    #[test]
+    fn reads_fields_into_the_region_above() {
+        let (memory_regions, warnings) = parse_smaps(SMAPS.as_bytes());
+        assert_eq!(warnings.skipped_lines, 0);
+        assert_eq!(memory_regions.len(), 2);
+        let heap = memory_regions[0].smaps.as_ref().unwrap();
+        assert_eq!((heap.rss, heap.pss, heap.shared_dirty, heap.private_dirty), (40 << 10, 36 << 10, 8 << 10, 32 << 10));
+        assert_eq!((heap.swap, heap.swap_pss), (12 << 10, 12 << 10));
+        assert_eq!(heap.vm_flags, ["rd", "wr", "mr", "mw", "me", "ac"]);
+        assert!(heap.interesting_flags().is_empty());
+        let libc = memory_regions[1].smaps.as_ref().unwrap();
+        assert_eq!((libc.rss, libc.pss, libc.locked), (100 << 10, 20 << 10, 4 << 10));
+        assert!(libc.is_locked());
+        assert_eq!(libc.interesting_flags(), ["lo"]);
+    }
+
+    #[test]
+    fn gives_regions_without_fields_empty_counts() {
+        let (memory_regions, _) = parse_smaps("1000-2000 rw-p 00000000 00:00 0\n".as_bytes());
+        assert_eq!(memory_regions[0].smaps, Some(SmapsInfo::default()));
+    }
+
+    #[test]
+    fn skips_and_counts_bad_lines() {
+        let input = format!("garbage here\n\n{}Rss: 4 kB\n", SMAPS);
+        let (memory_regions, warnings) = parse_smaps(input.as_bytes());
+        assert_eq!(memory_regions.len(), 2);
+        assert_eq!(warnings.skipped_lines, 1);
+        assert_eq!(warnings.parse_warnings[0].line, 1);
+        // A field after the last header still belongs to that region.
+        assert_eq!(memory_regions[1].smaps.as_ref().unwrap().rss, 4 << 10);
+    }
+
+    #[test]
+    fn ignores_fields_before_any_header() {
+        let (memory_regions, warnings) = parse_smaps("Rss: 4 kB\n".as_bytes());
+        assert!(memory_regions.is_empty());
+        assert_eq!(warnings.skipped_lines, 0);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::adb::AdbTarget;
+use crate::meminfo::{self, SystemMemory};
+use crate::MemoryRegion;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"MMSNAP\0\0";
+const VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub pid: u32,
+    pub timestamp: u64,
+    pub hostname: String,
+    pub kernel: String,
+    pub regions: Vec<MemoryRegion>,
+    // First bytes of readable regions by start, when taken with
+    // --hex-preview.
+    pub previews: Vec<(usize, Vec<u8>)>,
+    // The machine's /proc/meminfo when the snapshot was taken.
+    pub system: Option<SystemMemory>,
+}
+
+// Version 1 had no previews, version 2 no system memory.
+#[derive(Deserialize)]
+struct SnapshotV1 {
+    pid: u32,
+    timestamp: u64,
+    hostname: String,
+    kernel: String,
+    regions: Vec<MemoryRegion>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotV2 {
+    pid: u32,
+    timestamp: u64,
+    hostname: String,
+    kernel: String,
+    regions: Vec<MemoryRegion>,
+    previews: Vec<(usize, Vec<u8>)>,
+}
+
+pub fn host_info(adb: Option<&AdbTarget>) -> (String, String) {
+    let read = |path: &str, uname_flag: &str| {
+        let value = match adb {
+            Some(adb) => adb.shell(&["uname", uname_flag]).unwrap_or_default(),
+            None => fs::read_to_string(path).unwrap_or_default(),
+        };
+        value.trim().to_string()
+    };
+    (read("/proc/sys/kernel/hostname", "-n"), read("/proc/sys/kernel/osrelease", "-r"))
+}
+
+impl Snapshot {
+    pub fn capture(pid: u32, regions: Vec<MemoryRegion>, adb: Option<&AdbTarget>) -> Self {
+        let (hostname, kernel) = host_info(adb);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Snapshot { pid, timestamp, hostname, kernel, regions, previews: Vec::new(), system: meminfo::read(adb) }
+    }
+
+    // Magic and a version up front, then the bincode body; a reader that
+    // sees a newer version refuses it instead of misparsing, and reads the
+    // older ones it knows.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend(bincode::serialize(self)?);
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path)?;
+        if data.len() < 12 || &data[..8] != MAGIC {
+            return Err(format!("{} is not a memlayout snapshot", path).into());
+        }
+        let version = u32::from_le_bytes(data[8..12].try_into()?);
+        match version {
+            1 => {
+                let SnapshotV1 { pid, timestamp, hostname, kernel, regions } = bincode::deserialize(&data[12..])?;
+                Ok(Snapshot { pid, timestamp, hostname, kernel, regions, previews: Vec::new(), system: None })
+            }
+            2 => {
+                let SnapshotV2 { pid, timestamp, hostname, kernel, regions, previews } = bincode::deserialize(&data[12..])?;
+                Ok(Snapshot { pid, timestamp, hostname, kernel, regions, previews, system: None })
+            }
+            VERSION => Ok(bincode::deserialize(&data[12..])?),
+            _ => Err(format!("{} has snapshot version {}, expected {}", path, version, VERSION).into()),
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use crate::adb::AdbTarget;
+use crate::validate::{self, Warnings};
+use crate::{cache, capture, cortexm, espidf, gdbremote, gpu, parse_memory_regions, qemu, replay, smaps, v8heap, vmcore, wsl, MemoryRegion};
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// What a source can tell beyond the regions themselves, so callers know
+// which extras to attempt.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    // Regions carry smaps detail: RSS, swap, huge pages.
+    pub smaps: bool,
+    // Reading again gives the current state rather than the same regions.
+    pub live: bool,
+    // The regions belong to a process on this machine, whose /proc has its
+    // threads, cgroup and namespaces.
+    pub local: bool,
+}
+
+// Anything that can produce a memory map: a local process, a device, a
+// saved file, or whatever a plugin knows how to read.
+pub trait MemorySource {
+    fn name(&self) -> String;
+    fn capabilities(&self) -> Capabilities;
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String>;
+
+    // The regions with the lines that didn't parse, which sources reading
+    // maps or smaps text report.
+    fn read(&self) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+        Ok((self.regions()?, Warnings::default()))
+    }
+
+    // For the header; sources that aren't a process have none.
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+pub struct Procfs {
+    pub pid: u32,
+    pub smaps: bool,
+    // Reuse region lists parsed before while the maps are unchanged, smaps
+    // figures for at most this long.
+    pub cache: Option<Duration>,
+}
+
+impl MemorySource for Procfs {
+    fn name(&self) -> String {
+        format!("procfs:{}", self.pid)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: self.smaps, live: true, local: true }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        self.read().map(|(memory_regions, _)| memory_regions)
+    }
+
+    fn read(&self) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+        if let Some(max_age) = self.cache {
+            return cache::regions(self.pid, self.smaps, max_age);
+        }
+        let file = if self.smaps { "smaps" } else { "maps" };
+        let text = capture::read_proc_file(self.pid, file).map_err(|e| format!("Unable to open the {} file: {}", file, e))?;
+        Ok(if self.smaps { smaps::parse_smaps(text.as_bytes()) } else { parse_memory_regions(text.as_bytes()) })
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
+
+pub struct Adb {
+    pub target: AdbTarget,
+    pub pid: u32,
+    pub smaps: bool,
+}
+
+impl MemorySource for Adb {
+    fn name(&self) -> String {
+        format!("adb:{}:{}", self.target.serial, self.pid)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: self.smaps, live: true, local: false }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        self.read().map(|(memory_regions, _)| memory_regions)
+    }
+
+    fn read(&self) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+        let file = if self.smaps { "smaps" } else { "maps" };
+        let text = self.target.read_proc_file(self.pid, file).map_err(|e| format!("Unable to read {} over adb: {}", file, e))?;
+        Ok(if self.smaps { smaps::parse_smaps(text.as_bytes()) } else { parse_memory_regions(text.as_bytes()) })
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
+
+// A maps or smaps listing saved to disk, such as from a bug report. It
+// never changes, so it is read once.
+pub struct MapsFile {
+    path: String,
+    text: String,
+}
+
+impl MapsFile {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        Ok(MapsFile { path: path.to_string(), text })
+    }
+
+    fn is_smaps(&self) -> bool {
+        self.text.lines().any(|line| line.starts_with("Rss:"))
+    }
+}
+
+impl MemorySource for MapsFile {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: self.is_smaps(), live: false, local: false }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        self.read().map(|(memory_regions, _)| memory_regions)
+    }
+
+    fn read(&self) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+        Ok(if self.is_smaps() { smaps::parse_smaps(self.text.as_bytes()) } else { parse_memory_regions(self.text.as_bytes()) })
+    }
+}
+
+// The map an strace log or perf recording ends with; the replay
+// subcommand shows how it got there.
+pub struct Replay {
+    path: String,
+    memory_regions: Vec<MemoryRegion>,
+}
+
+impl Replay {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let events = replay::load(path, None)?;
+        Ok(Replay { path: path.to_string(), memory_regions: replay::AddressSpace::replay(&events).regions() })
+    }
+}
+
+impl MemorySource for Replay {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.memory_regions.clone())
+    }
+}
+
+// A QEMU guest's physical address space: RAM blocks, ROMs and MMIO
+// windows. Either asked of a running QEMU over its QMP socket, or parsed
+// from saved `info mtree` output.
+pub enum Qemu {
+    Qmp(String),
+    Mtree { path: String, memory_regions: Vec<MemoryRegion> },
+}
+
+impl Qemu {
+    pub fn open_mtree(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        Ok(Qemu::Mtree { path: path.to_string(), memory_regions: qemu::parse_mtree(&text)? })
+    }
+}
+
+impl MemorySource for Qemu {
+    fn name(&self) -> String {
+        match self {
+            Qemu::Qmp(endpoint) => format!("qmp:{}", endpoint),
+            Qemu::Mtree { path, .. } => path.clone(),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: false, live: matches!(self, Qemu::Qmp(_)), local: false }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        match self {
+            Qemu::Qmp(endpoint) => qemu::parse_mtree(&qemu::read_mtree(endpoint)?),
+            Qemu::Mtree { memory_regions, .. } => Ok(memory_regions.clone()),
+        }
+    }
+}
+
+// An embedded target's flash, RAM and ROM as its gdb server describes
+// them. The map is fixed for a chip, so it is asked for once.
+pub struct GdbRemote {
+    address: String,
+    memory_regions: Vec<MemoryRegion>,
+}
+
+impl GdbRemote {
+    pub fn open(address: &str) -> Result<Self, String> {
+        let xml = gdbremote::Remote::connect(address)?.memory_map()?;
+        Ok(GdbRemote { address: address.to_string(), memory_regions: gdbremote::parse_memory_map(&xml)? })
+    }
+}
+
+impl MemorySource for GdbRemote {
+    fn name(&self) -> String {
+        format!("gdb-remote:{}", self.address)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.memory_regions.clone())
+    }
+}
+
+// How an ESP-IDF image fills IRAM, DRAM and flash, per component, from
+// `idf.py size-components --format json`, optionally with the summary of
+// `idf.py size --format json` after a comma for the free space.
+pub struct EspIdf {
+    path: String,
+    memory_regions: Vec<MemoryRegion>,
+}
+
+impl EspIdf {
+    pub fn open(arg: &str) -> Result<Self, String> {
+        let (components, summary) = match arg.split_once(',') {
+            Some((components, summary)) => (components, Some(summary)),
+            None => (arg, None),
+        };
+        Ok(EspIdf { path: components.to_string(), memory_regions: espidf::read(components, summary)? })
+    }
+}
+
+impl MemorySource for EspIdf {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.memory_regions.clone())
+    }
+}
+
+// A reference diagram of a Cortex-M chip: a preset of its memories and
+// buses, with the peripherals of a CMSIS-SVD file after a comma.
+pub struct CortexM {
+    preset: String,
+    memory_regions: Vec<MemoryRegion>,
+}
+
+impl CortexM {
+    pub fn open(arg: &str) -> Result<Self, String> {
+        let (preset, svd) = match arg.split_once(',') {
+            Some((preset, svd)) => (preset, Some(svd)),
+            None => (arg, None),
+        };
+        Ok(CortexM { preset: preset.to_string(), memory_regions: cortexm::read(preset, svd)? })
+    }
+}
+
+impl MemorySource for CortexM {
+    fn name(&self) -> String {
+        format!("cortex-m:{}", self.preset)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.memory_regions.clone())
+    }
+}
+
+// A crashed kernel's physical memory from its dump, an ELF vmcore or
+// makedumpfile's kdump-compressed file, read once.
+pub struct Vmcore {
+    path: String,
+    dump: vmcore::Vmcore,
+}
+
+impl Vmcore {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Ok(Vmcore { path: path.to_string(), dump: vmcore::read(path)? })
+    }
+}
+
+impl MemorySource for Vmcore {
+    fn name(&self) -> String {
+        match &self.dump.release {
+            Some(release) => format!("{} (Linux {})", self.path, release),
+            None => self.path.clone(),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.dump.memory_regions.clone())
+    }
+}
+
+// GPU memory per client: VRAM, GTT and the driver's other regions from
+// every process's DRM fdinfo, or a saved amdgpu_gem_info or nvidia-smi
+// listing.
+pub struct Gpu {
+    pub arg: String,
+}
+
+impl MemorySource for Gpu {
+    fn name(&self) -> String {
+        format!("gpu:{}", self.arg)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: false, live: self.arg.is_empty(), local: false }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        gpu::read(&self.arg)
+    }
+}
+
+// A V8 heap's spaces on their own, for a heap snapshot or statistics
+// saved without the process.
+pub struct V8Heap {
+    path: String,
+    memory_regions: Vec<MemoryRegion>,
+}
+
+impl V8Heap {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Ok(V8Heap { path: path.to_string(), memory_regions: v8heap::layout(&v8heap::read(path)?) })
+    }
+}
+
+impl MemorySource for V8Heap {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(self.memory_regions.clone())
+    }
+}
+
+// A Windows process from inside WSL2, walked through interop each time
+// it is read, by PID or image name.
+pub struct Windows {
+    target: String,
+    pid: u32,
+    name: String,
+}
+
+impl Windows {
+    pub fn open(target: &str) -> Result<Self, String> {
+        let (pid, name, _) = wsl::windows_process(target)?;
+        Ok(Windows { target: pid.to_string(), pid, name })
+    }
+}
+
+impl MemorySource for Windows {
+    fn name(&self) -> String {
+        format!("windows:{}", self.name)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { smaps: false, live: true, local: false }
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>, String> {
+        Ok(wsl::windows_process(&self.target)?.2)
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
+
+// Regions as callers should see them: read from the source, then checked
+// and repaired once, with what was skipped and what was fixed.
+pub fn load(source: &dyn MemorySource) -> Result<(Vec<MemoryRegion>, Warnings), String> {
+    let (memory_regions, mut warnings) = source.read()?;
+    let (memory_regions, region_warnings) = validate::validate(memory_regions);
+    warnings.region_warnings = region_warnings;
+    Ok((memory_regions, warnings))
+}
+
+// Called with the text after "KIND:" and whether smaps detail is wanted,
+// which sources without it are free to ignore.
+pub type SourceFactory = fn(&str, bool) -> Result<Box<dyn MemorySource>, String>;
+
+// Kinds added by crates that embed memlayout, looked up by --source
+// KIND:ARG after the built-in ones.
+static REGISTRY: Mutex<Vec<(&'static str, SourceFactory)>> = Mutex::new(Vec::new());
+
+pub fn register_source(kind: &'static str, factory: SourceFactory) {
+    REGISTRY.lock().unwrap().push((kind, factory));
+}
+
+fn builtin(kind: &str) -> Option<SourceFactory> {
+    match kind {
+        "procfs" => Some(|arg, smaps| {
+            let pid = arg.parse().map_err(|_| format!("Invalid PID: {}", arg))?;
+            Ok(Box::new(Procfs { pid, smaps, cache: None }))
+        }),
+        "file" => Some(|arg, _| Ok(Box::new(MapsFile::open(arg)?))),
+        "strace" | "perf" => Some(|arg, _| Ok(Box::new(Replay::open(arg)?))),
+        "qmp" => Some(|arg, _| Ok(Box::new(Qemu::Qmp(arg.to_string())))),
+        "mtree" => Some(|arg, _| Ok(Box::new(Qemu::open_mtree(arg)?))),
+        "gdb-remote" => Some(|arg, _| Ok(Box::new(GdbRemote::open(arg)?))),
+        "esp-idf" => Some(|arg, _| Ok(Box::new(EspIdf::open(arg)?))),
+        "cortex-m" => Some(|arg, _| Ok(Box::new(CortexM::open(arg)?))),
+        "vmcore" => Some(|arg, _| Ok(Box::new(Vmcore::open(arg)?))),
+        "gpu" => Some(|arg, _| Ok(Box::new(Gpu { arg: arg.to_string() }))),
+        "v8-heap" => Some(|arg, _| Ok(Box::new(V8Heap::open(arg)?))),
+        "windows" => Some(|arg, _| Ok(Box::new(Windows::open(arg)?))),
+        _ => None,
+    }
+}
+
+pub fn kinds() -> Vec<&'static str> {
+    let mut kinds = vec!["procfs", "file", "strace", "perf", "qmp", "mtree", "gdb-remote", "esp-idf", "cortex-m", "vmcore", "gpu", "v8-heap", "windows"];
+    kinds.extend(REGISTRY.lock().unwrap().iter().map(|(kind, _)| *kind));
+    kinds
+}
+
+// "KIND:ARG", where the argument is whatever the kind makes of it.
+pub fn open(spec: &str, smaps: bool) -> Result<Box<dyn MemorySource>, String> {
+    let (kind, arg) = spec.split_once(':').unwrap_or((spec, ""));
+    let registered = REGISTRY.lock().unwrap().iter().find(|(registered, _)| *registered == kind).map(|(_, factory)| *factory);
+    match builtin(kind).or(registered) {
+        Some(factory) => factory(arg, smaps),
+        None => Err(format!("Unknown source {}; known sources are {}", kind, kinds().join(", "))),
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{display_name, format_size, MemoryRegion};
+
+pub const USED_COLOR: [u8; 3] = [230, 130, 20];
+pub const OVER_COLOR: [u8; 3] = [220, 30, 30];
+pub const HEADROOM_COLOR: [u8; 3] = [40, 180, 90];
+
+// A stack's reserve as addresses: it grows down from end, has used down to
+// used_from, and may go on to start before it overflows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackUsage {
+    pub name: String,
+    pub start: usize,
+    pub used_from: usize,
+    pub end: usize,
+    // Unknown for a main stack under an unlimited RLIMIT_STACK.
+    pub limited: bool,
+    pub over_threshold: bool,
+}
+
+impl StackUsage {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn used(&self) -> usize {
+        self.end - self.used_from
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reserved(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fraction(&self) -> Option<f64> {
+        self.limited.then(|| self.used() as f64 / self.reserved().max(1) as f64)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn stack_limit(limits: &str) -> Result<Option<usize>, String> {
+    let line = limits.lines().find(|line| line.starts_with("Max stack size")).ok_or("No stack size limit in limits")?;
+    match line["Max stack size".len()..].split_whitespace().next() {
+        Some("unlimited") => Ok(None),
+        Some(soft) => soft.parse().map(Some).map_err(|_| format!("Invalid stack size limit: {}", soft)),
+        None => Err("No stack size limit in limits".to_string()),
+    }
+}
+
+// The main stack's mapping only grows, so its size is how deep it has
+// ever been, and RLIMIT_STACK is how deep it may go, short of the mapping
+// below it. A thread stack is allocated whole, so its touched pages are
+// the high water mark. Either way a stack pointer deeper than that wins.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn usage(memory_regions: &[MemoryRegion], pid: u32, rlimit: Option<usize>, pointers: &[(u32, usize)], threshold: f64) -> Vec<StackUsage> {
+    let mut stacks = Vec::new();
+    for (index, region) in memory_regions.iter().enumerate() {
+        let Some(tid) = region.thread_id.filter(|_| region.attributes.allocated) else { continue };
+        let main = region.file_name.as_deref() == Some("[stack]");
+        let depth = pointers.iter().find(|(thread, sp)| *thread == tid && region.start <= *sp && *sp < region.end).map(|(_, sp)| region.end - sp);
+        let touched = region.smaps.as_ref().map(|smaps| smaps.rss + smaps.swap);
+        let high_water = if main { Some(region.size) } else { touched };
+        let Some(used) = high_water.max(depth).map(|used| used.min(region.size)) else { continue };
+        let below = memory_regions[..index].iter().rev().find(|other| other.attributes.allocated).map_or(0, |other| other.end);
+        let (start, limited) = match (main, rlimit) {
+            (true, Some(limit)) => (region.end.saturating_sub(limit).max(below).min(region.start), true),
+            (true, None) => (region.start, false),
+            (false, _) => (region.start, true),
+        };
+        let name = if main || tid == pid { "main stack".to_string() } else { display_name(region).unwrap_or_else(|| format!("stack (tid {})", tid)) };
+        let mut stack = StackUsage { name, start, used_from: region.end - used, end: region.end, limited, over_threshold: false };
+        stack.over_threshold = stack.fraction().is_some_and(|fraction| fraction >= threshold);
+        stacks.push(stack);
+    }
+    stacks
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn print(stacks: &[StackUsage], threshold: f64) {
+    println!("{:<24} {:>10} {:>10} {:>6} {:>10}", "stack", "used", "reserved", "%", "headroom");
+    for stack in stacks {
+        let (reserved, share, headroom) = match stack.fraction() {
+            Some(fraction) => (format_size(stack.reserved()), format!("{:.0}%", fraction * 100.0), format_size(stack.reserved() - stack.used())),
+            None => ("unlimited".to_string(), "-".to_string(), "-".to_string()),
+        };
+        println!("{:<24} {:>10} {:>10} {:>6} {:>10}", stack.name, format_size(stack.used()), reserved, share, headroom);
+    }
+    for stack in stacks.iter().filter(|stack| stack.over_threshold) {
+        eprintln!("warning: {} has used {} of {}, over {:.0}%", stack.name, format_size(stack.used()), format_size(stack.reserved()), threshold * 100.0);
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adb::AdbTarget;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture;
+use crate::{format_count, format_size};
+
+// What /proc/PID/status knows that a single read of the maps can't: the
+// peaks the process has reached since it started, and how it has been
+// scheduled.
+#[derive(Debug, Clone)]
+pub struct ProcessStatus {
+    pub vm_peak: Option<usize>,
+    pub vm_hwm: Option<usize>,
+    pub threads: Option<usize>,
+    pub voluntary_switches: Option<usize>,
+    pub involuntary_switches: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse(text: &str) -> ProcessStatus {
+    let value = |name: &str| -> Option<usize> {
+        let line = text.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?;
+        line.split_whitespace().next()?.parse().ok()
+    };
+    ProcessStatus {
+        vm_peak: value("VmPeak").map(|kb| kb * 1024),
+        vm_hwm: value("VmHWM").map(|kb| kb * 1024),
+        threads: value("Threads"),
+        voluntary_switches: value("voluntary_ctxt_switches"),
+        involuntary_switches: value("nonvoluntary_ctxt_switches"),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(pid: u32, adb: Option<&AdbTarget>) -> Result<ProcessStatus, String> {
+    let text = match adb {
+        Some(adb) => adb.read_proc_file(pid, "status")?,
+        None => capture::read_proc_file(pid, "status")?,
+    };
+    Ok(parse(&text))
+}
+
+impl ProcessStatus {
+    // Kernel threads have no Vm lines, so whatever is there is listed.
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(peak) = self.vm_peak {
+            parts.push(format!("VmPeak {}", format_size(peak)));
+        }
+        if let Some(hwm) = self.vm_hwm {
+            parts.push(format!("VmHWM {}", format_size(hwm)));
+        }
+        if let Some(threads) = self.threads {
+            parts.push(format!("{} threads", format_count(threads)));
+        }
+        if let (Some(voluntary), Some(involuntary)) = (self.voluntary_switches, self.involuntary_switches) {
+            parts.push(format!("context switches {} voluntary, {} involuntary", format_count(voluntary), format_count(involuntary)));
+        }
+        format!("status: {}", parts.join(", "))
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{fit_text, format_size, luminance, region_category, MemoryRegion, RenderOptions};
+use image::Rgb;
+use plotters::prelude::*;
+
+const CHART_WIDTH: u32 = 800;
+const LABEL_WIDTH: i32 = 70;
+const BAR_HEIGHT: i32 = 40;
+const ROW_HEIGHT: i32 = 18;
+const KERNEL_HALF: usize = 1 << 63;
+
+// The summary's categories, in the order they stack, with their colors.
+// Gaps aren't memory, so they are only listed, never stacked.
+const CATEGORIES: [(&str, Rgb<u8>); 8] = [
+    ("file-backed code", Rgb([227, 119, 194])),
+    ("file-backed data", Rgb([255, 187, 120])),
+    ("heap", Rgb([214, 39, 40])),
+    ("anon private", Rgb([31, 119, 180])),
+    ("anon shared", Rgb([148, 103, 189])),
+    ("stacks", Rgb([44, 160, 44])),
+    ("other", Rgb([127, 127, 127])),
+    ("gaps", Rgb([220, 220, 220])),
+];
+
+// region_category's finer kinds gathered into the summary's.
+fn category(region: &MemoryRegion) -> usize {
+    match region_category(region) {
+        "file" if region.attributes.executable => 0,
+        "file" => 1,
+        "heap" => 2,
+        "anon" | "named anon" | "jit" | "guard" | "shadow" => 3,
+        "memfd" | "sysv shm" | "shared anon" => 4,
+        "stack" => 5,
+        "gap" => 7,
+        _ => 6,
+    }
+}
+
+// Mapped bytes and RSS for each category; the gaps are the unmapped
+// bytes between the lowest and the highest mapping in user space, as
+// [vsyscall] up in the kernel's half would make them 16 EiB.
+fn totals(memory_regions: &[MemoryRegion]) -> [(usize, usize); 8] {
+    let mut totals = [(0, 0); 8];
+    let mut end = None;
+    for region in memory_regions.iter().filter(|region| region.attributes.allocated) {
+        let total = &mut totals[category(region)];
+        total.0 += region.size;
+        total.1 += region.smaps.as_ref().map_or(0, |smaps| smaps.rss);
+        if region.start >= KERNEL_HALF {
+            continue;
+        }
+        if let Some(end) = end.filter(|&end| region.start > end) {
+            totals[7].0 += region.start - end;
+        }
+        end = Some(end.map_or(region.end, |end: usize| end.max(region.end)));
+    }
+    totals
+}
+
+fn draw_bar<DB: DrawingBackend>(root: &DrawingArea<DB, plotters::coord::Shift>, label: &str, values: &[usize], y: i32, font: &FontDesc, ink: &RGBColor) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.draw(&Text::new(label.to_string(), (10, y + BAR_HEIGHT / 2 - 6), font.color(ink)))?;
+    let total: usize = values.iter().sum();
+    let bar_space = (CHART_WIDTH as i32 - LABEL_WIDTH - 90) as f64;
+    let mut x = LABEL_WIDTH as f64;
+    for (value, (_, color)) in values.iter().zip(CATEGORIES) {
+        let width = bar_space * *value as f64 / total.max(1) as f64;
+        let (left, right) = (x.round() as i32, (x + width).round() as i32);
+        x += width;
+        if right <= left {
+            continue;
+        }
+        root.draw(&Rectangle::new([(left, y), (right, y + BAR_HEIGHT)], RGBColor(color[0], color[1], color[2]).filled()))?;
+        if let Some(text) = fit_text(root, &format!("{:.0}%", 100.0 * *value as f64 / total as f64), font, right - left - 4)? {
+            let text_color = if luminance(color) > 128.0 { &BLACK } else { &WHITE };
+            root.draw(&Text::new(text, (left + 2, y + BAR_HEIGHT / 2 - 6), font.color(text_color)))?;
+        }
+    }
+    root.draw(&Text::new(format_size(total), (x.round() as i32 + 5, y + BAR_HEIGHT / 2 - 6), font.color(ink)))?;
+    Ok(())
+}
+
+// Mapped bytes, and RSS where smaps was read, as one stacked bar each,
+// with every category's totals listed under them.
+pub fn draw_summary_chart(memory_regions: &[MemoryRegion], title: &str, options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let totals = totals(memory_regions);
+    let has_rss = memory_regions.iter().any(|region| region.smaps.is_some());
+    let bars = if has_rss { 2 } else { 1 };
+    let legend_top = 36 + bars * (BAR_HEIGHT + 10) + 10;
+    let height = (legend_top + (CATEGORIES.len() as i32 + 1) * ROW_HEIGHT + 10) as u32;
+    let root = BitMapBackend::new(path, (CHART_WIDTH, height)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 11.0, FontStyle::Normal);
+    let bold = FontDesc::new(FontFamily::SansSerif, 11.0, FontStyle::Bold);
+    root.draw(&Text::new(title.to_string(), (10, 8), FontDesc::new(FontFamily::SansSerif, 14.0, FontStyle::Bold).color(&ink)))?;
+
+    // The gaps' slot stays empty in the bars.
+    let mapped: Vec<usize> = totals.iter().take(7).map(|(mapped, _)| *mapped).collect();
+    draw_bar(&root, "mapped", &mapped, 36, &font, &ink)?;
+    if has_rss {
+        let rss: Vec<usize> = totals.iter().take(7).map(|(_, rss)| *rss).collect();
+        draw_bar(&root, "RSS", &rss, 36 + BAR_HEIGHT + 10, &font, &ink)?;
+    }
+
+    let columns = [10, 200, 320];
+    for (text, x) in ["category", "mapped", if has_rss { "RSS" } else { "" }].iter().zip(columns) {
+        root.draw(&Text::new(text.to_string(), (x, legend_top), bold.color(&ink)))?;
+    }
+    for (i, ((name, color), (mapped, rss))) in CATEGORIES.iter().zip(totals).enumerate() {
+        let y = legend_top + (i as i32 + 1) * ROW_HEIGHT;
+        root.draw(&Rectangle::new([(10, y + 1), (22, y + 13)], RGBColor(color[0], color[1], color[2]).filled()))?;
+        let name = if i == 7 { "gaps (not drawn)" } else { name };
+        root.draw(&Text::new(name.to_string(), (28, y + 1), font.color(&ink)))?;
+        root.draw(&Text::new(format_size(mapped), (columns[1], y + 1), font.color(&ink)))?;
+        if has_rss && i != 7 {
+            root.draw(&Text::new(format_size(rss), (columns[2], y + 1), font.color(&ink)))?;
+        }
+    }
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use crate::MemoryRegion;
+use object::{Object, ObjectSegment};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Resolution {
+    pub address: usize,
+    pub path: String,
+    pub function: Option<String>,
+    pub location: Option<String>,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self.path.rsplit('/').next().unwrap_or(&self.path);
+        match (&self.function, &self.location) {
+            (Some(function), Some(location)) => write!(f, "{} ({})", function, location),
+            (Some(function), None) => write!(f, "{} [{}]", function, file),
+            (None, _) => write!(f, "{:#x} [{}]", self.address, file),
+        }
+    }
+}
+
+pub fn parse_address(s: &str) -> Result<usize, String> {
+    let digits = s.trim().trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).map_err(|_| format!("Invalid address: {}", s))
+}
+
+// Maps a file offset to the virtual address the ELF itself uses, via the
+// PT_LOAD segment containing it; this is what the symbol tables refer to.
+fn file_offset_to_vaddr(data: &[u8], file_offset: u64) -> Option<u64> {
+    let file = object::File::parse(data).ok()?;
+    file.segments().find_map(|segment| {
+        let (offset, size) = segment.file_range();
+        if file_offset >= offset && file_offset < offset + size {
+            Some(file_offset - offset + segment.address())
+        } else {
+            None
+        }
+    })
+}
+
+// Paths are looked up under root when the target has its own mount
+// namespace, since they name files as the target sees them.
+pub fn resolve(memory_regions: &[MemoryRegion], address: usize, root: Option<&Path>) -> Option<Resolution> {
+    let region = memory_regions.iter().find(|r| r.attributes.allocated && r.start <= address && address < r.end)?;
+    let path = region.file_name.as_deref().filter(|path| path.starts_with('/'))?;
+    let mapped = path.strip_suffix(" (deleted)").unwrap_or(path);
+    let on_disk = match root {
+        Some(root) => root.join(mapped.trim_start_matches('/')),
+        None => PathBuf::from(mapped),
+    };
+
+    let file_offset = (address - region.start + region.offset) as u64;
+    let data = fs::read(&on_disk).ok()?;
+    let probe = file_offset_to_vaddr(&data, file_offset)?;
+
+    let mut resolution = Resolution { address, path: path.to_string(), function: None, location: None };
+    lookup(&on_disk, probe, &mut resolution);
+
+    // Stripped binaries have nothing but dynamic exports; the separate debug
+    // file for the same build id shares its addresses.
+    #[cfg(feature = "debuginfod")]
+    if resolution.function.is_none() || resolution.location.is_none() {
+        if let Some(debug_file) = crate::debuginfod::build_id(&data).and_then(|id| crate::debuginfod::debuginfo(&id)) {
+            lookup(&debug_file, probe, &mut resolution);
+        }
+    }
+    Some(resolution)
+}
+
+fn lookup(path: &Path, probe: u64, resolution: &mut Resolution) {
+    let Ok(loader) = addr2line::Loader::new(path) else { return };
+    if resolution.function.is_none() {
+        resolution.function = loader
+            .find_symbol(probe)
+            .map(|name| addr2line::demangle_auto(Cow::from(name), None).into_owned());
+    }
+    if let Ok(Some(location)) = loader.find_location(probe) {
+        if let (Some(file), Some(line)) = (location.file, location.line) {
+            resolution.location = Some(format!("{}:{}", file.rsplit('/').next().unwrap_or(file), line));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use image::RgbImage;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+    Iterm,
+}
+
+// Terminals don't answer capability queries reliably over SSH, but the ones
+// that speak these protocols identify themselves in the environment.
+pub fn detect() -> Option<Protocol> {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || term == "xterm-ghostty" || program == "ghostty" {
+        Some(Protocol::Kitty)
+    } else if program == "iTerm.app" || program == "WezTerm" || env::var_os("ITERM_SESSION_ID").is_some() {
+        Some(Protocol::Iterm)
+    } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") || term == "yaft-256color" {
+        Some(Protocol::Sixel)
+    } else {
+        None
+    }
+}
+
+pub fn encode(img: &RgbImage, protocol: Protocol) -> String {
+    match protocol {
+        Protocol::Sixel => sixel(img),
+        Protocol::Kitty => kitty(&png_bytes(img)),
+        Protocol::Iterm => iterm(&png_bytes(img)),
+    }
+}
+
+fn png_bytes(img: &RgbImage) -> Vec<u8> {
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png)
+        .encode(img.as_raw(), img.width(), img.height(), image::ColorType::Rgb8)
+        .expect("Unable to encode the image");
+    png
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// The kitty graphics protocol caps each escape at 4096 bytes of payload.
+fn kitty(png: &[u8]) -> String {
+    let payload = base64(png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let control = if i == 0 { format!("a=T,f=100,m={}", more) } else { format!("m={}", more) };
+        write!(out, "\x1b_G{};{}\x1b\\", control, std::str::from_utf8(chunk).unwrap()).unwrap();
+    }
+    out.push('\n');
+    out
+}
+
+fn iterm(png: &[u8]) -> String {
+    format!("\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07\n", png.len(), base64(png))
+}
+
+// Sixel allows 256 registers; maps are mostly flat colors, so exact colors
+// are kept when they fit and otherwise everything snaps to a 6x7x6 cube.
+fn palette(img: &RgbImage) -> (Vec<[u8; 3]>, Vec<usize>) {
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    let mut index: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in img.pixels() {
+        index.entry(pixel.0).or_insert_with(|| {
+            colors.push(pixel.0);
+            colors.len() - 1
+        });
+    }
+    if colors.len() <= 256 {
+        return (colors, img.pixels().map(|pixel| index[&pixel.0]).collect());
+    }
+
+    let scale = |v: u32, n: u32| (v * 255 / (n - 1)) as u8;
+    let mut cube = Vec::new();
+    for r in 0..6 {
+        for g in 0..7 {
+            for b in 0..6 {
+                cube.push([scale(r, 6), scale(g, 7), scale(b, 6)]);
+            }
+        }
+    }
+    let level = |c: u8, n: u32| (c as u32 * (n - 1) + 127) / 255;
+    let indices = img
+        .pixels()
+        .map(|pixel| (level(pixel[0], 6) * 42 + level(pixel[1], 7) * 6 + level(pixel[2], 6)) as usize)
+        .collect();
+    (cube, indices)
+}
+
+fn sixel(img: &RgbImage) -> String {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (colors, indices) = palette(img);
+
+    let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+    for (i, [r, g, b]) in colors.iter().enumerate() {
+        let percent = |c: u8| c as u32 * 100 / 255;
+        write!(out, "#{};2;{};{};{}", i, percent(*r), percent(*g), percent(*b)).unwrap();
+    }
+
+    // Each band is six pixel rows; every color used in it gets one pass
+    // across the band, with "$" returning to its start.
+    for band in (0..height).step_by(6) {
+        let rows = (band..(band + 6).min(height)).collect::<Vec<_>>();
+        let mut used: Vec<usize> = rows.iter().flat_map(|y| indices[y * width..(y + 1) * width].iter().copied()).collect();
+        used.sort_unstable();
+        used.dedup();
+        for (pass, color) in used.iter().enumerate() {
+            if pass > 0 {
+                out.push('$');
+            }
+            write!(out, "#{}", color).unwrap();
+            let column = |x: usize| {
+                let bits = rows.iter().enumerate().filter(|(_, y)| indices[*y * width + x] == *color).fold(0u8, |acc, (bit, _)| acc | 1 << bit);
+                (63 + bits) as char
+            };
+            let mut x = 0;
+            while x < width {
+                let c = column(x);
+                let mut run = 1;
+                while x + run < width && column(x + run) == c {
+                    run += 1;
+                }
+                if run > 3 {
+                    write!(out, "!{}{}", run, c).unwrap();
+                } else {
+                    (0..run).for_each(|_| out.push(c));
+                }
+                x += run;
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\\n");
+    out
+}
@@ -0,0 +1,120 @@
+use crate::{display_name, format_size, layout, legend_entries, region_color, MemoryRegion, RenderOptions};
+use image::Rgb;
+use std::fmt::Write;
+
+const STRIP_WIDTH: usize = 24;
+
+const ASCII_LEGEND: [(char, &str); 6] = [
+    ('x', "executable"),
+    ('w', "writable"),
+    ('r', "read-only"),
+    ('-', "no access"),
+    ('=', "guard"),
+    ('.', "unmapped"),
+];
+
+fn ascii_fill(region: &MemoryRegion) -> char {
+    match &region.attributes {
+        _ if region.guard => '=',
+        attributes if !attributes.allocated => '.',
+        attributes if attributes.executable => 'x',
+        attributes if attributes.writable => 'w',
+        attributes if attributes.readable => 'r',
+        _ => '-',
+    }
+}
+
+// Prints the strip one line per `lines`, with the address and name of every
+// region that starts on a line beside it. Unicode output uses half blocks
+// for two rows per line, colored foreground over background.
+pub fn render(memory_regions: &[MemoryRegion], options: &RenderOptions, lines: u32, unicode: bool) -> String {
+    let rows_per_line = if unicode { 2 } else { 1 };
+    let rows = (lines * rows_per_line) as usize;
+    let extents = layout(memory_regions, rows as u32, rows_per_line as i32, options);
+    let mut owner: Vec<Option<usize>> = vec![None; rows];
+    for (i, (y, height)) in extents.iter().enumerate() {
+        for row in owner.iter_mut().skip(*y as usize).take(*height as usize) {
+            *row = Some(i);
+        }
+    }
+    let color = |row: usize| {
+        owner[row].map(|i| {
+            let region = &memory_regions[i];
+            options.emphasis.apply(region, region_color(region, options.color_by, &options.theme)).0
+        })
+    };
+
+    let mut out = String::new();
+    if let Some(header) = &options.header {
+        writeln!(out, "{}\n", header.lines().join("\n")).unwrap();
+    }
+    if let Some(notice) = &options.notice {
+        writeln!(out, "{}", notice).unwrap();
+    }
+
+    let rows_per_line = rows_per_line as usize;
+    for line in 0..lines as usize {
+        let top = line * rows_per_line;
+        let line_rows = top..top + rows_per_line;
+        if line_rows.clone().all(|row| owner[row].is_none()) {
+            break;
+        }
+
+        let starting: Vec<usize> = (0..memory_regions.len())
+            .filter(|&i| extents[i].1 > 0 && line_rows.contains(&(extents[i].0 as usize)))
+            .collect();
+        let address = starting.first().map_or(String::new(), |&i| format!("{:#x}", memory_regions[i].start));
+        write!(out, "{:>14} ", address).unwrap();
+
+        let collapsed = owner[top].filter(|&i| options.is_collapsed(&memory_regions[i]));
+        if let Some(i) = collapsed {
+            let fill = if unicode { "\u{2248}" } else { "~" };
+            out.push_str(&fill.repeat(STRIP_WIDTH));
+            writeln!(out, " {} unmapped", format_size(memory_regions[i].size)).unwrap();
+            continue;
+        }
+
+        if unicode {
+            let escape = |layer: u8, color: Option<Rgb<u8>>| match color {
+                Some(Rgb([r, g, b])) => format!("\x1b[{}8;2;{};{};{}m", layer, r, g, b),
+                None => format!("\x1b[{}9m", layer),
+            };
+            write!(out, "{}{}{}\x1b[0m", escape(3, color(top)), escape(4, color(top + 1)), "\u{2580}".repeat(STRIP_WIDTH)).unwrap();
+        } else {
+            out.push_str(&owner[top].map_or(' ', |i| ascii_fill(&memory_regions[i])).to_string().repeat(STRIP_WIDTH));
+        }
+
+        let mut labels: Vec<String> = starting.iter().filter_map(|&i| display_name(&memory_regions[i])).collect();
+        if labels.len() > 1 {
+            let more = labels.len() - 1;
+            labels.truncate(1);
+            labels[0] = format!("{} (+{})", labels[0], more);
+        }
+        for (address, label) in &options.annotations {
+            let marked = memory_regions.iter().zip(&extents).find(|(region, _)| region.start <= *address && *address < region.end);
+            if let Some((region, (y, height))) = marked {
+                let row = *y as usize + ((*address - region.start) as f64 / region.size as f64 * *height as f64) as usize;
+                if line_rows.contains(&row) {
+                    labels.push(format!("<- {}", label));
+                }
+            }
+        }
+        writeln!(out, " {}", labels.join(" ")).unwrap();
+    }
+
+    if options.legend.is_none() {
+        return out;
+    }
+    out.push('\n');
+    if unicode {
+        for entry in legend_entries(memory_regions, options) {
+            let Rgb([r, g, b]) = entry.color;
+            writeln!(out, "\x1b[38;2;{};{};{}m\u{2588}\u{2588}\x1b[0m {}", r, g, b, entry.label).unwrap();
+        }
+    } else {
+        for (fill, name) in ASCII_LEGEND {
+            writeln!(out, "{}{} {}", fill, fill, name).unwrap();
+        }
+    }
+    out
+}
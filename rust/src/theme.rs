@@ -0,0 +1,101 @@
+use crate::MemoryAttributes;
+use image::Rgb;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::str::FromStr;
+
+// Permission combinations in the order the theme stores them: the index is
+// readable << 2 | writable << 1 | executable.
+pub const PERMISSIONS: [&str; 8] = ["---", "--x", "-w-", "-wx", "r--", "r-x", "rw-", "rwx"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub gap: Rgb<u8>,
+    pub permissions: [Rgb<u8>; 8],
+}
+
+// A theme file overrides any of the classic colors, e.g.
+// {"gap": "#202020", "permissions": {"r-x": "#0050a0"}}
+#[derive(Deserialize)]
+struct ThemeFile {
+    gap: Option<String>,
+    #[serde(default)]
+    permissions: BTreeMap<String, String>,
+}
+
+fn parse_color(s: &str) -> Result<Rgb<u8>, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6).ok_or_else(|| format!("Invalid color: {}", s))?;
+    Ok(Rgb([(value >> 16) as u8, (value >> 8) as u8, value as u8]))
+}
+
+fn palette(colors: [u32; 8]) -> [Rgb<u8>; 8] {
+    colors.map(|c| Rgb([(c >> 16) as u8, (c >> 8) as u8, c as u8]))
+}
+
+// What --theme takes by name, for the completion scripts.
+#[cfg(not(target_arch = "wasm32"))]
+pub const BUILTIN: [&str; 4] = ["classic", "viridis", "high-contrast", "colorblind"];
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            // Readable, writable and executable drive red, green and blue.
+            "classic" => Some(Theme {
+                gap: Rgb([0, 0, 0]),
+                permissions: palette([0x808080, 0x0000ff, 0x00ff00, 0x00ffff, 0xff0000, 0xff00ff, 0xffff00, 0xffffff]),
+            }),
+            // Samples along viridis, from no access up to rwx.
+            "viridis" => Some(Theme {
+                gap: Rgb([245, 245, 245]),
+                permissions: palette([0x440154, 0x46327e, 0x365c8d, 0x277f8e, 0x1fa187, 0x4ac16d, 0xa0da39, 0xfde725]),
+            }),
+            "high-contrast" => Some(Theme {
+                gap: Rgb([0, 0, 0]),
+                permissions: palette([0x666666, 0x0000ff, 0x00c000, 0x00ffff, 0xffffff, 0xff0000, 0xffd700, 0xff00ff]),
+            }),
+            // Okabe-Ito, which stays distinct under protanopia and
+            // deuteranopia; the reddish colors only mark rare combinations.
+            "colorblind" => Some(Theme {
+                gap: Rgb([0, 0, 0]),
+                permissions: palette([0x999999, 0xcc79a7, 0xf0e442, 0x882255, 0x56b4e9, 0x0072b2, 0xe69f00, 0xd55e00]),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Theme, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        let file: ThemeFile = serde_json::from_str(&text).map_err(|e| format!("Invalid theme {}: {}", path, e))?;
+        let mut theme = Theme::builtin("classic").unwrap();
+        if let Some(gap) = &file.gap {
+            theme.gap = parse_color(gap)?;
+        }
+        for (perms, color) in &file.permissions {
+            let index = PERMISSIONS.iter().position(|p| p == perms).ok_or_else(|| format!("Unknown permissions in {}: {}", path, perms))?;
+            theme.permissions[index] = parse_color(color)?;
+        }
+        Ok(theme)
+    }
+
+    pub fn permission_color(&self, attributes: &MemoryAttributes) -> Rgb<u8> {
+        if !attributes.allocated {
+            return self.gap;
+        }
+        self.permissions[permission_index(attributes)]
+    }
+}
+
+pub fn permission_index(attributes: &MemoryAttributes) -> usize {
+    (attributes.readable as usize) << 2 | (attributes.writable as usize) << 1 | attributes.executable as usize
+}
+
+// A built-in theme name or the path of a theme file.
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::builtin(s).map_or_else(|| Theme::load(s), Ok)
+    }
+}
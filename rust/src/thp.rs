@@ -0,0 +1,76 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pagemap;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{region_category, MemoryRegion};
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+// From include/uapi/linux/kernel-page-flags.h.
+#[cfg(not(target_arch = "wasm32"))]
+const KPF_THP: u64 = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backing {
+    // Base pages, 4 KiB on most machines.
+    Small,
+    // A whole PMD sized block of THP pages, mapped by one PMD.
+    Pmd,
+    // THP pages that don't fill their block: a split or partly unmapped
+    // huge page, or a multi-size THP mapped by PTEs.
+    Pte,
+}
+
+pub const BACKINGS: [(Backing, &str, [u8; 3]); 3] = [
+    (Backing::Pmd, "PMD mapped THP", [150, 60, 220]),
+    (Backing::Pte, "PTE mapped THP", [240, 110, 200]),
+    (Backing::Small, "base pages", [120, 200, 255]),
+];
+
+pub fn color(backing: Backing) -> [u8; 3] {
+    BACKINGS.iter().find(|(known, _, _)| *known == backing).map(|(_, _, rgb)| *rgb).unwrap()
+}
+
+// The kernel's PMD size for THP, 2 MiB on x86-64.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pmd_size() -> usize {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/hpage_pmd_size").ok().and_then(|size| size.trim().parse().ok()).unwrap_or(2 << 20)
+}
+
+// THP backs anonymous memory; file and shmem THP are left out.
+#[cfg(not(target_arch = "wasm32"))]
+fn candidate(region: &MemoryRegion) -> bool {
+    matches!(region_category(region), "anon" | "named anon" | "jit" | "heap" | "stack") && region.smaps.as_ref().is_none_or(|smaps| smaps.rss > 0)
+}
+
+// The resident parts of anonymous regions by what backs them, as address
+// ranges with runs of the same backing joined.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn backing_ranges(pid: u32, memory_regions: &[MemoryRegion], page_size: usize) -> Result<Vec<(usize, usize, Backing)>, String> {
+    let pmd_size = pmd_size();
+    let mut pages: Vec<(usize, bool)> = Vec::new();
+    pagemap::walk(pid, memory_regions.iter().filter(|region| candidate(region)), page_size, |address, flags| pages.push((address, flags & KPF_THP != 0)))?;
+    let mut huge_pages_in_block: HashMap<usize, usize> = HashMap::new();
+    for (address, _) in pages.iter().filter(|(_, huge)| *huge) {
+        *huge_pages_in_block.entry(address / pmd_size).or_default() += 1;
+    }
+    let mut ranges: Vec<(usize, usize, Backing)> = Vec::new();
+    for (address, huge) in pages {
+        let backing = match huge {
+            true if huge_pages_in_block[&(address / pmd_size)] == pmd_size / page_size => Backing::Pmd,
+            true => Backing::Pte,
+            false => Backing::Small,
+        };
+        match ranges.last_mut() {
+            Some((_, end, last)) if *end == address && *last == backing => *end += page_size,
+            _ => ranges.push((address, address + page_size, backing)),
+        }
+    }
+    Ok(ranges)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bytes(ranges: &[(usize, usize, Backing)], backing: Backing, within: (usize, usize)) -> usize {
+    ranges.iter().filter(|range| range.2 == backing).map(|(start, end, _)| (*end).min(within.1).saturating_sub((*start).max(within.0))).sum()
+}
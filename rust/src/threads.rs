@@ -0,0 +1,70 @@
+use crate::MemoryRegion;
+use std::fs;
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+// kstkesp (field 29 of stat) is only filled in for some tasks on modern
+// kernels, so fall back to the SP column of the syscall file, which is
+// available while the thread is blocked and we may ptrace it.
+fn stack_pointer(pid: u32, tid: u32) -> Option<usize> {
+    let stat = fs::read_to_string(format!("/proc/{}/task/{}/stat", pid, tid)).ok()?;
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    if let Some(sp) = fields.get(26).and_then(|sp| sp.parse::<usize>().ok()) {
+        if sp != 0 {
+            return Some(sp);
+        }
+    }
+
+    let syscall = fs::read_to_string(format!("/proc/{}/task/{}/syscall", pid, tid)).ok()?;
+    let fields: Vec<&str> = syscall.split_whitespace().collect();
+    if fields.len() < 3 || fields[0] == "running" {
+        return None;
+    }
+    parse_hex(fields[fields.len() - 2]).filter(|&sp| sp != 0)
+}
+
+pub fn thread_stack_pointers(pid: u32) -> Vec<(u32, usize)> {
+    let entries = match fs::read_dir(format!("/proc/{}/task", pid)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut pointers: Vec<(u32, usize)> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|tid| Some((tid, stack_pointer(pid, tid)?)))
+        .collect();
+    pointers.sort();
+    pointers
+}
+
+fn tid_from_name(region: &MemoryRegion) -> Option<u32> {
+    if let Some(tid) = region.file_name.as_deref().and_then(|name| name.strip_prefix("[stack:")?.strip_suffix(']')) {
+        return tid.parse().ok();
+    }
+    region.anon_name()?.strip_prefix("stack_and_tls:")?.parse().ok()
+}
+
+pub fn label_thread_stacks(memory_regions: &mut [MemoryRegion], pid: Option<u32>) {
+    for region in memory_regions.iter_mut() {
+        if let Some(tid) = tid_from_name(region) {
+            region.thread_id = Some(tid);
+        } else if region.file_name.as_deref() == Some("[stack]") {
+            region.thread_id = pid;
+        }
+    }
+
+    let pid = match pid {
+        Some(pid) => pid,
+        None => return,
+    };
+    for (tid, sp) in thread_stack_pointers(pid) {
+        let index = memory_regions.partition_point(|region| region.end <= sp);
+        if let Some(region) = memory_regions.get_mut(index) {
+            if region.start <= sp && region.thread_id.is_none() {
+                region.thread_id = Some(tid);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::header::format_timestamp;
+use crate::snapshot::Snapshot;
+use crate::{animate, display_name, fit_text, format_size, layout, luminance, place_labels, region_color, scaled, MemoryRegion, RenderOptions, BREAK_HEIGHT, LEGEND_WIDTH};
+use plotters::prelude::*;
+
+const TITLE_HEIGHT: i32 = 36;
+const COLUMN_WIDTH: i32 = 160;
+const RIBBON_WIDTH: i32 = 36;
+
+// `count` snapshots spread evenly over the series, the first and the last
+// always among them.
+pub fn sample(snapshots: Vec<Snapshot>, count: usize) -> Vec<Snapshot> {
+    let count = count.max(2);
+    if snapshots.len() <= count {
+        return snapshots;
+    }
+    let last = snapshots.len() - 1;
+    let picks: Vec<usize> = (0..count).map(|i| i * last / (count - 1)).collect();
+    snapshots.into_iter().enumerate().filter(|(index, _)| picks.contains(index)).map(|(_, snapshot)| snapshot).collect()
+}
+
+// Every frame split at the boundaries of all of them, with the gaps between
+// intervals mapped nowhere, so intervals line up across the columns.
+fn columns(frames: &[Vec<MemoryRegion>]) -> Vec<Vec<MemoryRegion>> {
+    animate::align_frames(frames)
+        .into_iter()
+        .map(|frame| {
+            let mut column = Vec::with_capacity(frame.len());
+            let mut end = 0;
+            for region in frame {
+                if region.start > end {
+                    column.push(MemoryRegion::gap(end, region.start));
+                }
+                end = region.end;
+                column.push(region);
+            }
+            column
+        })
+        .collect()
+}
+
+// The same mapping in both columns: still there, from the same file.
+fn persists(before: &MemoryRegion, after: &MemoryRegion) -> bool {
+    before.attributes.allocated && after.attributes.allocated && before.file_name == after.file_name && before.inode == after.inode
+}
+
+// The snapshots as adjacent columns on one address scale, oldest first,
+// with a faint ribbon between two columns wherever a mapping lasts from
+// one to the next. Regions that appear start without a ribbon on their
+// left, those that go end without one on their right.
+pub fn draw_timeline(snapshots: &[Snapshot], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let frames: Vec<Vec<MemoryRegion>> = snapshots.iter().map(|snapshot| snapshot.regions.clone()).collect();
+    let columns = columns(&frames);
+    let count = columns.len() as i32;
+    let legend_width = px(LEGEND_WIDTH as i32);
+    let width = legend_width + count * px(COLUMN_WIDTH) + (count - 1) * px(RIBBON_WIDTH) + px(10);
+    let map_height = px(options.height as i32);
+    let root = BitMapBackend::new(path, (width as u32, (map_height + px(TITLE_HEIGHT)) as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let (title, map) = root.split_vertically(px(TITLE_HEIGHT));
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let title_font = FontDesc::new(FontFamily::SansSerif, 11.0 * options.scale_factor, FontStyle::Bold);
+    let column_x = |column: i32| legend_width + column * (px(COLUMN_WIDTH) + px(RIBBON_WIDTH));
+
+    // An interval mapped in any snapshot is a region of the shared layout,
+    // so only what is unmapped throughout collapses.
+    let union: Vec<MemoryRegion> = (0..columns[0].len()).map(|i| columns.iter().map(|column| &column[i]).find(|region| region.attributes.allocated).unwrap_or(&columns[0][i]).clone()).collect();
+    let extents = layout(&union, map_height as u32, px(BREAK_HEIGHT), options);
+
+    let first = snapshots[0].timestamp;
+    if let Some(text) = fit_text(&title, &format!("pid {}", snapshots[0].pid), &title_font, legend_width - px(10))? {
+        title.draw(&Text::new(text, (px(5), px(4)), title_font.color(&ink)))?;
+    }
+    for (index, (snapshot, column)) in snapshots.iter().zip(&columns).enumerate() {
+        let x = column_x(index as i32);
+        let when = if index == 0 { format_timestamp(snapshot.timestamp) } else { format!("+{}s", snapshot.timestamp - first) };
+        let mapped: usize = column.iter().filter(|region| region.attributes.allocated).map(|region| region.size).sum();
+        for (line, text) in [when, format!("{} mapped", format_size(mapped))].iter().enumerate() {
+            if let Some(text) = fit_text(&title, text, if line == 0 { &title_font } else { &font }, px(COLUMN_WIDTH))? {
+                title.draw(&Text::new(text, (x, px(4) + line as i32 * px(15)), if line == 0 { title_font.color(&ink) } else { font.color(&ink) }))?;
+            }
+        }
+    }
+
+    // Ribbons first, so the columns' edges stay crisp over them.
+    for (index, pair) in columns.windows(2).enumerate() {
+        let (left, right) = (column_x(index as i32) + px(COLUMN_WIDTH), column_x(index as i32 + 1));
+        for ((before, after), &(y, height)) in pair[0].iter().zip(&pair[1]).zip(&extents) {
+            if persists(before, after) {
+                let color = region_color(after, options.color_by, &options.theme);
+                map.draw(&Rectangle::new([(left, y), (right, y + height.max(1))], RGBColor(color[0], color[1], color[2]).mix(0.25).filled()))?;
+            }
+        }
+    }
+
+    let gap = options.theme.gap;
+    for (index, column) in columns.iter().enumerate() {
+        let x = column_x(index as i32);
+        let right = x + px(COLUMN_WIDTH);
+        for (i, (region, &(y, height))) in column.iter().zip(&extents).enumerate() {
+            if !region.attributes.allocated {
+                if options.is_collapsed(&union[i]) {
+                    map.draw(&Rectangle::new([(x, y), (right, y + height)], RGBColor(gap[0], gap[1], gap[2]).mix(0.5).filled()))?;
+                    if index == 0 {
+                        map.draw(&Text::new(format!("{} unmapped", format_size(region.size)), (x + px(3), y + (height - px(10)) / 2), font.color(&ink)))?;
+                    }
+                } else {
+                    map.draw(&Rectangle::new([(x, y), (right, y + height)], RGBColor(gap[0], gap[1], gap[2]).filled()))?;
+                }
+                continue;
+            }
+            let color = region_color(region, options.color_by, &options.theme);
+            map.draw(&Rectangle::new([(x, y), (right, y + height)], RGBColor(color[0], color[1], color[2]).filled()))?;
+            // Pieces split by other snapshots' boundaries are labeled once,
+            // at the top of the run they came from.
+            let continues = i > 0 && column[i - 1].attributes.allocated && column[i - 1].end == region.start && column[i - 1].file_name == region.file_name;
+            if height >= px(11) && !continues {
+                if let Some(label) = fit_text(&map, &display_name(region).unwrap_or_else(|| format_size(region.size)), &font, px(COLUMN_WIDTH - 6))? {
+                    let text = if luminance(color) > 128.0 { &BLACK } else { &WHITE };
+                    map.draw(&Text::new(label, (x + px(3), y + px(1)), font.color(text)))?;
+                }
+            }
+        }
+    }
+
+    // Where each run of intervals mapped somewhere starts, in the address
+    // column.
+    let label_height = px(12);
+    let starts: Vec<usize> = (0..union.len()).filter(|&i| union[i].attributes.allocated && (i == 0 || !union[i - 1].attributes.allocated || union[i - 1].end != union[i].start)).collect();
+    let wanted: Vec<i32> = starts.iter().map(|&i| extents[i].0).collect();
+    for (&i, top) in starts.iter().zip(place_labels(&wanted, label_height, map_height, options.label_placement)) {
+        let Some(top) = top else { continue };
+        let y = extents[i].0;
+        if top != y {
+            map.draw(&PathElement::new(vec![(legend_width - px(28), top + label_height / 2), (legend_width - px(2), y)], ink))?;
+        }
+        map.draw(&Text::new(format!("{:#x}", union[i].start), (px(5), top), font.color(&ink)))?;
+    }
+    root.present()?;
+    Ok(())
+}
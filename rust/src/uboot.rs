@@ -0,0 +1,148 @@
+use crate::symbols::parse_address;
+use crate::{carve, MemoryAttributes, MemoryRegion};
+use std::fs;
+
+// What U-Boot keeps for itself in DRAM, drawn over the banks, and the
+// addresses images get loaded to, drawn as markers since only the load
+// tells their size.
+pub struct Layout {
+    pub memory_regions: Vec<MemoryRegion>,
+    pub areas: Vec<MemoryRegion>,
+    pub images: Vec<(usize, String)>,
+}
+
+fn region(start: usize, end: usize, name: String, (writable, executable): (bool, bool)) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end,
+        size: end - start,
+        attributes: MemoryAttributes { readable: true, writable, executable, shared: false, allocated: true },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+fn area(start: usize, end: usize, name: &str) -> MemoryRegion {
+    let perms = match name {
+        "code" => (false, true),
+        "devicetree" | "fdt" => (false, false),
+        _ => (true, false),
+    };
+    region(start, end, format!("u-boot: {}", name), perms)
+}
+
+// "[0x40000000-0xbfffffff], 0x80000000 bytes" from lmb_dump_all, inclusive.
+fn lmb_range(value: &str) -> Option<(usize, usize)> {
+    let range = value.rsplit_once('[')?.1.split_once(']')?.0;
+    let (start, end) = range.split_once('-')?;
+    Some((parse_address(start).ok()?, parse_address(end).ok()?.checked_add(1)?))
+}
+
+// The table of `meminfo`, "Region Base Size End Gap", all in hex without
+// 0x. U-Boot's own areas sit in the last bank after relocation; "free" is
+// what is left below them.
+fn meminfo_areas(text: &str) -> Vec<MemoryRegion> {
+    let mut lines = text.lines().skip_while(|line| !(line.starts_with("Region") && line.contains("Base") && line.contains("Size")));
+    let rows = lines.by_ref().skip(1).skip_while(|line| line.starts_with('-')).take_while(|line| !line.trim().is_empty());
+    let mut areas = Vec::new();
+    for row in rows {
+        let fields: Vec<&str> = row.split_whitespace().collect();
+        let (Some(name), Some(base), Some(size)) = (fields.first(), fields.get(1).and_then(|field| parse_address(field).ok()), fields.get(2).and_then(|field| parse_address(field).ok())) else { continue };
+        if size > 0 && *name != "free" {
+            areas.push(area(base, base + size, name));
+        }
+    }
+    areas
+}
+
+// A capture of bdinfo, meminfo and printenv from the console, in any
+// order and with whatever else was typed in between.
+pub fn parse(text: &str) -> Result<Layout, String> {
+    let mut banks: Vec<(usize, usize)> = Vec::new();
+    let mut lmb_memory: Vec<(usize, usize)> = Vec::new();
+    let mut areas = meminfo_areas(text);
+    let from_meminfo = !areas.is_empty();
+    let mut bdinfo: Vec<(String, usize)> = Vec::new();
+    let mut images: Vec<(usize, String)> = Vec::new();
+    let mut bank_start = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some((key, value)) = line.split_once(" = ").or_else(|| line.split_once("\t= ")) {
+            let (key, value) = (key.trim(), value.trim());
+            let number = parse_address(value.split_whitespace().next().unwrap_or(value)).ok();
+            match (key, number) {
+                ("-> start", Some(start)) => bank_start = Some(start),
+                ("-> size", Some(size)) if size > 0 => banks.extend(bank_start.take().map(|start| (start, start + size))),
+                (_, Some(number)) => bdinfo.push((key.to_string(), number)),
+                _ => {}
+            }
+        } else if line.starts_with("memory[") {
+            lmb_memory.extend(lmb_range(line));
+        } else if line.starts_with("reserved[") && !from_meminfo {
+            areas.extend(lmb_range(line).map(|(start, end)| area(start, end, "reserved")));
+        } else if let Some((name, value)) = line.split_once('=') {
+            // printenv: kernel_addr_r=0x40400000, loadaddr=0x82000000, ...
+            if !name.contains(char::is_whitespace) && (name.ends_with("addr_r") || name.ends_with("addr")) {
+                if let Ok(address) = parse_address(value.trim()) {
+                    images.push((address, name.to_string()));
+                }
+            }
+        }
+    }
+    if banks.is_empty() {
+        banks = lmb_memory;
+    }
+    if banks.is_empty() {
+        return Err("No DRAM banks in the capture; it needs bdinfo output".to_string());
+    }
+    // Without meminfo only bdinfo's pointers say where U-Boot lives.
+    if !from_meminfo {
+        let value = |name: &str| bdinfo.iter().find(|(key, _)| key == name).map(|(_, value)| *value);
+        if let (Some(fdt), Some(size)) = (value("fdt_blob"), value("fdt_size")) {
+            areas.insert(0, area(fdt, fdt + size, "devicetree"));
+        }
+        for (key, label) in [("relocaddr", "relocaddr"), ("TLB addr", "TLB"), ("FB base", "framebuffer"), ("sp start", "stack")] {
+            if let Some(address) = value(key) {
+                images.push((address, format!("u-boot {}", label)));
+            }
+        }
+    }
+    // Of overlapping entries, such as lmb's after meminfo's own, the later
+    // keeps what is left.
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    for area in &areas {
+        let pieces = carve(area, &regions);
+        regions.extend(pieces);
+    }
+    for (index, (start, end)) in banks.iter().enumerate() {
+        regions.extend(carve(&region(*start, *end, format!("DRAM bank {}", index), (true, false)), &areas));
+    }
+    regions.sort_by_key(|region| region.start);
+    images.sort();
+    images.dedup();
+    Ok(Layout { memory_regions: regions, areas, images })
+}
+
+pub fn read(path: &str) -> Result<Layout, String> {
+    parse(&fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?)
+}
+
+// Load addresses inside something U-Boot is using, or outside DRAM, are
+// what goes wrong when an image is loaded over the relocated U-Boot.
+pub fn collisions(layout: &Layout) -> Vec<String> {
+    let mut collisions = Vec::new();
+    for (address, name) in layout.images.iter().filter(|(_, name)| !name.starts_with("u-boot ")) {
+        if let Some(area) = layout.areas.iter().find(|area| area.start <= *address && *address < area.end) {
+            collisions.push(format!("{} {:#x} is inside {} ({:#x}-{:#x})", name, address, area.file_name.as_deref().unwrap_or("?"), area.start, area.end));
+        } else if !layout.memory_regions.iter().any(|region| region.start <= *address && *address < region.end) {
+            collisions.push(format!("{} {:#x} is outside DRAM", name, address));
+        }
+    }
+    collisions
+}
@@ -0,0 +1,99 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::Read;
+
+const RELEASE_FEED: &str = "https://api.github.com/repos/cradiator/memory_map_visualizer/releases/latest";
+
+pub struct Release {
+    pub version: String,
+    pub asset_url: Option<String>,
+    // The asset's SHA-256, published beside it as NAME.sha256 in
+    // sha256sum's format.
+    pub checksum_url: Option<String>,
+}
+
+fn asset_name() -> String {
+    format!("memlayout-{}-{}", env::consts::ARCH, env::consts::OS)
+}
+
+pub fn latest_release() -> Result<Release, Box<dyn std::error::Error>> {
+    let body = ureq::get(RELEASE_FEED).set("User-Agent", "memlayout").call()?.into_string()?;
+    let release: serde_json::Value = serde_json::from_str(&body)?;
+
+    let version = release["tag_name"].as_str().ok_or("Release feed has no tag_name")?.trim_start_matches('v').to_string();
+    let download_url = |wanted: &str| {
+        release["assets"].as_array().and_then(|assets| {
+            assets
+                .iter()
+                .find(|asset| asset["name"].as_str() == Some(wanted))
+                .and_then(|asset| asset["browser_download_url"].as_str())
+                .map(str::to_string)
+        })
+    };
+    let asset_url = download_url(&asset_name());
+    let checksum_url = download_url(&format!("{}.sha256", asset_name()));
+    Ok(Release { version, asset_url, checksum_url })
+}
+
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !url.starts_with("https://") {
+        return Err(format!("Refusing to download {} over anything but https", url).into());
+    }
+    let mut data = Vec::new();
+    ureq::get(url).set("User-Agent", "memlayout").call()?.into_reader().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// The first field of a sha256sum line, "HEX  NAME".
+fn parse_checksum(text: &str) -> Result<Vec<u8>, String> {
+    let hex = text.split_whitespace().next().filter(|hex| hex.len() == 64).ok_or("Malformed checksum file")?;
+    (0..64).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| "Malformed checksum file".to_string())).collect()
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version.split(['.', '-']).map_while(|part| part.parse().ok()).collect()
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    parse_version(candidate) > parse_version(current)
+}
+
+pub fn check_update() -> Result<Option<Release>, Box<dyn std::error::Error>> {
+    let release = latest_release()?;
+    if is_newer(&release.version, env!("CARGO_PKG_VERSION")) {
+        Ok(Some(release))
+    } else {
+        Ok(None)
+    }
+}
+
+// Downloads next to the running binary and renames over it, so a failed
+// download never leaves a half-written executable in place. Nothing is
+// written unless the download matches the release's published SHA-256.
+pub fn self_update() -> Result<String, Box<dyn std::error::Error>> {
+    let release = match check_update()? {
+        Some(release) => release,
+        None => return Ok(format!("memlayout {} is up to date", env!("CARGO_PKG_VERSION"))),
+    };
+    let url = release.asset_url.ok_or_else(|| format!("Release {} has no {} asset", release.version, asset_name()))?;
+    let checksum_url = release.checksum_url.ok_or_else(|| format!("Release {} publishes no checksum for {}; not updating", release.version, asset_name()))?;
+
+    let expected = parse_checksum(&String::from_utf8(download(&checksum_url)?)?)?;
+    let binary = download(&url)?;
+    if Sha256::digest(&binary).as_slice() != expected.as_slice() {
+        return Err(format!("{} does not match its published SHA-256; not updating", asset_name()).into());
+    }
+
+    let exe = env::current_exe()?;
+    let staging = exe.with_extension("update");
+    fs::write(&staging, &binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staging, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&staging, &exe)?;
+
+    Ok(format!("Updated memlayout {} -> {}", env!("CARGO_PKG_VERSION"), release.version))
+}
@@ -0,0 +1,93 @@
+use crate::{format_size, region_category, MemoryRegion};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// User address space widths a process can have: 32-bit programs (a
+// compat process gets all 4 GiB), arm64's 39-, 42- and 48-bit VA configs,
+// x86-64's 47 and 5-level paging's 56.
+const ADDRESS_BITS: [u32; 6] = [32, 39, 42, 47, 48, 56];
+// [vsyscall] and such sit in the kernel's half, outside any of them.
+const KERNEL_HALF: usize = if usize::BITS == 64 { (1u64 << 63) as usize } else { usize::MAX };
+
+#[derive(Serialize)]
+pub struct Share {
+    pub bytes: usize,
+    pub percent_of_address_space: f64,
+    pub percent_of_mapped: f64,
+}
+
+// Mapped is anything with an access bit, reserved what is mapped
+// PROT_NONE (guard pages, runtimes' address space reservations), free the
+// rest of the user address space.
+#[derive(Serialize)]
+pub struct Utilization {
+    pub address_bits: u32,
+    pub address_space: usize,
+    pub mapped_bytes: usize,
+    pub reserved_bytes: usize,
+    pub free_bytes: usize,
+    pub mapped_percent: f64,
+    pub reserved_percent: f64,
+    pub free_percent: f64,
+    pub by_category: BTreeMap<&'static str, Share>,
+}
+
+fn percent(bytes: usize, of: usize) -> f64 {
+    if of == 0 {
+        0.0
+    } else {
+        100.0 * bytes as f64 / of as f64
+    }
+}
+
+// The maps don't say how wide the address space is, so the narrowest that
+// holds the highest user mapping stands in for it; a stack sits at its top.
+fn address_bits(highest: usize) -> u32 {
+    ADDRESS_BITS.into_iter().find(|bits| highest <= 1usize << bits).unwrap_or(64)
+}
+
+pub fn utilization(memory_regions: &[MemoryRegion]) -> Utilization {
+    let user: Vec<&MemoryRegion> = memory_regions.iter().filter(|region| region.attributes.allocated && region.start < KERNEL_HALF).collect();
+    let bits = address_bits(user.iter().map(|region| region.end).max().unwrap_or(0));
+    let address_space = if bits >= 64 { usize::MAX } else { 1 << bits };
+    let (mut mapped, mut reserved) = (0, 0);
+    let mut by_category: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for region in &user {
+        let attributes = &region.attributes;
+        if attributes.readable || attributes.writable || attributes.executable {
+            mapped += region.size;
+        } else {
+            reserved += region.size;
+        }
+        *by_category.entry(region_category(region)).or_default() += region.size;
+    }
+    let free = address_space.saturating_sub(mapped + reserved);
+    Utilization {
+        address_bits: bits,
+        address_space,
+        mapped_bytes: mapped,
+        reserved_bytes: reserved,
+        free_bytes: free,
+        mapped_percent: percent(mapped, address_space),
+        reserved_percent: percent(reserved, address_space),
+        free_percent: percent(free, address_space),
+        by_category: by_category
+            .into_iter()
+            .map(|(category, bytes)| (category, Share { bytes, percent_of_address_space: percent(bytes, address_space), percent_of_mapped: percent(bytes, mapped + reserved) }))
+            .collect(),
+    }
+}
+
+impl Utilization {
+    // Shares of a 128 TiB space are tiny, so they keep 6 decimals.
+    pub fn print(&self) {
+        println!("Address space: {} ({}-bit user space, inferred from the highest mapping)", format_size(self.address_space), self.address_bits);
+        println!("  mapped   {:>12} {:>11.6}%", format_size(self.mapped_bytes), self.mapped_percent);
+        println!("  reserved {:>12} {:>11.6}%", format_size(self.reserved_bytes), self.reserved_percent);
+        println!("  free     {:>12} {:>11.6}%", format_size(self.free_bytes), self.free_percent);
+        println!("By category (of the address space, of what is mapped or reserved):");
+        for (category, share) in &self.by_category {
+            println!("  {:<12} {:>12} {:>11.6}% {:>6.1}%", category, format_size(share.bytes), share.percent_of_address_space, share.percent_of_mapped);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{format_size, MemoryAttributes, MemoryRegion};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+
+// Objects above this go to the large object space instead of a regular
+// page (kMaxRegularHeapObjectSize on 64-bit builds).
+const LARGE_OBJECT: u64 = 128 << 10;
+// V8 allocates its heap in pages of this size, aligned to it.
+const PAGE: usize = 256 << 10;
+// Spaces are laid out on this boundary with this much room between them.
+const SPACE_ALIGN: usize = 1 << 20;
+
+// A V8 space: used bytes and, from heap space statistics, what it has
+// committed beyond that.
+pub struct Space {
+    pub name: String,
+    pub used: u64,
+    pub available: u64,
+}
+
+pub struct Heap {
+    pub spaces: Vec<Space>,
+    // Whether the spaces are V8's own or estimated from a heap snapshot.
+    pub estimated: bool,
+}
+
+impl Heap {
+    pub fn used(&self) -> u64 {
+        self.spaces.iter().filter(|space| space.name != "external").map(|space| space.used).sum()
+    }
+
+    pub fn committed(&self) -> u64 {
+        self.spaces.iter().filter(|space| space.name != "external").map(|space| space.used + space.available).sum()
+    }
+
+    pub fn label(&self) -> String {
+        let spaces: Vec<String> = self.spaces.iter().filter(|space| space.used > 0).map(|space| format!("{} {}", space.name, format_size(space.used as usize))).collect();
+        format!("V8 heap: {} used of {} ({})", format_size(self.used() as usize), format_size(self.committed() as usize), spaces.join(", "))
+    }
+}
+
+#[derive(Deserialize)]
+struct SnapshotMeta {
+    node_fields: Vec<String>,
+    node_types: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    meta: SnapshotMeta,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    snapshot: SnapshotHeader,
+    nodes: Vec<u64>,
+}
+
+// A .heapsnapshot has objects, not spaces, so the spaces are estimated the
+// way V8 would place the objects: compiled code in code space, anything
+// over the regular object limit in the large object space and the rest in
+// the old and new spaces, which a snapshot can't tell apart. Native nodes,
+// such as Buffer contents, live outside the heap and count as external.
+fn parse_snapshot(snapshot: Snapshot) -> Result<Heap, String> {
+    let fields = &snapshot.snapshot.meta.node_fields;
+    let position = |name: &str| fields.iter().position(|field| field == name).ok_or_else(|| format!("The heap snapshot nodes have no {} field", name));
+    let (type_field, size_field) = (position("type")?, position("self_size")?);
+    let types: Vec<&str> = snapshot.snapshot.meta.node_types.first().and_then(Value::as_array).map(|types| types.iter().filter_map(Value::as_str).collect()).unwrap_or_default();
+    let mut sizes = [0u64; 4];
+    for node in snapshot.nodes.chunks_exact(fields.len()) {
+        let size = node[size_field];
+        let index = match types.get(node[type_field] as usize).copied() {
+            Some("code") => 1,
+            Some("native") => 3,
+            _ if size > LARGE_OBJECT => 2,
+            _ => 0,
+        };
+        sizes[index] += size;
+    }
+    let names = ["old_space + new_space", "code_space", "large_object_space", "external"];
+    Ok(Heap { spaces: names.iter().zip(sizes).map(|(name, used)| Space { name: name.to_string(), used, available: 0 }).collect(), estimated: true })
+}
+
+// v8.getHeapSpaceStatistics() as JSON: space_name, space_used_size and
+// space_available_size for each space.
+fn parse_statistics(statistics: &[Value]) -> Result<Heap, String> {
+    let spaces: Vec<Space> = statistics
+        .iter()
+        .filter_map(|space| {
+            Some(Space {
+                name: space["space_name"].as_str()?.to_string(),
+                used: space["space_used_size"].as_u64()?,
+                available: space["space_available_size"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect();
+    if spaces.is_empty() {
+        return Err("No spaces in the heap space statistics".to_string());
+    }
+    Ok(Heap { spaces, estimated: false })
+}
+
+pub fn read(path: &str) -> Result<Heap, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    if text.trim_start().starts_with('[') {
+        let statistics: Vec<Value> = serde_json::from_str(&text).map_err(|e| format!("{} is not JSON: {}", path, e))?;
+        return parse_statistics(&statistics);
+    }
+    let snapshot: Snapshot = serde_json::from_str(&text).map_err(|e| format!("{} is neither a .heapsnapshot nor v8.getHeapSpaceStatistics() JSON: {}", path, e))?;
+    parse_snapshot(snapshot)
+}
+
+pub fn print(heap: &Heap) {
+    println!("{:<26} {:>10} {:>10}", "space", "used", "available");
+    for space in &heap.spaces {
+        println!("{:<26} {:>10} {:>10}", space.name, format_size(space.used as usize), format_size(space.available as usize));
+    }
+    if heap.estimated {
+        println!("spaces estimated from the snapshot's objects; v8.getHeapSpaceStatistics() gives V8's own");
+    }
+}
+
+// V8's pages are private anonymous mappings in multiples of its page
+// size, and with pointer compression all within one 4 GiB cage, so the
+// summary goes on the 4 GiB window holding most of them.
+pub fn anchor(memory_regions: &[MemoryRegion]) -> Option<usize> {
+    let pages: Vec<&MemoryRegion> = memory_regions
+        .iter()
+        .filter(|region| region.attributes.allocated && region.file_name.is_none() && region.attributes.writable && !region.attributes.shared)
+        .filter(|region| region.start % PAGE == 0 && region.size % PAGE == 0)
+        .collect();
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for region in &pages {
+        match windows.iter_mut().find(|(window, _)| *window == region.start >> 32) {
+            Some((_, bytes)) => *bytes += region.size,
+            None => windows.push((region.start >> 32, region.size)),
+        }
+    }
+    let (window, _) = windows.into_iter().max_by_key(|(_, bytes)| *bytes)?;
+    pages.iter().find(|region| region.start >> 32 == window).map(|region| region.start)
+}
+
+fn region(start: usize, size: usize, name: String, executable: bool, allocated: bool) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end: start + size,
+        size,
+        attributes: MemoryAttributes { readable: allocated, writable: allocated && !executable, executable, shared: false, allocated },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// Without a process the spaces are drawn on their own, as espidf.rs does:
+// made-up addresses, each space its used bytes followed by what it has
+// available.
+pub fn layout(heap: &Heap) -> Vec<MemoryRegion> {
+    let mut memory_regions = Vec::new();
+    let mut base = 0;
+    for space in heap.spaces.iter().filter(|space| space.used + space.available > 0) {
+        let executable = space.name.starts_with("code");
+        let (used, available) = (space.used as usize, space.available as usize);
+        if used > 0 {
+            memory_regions.push(region(base, used, format!("{} used", space.name), executable, true));
+        }
+        if available > 0 {
+            memory_regions.push(region(base + used, available, format!("{} available", space.name), false, false));
+        }
+        base = (base + used + available).next_multiple_of(SPACE_ALIGN) + SPACE_ALIGN;
+    }
+    memory_regions
+}
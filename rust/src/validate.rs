@@ -0,0 +1,197 @@
+use crate::report::hex;
+use crate::MemoryRegion;
+use serde::{Deserialize, Serialize};
+
+// The most skipped lines listed for one input; one in the wrong format
+// can have millions.
+const MAX_WARNINGS: usize = 1000;
+
+// A line of maps or smaps input that didn't parse, numbered from 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub line: usize,
+    pub reason: String,
+    pub text: String,
+}
+
+// A region validate() dropped, clipped or moved, with its range as given.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionWarning {
+    pub kind: &'static str,
+    #[serde(serialize_with = "hex")]
+    pub start: usize,
+    #[serde(serialize_with = "hex")]
+    pub end: usize,
+    pub message: String,
+}
+
+// What went wrong reading one input, returned with its regions so each
+// capture reports its own.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Warnings {
+    // Every line skipped, of which the first MAX_WARNINGS are listed.
+    pub skipped_lines: usize,
+    pub parse_warnings: Vec<ParseWarning>,
+    pub region_warnings: Vec<RegionWarning>,
+}
+
+impl Warnings {
+    // Logs a line the parser gives up on and keeps it for the report.
+    pub fn skip(&mut self, what: &str, line: usize, reason: &str, text: &str) {
+        tracing::warn!(line, text, "skipping {} line: {}", what, reason);
+        self.skipped_lines += 1;
+        if self.parse_warnings.len() < MAX_WARNINGS {
+            self.parse_warnings.push(ParseWarning { line, reason: reason.to_string(), text: text.to_string() });
+        }
+    }
+
+    // For --strict: a dump in an unexpected format shouldn't quietly turn
+    // into a map with regions missing.
+    pub fn strict(&self) -> Result<(), String> {
+        match self.parse_warnings.first() {
+            Some(first) => Err(format!("{} input lines did not parse, the first at line {}: {}: {}", self.skipped_lines, first.line, first.reason, first.text)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn print(&self) {
+        if self.skipped_lines > 0 {
+            println!("Skipped {} unparseable input lines:", self.skipped_lines);
+            for warning in &self.parse_warnings {
+                println!("  line {}: {}: {}", warning.line, warning.reason, warning.text);
+            }
+            if self.skipped_lines > self.parse_warnings.len() {
+                println!("  and {} more", self.skipped_lines - self.parse_warnings.len());
+            }
+        }
+        if !self.region_warnings.is_empty() {
+            println!("Repaired {} problems in the region list:", self.region_warnings.len());
+            for warning in &self.region_warnings {
+                println!("  {:#x}-{:#x} {}: {}", warning.start, warning.end, warning.kind, warning.message);
+            }
+        }
+    }
+}
+
+// What a region list from a core dump, a snapshot or someone else's tool
+// can get wrong that /proc/PID/maps never does. Each is logged and
+// returned as a warning with the range involved, and repaired, so the map
+// shows what the input most likely meant rather than bars drawn over each
+// other.
+//
+// Regions whose end is before their start, or equal to it, are dropped.
+// Input out of address order is sorted. A region overlapping the one
+// before it loses the overlapping part, and all of itself when the one
+// before covers it.
+pub fn validate(memory_regions: Vec<MemoryRegion>) -> (Vec<MemoryRegion>, Vec<RegionWarning>) {
+    let mut warnings = Vec::new();
+    let mut warn = |kind: &'static str, start: usize, end: usize, message: String| {
+        tracing::warn!(kind, start = %format!("{:#x}", start), end = %format!("{:#x}", end), "{}", message);
+        warnings.push(RegionWarning { kind, start, end, message });
+    };
+
+    let mut valid = Vec::with_capacity(memory_regions.len());
+    for region in memory_regions {
+        if region.end < region.start {
+            warn("inverted", region.start, region.end, "dropped a region that ends before it starts".to_string());
+        } else if region.end == region.start {
+            warn("empty", region.start, region.end, "dropped a zero-size region".to_string());
+        } else {
+            valid.push(region);
+        }
+    }
+
+    let out_of_order = valid.windows(2).filter(|pair| pair[1].start < pair[0].start).count();
+    if out_of_order > 0 {
+        let first = &valid[valid.windows(2).position(|pair| pair[1].start < pair[0].start).unwrap() + 1];
+        warn("out-of-order", first.start, first.end, format!("sorted the regions, {} of them listed before a lower address", out_of_order));
+        valid.sort_by_key(|region| region.start);
+    }
+
+    let mut repaired: Vec<MemoryRegion> = Vec::with_capacity(valid.len());
+    for mut region in valid {
+        let Some(previous) = repaired.last().filter(|previous| region.start < previous.end) else {
+            repaired.push(region);
+            continue;
+        };
+        if region.end <= previous.end {
+            warn("overlap", region.start, region.end, format!("dropped a region inside {:#x}-{:#x}", previous.start, previous.end));
+            continue;
+        }
+        warn("overlap", region.start, region.end, format!("clipped a region to start at {:#x}, where {:#x}-{:#x} ends", previous.end, previous.start, previous.end));
+        let clipped = previous.end - region.start;
+        region.start = previous.end;
+        region.size = region.end - region.start;
+        if region.file_name.is_some() {
+            region.offset += clipped;
+        }
+        repaired.push(region);
+    }
+    (repaired, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(start: usize, end: usize) -> MemoryRegion {
+        let line = "00001000-00002000 rw-p 00000000 00:00 0";
+        MemoryRegion { start, end, size: end.wrapping_sub(start), ..line.parse().unwrap() }
+    }
+
+    fn ranges(memory_regions: &[MemoryRegion]) -> Vec<(usize, usize)> {
+        memory_regions.iter().map(|region| (region.start, region.end)).collect()
+    }
+
+    #[test]
+    fn valid_input_is_unchanged() {
+        let (repaired, warnings) = validate(vec![region(0x1000, 0x2000), region(0x2000, 0x3000), region(0x5000, 0x6000)]);
+        assert_eq!(ranges(&repaired), [(0x1000, 0x2000), (0x2000, 0x3000), (0x5000, 0x6000)]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn drops_inverted_regions() {
+        let (repaired, warnings) = validate(vec![region(0x1000, 0x2000), region(0x4000, 0x3000)]);
+        assert_eq!(ranges(&repaired), [(0x1000, 0x2000)]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].kind, warnings[0].start, warnings[0].end), ("inverted", 0x4000, 0x3000));
+    }
+
+    #[test]
+    fn drops_empty_regions() {
+        let (repaired, warnings) = validate(vec![region(0x1000, 0x1000), region(0x2000, 0x3000)]);
+        assert_eq!(ranges(&repaired), [(0x2000, 0x3000)]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].kind, warnings[0].start), ("empty", 0x1000));
+    }
+
+    #[test]
+    fn sorts_out_of_order_input() {
+        let (repaired, warnings) = validate(vec![region(0x3000, 0x4000), region(0x1000, 0x2000), region(0x5000, 0x6000)]);
+        assert_eq!(ranges(&repaired), [(0x1000, 0x2000), (0x3000, 0x4000), (0x5000, 0x6000)]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].kind, warnings[0].start), ("out-of-order", 0x1000));
+    }
+
+    #[test]
+    fn clips_a_region_overlapping_the_one_before() {
+        let mut mapped = region(0x1800, 0x3000);
+        mapped.file_name = Some("/usr/lib/libc.so.6".to_string());
+        mapped.offset = 0x10000;
+        let (repaired, warnings) = validate(vec![region(0x1000, 0x2000), mapped]);
+        assert_eq!(ranges(&repaired), [(0x1000, 0x2000), (0x2000, 0x3000)]);
+        assert_eq!(repaired[1].size, 0x1000);
+        assert_eq!(repaired[1].offset, 0x10800);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].kind, warnings[0].start, warnings[0].end), ("overlap", 0x1800, 0x3000));
+    }
+
+    #[test]
+    fn drops_a_region_inside_the_one_before() {
+        let (repaired, warnings) = validate(vec![region(0x1000, 0x4000), region(0x2000, 0x3000), region(0x4000, 0x5000)]);
+        assert_eq!(ranges(&repaired), [(0x1000, 0x4000), (0x4000, 0x5000)]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].kind, warnings[0].start, warnings[0].end), ("overlap", 0x2000, 0x3000));
+    }
+}
@@ -0,0 +1,22 @@
+use std::process::{Command, Stdio};
+
+// Hands a file or URL to the desktop's default viewer. The viewer is left
+// running; a thread reaps the launcher so a long-running serve doesn't
+// collect a zombie.
+pub fn open(target: &str) {
+    let (program, args): (&str, Vec<&str>) = if cfg!(target_os = "macos") {
+        ("open", vec![target])
+    } else if cfg!(windows) {
+        // start takes its first quoted argument as the window title.
+        ("cmd", vec!["/C", "start", "", target])
+    } else {
+        ("xdg-open", vec![target])
+    };
+    let launched = Command::new(program).args(&args).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+    match launched {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => tracing::warn!("unable to open {} with {}: {}", target, program, e),
+    }
+}
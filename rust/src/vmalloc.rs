@@ -0,0 +1,68 @@
+use crate::symbols::parse_address;
+use crate::{MemoryAttributes, MemoryRegion};
+use std::collections::BTreeMap;
+use std::fs;
+
+// Lines look like "0xffffc90000000000-0xffffc90000005000   20480
+// start_kernel+0x4b4/0x6e0 pages=4 vmalloc N0=4"; the caller is missing for
+// some areas, such as those not yet purged.
+fn parse_line(line: &str) -> Option<MemoryRegion> {
+    let mut fields = line.split_whitespace();
+    let (start, end) = fields.next()?.split_once('-')?;
+    let (start, end) = (parse_address(start).ok()?, parse_address(end).ok()?);
+    let size: usize = fields.next()?.parse().ok()?;
+    let rest: Vec<&str> = fields.collect();
+    let caller = rest.first().filter(|field| !field.contains('=') && !is_kind(field)).map(|caller| caller.split('+').next().unwrap_or(caller));
+    let ioremap = rest.contains(&"ioremap");
+    // Callers become anonymous names, so each one gets its own color.
+    let name = format!("[anon:{}]", caller.unwrap_or("unknown"));
+    Some(MemoryRegion {
+        start,
+        end,
+        size: size.min(end.saturating_sub(start)),
+        attributes: MemoryAttributes { readable: true, writable: true, executable: false, shared: ioremap, allocated: true },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    })
+}
+
+fn is_kind(field: &str) -> bool {
+    matches!(field, "ioremap" | "vmalloc" | "vmap" | "user" | "vpages" | "unpurged" | "vm_map_ram")
+}
+
+// Readable only by root; others get an empty file or zeroed addresses.
+pub fn read_vmallocinfo(path: &str) -> Result<Vec<MemoryRegion>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut memory_regions: Vec<MemoryRegion> = text.lines().filter_map(parse_line).filter(|region| region.start != 0).collect();
+    if memory_regions.is_empty() {
+        return Err(format!("{} lists no allocations; it needs root", path));
+    }
+    memory_regions.sort_by_key(|region| region.start);
+    // Areas never overlap, but lazily freed ones can still be listed.
+    memory_regions.dedup_by_key(|region| region.start);
+    Ok(memory_regions)
+}
+
+pub struct CallerTotal {
+    pub caller: String,
+    pub allocations: usize,
+    pub bytes: usize,
+}
+
+pub fn caller_totals(memory_regions: &[MemoryRegion]) -> Vec<CallerTotal> {
+    let mut totals: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for region in memory_regions {
+        let entry = totals.entry(region.anon_name().unwrap_or("unknown")).or_default();
+        entry.0 += 1;
+        entry.1 += region.size;
+    }
+    let mut totals: Vec<CallerTotal> = totals.into_iter().map(|(caller, (allocations, bytes))| CallerTotal { caller: caller.to_string(), allocations, bytes }).collect();
+    totals.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.caller.cmp(&b.caller)));
+    totals
+}
@@ -0,0 +1,172 @@
+use crate::{carve, MemoryAttributes, MemoryRegion};
+use object::elf::{FileHeader32, FileHeader64, PT_LOAD};
+use object::read::elf::{ElfFile, FileHeader, ProgramHeader};
+use object::read::ReadCache;
+use object::{Endianness, FileKind};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+// What a crashed kernel left behind: its physical memory by what the dump
+// holds of it, and the kernel release it ran.
+pub struct Vmcore {
+    pub memory_regions: Vec<MemoryRegion>,
+    pub release: Option<String>,
+}
+
+// Where x86_64 maps the kernel image, apart from the direct map.
+const START_KERNEL_MAP: u64 = 0xffff_ffff_8000_0000;
+
+// Dumped pages are drawn as RAM; pages the dump filter left out (free,
+// cache, zero or user pages, by dump level) have nothing to read.
+fn region(start: u64, end: u64, name: &str) -> MemoryRegion {
+    let (readable, writable, executable) = match name {
+        "[kernel text]" => (true, false, true),
+        "[excluded]" => (false, false, false),
+        _ => (true, true, false),
+    };
+    MemoryRegion {
+        start: start as usize,
+        end: end as usize,
+        size: (end - start) as usize,
+        attributes: MemoryAttributes { readable, writable, executable, shared: false, allocated: true },
+        offset: 0,
+        device: (0, 0),
+        inode: 0,
+        file_name: Some(name.to_string()),
+        thread_id: None,
+        guard: false,
+        smaps: None,
+        mappings: 1,
+    }
+}
+
+// "OSRELEASE=6.1.0-18-amd64" among the KEY=VALUE lines.
+fn vmcoreinfo_value<'a>(vmcoreinfo: &'a str, key: &str) -> Option<&'a str> {
+    vmcoreinfo.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+}
+
+// /proc/vmcore as saved by cp or makedumpfile -E: a PT_LOAD per range of
+// physical memory, p_filesz short of p_memsz where pages were left out.
+// The segment that maps the kernel image at its own virtual address
+// rather than through the direct map is drawn as the kernel text.
+fn parse_elf<Elf: FileHeader<Endian = Endianness>>(file: &File) -> Result<Vmcore, String> {
+    let cache = ReadCache::new(file);
+    let elf = ElfFile::<Elf, &ReadCache<&File>>::parse(&cache).map_err(|e| format!("Invalid ELF vmcore: {}", e))?;
+    let endian = elf.endian();
+    let mut vmcoreinfo = None;
+    let mut ram = Vec::new();
+    let mut text = Vec::new();
+    for header in elf.elf_program_headers() {
+        if let Ok(Some(mut notes)) = header.notes(endian, elf.data()) {
+            while let Ok(Some(note)) = notes.next() {
+                if note.name() == b"VMCOREINFO" {
+                    vmcoreinfo = Some(String::from_utf8_lossy(note.desc()).into_owned());
+                }
+            }
+        }
+        if header.p_type(endian) != PT_LOAD || header.p_memsz(endian).into() == 0 {
+            continue;
+        }
+        let (start, size, file_size, virtual_start) = (header.p_paddr(endian).into(), header.p_memsz(endian).into(), header.p_filesz(endian).into(), header.p_vaddr(endian).into());
+        if virtual_start >= START_KERNEL_MAP {
+            text.push(region(start, start + size, "[kernel text]"));
+            continue;
+        }
+        let dumped = file_size.min(size);
+        if dumped > 0 {
+            ram.push(region(start, start + dumped, "[System RAM]"));
+        }
+        if dumped < size {
+            ram.push(region(start + dumped, start + size, "[excluded]"));
+        }
+    }
+    if ram.is_empty() && text.is_empty() {
+        return Err("The ELF file has no PT_LOAD segments; is it a vmcore?".to_string());
+    }
+    let mut memory_regions: Vec<MemoryRegion> = ram.iter().flat_map(|region| carve(region, &text)).collect();
+    memory_regions.extend(text);
+    memory_regions.sort_by_key(|region| region.start);
+    let release = vmcoreinfo.as_deref().and_then(|info| vmcoreinfo_value(info, "OSRELEASE")).map(str::to_string);
+    Ok(Vmcore { memory_regions, release })
+}
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![0; length];
+    file.seek(SeekFrom::Start(offset)).and_then(|_| file.read_exact(&mut buffer)).map_err(|e| format!("Truncated dump file: {}", e))?;
+    Ok(buffer)
+}
+
+fn field(bytes: &[u8], offset: usize, width: usize) -> u64 {
+    bytes[offset..offset + width].iter().rev().fold(0, |value, byte| value << 8 | *byte as u64)
+}
+
+// makedumpfile's kdump-compressed format (and the older diskdump): a
+// header block, a sub header, then two bitmaps a bit per page frame, the
+// first marking frames that are RAM and the second those written out.
+// Laid out as makedumpfile on a little-endian 64-bit machine writes it.
+fn parse_kdump(file: &mut File) -> Result<Vmcore, String> {
+    let header = read_at(file, 0, 448)?;
+    let version = field(&header, 8, 4);
+    let release = String::from_utf8_lossy(&header[142..207]).trim_end_matches('\0').to_string();
+    let block_size = field(&header, 428, 4);
+    let (sub_header_blocks, bitmap_blocks) = (field(&header, 432, 4), field(&header, 436, 4));
+    if !block_size.is_power_of_two() || block_size < 512 {
+        return Err(format!("Invalid kdump block size {}", block_size));
+    }
+    // max_mapnr is only 32 bits in the header; from version 6 the sub
+    // header has all 64.
+    let mut frames = field(&header, 440, 4);
+    if version >= 6 {
+        frames = field(&read_at(file, block_size, 104)?, 96, 8);
+    }
+    let bitmap = read_at(file, block_size * (1 + sub_header_blocks), (bitmap_blocks * block_size) as usize)?;
+    let (valid, dumped) = bitmap.split_at(bitmap.len() / 2);
+    let frames = frames.min(valid.len() as u64 * 8);
+    let state = |frame: u64| {
+        let bit = |map: &[u8]| map[(frame / 8) as usize] >> (frame % 8) & 1 == 1;
+        match (bit(valid), bit(dumped)) {
+            (_, true) => Some("[System RAM]"),
+            (true, false) => Some("[excluded]"),
+            _ => None,
+        }
+    };
+    let mut memory_regions: Vec<MemoryRegion> = Vec::new();
+    let mut frame = 0;
+    while frame < frames {
+        // Whole bytes of the same state at a time; a large machine has
+        // hundreds of millions of frames.
+        let byte = (frame / 8) as usize;
+        let step = if frame % 8 == 0 && frame + 8 <= frames && matches!((valid[byte], dumped[byte]), (0, 0) | (0xff, 0xff) | (0xff, 0)) { 8 } else { 1 };
+        if let Some(name) = state(frame) {
+            let (start, end) = (frame * block_size, (frame + step) * block_size);
+            match memory_regions.last_mut() {
+                Some(last) if last.end as u64 == start && last.file_name.as_deref() == Some(name) => {
+                    last.end = end as usize;
+                    last.size = last.end - last.start;
+                }
+                _ => memory_regions.push(region(start, end, name)),
+            }
+        }
+        frame += step;
+    }
+    if memory_regions.is_empty() {
+        return Err("The kdump bitmaps mark no memory".to_string());
+    }
+    Ok(Vmcore { memory_regions, release: Some(release).filter(|release| !release.is_empty()) })
+}
+
+pub fn read(path: &str) -> Result<Vmcore, String> {
+    let mut file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path, e))?;
+    let magic = read_at(&mut file, 0, 16)?;
+    if magic.starts_with(b"KDUMP   ") || magic.starts_with(b"DISKDUMP") {
+        return parse_kdump(&mut file);
+    }
+    if magic.starts_with(b"makedumpfile") {
+        return Err(format!("{} is in makedumpfile's flattened format; rearrange it first with makedumpfile -R", path));
+    }
+    match FileKind::parse(&magic[..]) {
+        Ok(FileKind::Elf64) => parse_elf::<FileHeader64<Endianness>>(&file),
+        Ok(FileKind::Elf32) => parse_elf::<FileHeader32<Endianness>>(&file),
+        _ => Err(format!("{} is neither an ELF vmcore nor kdump-compressed", path)),
+    }
+}
@@ -0,0 +1,16 @@
+// Exports for the browser build (wasm-pack build --target web -- --no-default-features),
+// which only sees text pasted into the page, never /proc.
+use crate::{parse_memory_regions, validate};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub fn render_svg(maps: &str, width: u32, height: u32) -> Result<String, JsValue> {
+    crate::render_svg(maps, width, height).map_err(|e| JsValue::from_str(&e))
+}
+
+// The parsed regions as a JSON array, for the page's own tables.
+#[wasm_bindgen]
+pub fn parse_maps(maps: &str) -> Result<String, JsValue> {
+    let (memory_regions, _) = validate::validate(parse_memory_regions(maps.as_bytes()).0);
+    serde_json::to_string(&memory_regions).map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,228 @@
+use crate::{format_size, terminal, MemoryAttributes, MemoryRegion};
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::Command;
+
+// Where interop usually finds PowerShell when the Windows PATH isn't
+// appended to WSL's (appendWindowsPath=false in wsl.conf).
+const POWERSHELL: [&str; 2] = ["powershell.exe", "/mnt/c/Windows/System32/WindowsPowerShell/v1.0/powershell.exe"];
+
+// Walks a Windows process's address space with VirtualQueryEx, one line
+// per region that isn't free: base, size, state, protection and type in
+// hex, then the mapped file with its volume device turned back into a
+// drive letter. The first line is "pid ID NAME".
+const WALKER: &str = r#"$ErrorActionPreference = 'Stop'
+Add-Type -TypeDefinition @'
+using System;
+using System.Collections.Generic;
+using System.Runtime.InteropServices;
+using System.Text;
+
+public static class MemoryWalker {
+    [StructLayout(LayoutKind.Sequential)]
+    struct Info {
+        public ulong BaseAddress;
+        public ulong AllocationBase;
+        public uint AllocationProtect;
+        public uint Alignment1;
+        public ulong RegionSize;
+        public uint State;
+        public uint Protect;
+        public uint Type;
+        public uint Alignment2;
+    }
+
+    [DllImport("kernel32.dll", SetLastError = true)]
+    static extern IntPtr OpenProcess(uint access, bool inherit, int pid);
+    [DllImport("kernel32.dll")]
+    static extern UIntPtr VirtualQueryEx(IntPtr process, IntPtr address, out Info info, UIntPtr length);
+    [DllImport("kernel32.dll", CharSet = CharSet.Unicode)]
+    static extern uint K32GetMappedFileNameW(IntPtr process, IntPtr address, StringBuilder name, uint size);
+    [DllImport("kernel32.dll", CharSet = CharSet.Unicode)]
+    static extern uint QueryDosDeviceW(string device, StringBuilder target, int size);
+
+    public static string Walk(int pid) {
+        IntPtr process = OpenProcess(0x0410, false, pid);
+        if (process == IntPtr.Zero) throw new System.ComponentModel.Win32Exception();
+        var drives = new Dictionary<string, string>();
+        foreach (var drive in Environment.GetLogicalDrives()) {
+            var letter = drive.TrimEnd('\\');
+            var device = new StringBuilder(1024);
+            if (QueryDosDeviceW(letter, device, 1024) != 0) drives[device.ToString() + "\\"] = letter + "\\";
+        }
+        var output = new StringBuilder();
+        var name = new StringBuilder(1024);
+        Info info;
+        ulong address = 0;
+        while (VirtualQueryEx(process, new IntPtr((long)address), out info, new UIntPtr((uint)Marshal.SizeOf(typeof(Info)))) != UIntPtr.Zero) {
+            if (info.State != 0x10000) {
+                string path = "";
+                name.Clear();
+                if (info.Type != 0x20000 && K32GetMappedFileNameW(process, new IntPtr((long)info.BaseAddress), name, 1024) != 0) {
+                    path = name.ToString();
+                    foreach (var drive in drives) {
+                        if (path.StartsWith(drive.Key)) {
+                            path = drive.Value + path.Substring(drive.Key.Length);
+                            break;
+                        }
+                    }
+                }
+                output.AppendFormat("{0:x} {1:x} {2:x} {3:x} {4:x} {5}\n", info.BaseAddress, info.RegionSize, info.State, info.Protect, info.Type, path);
+            }
+            address = info.BaseAddress + info.RegionSize;
+        }
+        return output.ToString();
+    }
+}
+'@
+$target = 'TARGET'
+$process = if ($target -match '^\d+$') { Get-Process -Id $target } else { Get-Process -Name $target | Select-Object -First 1 }
+"pid $($process.Id) $($process.ProcessName)"
+[Console]::Out.Write([MemoryWalker]::Walk($process.Id))
+"#;
+
+const MEM_COMMIT: usize = 0x1000;
+const MEM_MAPPED: usize = 0x40000;
+const PAGE_GUARD: usize = 0x100;
+
+// Running inside WSL: its kernel says so in its release, and the
+// distribution's name is in the environment of anything started by wsl.exe.
+pub fn is_wsl() -> bool {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default().to_lowercase();
+    release.contains("microsoft") || std::env::var_os("WSL_DISTRO_NAME").is_some()
+}
+
+// The mounts of Windows directories a process sees: /mnt/c and such, and
+// /usr/lib/wsl for the drivers, as (mount point, Windows path). WSL2
+// serves them over 9p (or virtiofs), naming the Windows side in the
+// superblock options: "aname=drvfs;path=C:\;uid=1000".
+pub fn windows_mounts(pid: u32) -> Vec<(String, String)> {
+    let text = fs::read_to_string(format!("/proc/{}/mountinfo", pid)).unwrap_or_default();
+    let mut mounts = Vec::new();
+    for line in text.lines() {
+        let Some((fields, filesystem)) = line.split_once(" - ") else { continue };
+        let mut filesystem = filesystem.split_whitespace();
+        let (Some(kind), Some(source), options) = (filesystem.next(), filesystem.next(), filesystem.next().unwrap_or("")) else { continue };
+        if kind != "9p" && kind != "virtiofs" && kind != "drvfs" {
+            continue;
+        }
+        let Some(mount_point) = fields.split_whitespace().nth(4) else { continue };
+        let path = options.split([';', ',']).find_map(|option| option.strip_prefix("path=")).unwrap_or(source);
+        mounts.push((mount_point.replace("\\040", " "), path.to_string()));
+    }
+    // Longest first, so nested mounts win.
+    mounts.sort_by_key(|(mount_point, _)| std::cmp::Reverse(mount_point.len()));
+    mounts
+}
+
+// "/mnt/c/Users/me/a.dll" as "C:\Users\me\a.dll".
+fn windows_path(path: &str, mounts: &[(String, String)]) -> Option<String> {
+    let (mount_point, windows) = mounts.iter().find(|(mount_point, _)| (path.starts_with(mount_point.as_str()) && path[mount_point.len()..].starts_with('/')) || path == mount_point)?;
+    let rest = path[mount_point.len()..].trim_start_matches('/').replace('/', "\\");
+    Some(format!("{}\\{}", windows.trim_end_matches('\\'), rest))
+}
+
+// What reads differently in WSL2: files mapped from Windows drives come
+// over 9p, where every page fault is a round trip to the host and shared
+// writable mappings aren't kept coherent with Windows programs; and a PID
+// whose executable is /init is WSL's interop relay for a Windows program,
+// whose memory isn't in this map at all.
+pub fn report(pid: u32, memory_regions: &[MemoryRegion]) {
+    if fs::read_link(format!("/proc/{}/exe", pid)).is_ok_and(|exe| exe.as_os_str() == "/init") {
+        eprintln!("PID {} is WSL's interop relay for a Windows program; --source windows:NAME maps the program itself", pid);
+    }
+    let mounts = windows_mounts(pid);
+    if mounts.is_empty() {
+        return;
+    }
+    let mut files: BTreeMap<String, (usize, bool)> = BTreeMap::new();
+    for region in memory_regions {
+        let Some(path) = region.file_name.as_deref().and_then(|path| windows_path(path, &mounts)) else { continue };
+        let file = files.entry(path).or_default();
+        file.0 += region.size;
+        file.1 |= region.attributes.shared && region.attributes.writable;
+    }
+    if files.is_empty() {
+        return;
+    }
+    println!("{} mapped from {} Windows files over 9p, faulted in through the host:", format_size(files.values().map(|(bytes, _)| bytes).sum()), files.len());
+    for (path, (bytes, shared_writable)) in &files {
+        let note = if *shared_writable { " (shared and writable: Windows programs won't see the writes)" } else { "" };
+        println!("{:>10}  {}{}", format_size(*bytes), path, note);
+    }
+}
+
+// Only a PID or a bare process name goes into the script.
+fn valid_target(target: &str) -> bool {
+    !target.is_empty() && target.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+}
+
+fn powershell(script: &str) -> Result<String, String> {
+    // -EncodedCommand takes the script as base64 of UTF-16LE; given on
+    // stdin instead, PowerShell runs it line by line and the here-string
+    // breaks.
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let encoded = terminal::base64(&utf16);
+    let mut errors = Vec::new();
+    for program in POWERSHELL {
+        match Command::new(program).args(["-NoProfile", "-NonInteractive", "-EncodedCommand", &encoded]).output() {
+            Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            Ok(output) => return Err(format!("PowerShell failed: {}", String::from_utf8_lossy(&output.stderr).lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim())),
+            Err(e) => errors.push(format!("{}: {}", program, e)),
+        }
+    }
+    Err(format!("Unable to run PowerShell through WSL interop ({})", errors.join("; ")))
+}
+
+// VirtualQueryEx's protection constants as read, write and execute.
+fn permissions(protect: usize) -> (bool, bool, bool) {
+    match protect & 0xff {
+        0x02 => (true, false, false),
+        0x04 | 0x08 => (true, true, false),
+        0x10 => (false, false, true),
+        0x20 => (true, false, true),
+        0x40 | 0x80 => (true, true, true),
+        _ => (false, false, false),
+    }
+}
+
+// The walker's lines as regions. Reserved memory has no access, like a
+// PROT_NONE reservation; image and mapped views keep their file.
+fn parse(text: &str) -> Result<(u32, String, Vec<MemoryRegion>), String> {
+    let mut lines = text.lines();
+    let header = lines.next().and_then(|line| line.trim().strip_prefix("pid ")).ok_or("No output from the Windows memory walker")?;
+    let (pid, name) = header.split_once(' ').unwrap_or((header, ""));
+    let pid = pid.parse().map_err(|_| format!("Invalid walker header: {}", header))?;
+    let mut memory_regions = Vec::new();
+    for line in lines.map(str::trim_end).filter(|line| !line.is_empty()) {
+        let mut fields = line.splitn(6, ' ');
+        let mut number = || fields.next().and_then(|field| usize::from_str_radix(field, 16).ok()).ok_or_else(|| format!("Invalid walker line: {}", line));
+        let (start, size, state, protect, kind) = (number()?, number()?, number()?, number()?, number()?);
+        let path = fields.next().filter(|path| !path.is_empty());
+        let (readable, writable, executable) = if state == MEM_COMMIT { permissions(protect) } else { (false, false, false) };
+        memory_regions.push(MemoryRegion {
+            start,
+            end: start + size,
+            size,
+            attributes: MemoryAttributes { readable, writable, executable, shared: kind == MEM_MAPPED && protect & 0xff != 0x08, allocated: true },
+            offset: 0,
+            device: (0, 0),
+            inode: 0,
+            file_name: path.map(str::to_string),
+            thread_id: None,
+            guard: protect & PAGE_GUARD != 0,
+            smaps: None,
+            mappings: 1,
+        });
+    }
+    Ok((pid, name.trim().to_string(), memory_regions))
+}
+
+// A Windows process's map, seen from inside WSL: its PID or image name
+// without .exe, as Get-Process takes them.
+pub fn windows_process(target: &str) -> Result<(u32, String, Vec<MemoryRegion>), String> {
+    if !valid_target(target) {
+        return Err(format!("Invalid Windows process: {}; give its PID or its name without .exe", target));
+    }
+    parse(&powershell(&WALKER.replace("'TARGET'", &format!("'{}'", target.trim_end_matches(".exe"))))?)
+}
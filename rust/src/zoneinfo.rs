@@ -0,0 +1,232 @@
+use crate::{format_size, scaled, RenderOptions};
+use plotters::prelude::*;
+use std::fs;
+
+const CHART_WIDTH: i32 = 900;
+const ROW_HEIGHT: i32 = 62;
+const TREND_HEIGHT: i32 = 220;
+
+// A zone's free pages against its watermarks, all in pages.
+pub struct Zone {
+    // "Node 0 Normal".
+    pub name: String,
+    pub free: usize,
+    pub min: usize,
+    pub low: usize,
+    pub high: usize,
+    // Raised on fragmentation events and added to every watermark until
+    // kswapd has reclaimed it.
+    pub boost: usize,
+    pub managed: usize,
+}
+
+impl Zone {
+    // What reclaim does at this level of free pages: kswapd wakes below
+    // low and reclaims up to high; below min allocations reclaim directly.
+    pub fn state(&self) -> &'static str {
+        if self.free < self.min + self.boost {
+            "below min, direct reclaim"
+        } else if self.free < self.low + self.boost {
+            "below low, kswapd woken"
+        } else if self.free < self.high + self.boost {
+            "under high, kswapd reclaims up to it"
+        } else {
+            "ok"
+        }
+    }
+
+    fn state_color(&self) -> RGBColor {
+        match self.state() {
+            "ok" => RGBColor(60, 170, 80),
+            "under high, kswapd reclaims up to it" => RGBColor(230, 200, 40),
+            "below low, kswapd woken" => RGBColor(240, 130, 30),
+            _ => RGBColor(210, 40, 40),
+        }
+    }
+}
+
+// "Node 0, zone   Normal" starts each zone; "pages free N" and the
+// indented "min", "low", "high", "boost" and "managed" lines follow. The
+// per-node stats of the first zone of a node come in between and are
+// skipped. Zones without managed pages, such as an empty Movable, are left
+// out.
+pub fn parse(text: &str) -> Result<Vec<Zone>, String> {
+    let mut zones: Vec<Zone> = Vec::new();
+    for line in text.lines() {
+        if let Some((node, zone)) = line.split_once(", zone") {
+            zones.push(Zone { name: format!("{} {}", node.trim(), zone.trim()), free: 0, min: 0, low: 0, high: 0, boost: 0, managed: 0 });
+            continue;
+        }
+        let Some(zone) = zones.last_mut() else { continue };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (key, value) = match fields.as_slice() {
+            ["pages", "free", value] => ("free", value),
+            [key, value] => (*key, value),
+            _ => continue,
+        };
+        let Ok(value) = value.parse::<usize>() else { continue };
+        match key {
+            "free" => zone.free = value,
+            "min" => zone.min = value,
+            "low" => zone.low = value,
+            "high" => zone.high = value,
+            "boost" => zone.boost = value,
+            "managed" => zone.managed = value,
+            _ => {}
+        }
+    }
+    zones.retain(|zone| zone.managed > 0);
+    if zones.is_empty() {
+        return Err("No populated zones in zoneinfo".to_string());
+    }
+    Ok(zones)
+}
+
+pub fn read(path: &str) -> Result<Vec<Zone>, String> {
+    parse(&fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?)
+}
+
+pub fn print(zones: &[Zone], page_size: usize) {
+    println!("{:<16} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  state", "zone", "managed", "free", "min", "low", "high", "boost");
+    for zone in zones {
+        let size = |pages: usize| format_size(pages * page_size);
+        println!("{:<16} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  {}", zone.name, size(zone.managed), size(zone.free), size(zone.min), size(zone.low), size(zone.high), size(zone.boost), zone.state());
+    }
+}
+
+// A bar per zone of its free pages, colored by state, with min, low and
+// high (boost included) marked across it. The scale stops at twenty times
+// high so the watermarks stay apart; an arrow marks free pages past it.
+// Below, once there is more than one sample, each zone's free pages as a
+// multiple of its high watermark over the seconds sampled.
+pub fn draw(zones: &[Zone], page_size: usize, trend: &[(f64, Vec<f64>)], options: &RenderOptions, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let px = |v: i32| scaled(v, options.scale_factor);
+    let rows_top = 40;
+    let trend_top = rows_top + zones.len() as i32 * ROW_HEIGHT + 10;
+    let height = px(trend_top + if trend.len() > 1 { TREND_HEIGHT } else { 0 } + 10);
+    let root = BitMapBackend::new(path, (px(CHART_WIDTH) as u32, height as u32)).into_drawing_area();
+    root.fill(&options.background())?;
+    let ink = options.foreground();
+    let font = FontDesc::new(FontFamily::SansSerif, 10.0 * options.scale_factor, FontStyle::Normal);
+    let bold = FontDesc::new(FontFamily::SansSerif, 11.0 * options.scale_factor, FontStyle::Bold);
+    root.draw(&Text::new("zone free pages against watermarks", (px(10), px(8)), FontDesc::new(FontFamily::SansSerif, 14.0 * options.scale_factor, FontStyle::Bold).color(&ink)))?;
+
+    let (left, right) = (px(10), px(CHART_WIDTH - 20));
+    for (row, zone) in zones.iter().enumerate() {
+        let y = px(rows_top + row as i32 * ROW_HEIGHT);
+        let title = format!("{}: {} free of {} ({})", zone.name, format_size(zone.free * page_size), format_size(zone.managed * page_size), zone.state());
+        root.draw(&Text::new(title, (left, y), bold.color(&ink)))?;
+        let high = (zone.high + zone.boost).max(1);
+        let scale = (zone.free + zone.free / 10).clamp(high * 4, high * 20) as f64;
+        let x = |pages: usize| left + ((pages as f64 / scale).min(1.0) * (right - left) as f64).round() as i32;
+        let (top, bottom) = (y + px(16), y + px(38));
+        let gap = options.theme.gap.0;
+        root.draw(&Rectangle::new([(left, top), (right, bottom)], RGBColor(gap[0], gap[1], gap[2]).filled()))?;
+        root.draw(&Rectangle::new([(left, top), (x(zone.free).max(left + 1), bottom)], zone.state_color().filled()))?;
+        root.draw(&Rectangle::new([(left, top), (right, bottom)], ink))?;
+        for (label, pages) in [("min", zone.min), ("low", zone.low), ("high", zone.high)] {
+            let mark = x(pages + zone.boost);
+            root.draw(&Rectangle::new([(mark, top - px(3)), (mark + px(1), bottom + px(3))], ink.filled()))?;
+            root.draw(&Text::new(label, (mark + px(2), bottom + px(3)), font.color(&ink)))?;
+        }
+        if zone.free as f64 > scale {
+            let middle = (top + bottom) / 2;
+            root.draw(&Polygon::new(vec![(right - px(12), top + px(4)), (right - px(3), middle), (right - px(12), bottom - px(4))], BLACK.filled()))?;
+        }
+    }
+
+    if trend.len() > 1 {
+        let area = root.margin(px(trend_top), px(10), px(10), px(20));
+        let last = trend.last().map_or(1.0, |(seconds, _)| seconds.max(1.0));
+        let most = trend.iter().flat_map(|(_, ratios)| ratios.iter().cloned()).fold(2.0, f64::max);
+        let mut chart = ChartBuilder::on(&area)
+            .caption("free pages / high watermark over time", ("sans-serif", 12.0 * options.scale_factor).into_font().color(&ink))
+            .x_label_area_size(px(22))
+            .y_label_area_size(px(40))
+            .build_cartesian_2d(0.0..last, 0.0..most * 1.1)?;
+        chart.configure_mesh().x_desc("seconds").label_style(font.color(&ink)).axis_style(ink).light_line_style(ink.mix(0.1)).draw()?;
+        chart.draw_series(LineSeries::new([(0.0, 1.0), (last, 1.0)], RED.stroke_width(1)))?;
+        for (index, zone) in zones.iter().enumerate() {
+            let color = Palette99::pick(index).to_rgba();
+            chart
+                .draw_series(LineSeries::new(trend.iter().filter_map(|(seconds, ratios)| Some((*seconds, *ratios.get(index)?))), color.stroke_width(2)))?
+                .label(zone.name.clone())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 14, y)], color.stroke_width(2)));
+        }
+        chart.configure_series_labels().label_font(font.color(&ink)).background_style(options.background().mix(0.8)).border_style(ink).draw()?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+// For the trend: free pages over the boosted high watermark, 1.0 being
+// where kswapd stops reclaiming.
+pub fn headroom(zone: &Zone) -> f64 {
+    zone.free as f64 / (zone.high + zone.boost).max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONEINFO: &str = "\
+Node 0, zone      DMA
+  per-node stats
+      nr_inactive_anon 18230
+      nr_active_anon 412566
+      nr_inactive_file 301877
+  pages free     3840
+        boost    0
+        min      11
+        low      14
+        high     17
+        spanned  4095
+        present  3998
+        managed  3840
+        cma      0
+        protection: (0, 2910, 15833, 15833, 15833)
+Node 0, zone   Normal
+  pages free     30110
+        boost    8192
+        min      16822
+        low      21027
+        high     25232
+        spanned  3358720
+        present  3358720
+        managed  3306215
+        protection: (0, 0, 0, 0, 0)
+Node 0, zone  Movable
+  pages free     0
+        boost    0
+        min      0
+        low      0
+        high     0
+        spanned  0
+        present  0
+        managed  0
+";
+
+    #[test]
+    fn parses_zoneinfo() {
+        let zones = parse(ZONEINFO).unwrap();
+        // The empty Movable zone is left out.
+        assert_eq!(zones.iter().map(|zone| zone.name.as_str()).collect::<Vec<_>>(), ["Node 0 DMA", "Node 0 Normal"]);
+        let normal = &zones[1];
+        assert_eq!((normal.free, normal.min, normal.low, normal.high, normal.boost, normal.managed), (30110, 16822, 21027, 25232, 8192, 3306215));
+        assert_eq!(zones[0].free, 3840);
+    }
+
+    #[test]
+    fn boost_raises_every_watermark() {
+        let zones = parse(ZONEINFO).unwrap();
+        assert_eq!(zones[0].state(), "ok");
+        // 30110 free is above high, but not above high plus the boost.
+        assert_eq!(zones[1].state(), "under high, kswapd reclaims up to it");
+        assert!((headroom(&zones[1]) - 30110.0 / 33424.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_input_without_zones() {
+        assert!(parse("  pages free 12\n").is_err());
+    }
+}